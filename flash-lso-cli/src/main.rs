@@ -0,0 +1,144 @@
+//! A command line tool for inspecting Flash Local Shared Object files (Lso) and converting them between AMF versions
+//!
+//! Exits with `EXIT_READ_ERROR` if a file can't be read or decoded, `EXIT_MISSING_PATH` if `get` is given a path that
+//! isn't in the file and `EXIT_WRITE_ERROR` if a converted file can't be written. Invalid arguments exit with 2
+
+#![deny(missing_docs, clippy::missing_docs_in_private_items)]
+
+use clap::{Arg, Command};
+use flash_lso::errors::FileError;
+use flash_lso::extra::flex;
+use flash_lso::read::Reader;
+use flash_lso::types::Lso;
+use flash_lso::write::Writer;
+use std::process::ExitCode;
+
+/// The exit code when a file can't be read or decoded
+const EXIT_READ_ERROR: u8 = 1;
+
+/// The exit code when the path given to `get` isn't in the file
+const EXIT_MISSING_PATH: u8 = 3;
+
+/// The exit code when a converted file can't be encoded or written
+const EXIT_WRITE_ERROR: u8 = 4;
+
+fn main() -> ExitCode {
+    let input = || {
+        Arg::new("FILE")
+            .help("The .sol file to read")
+            .required(true)
+    };
+
+    let matched = Command::new("flash-lso-cli")
+        .about("Inspect and convert Flash Local Shared Object (.sol) files")
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand(
+            Command::new("dump")
+                .about("Pretty-print the values in a file")
+                .arg(input()),
+        )
+        .subcommand(
+            Command::new("json")
+                .about("Print a file as json")
+                .arg(input()),
+        )
+        .subcommand(
+            Command::new("get")
+                .about("Print the value at a path, such as player.inventory[3].count")
+                .arg(input())
+                .arg(
+                    Arg::new("PATH")
+                        .help("The path of the value")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("convert")
+                .about("Convert a file to another AMF version")
+                .arg(
+                    Arg::new("INPUT")
+                        .help("The .sol file to read")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("OUTPUT")
+                        .help("The .sol file to write")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .help("The AMF version to write")
+                        .value_parser(["amf0", "amf3"])
+                        .required(true),
+                ),
+        )
+        .subcommand_required(true)
+        .get_matches();
+
+    let (cmd, args) = matched.subcommand().unwrap();
+    let arg = |name: &str| args.get_one::<String>(name).unwrap();
+
+    let file_name = if cmd == "convert" {
+        arg("INPUT")
+    } else {
+        arg("FILE")
+    };
+    let lso = match read_file(file_name) {
+        Ok(lso) => lso,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(EXIT_READ_ERROR);
+        }
+    };
+
+    match cmd {
+        "dump" => {
+            println!("{} ({})", lso.header.name, lso.header.format_version);
+            for element in &lso.body {
+                println!("{}: {}", element.name, element.value());
+            }
+        }
+        "json" => {
+            let json = serde_json::to_string_pretty(&lso).expect("Unable to encode lso as json");
+            println!("{}", json);
+        }
+        "get" => match lso.get_path(arg("PATH")) {
+            Some(value) => println!("{}", value),
+            None => {
+                eprintln!("No value at {}", arg("PATH"));
+                return ExitCode::from(EXIT_MISSING_PATH);
+            }
+        },
+        "convert" => {
+            let converted = match arg("to").as_str() {
+                "amf0" => lso.to_amf0().map_err(|e| e.to_string()),
+                _ => Ok(lso.to_amf3()),
+            };
+            let result = converted.and_then(|mut converted| {
+                write_file(&mut converted, arg("OUTPUT")).map_err(|e| e.to_string())
+            });
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                return ExitCode::from(EXIT_WRITE_ERROR);
+            }
+        }
+        _ => unreachable!("Unknown command {}", cmd),
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Read the file at the given path into an Lso, decoding flex types
+fn read_file(path: &str) -> Result<Lso<'static>, FileError> {
+    let mut reader = Reader::default();
+    flex::read::register_decoders(&mut reader.amf3_decoder);
+    reader.parse_file(path)
+}
+
+/// Write an Lso to the file at the given path, encoding flex types
+fn write_file(lso: &mut Lso<'static>, path: &str) -> Result<(), FileError> {
+    let mut writer = Writer::default();
+    flex::write::register_encoders(&mut writer.amf3_encoder);
+    writer.write_file(lso, path)
+}
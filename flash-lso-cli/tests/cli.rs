@@ -0,0 +1,34 @@
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_flash-lso-cli"))
+        .args(args)
+        .current_dir(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../flash-lso/tests/sol"
+        ))
+        .output()
+        .expect("Failed to run flash-lso-cli")
+}
+
+#[test]
+pub fn test_get() {
+    let output = run(&["get", "AS2-Demo.sol", "myObject2.p3"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "\"hallo\"");
+}
+
+#[test]
+pub fn test_exit_codes() {
+    for file in ["missing.sol", "AMF0-ECMAArray-Unterminated.sol"] {
+        let output = run(&["get", file, "myInt"]);
+        assert_eq!(output.status.code(), Some(1), "{}", file);
+        assert!(!output.stderr.is_empty());
+    }
+
+    let output = run(&["get", "AS2-Demo.sol", "myObject2.missing"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+
+    assert_eq!(run(&["get", "AS2-Demo.sol"]).status.code(), Some(2));
+}
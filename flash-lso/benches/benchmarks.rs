@@ -1,12 +1,16 @@
-#![feature(test)]
-
-extern crate test;
-
 use flash_lso::read::Reader;
+use flash_lso::types::{AMFVersion, Element, Lso, Value};
+use std::rc::Rc;
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
-criterion_group!(benches, criterion_benchmark);
+criterion_group!(
+    benches,
+    criterion_benchmark,
+    large_vector_benchmark,
+    string_heavy_benchmark,
+    duplicate_string_benchmark
+);
 criterion_main!(benches);
 
 macro_rules! auto_bench {
@@ -97,3 +101,77 @@ auto_bench! {
         [bench_party_1, "Party1"],
         [bench_metadata_history, "MetadataHistory"]
 }
+
+fn large_vector_benchmark(c: &mut Criterion) {
+    let mut lso = Lso::new(
+        vec![
+            Element::new(
+                "ints",
+                Rc::new(Value::VectorInt((0..1_000_000).collect(), false)),
+            ),
+            Element::new(
+                "uints",
+                Rc::new(Value::VectorUInt((0..1_000_000).collect(), false)),
+            ),
+        ],
+        "vectors",
+        AMFVersion::AMF3,
+    );
+    let input_bytes = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+
+    c.bench_function("parse_1m_element_int_vectors", |b| {
+        b.iter(|| {
+            black_box(Reader::default().parse(&input_bytes).unwrap());
+        })
+    });
+}
+
+fn string_heavy_benchmark(c: &mut Criterion) {
+    let strings = (0..100_000)
+        .map(|i| {
+            Rc::new(Value::String(
+                format!("string number {:08} {}", i, "x".repeat(64)).into(),
+            ))
+        })
+        .collect();
+    let mut lso = Lso::new(
+        vec![Element::new(
+            "strings",
+            Rc::new(Value::StrictArray(strings)),
+        )],
+        "strings",
+        AMFVersion::AMF3,
+    );
+    let input_bytes = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+
+    // Strings borrow from the input, so only detaching them with into_owned copies
+    c.bench_function("parse_100k_strings", |b| {
+        b.iter(|| {
+            black_box(Reader::default().parse(&input_bytes).unwrap());
+        })
+    });
+    c.bench_function("parse_100k_strings_owned", |b| {
+        b.iter(|| {
+            black_box(Reader::default().parse(&input_bytes).unwrap().into_owned());
+        })
+    });
+}
+
+fn duplicate_string_benchmark(c: &mut Criterion) {
+    // Each distinct string is written once and then as a reference, so encoding is dominated by string table lookups
+    let elements = (0..5000)
+        .map(|i| {
+            Element::new(
+                format!("value{}", i),
+                Rc::new(Value::String(format!("repeated {}", i % 50).into())),
+            )
+        })
+        .collect();
+    let mut lso = Lso::new(elements, "duplicates", AMFVersion::AMF3);
+
+    c.bench_function("write_5k_duplicate_strings", |b| {
+        b.iter(|| {
+            black_box(flash_lso::write::write_to_bytes(&mut lso).unwrap());
+        })
+    });
+}
@@ -0,0 +1,33 @@
+use flash_lso::read::Reader;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+criterion_group!(benches, parse_many_benchmark);
+criterion_main!(benches);
+
+fn parse_many_benchmark(c: &mut Criterion) {
+    // A synthetic batch of a mix of AMF0 and AMF3 files
+    let files: Vec<Vec<u8>> = [
+        &include_bytes!("../tests/sol/AS2-Demo.sol")[..],
+        &include_bytes!("../tests/sol/AS3-Demo.sol")[..],
+        &include_bytes!("../tests/sol/AS3-Dictionary-Demo.sol")[..],
+        &include_bytes!("../tests/sol/AS2-LongString-Demo.sol")[..],
+    ]
+    .iter()
+    .cycle()
+    .take(1000)
+    .map(|data| data.to_vec())
+    .collect();
+
+    c.bench_function("parse_1000_files_sequential", |b| {
+        b.iter(|| {
+            for data in &files {
+                black_box(Reader::default().parse(data).is_ok());
+            }
+        })
+    });
+
+    c.bench_function("parse_1000_files_parallel", |b| {
+        b.iter(|| black_box(flash_lso::par::parse_many(&files, |lso| lso.is_ok())))
+    });
+}
@@ -1,11 +1,31 @@
+use crate::errors::Error;
+use crate::nom_utils::AMFResult;
+use crate::prelude::*;
+use crate::types::{AMFVersion, Value};
+
 /// Support for reading AMF0 data
 pub mod read;
 
 /// AMF0 type markers
-mod type_marker;
+pub(crate) mod type_marker;
 
 /// Support for writing AMF0 data
 pub mod write;
 
 /// High-level writer that assists in generation of self-referential amf0 data
 pub mod writer;
+
+/// Read a single AMF0 value from the start of a slice, without an lso header, returning it with the data after it
+///
+/// This is `decode_value` with `AMFVersion::AMF0`, references can only refer to values earlier in the same value
+pub fn decode(i: &[u8]) -> AMFResult<'_, Value<'_>> {
+    crate::read::decode_value(i, AMFVersion::AMF0)
+}
+
+/// Write a single value as AMF0, without an lso header, this is the inverse of `decode`
+///
+/// This is `encode_value` with `AMFVersion::AMF0`, each call starts with empty reference tables so only values shared
+/// within this value are written as references
+pub fn encode<'a>(value: &Value<'_>) -> Result<Vec<u8>, Error<'a>> {
+    crate::write::encode_value(value, AMFVersion::AMF0)
+}
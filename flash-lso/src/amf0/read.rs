@@ -1,62 +1,48 @@
 //! Support for decoding AMF0 data
 use crate::amf0::type_marker::TypeMarker;
+use crate::prelude::*;
 
 #[cfg(feature = "amf3")]
 use crate::amf3;
-use crate::nom_utils::{take_str, AMFResult};
-use crate::types::{ClassDefinition, Element, ObjectId, Reference, Value};
+use crate::errors::{at_path, Error, PathSegment};
+use crate::nom_utils::{check_alloc, take_str, AMFResult};
+use crate::read::{DEFAULT_MAX_DEPTH, VALUE_ALLOC};
+use crate::spans::SpanRecorder;
+use crate::types::{
+    ClassDefinition, Element, ObjectId, Reference, SurrogateStrings, Utf8Mode, Value,
+};
 use crate::PADDING;
+use core::convert::{TryFrom, TryInto};
 use nom::bytes::complete::{tag, take};
-use nom::combinator::{map, map_res};
+use nom::combinator::map;
 use nom::error::{make_error, ErrorKind};
 use nom::multi::{many0, many_m_n};
 use nom::number::complete::{be_f64, be_u16, be_u32, be_u8};
 use nom::Err;
-use std::convert::{TryFrom, TryInto};
-use std::rc::Rc;
 
 pub(crate) fn parse_string(i: &[u8]) -> AMFResult<'_, &str> {
     let (i, length) = be_u16(i)?;
     take_str(i, length)
 }
 
-fn parse_element_number(i: &[u8]) -> AMFResult<'_, Rc<Value>> {
+fn parse_element_number(i: &[u8]) -> AMFResult<'_, Rc<Value<'_>>> {
     let (i, v) = be_f64(i)?;
     Ok((i, Rc::new(Value::Number(v))))
 }
 
-fn parse_element_bool(i: &[u8]) -> AMFResult<'_, Rc<Value>> {
+/// Any non-zero byte is read as true, as some writers use values other than 1
+fn parse_element_bool(i: &[u8]) -> AMFResult<'_, Rc<Value<'_>>> {
     let (i, v) = be_u8(i)?;
     Ok((i, Rc::new(Value::Bool(v > 0))))
 }
 
-fn parse_element_string(i: &[u8]) -> AMFResult<'_, Rc<Value>> {
-    let (i, v) = parse_string(i)?;
-    Ok((i, Rc::new(Value::String(v.to_string()))))
-}
-
-fn parse_element_date(i: &[u8]) -> AMFResult<'_, Rc<Value>> {
+fn parse_element_date(i: &[u8]) -> AMFResult<'_, Rc<Value<'_>>> {
     let (i, millis) = be_f64(i)?;
     let (i, time_zone) = be_u16(i)?;
 
     Ok((i, Rc::new(Value::Date(millis, Some(time_zone)))))
 }
 
-fn parse_long_string_internal(i: &[u8]) -> AMFResult<'_, &str> {
-    let (i, length) = be_u32(i)?;
-    map_res(take(length), std::str::from_utf8)(i)
-}
-
-fn parse_element_long_string(i: &[u8]) -> AMFResult<'_, Rc<Value>> {
-    let (i, str) = parse_long_string_internal(i)?;
-    Ok((i, Rc::new(Value::String(str.to_string()))))
-}
-
-fn parse_element_xml(i: &[u8]) -> AMFResult<'_, Rc<Value>> {
-    let (i, content) = parse_long_string_internal(i)?;
-    Ok((i, Rc::new(Value::XML(content.to_string(), true))))
-}
-
 fn read_type_marker(i: &[u8]) -> AMFResult<'_, TypeMarker> {
     let (i, type_) = be_u8(i)?;
     Ok((
@@ -65,37 +51,146 @@ fn read_type_marker(i: &[u8]) -> AMFResult<'_, TypeMarker> {
     ))
 }
 
+/// Decodes the bytes of an AMF0 string that aren't valid in the `Utf8Mode` of the decoder, see
+/// `AMF0Decoder::string_decoder`
+pub type StringDecoderFn = Rc<dyn for<'b> Fn(&'b [u8]) -> Cow<'b, str>>;
+
 /// Handles decoding AMF0
-#[derive(Default)]
-pub struct AMF0Decoder {
+///
+/// The values read borrow from the input, so a decoder can only read from inputs that live for `'a`
+pub struct AMF0Decoder<'a> {
     /// Cache of previously read values, that can be referenced later
-    cache: Vec<Rc<Value>>,
+    pub(crate) cache: Vec<Rc<Value<'a>>>,
+
+    /// How strings are decoded, this also applies to any AMF3 data embedded in the AMF0 data
+    pub utf8_mode: Utf8Mode,
+
+    /// Where the form of strings read with `Utf8Mode::Flash` is recorded, this is shared with any AMF3 data embedded in
+    /// the AMF0 data
+    ///
+    /// `Reader` replaces this for each lso it reads and gives it to the lso as `Lso::surrogate_strings`
+    pub surrogate_strings: SurrogateStrings,
+
+    /// Called for strings that `utf8_mode` can't decode, instead of failing, such as to read Latin-1 from old files
+    ///
+    /// AMF0 never specified an encoding for strings, AMF3 requires UTF-8 so this doesn't apply to any AMF3 data
+    /// embedded in the AMF0 data. By default there is none, so invalid strings are an error with `Utf8Mode::Strict`
+    pub string_decoder: Option<StringDecoderFn>,
+
+    /// The deepest nesting of values that can be read, this also applies to any AMF3 data embedded in the AMF0 data
+    pub max_depth: usize,
+
+    /// The number of values being read that contain the value being read now
+    pub(crate) depth: usize,
+
+    /// The budget in bytes for the values read, see `Reader::set_max_alloc`, this also applies to any AMF3 data
+    /// embedded in the AMF0 data
+    pub max_alloc: usize,
+
+    /// The bytes counted against `max_alloc` so far
+    pub(crate) allocated: usize,
+
+    /// The path to the value that last failed to decode, innermost first
+    pub(crate) error_path: Vec<PathSegment>,
+
+    /// The error of the element that ended the body, which isn't returned as the body ends without an error there
+    pub(crate) element_error: Option<Err<Error<'a>>>,
 
-    #[cfg(feature = "amf3")]
-    amf3_decoder: amf3::read::AMF3Decoder,
+    /// Records the extent of each value read, when reading with `Reader::parse_annotated`
+    pub(crate) spans: Option<SpanRecorder>,
 }
 
-impl AMF0Decoder {
-    fn parse_element_reference<'a>(&self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+impl Default for AMF0Decoder<'_> {
+    fn default() -> Self {
+        Self {
+            cache: Vec::new(),
+            utf8_mode: Utf8Mode::default(),
+            surrogate_strings: SurrogateStrings::default(),
+            string_decoder: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            depth: 0,
+            max_alloc: usize::MAX,
+            allocated: 0,
+            error_path: Vec::new(),
+            element_error: None,
+            spans: None,
+        }
+    }
+}
+
+impl<'a> AMF0Decoder<'a> {
+    /// Get the path to the value that failed to decode after an error, outermost first
+    pub fn error_path(&self) -> Vec<PathSegment> {
+        self.error_path.iter().rev().cloned().collect()
+    }
+
+    pub(crate) fn parse_string(&self, i: &'a [u8]) -> AMFResult<'a, Cow<'a, str>> {
+        let (i, length) = be_u16(i)?;
+        self.take_string(i, length.into())
+    }
+
+    fn parse_long_string_internal(&self, i: &'a [u8]) -> AMFResult<'a, Cow<'a, str>> {
+        let (i, length) = be_u32(i)?;
+        self.take_string(i, length)
+    }
+
+    /// Read a string of the given length with `utf8_mode`, or `string_decoder` if it isn't valid
+    fn take_string(&self, i: &'a [u8], length: u32) -> AMFResult<'a, Cow<'a, str>> {
+        let (rest, bytes) = take(length)(i)?;
+        match (
+            self.utf8_mode.decode_with(bytes, &self.surrogate_strings),
+            &self.string_decoder,
+        ) {
+            (Some(s), _) => Ok((rest, s)),
+            (None, Some(decode)) => Ok((rest, decode(bytes))),
+            (None, None) => Err(Err::Error(make_error(i, ErrorKind::MapRes))),
+        }
+    }
+
+    fn parse_element_string(&self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
+        let (i, v) = self.parse_string(i)?;
+        Ok((i, Rc::new(Value::String(v))))
+    }
+
+    fn parse_element_long_string(&self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
+        let (i, str) = self.parse_long_string_internal(i)?;
+        Ok((i, Rc::new(Value::String(str))))
+    }
+
+    fn parse_element_xml(&self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
+        let (i, content) = self.parse_long_string_internal(i)?;
+        Ok((i, Rc::new(Value::XML(content, true))))
+    }
+
+    /// Read a reference to an earlier object or array, which must already be in the reference table
+    ///
+    /// The value is kept as a `Reference`, use `resolve_reference` to get the value it refers to
+    fn parse_element_reference(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         let (i, reference_index) = be_u16(i)?;
+        if usize::from(reference_index) >= self.cache.len() {
+            return Err(Err::Error(Error::InvalidReference(reference_index)));
+        }
+        if let Some(spans) = &mut self.spans {
+            spans.reference(usize::from(reference_index));
+        }
 
         Ok((i, Rc::new(Value::Reference(Reference(reference_index)))))
     }
 
-    fn parse_element_mixed_array<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+    fn parse_element_mixed_array(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         let (i, array_length) = be_u32(i)?;
         map(
             |i| self.parse_array_element(i),
-            move |elms: Vec<Element>| Rc::new(Value::ECMAArray(Vec::new(), elms, array_length)),
+            move |elms: Vec<Element<'a>>| Rc::new(Value::ECMAArray(Vec::new(), elms, array_length)),
         )(i)
     }
 
-    fn parse_element_typed_object<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
-        let (i, name) = parse_string(i)?;
+    fn parse_element_typed_object(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
+        let (i, name) = self.parse_string(i)?;
 
         map(
             |i| self.parse_array_element(i),
-            move |elms: Vec<Element>| {
+            move |elms: Vec<Element<'a>>| {
                 Rc::new(Value::Object(
                     ObjectId::INVALID,
                     elms,
@@ -105,19 +200,19 @@ impl AMF0Decoder {
         )(i)
     }
 
-    fn parse_element_object<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+    fn parse_element_object(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         let (i, v) = self.parse_array_element(i)?;
         Ok((i, Rc::new(Value::Object(ObjectId::INVALID, v, None))))
     }
 
     #[cfg(fuzzing)]
     /// For fuzzing
-    pub fn fuzz_parse_element_array<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+    pub fn fuzz_parse_element_array(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         self.parse_element_array(i)
     }
 
     /// Parse an array of elements
-    fn parse_element_array<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+    fn parse_element_array(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         let (i, length) = be_u32(i)?;
 
         let length_usize = length
@@ -128,22 +223,29 @@ impl AMF0Decoder {
         if i.len() < length_usize {
             return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
         }
+        check_alloc(i, self.allocated, self.max_alloc, length_usize, VALUE_ALLOC)?;
 
         // This must parse length elements
-        let (i, elements) =
-            many_m_n(length_usize, length_usize, |i| self.parse_single_element(i))(i)?;
+        let mut index = 0;
+        let (i, elements) = many_m_n(length_usize, length_usize, |i| {
+            let result = self.parse_at(i, PathSegment::Index(index));
+            index += 1;
+            result
+        })(i)?;
 
         Ok((i, Rc::new(Value::StrictArray(elements))))
     }
 
-    fn parse_array_element<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Vec<Element>> {
+    fn parse_array_element(&mut self, i: &'a [u8]) -> AMFResult<'a, Vec<Element<'a>>> {
         let mut out = Vec::new();
 
         let mut i = i;
         loop {
-            let (k, _) = parse_string(i)?;
+            // The properties end with an empty name followed by the object end marker, an object end marker after any
+            // other name is an error when read as a value
+            let (k, name) = self.parse_string(i)?;
             let (k, next_type) = read_type_marker(k)?;
-            if next_type == TypeMarker::ObjectEnd {
+            if name.is_empty() && next_type == TypeMarker::ObjectEnd {
                 i = k;
                 break;
             }
@@ -157,10 +259,31 @@ impl AMF0Decoder {
         Ok((i, out))
     }
 
-    fn parse_element_amf3<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+    fn parse_element_amf3(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         #[cfg(feature = "amf3")]
         {
-            let (i, x) = self.amf3_decoder.parse_single_element(i)?;
+            // Each switch to AMF3 starts with empty reference tables, so references can't reach into another region
+            let mut decoder = amf3::read::AMF3Decoder::default();
+            decoder.utf8_mode = self.utf8_mode;
+            decoder.surrogate_strings = self.surrogate_strings.clone();
+            decoder.options.max_depth = self.max_depth;
+            decoder.depth = self.depth;
+            decoder.options.max_alloc = self.max_alloc;
+            decoder.allocated = self.allocated;
+            // The AMF3 decoder has its own reference table, so the slots of this one are put back after
+            let definitions = self
+                .spans
+                .as_mut()
+                .map(|spans| core::mem::take(&mut spans.definitions));
+            decoder.spans = self.spans.take();
+            let result = decoder.parse_single_element(i);
+            self.error_path.append(&mut decoder.error_path);
+            self.allocated = decoder.allocated;
+            self.spans = decoder.spans.take();
+            if let (Some(spans), Some(definitions)) = (&mut self.spans, definitions) {
+                spans.definitions = definitions;
+            }
+            let (i, x) = result?;
             Ok((i, Rc::new(Value::AMF3(x))))
         }
         #[cfg(not(feature = "amf3"))]
@@ -169,70 +292,100 @@ impl AMF0Decoder {
         }
     }
 
+    /// Parse an object or array, recording it in the reference table
+    ///
+    /// The slot in the table is taken before the contents are read, so values nested inside get later indices and a
+    /// reference back to this value from inside it refers to the right slot
+    fn parse_complex(
+        &mut self,
+        i: &'a [u8],
+        parser: fn(&mut Self, &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>>,
+    ) -> AMFResult<'a, Rc<Value<'a>>> {
+        let index = self.cache.len();
+        self.cache.push(Rc::new(Value::Undefined));
+        if let Some(spans) = &mut self.spans {
+            spans.define(index);
+        }
+
+        let (i, v) = parser(self, i)?;
+        self.cache[index] = Rc::clone(&v);
+        Ok((i, v))
+    }
+
+    /// Get the value a reference read by this decoder refers to
+    ///
+    /// A reference to a value that contains it gives the value as it was when the reference was read
+    pub fn resolve_reference(&self, reference: Reference) -> Option<Rc<Value<'a>>> {
+        self.cache.get(usize::from(reference.0)).cloned()
+    }
+
+    /// Parse a value, recording the given segment of the path to it if it fails
+    fn parse_at(&mut self, i: &'a [u8], segment: PathSegment) -> AMFResult<'a, Rc<Value<'a>>> {
+        if let Some(spans) = &mut self.spans {
+            spans.push(&segment);
+        }
+        let result = self.parse_single_element(i);
+        if let Some(spans) = &mut self.spans {
+            if let Ok((j, _)) = &result {
+                spans.record(i.len(), j.len());
+            }
+            spans.pop();
+        }
+        at_path(&mut self.error_path, || segment, result)
+    }
+
     /// Parse a single AMF0 element
-    pub fn parse_single_element<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+    ///
+    /// This fails with `Error::DepthExceeded` if the value is nested deeper than `max_depth`, or
+    /// `Error::AllocationLimit` if it would go over `max_alloc`
+    pub fn parse_single_element(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
+        if self.depth >= self.max_depth {
+            return Err(Err::Error(Error::DepthExceeded(i)));
+        }
+        self.allocated = check_alloc(i, self.allocated, self.max_alloc, 1, VALUE_ALLOC)?;
+        self.depth += 1;
+        let result = self.parse_value(i);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_value(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         // Get the type of the next element
+        let marker = i;
         let (i, type_) = read_type_marker(i)?;
 
-        let cache_idx = self.cache.len();
-        self.cache.push(Rc::new(Value::Undefined));
-
         let (i, v) = match type_ {
             TypeMarker::Number => parse_element_number(i),
             TypeMarker::Boolean => parse_element_bool(i),
-            TypeMarker::String => parse_element_string(i),
-            TypeMarker::Object => {
-                let (i, v) = self.parse_element_object(i)?;
-                self.cache[cache_idx] = Rc::clone(&v);
-                Ok((i, v))
-            }
+            TypeMarker::String => self.parse_element_string(i),
+            TypeMarker::Object => self.parse_complex(i, Self::parse_element_object),
             TypeMarker::Null => Ok((i, Rc::new(Value::Null))),
             TypeMarker::Undefined => Ok((i, Rc::new(Value::Undefined))),
-            TypeMarker::Reference => {
-                let (i, v) = self.parse_element_reference(i)?;
-                Ok((i, v))
-            }
-            TypeMarker::MixedArrayStart => {
-                let (i, v) = self.parse_element_mixed_array(i)?;
-                self.cache[cache_idx] = Rc::clone(&v);
-                Ok((i, v))
-            }
-            TypeMarker::Array => {
-                let (i, v) = self.parse_element_array(i)?;
-                self.cache[cache_idx] = Rc::clone(&v);
-                Ok((i, v))
-            }
+            TypeMarker::Reference => self.parse_element_reference(i),
+            TypeMarker::MixedArrayStart => self.parse_complex(i, Self::parse_element_mixed_array),
+            TypeMarker::Array => self.parse_complex(i, Self::parse_element_array),
             TypeMarker::Date => parse_element_date(i),
-            TypeMarker::LongString => parse_element_long_string(i),
+            TypeMarker::LongString => self.parse_element_long_string(i),
             TypeMarker::Unsupported => Ok((i, Rc::new(Value::Unsupported))),
-            TypeMarker::Xml => parse_element_xml(i),
-            TypeMarker::TypedObject => {
-                let (i, v) = self.parse_element_typed_object(i)?;
-                self.cache[cache_idx] = Rc::clone(&v);
-                Ok((i, v))
-            }
+            TypeMarker::Xml => self.parse_element_xml(i),
+            TypeMarker::TypedObject => self.parse_complex(i, Self::parse_element_typed_object),
             TypeMarker::AMF3 => self.parse_element_amf3(i),
-            TypeMarker::MovieClip | TypeMarker::RecordSet | TypeMarker::ObjectEnd => Err(
-                Err::Error(crate::errors::Error::UnsupportedType(type_ as u8)),
-            ),
+            TypeMarker::MovieClip | TypeMarker::RecordSet | TypeMarker::ObjectEnd => {
+                Err(Err::Error(Error::UnsupportedType(marker, type_ as u8)))
+            }
         }?;
 
         Ok((i, v))
     }
 
-    fn parse_element<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Element> {
-        let (i, name) = parse_string(i)?;
+    fn parse_element(&mut self, i: &'a [u8]) -> AMFResult<'a, Element<'a>> {
+        let (i, name) = self.parse_string(i)?;
+        let (i, value) = self.parse_at(i, PathSegment::Name(name.to_string()))?;
 
-        map(
-            |i| self.parse_single_element(i),
-            move |v| Element {
-                name: name.to_string(),
-                value: v,
-            },
-        )(i)
+        Ok((i, Element { name, value }))
     }
 
-    fn parse_element_and_padding<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Element> {
+    pub(crate) fn parse_element_and_padding(&mut self, i: &'a [u8]) -> AMFResult<'a, Element<'a>> {
         let (i, e) = self.parse_element(i)?;
         let (i, _) = tag(PADDING)(i)?;
 
@@ -240,16 +393,30 @@ impl AMF0Decoder {
     }
 
     /// Parse a sequence of `PADDING` delimited `Values`
-    pub fn parse_body<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Vec<Element>> {
-        many0(|i| self.parse_element_and_padding(i))(i)
+    ///
+    /// The body itself is the first entry of the reference table, so references to values in it start at 1
+    pub fn parse_body(&mut self, i: &'a [u8]) -> AMFResult<'a, Vec<Element<'a>>> {
+        let index = self.cache.len();
+        self.cache.push(Rc::new(Value::Undefined));
+
+        let (i, body) = many0(|i| {
+            // The body ends at the first element that fails, so only the path to that one is kept
+            self.error_path.clear();
+            let result = self.parse_element_and_padding(i);
+            self.element_error = result.as_ref().err().cloned();
+            result
+        })(i)?;
+
+        self.cache[index] = Rc::new(Value::Object(ObjectId::INVALID, body.clone(), None));
+        Ok((i, body))
     }
 
     /// Convert the given value into a reference, if possible
     /// This reference is only valid for values sourced from this decoder and will only reference values decoded by it
-    pub fn as_reference(&self, v: &Value) -> Option<Reference> {
+    pub fn as_reference(&self, v: &Value<'_>) -> Option<Reference> {
         self.cache
             .iter()
-            .position(|cv| *cv == Rc::new(v.clone()))
+            .position(|cv| **cv == *v)
             .map(|r| Reference(r as _))
     }
 }
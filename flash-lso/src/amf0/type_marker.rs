@@ -1,61 +1,63 @@
+use crate::markers;
+
 /// Type markers used in AMF0
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 #[repr(u8)]
 pub(crate) enum TypeMarker {
     /// Number
-    Number = 0,
+    Number = markers::amf0::NUMBER,
 
     /// Boolean
-    Boolean = 1,
+    Boolean = markers::amf0::BOOLEAN,
 
     /// String
-    String = 2,
+    String = markers::amf0::STRING,
 
     /// Object start
-    Object = 3,
+    Object = markers::amf0::OBJECT,
 
     /// MovieClip (unused)
-    MovieClip = 4,
+    MovieClip = markers::amf0::MOVIE_CLIP,
 
     /// Null
-    Null = 5,
+    Null = markers::amf0::NULL,
 
     /// Undefined
-    Undefined = 6,
+    Undefined = markers::amf0::UNDEFINED,
 
     /// Reference (unused)
-    Reference = 7,
+    Reference = markers::amf0::REFERENCE,
 
     /// Start of a mixed array
-    MixedArrayStart = 8,
+    MixedArrayStart = markers::amf0::MIXED_ARRAY_START,
 
     /// Object end
-    ObjectEnd = 9,
+    ObjectEnd = markers::amf0::OBJECT_END,
 
     /// Array start
-    Array = 10,
+    Array = markers::amf0::ARRAY,
 
     /// Date with timezone
-    Date = 11,
+    Date = markers::amf0::DATE,
 
     /// Long string (length > 65535)
-    LongString = 12,
+    LongString = markers::amf0::LONG_STRING,
 
     /// Unsupported
-    Unsupported = 13,
+    Unsupported = markers::amf0::UNSUPPORTED,
 
     /// Recordset
-    RecordSet = 14,
+    RecordSet = markers::amf0::RECORD_SET,
 
     /// XML
-    Xml = 15,
+    Xml = markers::amf0::XML,
 
     /// Typed object start
-    TypedObject = 16,
+    TypedObject = markers::amf0::TYPED_OBJECT,
 
     /// Embedded AMF3 element
-    AMF3 = 17,
+    AMF3 = markers::amf0::AMF3,
 }
 
 impl TryFrom<u8> for TypeMarker {
@@ -63,24 +65,24 @@ impl TryFrom<u8> for TypeMarker {
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
-            0 => Ok(Self::Number),
-            1 => Ok(Self::Boolean),
-            2 => Ok(Self::String),
-            3 => Ok(Self::Object),
-            4 => Ok(Self::MovieClip),
-            5 => Ok(Self::Null),
-            6 => Ok(Self::Undefined),
-            7 => Ok(Self::Reference),
-            8 => Ok(Self::MixedArrayStart),
-            9 => Ok(Self::ObjectEnd),
-            10 => Ok(Self::Array),
-            11 => Ok(Self::Date),
-            12 => Ok(Self::LongString),
-            13 => Ok(Self::Unsupported),
-            14 => Ok(Self::RecordSet),
-            15 => Ok(Self::Xml),
-            16 => Ok(Self::TypedObject),
-            17 => Ok(Self::AMF3),
+            markers::amf0::NUMBER => Ok(Self::Number),
+            markers::amf0::BOOLEAN => Ok(Self::Boolean),
+            markers::amf0::STRING => Ok(Self::String),
+            markers::amf0::OBJECT => Ok(Self::Object),
+            markers::amf0::MOVIE_CLIP => Ok(Self::MovieClip),
+            markers::amf0::NULL => Ok(Self::Null),
+            markers::amf0::UNDEFINED => Ok(Self::Undefined),
+            markers::amf0::REFERENCE => Ok(Self::Reference),
+            markers::amf0::MIXED_ARRAY_START => Ok(Self::MixedArrayStart),
+            markers::amf0::OBJECT_END => Ok(Self::ObjectEnd),
+            markers::amf0::ARRAY => Ok(Self::Array),
+            markers::amf0::DATE => Ok(Self::Date),
+            markers::amf0::LONG_STRING => Ok(Self::LongString),
+            markers::amf0::UNSUPPORTED => Ok(Self::Unsupported),
+            markers::amf0::RECORD_SET => Ok(Self::RecordSet),
+            markers::amf0::XML => Ok(Self::Xml),
+            markers::amf0::TYPED_OBJECT => Ok(Self::TypedObject),
+            markers::amf0::AMF3 => Ok(Self::AMF3),
             _ => Err(()),
         }
     }
@@ -1,14 +1,14 @@
+use crate::io::Write;
 /// Support for encoding AMF0
-use crate::types::{Element, Reference, Value};
+use crate::prelude::*;
+use crate::types::{Element, Reference, SurrogateStrings, Utf8Mode, Value};
 use crate::PADDING;
-use std::io::Write;
 
 use crate::amf0::type_marker::TypeMarker;
+use crate::io::Result;
 use crate::nom_utils::write_string;
 use crate::write::WriteExt;
-use std::io::Result;
-use std::ops::Deref;
-use std::rc::Rc;
+use core::ops::Deref;
 
 #[cfg(feature = "amf3")]
 use crate::amf3::write::AMF3Encoder;
@@ -35,34 +35,6 @@ fn write_bool_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, s: bool) -> Res
     Ok(())
 }
 
-fn write_long_string_content<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, s: &'b str) -> Result<()> {
-    writer.write_u32(s.len() as u32)?;
-    writer.write_all(s.as_bytes())?;
-    Ok(())
-}
-
-fn write_long_string_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, s: &'b str) -> Result<()> {
-    write_type_marker(writer, TypeMarker::LongString)?;
-    write_long_string_content(writer, s)?;
-    Ok(())
-}
-
-fn write_string_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, s: &'b str) -> Result<()> {
-    write_type_marker(writer, TypeMarker::String)?;
-    write_string(writer, s)?;
-    Ok(())
-}
-
-fn write_object_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, o: &'b [Element]) -> Result<()> {
-    write_type_marker(writer, TypeMarker::Object)?;
-    for element in o {
-        write_element(writer, element)?;
-    }
-    writer.write_u16(0)?;
-    write_type_marker(writer, TypeMarker::ObjectEnd)?;
-    Ok(())
-}
-
 fn write_null_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W) -> Result<()> {
     write_type_marker(writer, TypeMarker::Null)
 }
@@ -71,18 +43,6 @@ fn write_undefined_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W) -> Result<
     write_type_marker(writer, TypeMarker::Undefined)
 }
 
-fn write_strict_array_element<'a, 'b: 'a, W: Write + 'a>(
-    writer: &mut W,
-    elements: &'b [Rc<Value>],
-) -> Result<()> {
-    write_type_marker(writer, TypeMarker::Array)?;
-    writer.write_u32(elements.len() as u32)?;
-    for element in elements {
-        write_value(writer, element)?;
-    }
-    Ok(())
-}
-
 fn write_date_element<'a, 'b: 'a, W: Write + 'a>(
     writer: &mut W,
     date: f64,
@@ -94,132 +54,230 @@ fn write_date_element<'a, 'b: 'a, W: Write + 'a>(
     Ok(())
 }
 
+fn write_long_string_bytes<'a, W: Write + 'a>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+    writer.write_u32(bytes.len() as u32)?;
+    writer.write_all(bytes)
+}
+
 fn write_unsupported_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W) -> Result<()> {
     write_type_marker(writer, TypeMarker::Unsupported)
 }
 
-fn write_xml_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, content: &'b str) -> Result<()> {
-    write_type_marker(writer, TypeMarker::Xml)?;
-    write_long_string_content(writer, content)?;
-    Ok(())
-}
+/// Handles encoding AMF0
+#[derive(Default)]
+pub struct AMF0Encoder {
+    /// How strings are encoded, this also applies to any AMF3 data embedded in the AMF0 data
+    pub utf8_mode: Utf8Mode,
 
-fn write_typed_object_element<'a, 'b: 'a, W: Write + 'a>(
-    writer: &mut W,
-    name: &'b str,
-    elements: &'b [Element],
-) -> Result<()> {
-    write_type_marker(writer, TypeMarker::TypedObject)?;
-    write_string(writer, name)?;
-    for element in elements {
-        write_element(writer, element)?;
-    }
-    writer.write_u16(0)?;
-    write_type_marker(writer, TypeMarker::ObjectEnd)?;
-    Ok(())
+    /// The form to write strings in with `Utf8Mode::Flash`, this is shared with any AMF3 data embedded in the AMF0 data
+    ///
+    /// `Writer` sets this to the `Lso::surrogate_strings` of the lso it writes
+    pub surrogate_strings: SurrogateStrings,
 }
 
-fn write_dense_element<'a, 'b: 'a, W: Write + 'a>(
-    writer: &mut W,
-    index: usize,
-    element: &'b Rc<Value>,
-) -> Result<()> {
-    let index_str = index.to_string();
+impl AMF0Encoder {
+    /// Encode a string with `utf8_mode`
+    pub(crate) fn encode_string<'s>(&self, s: &'s str) -> Cow<'s, [u8]> {
+        self.utf8_mode.encode_with(s, &self.surrogate_strings)
+    }
 
-    writer.write_u16(index_str.len() as u16)?;
-    writer.write_all(index_str.as_bytes())?;
-    write_value(writer, element)?;
+    fn write_long_string_content<'a, 'b: 'a, W: Write + 'a>(
+        &self,
+        writer: &mut W,
+        s: &'b str,
+    ) -> Result<()> {
+        write_long_string_bytes(writer, &self.encode_string(s))
+    }
 
-    Ok(())
-}
+    /// Write a string element, as a long string if it is too long for a string
+    fn write_string_element<'a, 'b: 'a, W: Write + 'a>(
+        &self,
+        writer: &mut W,
+        s: &'b str,
+    ) -> Result<()> {
+        let bytes = self.encode_string(s);
+        if bytes.len() > 65535 {
+            write_type_marker(writer, TypeMarker::LongString)?;
+            write_long_string_bytes(writer, &bytes)
+        } else {
+            write_type_marker(writer, TypeMarker::String)?;
+            write_string(writer, &bytes)
+        }
+    }
 
-fn write_mixed_array<'a, 'b: 'a, W: Write + 'a>(
-    writer: &mut W,
-    dense: &'b [Rc<Value>],
-    elements: &'b [Element],
-    length: u32,
-) -> Result<()> {
-    //TODO: what is the u16 padding
-    //TODO: sometimes array length is ignored (u32) sometimes its: elements.len() as u32
+    fn write_object_element<'a, 'b: 'a, W: Write + 'a>(
+        &self,
+        writer: &mut W,
+        o: &'b [Element<'_>],
+    ) -> Result<()> {
+        write_type_marker(writer, TypeMarker::Object)?;
+        for element in o {
+            self.write_element(writer, element)?;
+        }
+        writer.write_u16(0)?;
+        write_type_marker(writer, TypeMarker::ObjectEnd)?;
+        Ok(())
+    }
 
-    write_type_marker(writer, TypeMarker::MixedArrayStart)?;
-    writer.write_u32(length)?;
-    for (idx, value) in dense.iter().enumerate() {
-        write_dense_element(writer, idx, value)?
+    fn write_strict_array_element<'a, 'b: 'a, W: Write + 'a>(
+        &self,
+        writer: &mut W,
+        elements: &'b [Rc<Value<'_>>],
+    ) -> Result<()> {
+        write_type_marker(writer, TypeMarker::Array)?;
+        writer.write_u32(elements.len() as u32)?;
+        for element in elements {
+            self.write_value(writer, element)?;
+        }
+        Ok(())
     }
-    for element in elements {
-        write_element(writer, element)?
+
+    fn write_xml_element<'a, 'b: 'a, W: Write + 'a>(
+        &self,
+        writer: &mut W,
+        content: &'b str,
+    ) -> Result<()> {
+        write_type_marker(writer, TypeMarker::Xml)?;
+        self.write_long_string_content(writer, content)?;
+        Ok(())
     }
-    writer.write_u16(0)?;
-    write_type_marker(writer, TypeMarker::ObjectEnd)?;
-    Ok(())
-}
 
-/// Write a AMF0 Value to the writer
-pub fn write_value<'a, 'b: 'a, W: Write + 'a>(
-    writer: &mut W,
-    element: &'b Rc<Value>,
-) -> Result<()> {
-    match element.deref() {
-        Value::Number(n) => write_number_element(writer, *n),
-        Value::Bool(b) => write_bool_element(writer, *b),
-        Value::String(s) => {
-            if s.len() > 65535 {
-                write_long_string_element(writer, s)
-            } else {
-                write_string_element(writer, s)
-            }
-        }
-        Value::Object(_, elements, class_def) => {
-            if let Some(class_def) = class_def {
-                write_typed_object_element(writer, &class_def.name, elements)
-            } else {
-                write_object_element(writer, elements)
-            }
+    fn write_typed_object_element<'a, 'b: 'a, W: Write + 'a>(
+        &self,
+        writer: &mut W,
+        name: &'b str,
+        elements: &'b [Element<'_>],
+    ) -> Result<()> {
+        write_type_marker(writer, TypeMarker::TypedObject)?;
+        write_string(writer, &self.encode_string(name))?;
+        for element in elements {
+            self.write_element(writer, element)?;
         }
-        Value::Null => write_null_element(writer),
-        Value::Undefined => write_undefined_element(writer),
-        Value::StrictArray(a) => write_strict_array_element(writer, a.as_slice()),
-        Value::Date(d, tz) => write_date_element(writer, *d, *tz),
-        Value::Unsupported => write_unsupported_element(writer),
-        Value::XML(x, _string) => write_xml_element(writer, x),
-        Value::ECMAArray(dense, elems, elems_length) => {
-            write_mixed_array(writer, dense, elems, *elems_length)
+        writer.write_u16(0)?;
+        write_type_marker(writer, TypeMarker::ObjectEnd)?;
+        Ok(())
+    }
+
+    fn write_dense_element<'a, 'b: 'a, W: Write + 'a>(
+        &self,
+        writer: &mut W,
+        index: usize,
+        element: &'b Rc<Value<'_>>,
+    ) -> Result<()> {
+        let index_str = index.to_string();
+
+        writer.write_u16(index_str.len() as u16)?;
+        writer.write_all(index_str.as_bytes())?;
+        self.write_value(writer, element)?;
+
+        Ok(())
+    }
+
+    fn write_mixed_array<'a, 'b: 'a, W: Write + 'a>(
+        &self,
+        writer: &mut W,
+        dense: &'b [Rc<Value<'_>>],
+        elements: &'b [Element<'_>],
+        length: u32,
+    ) -> Result<()> {
+        //TODO: what is the u16 padding
+        //TODO: sometimes array length is ignored (u32) sometimes its: elements.len() as u32
+
+        // The length can't be less than the number of dense elements, which are written with their index as a name
+        write_type_marker(writer, TypeMarker::MixedArrayStart)?;
+        writer.write_u32(length.max(dense.len() as u32))?;
+        for (idx, value) in dense.iter().enumerate() {
+            self.write_dense_element(writer, idx, value)?
         }
-        #[cfg(feature = "amf3")]
-        Value::AMF3(e) => {
-            write_type_marker(writer, TypeMarker::AMF3)?;
-            let encoder = AMF3Encoder::default();
-            encoder.write_value_element(writer, e)
+        for element in elements {
+            self.write_element(writer, element)?
         }
-        Value::Reference(r) => write_reference_element(writer, r),
-        _ => {
-            write_unsupported_element(writer) /* Not in amf0, TODO: use the amf3 embedding for every thing else */
+        writer.write_u16(0)?;
+        write_type_marker(writer, TypeMarker::ObjectEnd)?;
+        Ok(())
+    }
+
+    /// Write a AMF0 Value to the writer
+    pub fn write_value<'a, 'b: 'a, W: Write + 'a>(
+        &self,
+        writer: &mut W,
+        element: &'b Rc<Value<'_>>,
+    ) -> Result<()> {
+        match element.deref() {
+            Value::Number(n) => write_number_element(writer, *n),
+            Value::Bool(b) => write_bool_element(writer, *b),
+            Value::String(s) => self.write_string_element(writer, s),
+            Value::Object(_, elements, class_def) => {
+                if let Some(class_def) = class_def {
+                    self.write_typed_object_element(writer, &class_def.name, elements)
+                } else {
+                    self.write_object_element(writer, elements)
+                }
+            }
+            Value::Null => write_null_element(writer),
+            Value::Undefined => write_undefined_element(writer),
+            Value::StrictArray(a) => self.write_strict_array_element(writer, a.as_slice()),
+            Value::Date(d, tz) => write_date_element(writer, *d, *tz),
+            Value::Unsupported => write_unsupported_element(writer),
+            Value::XML(x, _string) => self.write_xml_element(writer, x),
+            Value::ECMAArray(dense, elems, elems_length) => {
+                self.write_mixed_array(writer, dense, elems, *elems_length)
+            }
+            #[cfg(feature = "amf3")]
+            Value::AMF3(e) => {
+                write_type_marker(writer, TypeMarker::AMF3)?;
+                let mut encoder = AMF3Encoder::default();
+                encoder.utf8_mode = self.utf8_mode;
+                encoder.surrogate_strings = self.surrogate_strings.clone();
+                encoder.write_value_element(writer, e)
+            }
+            Value::Reference(r) => write_reference_element(writer, r),
+            _ => {
+                write_unsupported_element(writer) /* Not in amf0, TODO: use the amf3 embedding for every thing else */
+            }
         }
     }
-}
 
-fn write_element<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, element: &'b Element) -> Result<()> {
-    write_string(writer, &element.name)?;
-    write_value(writer, &element.value)?;
-    Ok(())
-}
+    fn write_element<'a, 'b: 'a, W: Write + 'a>(
+        &self,
+        writer: &mut W,
+        element: &'b Element<'_>,
+    ) -> Result<()> {
+        write_string(writer, &self.encode_string(&element.name)).map_err(|e| {
+            let name: String = element.name.chars().take(32).collect();
+            crate::io::Error::new(e.kind(), format!("Name of element \"{}...\": {}", name, e))
+        })?;
+        self.write_value(writer, &element.value)?;
+        Ok(())
+    }
 
-fn write_element_and_padding<'a, 'b: 'a, W: Write + 'a>(
-    writer: &mut W,
-    element: &'b Element,
-) -> Result<()> {
-    write_element(writer, element)?;
-    writer.write_all(&PADDING)?;
-    Ok(())
+    fn write_element_and_padding<'a, 'b: 'a, W: Write + 'a>(
+        &self,
+        writer: &mut W,
+        element: &'b Element<'_>,
+    ) -> Result<()> {
+        self.write_element(writer, element)?;
+        writer.write_all(&PADDING)?;
+        Ok(())
+    }
+
+    /// Write a sequence of `PADDING` delimited elements
+    pub fn write_body<'a, 'b: 'a, W: Write + 'a>(
+        &self,
+        writer: &mut W,
+        elements: &'b [Element<'_>],
+    ) -> Result<()> {
+        for element in elements {
+            self.write_element_and_padding(writer, element)?;
+        }
+        Ok(())
+    }
 }
 
-pub(crate) fn write_body<'a, 'b: 'a, W: Write + 'a>(
+/// Write a AMF0 Value to the writer
+pub fn write_value<'a, 'b: 'a, W: Write + 'a>(
     writer: &mut W,
-    elements: &'b [Element],
+    element: &'b Rc<Value<'_>>,
 ) -> Result<()> {
-    for element in elements {
-        write_element_and_padding(writer, element)?;
-    }
-    Ok(())
+    AMF0Encoder::default().write_value(writer, element)
 }
@@ -1,14 +1,14 @@
-use std::{collections::BTreeMap, rc::Rc};
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
 
 use crate::types::{AMFVersion, Element, Lso, Reference, Value};
 
 use super::{ArrayWriter, CacheKey, ObjWriter, ObjectWriter};
 
 /// A writer for Amf0 encoded data
-#[derive(Default)]
 pub struct Amf0Writer {
     /// The elements present at the root level
-    pub(crate) elements: Vec<Element>,
+    pub(crate) elements: Vec<Element<'static>>,
 
     /// The current reference number
     pub(crate) ref_num: u16,
@@ -17,13 +17,25 @@ pub struct Amf0Writer {
     pub(crate) cache: BTreeMap<CacheKey, Reference>,
 }
 
+impl Default for Amf0Writer {
+    fn default() -> Self {
+        Self {
+            elements: Vec::new(),
+            // The body itself is reference 0, see `AMF0Decoder::parse_body`
+            ref_num: 1,
+            cache: BTreeMap::new(),
+        }
+    }
+}
+
 impl<'a> ObjWriter<'a> for Amf0Writer {
-    fn add_element(&mut self, name: &str, s: Value, inc_ref: bool) {
+    fn add_element(&mut self, name: &str, s: Value<'static>, inc_ref: bool) {
         if inc_ref {
             self.ref_num += 1;
         }
 
-        self.elements.push(Element::new(name, Rc::new(s)))
+        self.elements
+            .push(Element::new(name.to_string(), Rc::new(s)))
     }
 
     fn object<'c: 'a, 'd>(
@@ -101,7 +113,7 @@ impl<'a> ObjWriter<'a> for Amf0Writer {
 
 impl Amf0Writer {
     /// Produce an `Lso` with the given name
-    pub fn commit_lso(self, name: &str) -> Lso {
+    pub fn commit_lso(self, name: &str) -> Lso<'static> {
         Lso::new(self.elements, name, AMFVersion::AMF0)
     }
 }
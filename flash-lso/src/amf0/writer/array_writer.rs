@@ -1,5 +1,4 @@
-use std::rc::Rc;
-
+use crate::prelude::*;
 use crate::types::{Element, Reference, Value};
 
 use super::{CacheKey, ObjWriter, ObjectWriter};
@@ -7,14 +6,14 @@ use super::{CacheKey, ObjWriter, ObjectWriter};
 /// A writer for encoding `ECMAArray` contents
 pub struct ArrayWriter<'a, 'b> {
     /// The elements in this array
-    pub(crate) elements: Vec<Element>,
+    pub(crate) elements: Vec<Element<'static>>,
 
     /// The parent of this writer
     pub(crate) parent: &'a mut dyn ObjWriter<'b>,
 }
 
 impl<'a, 'b> ObjWriter<'a> for ArrayWriter<'a, 'b> {
-    fn add_element(&mut self, name: &str, s: Value, inc_ref: bool) {
+    fn add_element(&mut self, name: &str, s: Value<'static>, inc_ref: bool) {
         if inc_ref {
             self.make_reference();
         }
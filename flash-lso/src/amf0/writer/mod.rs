@@ -13,12 +13,12 @@ pub use object_writer::ObjectWriter;
 #[test]
 fn fff() {
     let mut w = Amf0Writer::default();
-    let (aw, _) = w.object(CacheKey::from_ptr(std::ptr::null::<u8>()));
+    let (aw, _) = w.object(CacheKey::from_ptr(core::ptr::null::<u8>()));
     let mut aw = aw.unwrap();
     {
         aw.string("asdf", "asfd");
         {
-            let (aw2, _) = aw.object(CacheKey::from_ptr(1 as *const u8));
+            let (aw2, _) = aw.object(CacheKey::from_ptr(core::ptr::dangling::<u8>()));
             let mut aw2 = aw2.unwrap();
             aw2.string("asf", "asdf");
             aw2.commit("asf");
@@ -1,3 +1,4 @@
+use crate::prelude::*;
 use crate::types::{Reference, Value};
 
 use super::{ArrayWriter, CacheKey, ObjectWriter};
@@ -5,7 +6,7 @@ use super::{ArrayWriter, CacheKey, ObjectWriter};
 /// A trait of common functions between writers
 pub trait ObjWriter<'a> {
     /// Add an element to this object
-    fn add_element(&mut self, name: &str, s: Value, inc_ref: bool);
+    fn add_element(&mut self, name: &str, s: Value<'static>, inc_ref: bool);
 
     /// Create a writer that can serialize an object
     ///
@@ -34,7 +35,7 @@ pub trait ObjWriter<'a> {
 
     /// Write a string
     fn string(&mut self, name: &str, s: &str) {
-        self.add_element(name, Value::String(s.to_string()), true);
+        self.add_element(name, Value::String(s.to_string().into()), true);
     }
 
     /// Write a number
@@ -69,7 +70,7 @@ pub trait ObjWriter<'a> {
 
     /// Write a XML
     fn xml(&mut self, name: &str, v: &str, s: bool) {
-        self.add_element(name, Value::XML(v.to_string(), s), true);
+        self.add_element(name, Value::XML(v.to_string().into(), s), true);
     }
 
     /// Create a reference in the root
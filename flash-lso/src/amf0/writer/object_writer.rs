@@ -1,5 +1,4 @@
-use std::rc::Rc;
-
+use crate::prelude::*;
 use crate::types::{Element, ObjectId, Reference, Value};
 
 use super::{ArrayWriter, CacheKey, ObjWriter};
@@ -7,19 +6,20 @@ use super::{ArrayWriter, CacheKey, ObjWriter};
 /// A writer for encoding the contents of a child object
 pub struct ObjectWriter<'a, 'b> {
     /// The elements of this object
-    pub(crate) elements: Vec<Element>,
+    pub(crate) elements: Vec<Element<'static>>,
 
     /// The parent of this writer
     pub(crate) parent: &'a mut dyn ObjWriter<'b>,
 }
 
 impl<'a, 'b> ObjWriter<'a> for ObjectWriter<'a, 'b> {
-    fn add_element(&mut self, name: &str, s: Value, inc_ref: bool) {
+    fn add_element(&mut self, name: &str, s: Value<'static>, inc_ref: bool) {
         if inc_ref {
             self.parent.make_reference();
         }
 
-        self.elements.push(Element::new(name, Rc::new(s)));
+        self.elements
+            .push(Element::new(name.to_string(), Rc::new(s)));
     }
 
     fn object<'c: 'a, 'd>(
@@ -1,4 +1,5 @@
 use crate::amf3::read::AMF3Decoder;
+use crate::prelude::*;
 
 use crate::amf3::write::AMF3Encoder;
 
@@ -6,22 +7,138 @@ use crate::types::Element;
 use crate::types::*;
 
 use crate::nom_utils::AMFResult;
-use std::rc::Rc;
 
 /// A trait to define encoding for custom types for use with Externalized objects
 pub trait CustomEncoder {
     /// This should implement the encoding of a given set of external elements for the given class definition
     /// Access to the AMF3Encoder is given to allow access to caches
     /// This implements the encoding side of externalized type support
-    fn encode(
+    fn encode<'v>(
         &self,
-        elements: &[Element],
+        elements: &[Element<'v>],
         class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
+        encoder: &AMF3Encoder<'v>,
     ) -> Vec<u8>;
 }
 
 //TODO: combine with trait
 /// Type used for specifying a custom decoder for a AMF3 external type
 pub type ExternalDecoderFn =
-    Rc<Box<dyn for<'a> Fn(&'a [u8], &mut AMF3Decoder) -> AMFResult<'a, Vec<Element>>>>;
+    Rc<Box<dyn for<'a> Fn(&'a [u8], &mut AMF3Decoder<'a>) -> AMFResult<'a, Vec<Element<'a>>>>>;
+
+/// A decoder for the body of an externalizable class, see `AMF3Decoder::register`
+///
+/// The elements it decodes become the custom elements of a `Value::Custom` with the class definition of the object.
+/// Use an `ExternalEncoder` for the same class to write them back out
+/// ```
+/// use flash_lso::amf3::custom_encoder::{ExternalDecoder, ExternalEncoder};
+/// use flash_lso::amf3::read::AMF3Decoder;
+/// use flash_lso::amf3::write::AMF3Encoder;
+/// use flash_lso::errors::Error;
+/// use flash_lso::read::Reader;
+/// use flash_lso::types::{AMFVersion, Attribute, ClassDefinition, Element, Lso, Value};
+/// use flash_lso::write::{write_to_bytes_with, Writer};
+/// use nom::number::complete::be_u32;
+/// use nom::IResult;
+///
+/// /// A class that writes its level and score as two big-endian u32s
+/// struct SaveBlob;
+///
+/// impl ExternalDecoder for SaveBlob {
+///     fn name(&self) -> &str {
+///         "com.game.SaveBlob"
+///     }
+///
+///     fn decode<'a>(
+///         &self,
+///         i: &'a [u8],
+///         _decoder: &mut AMF3Decoder<'a>,
+///     ) -> IResult<&'a [u8], Vec<Element<'a>>, Error<'a>> {
+///         let (i, level) = be_u32(i)?;
+///         let (i, score) = be_u32(i)?;
+///         Ok((i, vec![
+///             Element::new("level", Value::Number(level.into())),
+///             Element::new("score", Value::Number(score.into())),
+///         ]))
+///     }
+/// }
+///
+/// impl ExternalEncoder for SaveBlob {
+///     fn name(&self) -> &str {
+///         "com.game.SaveBlob"
+///     }
+///
+///     fn encode<'v>(
+///         &self,
+///         elements: &[Element<'v>],
+///         _class_def: &Option<ClassDefinition>,
+///         _encoder: &AMF3Encoder<'v>,
+///     ) -> Vec<u8> {
+///         elements
+///             .iter()
+///             .flat_map(|e| (e.as_f64().unwrap_or_default() as u32).to_be_bytes())
+///             .collect()
+///     }
+/// }
+///
+/// let blob = Value::Custom(
+///     vec![
+///         Element::new("level", Value::Number(3.0)),
+///         Element::new("score", Value::Number(1200.0)),
+///     ],
+///     vec![],
+///     Some(ClassDefinition {
+///         name: "com.game.SaveBlob".into(),
+///         attributes: Attribute::External.into(),
+///         static_properties: vec![],
+///     }),
+/// );
+/// let mut lso = Lso::new(vec![Element::new("save", blob)], "game", AMFVersion::AMF3);
+///
+/// let mut writer = Writer::default();
+/// writer.amf3_encoder.register(Box::new(SaveBlob));
+/// let bytes = write_to_bytes_with(&mut lso, writer).expect("Failed to write lso");
+///
+/// let mut reader = Reader::default();
+/// reader.amf3_decoder.register(Box::new(SaveBlob));
+/// assert_eq!(reader.parse(&bytes).expect("Failed to read lso"), lso);
+/// ```
+pub trait ExternalDecoder {
+    /// The name of the class this decodes
+    fn name(&self) -> &str;
+
+    /// Decode the body of an object of this class from the start of `i`, returning the input after it
+    ///
+    /// Values inside the body can be read with `decoder`, such as with `AMF3Decoder::parse_single_element`, so that
+    /// they share its reference tables
+    fn decode<'a>(
+        &self,
+        i: &'a [u8],
+        decoder: &mut AMF3Decoder<'a>,
+    ) -> AMFResult<'a, Vec<Element<'a>>>;
+}
+
+/// An encoder for the body of an externalizable class, see `AMF3Encoder::register` and `ExternalDecoder`
+pub trait ExternalEncoder {
+    /// The name of the class this encodes
+    fn name(&self) -> &str;
+
+    /// Encode the custom elements of a `Value::Custom` of this class, see `CustomEncoder::encode`
+    fn encode<'v>(
+        &self,
+        elements: &[Element<'v>],
+        class_def: &Option<ClassDefinition>,
+        encoder: &AMF3Encoder<'v>,
+    ) -> Vec<u8>;
+}
+
+impl CustomEncoder for Box<dyn ExternalEncoder> {
+    fn encode<'v>(
+        &self,
+        elements: &[Element<'v>],
+        class_def: &Option<ClassDefinition>,
+        encoder: &AMF3Encoder<'v>,
+    ) -> Vec<u8> {
+        self.as_ref().encode(elements, class_def, encoder)
+    }
+}
@@ -0,0 +1,99 @@
+use crate::prelude::*;
+use crate::types::{Element, ObjectId, Value};
+use core::hash::Hasher;
+
+/// Complex values that have already been written, looked up by structure, see `Value::structural_eq`
+#[derive(Default)]
+pub(crate) struct DedupTable<'v> {
+    values: HashMap<u64, Vec<(Rc<Value<'v>>, usize)>>,
+}
+
+impl<'v> DedupTable<'v> {
+    /// Get a stored value with the same structure and its reference index, if there is one
+    pub(crate) fn get(&self, value: &Value<'_>) -> Option<(Rc<Value<'v>>, usize)> {
+        self.values
+            .get(&structural_hash(value))?
+            .iter()
+            .find(|(v, _)| v.structural_eq(value))
+            .map(|(v, index)| (Rc::clone(v), *index))
+    }
+
+    /// Store a value with the given reference index
+    pub(crate) fn store(&mut self, value: &Rc<Value<'v>>, index: usize) {
+        self.values
+            .entry(structural_hash(value))
+            .or_default()
+            .push((Rc::clone(value), index));
+    }
+}
+
+fn structural_hash(value: &Value<'_>) -> u64 {
+    let mut hasher = Fnv(FNV_OFFSET);
+    value.structural_hash(&mut hasher);
+    hasher.finish()
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// An FNV-1a hasher, which is all that's needed as values with the same hash are still compared, and is available
+/// without `std`
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+/// Call `f` with the ids of each pair of objects at the same position in two values with the same structure
+///
+/// This is used when a value is written as a reference to an earlier one, so that `Amf3ObjectReference`s to objects
+/// inside it refer to the matching objects inside the earlier value
+pub(crate) fn for_each_object_pair(
+    earlier: &Value<'_>,
+    value: &Value<'_>,
+    f: &mut impl FnMut(ObjectId, ObjectId),
+) {
+    fn values(a: &[Rc<Value<'_>>], b: &[Rc<Value<'_>>], f: &mut impl FnMut(ObjectId, ObjectId)) {
+        a.iter()
+            .zip(b)
+            .for_each(|(a, b)| for_each_object_pair(a, b, f));
+    }
+    fn elements(a: &[Element<'_>], b: &[Element<'_>], f: &mut impl FnMut(ObjectId, ObjectId)) {
+        a.iter()
+            .zip(b)
+            .for_each(|(a, b)| for_each_object_pair(&a.value, &b.value, f));
+    }
+
+    match (earlier, value) {
+        (Value::Object(a_id, a, _), Value::Object(b_id, b, _)) => {
+            f(*a_id, *b_id);
+            elements(a, b, f);
+        }
+        (Value::ECMAArray(a_dense, a, _), Value::ECMAArray(b_dense, b, _)) => {
+            values(a_dense, b_dense, f);
+            elements(a, b, f);
+        }
+        (Value::StrictArray(a), Value::StrictArray(b))
+        | (Value::VectorObject(a, _, _), Value::VectorObject(b, _, _)) => values(a, b, f),
+        (Value::AMF3(a), Value::AMF3(b)) => for_each_object_pair(a, b, f),
+        (Value::Dictionary(a, _), Value::Dictionary(b, _)) => {
+            for ((a_k, a_v), (b_k, b_v)) in a.iter().zip(b) {
+                for_each_object_pair(a_k, b_k, f);
+                for_each_object_pair(a_v, b_v, f);
+            }
+        }
+        (Value::Custom(a_custom, a, _), Value::Custom(b_custom, b, _)) => {
+            elements(a_custom, b_custom, f);
+            elements(a, b, f);
+        }
+        _ => {}
+    }
+}
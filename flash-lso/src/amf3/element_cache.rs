@@ -1,11 +1,17 @@
 use crate::amf3::length::Length;
-use std::cell::RefCell;
-use std::fmt::Debug;
+use crate::prelude::*;
+use core::cell::RefCell;
+use core::fmt::Debug;
+use core::hash::Hash;
 
 /// Abstraction over the Amf3 caching mechanism
+///
+/// Items are kept in the order they were stored, which is their index in the table, along with a map from each item to
+/// its index so that looking up an item doesn't scan the table
 #[derive(Clone, Debug)]
 pub struct ElementCache<T> {
     cache: RefCell<Vec<T>>,
+    indices: RefCell<HashMap<T, usize>>,
 }
 
 impl<T> Default for ElementCache<T> {
@@ -13,25 +19,41 @@ impl<T> Default for ElementCache<T> {
     fn default() -> Self {
         ElementCache {
             cache: RefCell::new(Vec::new()),
+            indices: RefCell::new(HashMap::new()),
         }
     }
 }
 
-impl<T: PartialEq + Clone + Debug> ElementCache<T> {
+impl<T: Eq + Ord + Hash + Clone + Debug> ElementCache<T> {
     /// Check if the cache contains a given element
     #[inline]
     pub(crate) fn has(&self, val: &T) -> bool {
-        self.cache.borrow().contains(val)
+        self.indices.borrow().contains_key(val)
     }
 
     /// Add the given item to the cache, if the item already exists will do nothing
     #[inline]
     pub(crate) fn store(&self, val: T) {
         if !self.has(&val) {
-            self.cache.borrow_mut().push(val);
+            let mut cache = self.cache.borrow_mut();
+            self.indices.borrow_mut().insert(val.clone(), cache.len());
+            cache.push(val);
         }
     }
 
+    /// Get the number of items in the cache
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.cache.borrow().len()
+    }
+
+    /// Remove every item from the cache
+    #[inline]
+    pub(crate) fn clear(&self) {
+        self.cache.borrow_mut().clear();
+        self.indices.borrow_mut().clear();
+    }
+
     /// Retrieve the item at the given index from the cache
     #[inline]
     pub fn get_element(&self, index: usize) -> Option<T> {
@@ -41,7 +63,7 @@ impl<T: PartialEq + Clone + Debug> ElementCache<T> {
     /// Retrieve the index for the given value
     #[inline]
     pub(crate) fn get_index(&self, val: T) -> Option<usize> {
-        self.cache.borrow().iter().position(|i| *i == val)
+        self.indices.borrow().get(&val).copied()
     }
 
     /// Get a Length reference to an item in the cache
@@ -66,17 +88,18 @@ impl<T: PartialEq + Clone + Debug> ElementCache<T> {
     }
 }
 
-impl<T: PartialEq + Clone + Debug> ElementCache<Vec<T>> {
+impl<T: Eq + Ord + Hash + Clone + Debug> ElementCache<Vec<T>> {
     /// See #store, will convert slices of &\[T\] into Vec<T> before storing
     #[inline]
-    #[allow(unused)]
     pub(crate) fn store_slice(&self, val: &[T]) {
-        self.store(val.to_vec());
+        if self.get_slice_index(val).is_none() {
+            self.store(val.to_vec());
+        }
     }
 
-    /// See #get_index, will convert slices of &\[T\] into Vec<T> before retrieving
+    /// See #get_index, looks up slices of &\[T\] without converting them into Vec<T>
     #[inline]
     pub fn get_slice_index(&self, val: &[T]) -> Option<usize> {
-        self.get_index(val.to_vec())
+        self.indices.borrow().get(val).copied()
     }
 }
@@ -0,0 +1,404 @@
+use crate::amf3::length::Length;
+use crate::amf3::read::{
+    check_count, read_be_block, read_int, read_int_signed, read_length, AMF3Decoder, REFERENCE_FLAG,
+};
+use crate::amf3::type_marker::TypeMarker;
+use crate::errors::Error;
+use crate::nom_utils::AMFResult;
+use crate::prelude::*;
+use crate::types::{Attribute, ClassDefinition, ObjectId, Value};
+use crate::PADDING;
+use nom::bytes::complete::{tag, take};
+use nom::combinator::map_res;
+use nom::error::{make_error, ErrorKind};
+use nom::multi::many_m_n;
+use nom::number::complete::{be_f64, be_u8};
+use nom::Err;
+
+use core::convert::TryInto;
+
+/// A single step of a streamed AMF3 decode
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event<'a> {
+    /// A value with no children, this is also used for byte arrays, dates, xml and numeric vectors
+    Value(Value<'a>),
+
+    /// A reference to an earlier object, array, dictionary or other complex value, by its index in the object reference table
+    Reference(usize),
+
+    /// The name of the next value in the enclosing object, array or body
+    Property(Cow<'a, str>),
+
+    /// The start of an object, its properties follow as `Property` / value pairs
+    BeginObject(ClassDefinition),
+
+    /// The end of the current object
+    EndObject,
+
+    /// The start of an array with the given dense length
+    /// Associative entries come first as `Property` / value pairs, followed by the dense values
+    BeginArray(u32),
+
+    /// The end of the current array
+    EndArray,
+
+    /// The start of an object vector with the given type name and fixed length flag
+    BeginVector(String, bool),
+
+    /// The end of the current object vector
+    EndVector,
+
+    /// The start of a dictionary with the given weak keys flag, keys and values alternate until the end
+    BeginDictionary(bool),
+
+    /// The end of the current dictionary
+    EndDictionary,
+}
+
+enum Frame<'a> {
+    /// Back to back values until the input runs out
+    Values,
+    /// A Lso body of named elements separated by padding
+    Body { needs_padding: bool },
+    /// The remaining properties of an object
+    Object {
+        static_properties: vec::IntoIter<String>,
+        dynamic: bool,
+    },
+    /// The associative portion of an array, followed by `dense` values
+    Associative { dense: u32 },
+    /// A fixed number of values followed by the given end event
+    Sequence { remaining: usize, end: Event<'a> },
+}
+
+/// Decodes AMF3 data as a stream of events without building the `Value` tree
+///
+/// Complex values are registered in the object reference table as placeholders so that references can be reported by index,
+/// the values themselves are never kept. As a result references to streamed data from inside an external decoder will not
+/// see the original value.
+///
+/// Example of use
+/// ```
+/// use flash_lso::amf3::events::{Event, EventReader};
+/// use flash_lso::read::Reader;
+/// let data = std::fs::read("tests/sol/AS3-Demo.sol").expect("Couldn't read file");
+/// let (body, _) = Reader::default().parse_header(&data).expect("Failed to parse header");
+/// for event in EventReader::body(body) {
+///     if let Event::Property(name) = event.expect("Failed to read event") {
+///         println!("{}", name);
+///     }
+/// }
+/// ```
+pub struct EventReader<'a> {
+    /// Handles the string and trait caches as well as external decoders
+    pub decoder: AMF3Decoder<'a>,
+
+    i: &'a [u8],
+    stack: Vec<Frame<'a>>,
+    value_pending: bool,
+    placeholder: Rc<Value<'a>>,
+}
+
+impl<'a> EventReader<'a> {
+    fn with_frame(i: &'a [u8], frame: Frame<'a>) -> Self {
+        Self {
+            decoder: AMF3Decoder::default(),
+            i,
+            stack: vec![frame],
+            value_pending: false,
+            placeholder: Rc::new(Value::Object(ObjectId::INVALID, Vec::new(), None)),
+        }
+    }
+
+    /// Stream a sequence of AMF3 values from the given slice
+    pub fn new(i: &'a [u8]) -> Self {
+        Self::with_frame(i, Frame::Values)
+    }
+
+    /// Stream an AMF3 Lso body from the given slice, each element is reported as a `Property` followed by its value
+    pub fn body(i: &'a [u8]) -> Self {
+        Self::with_frame(
+            i,
+            Frame::Body {
+                needs_padding: false,
+            },
+        )
+    }
+
+    /// The input that has not been read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.i
+    }
+
+    fn register(&mut self) {
+        self.decoder
+            .object_reference_table
+            .push(Rc::clone(&self.placeholder));
+    }
+
+    fn read_size(&mut self, i: &'a [u8]) -> AMFResult<'a, Result<usize, Event<'a>>> {
+        let (i, len) = read_length(i)?;
+        match len {
+            Length::Reference(index) => {
+                if index >= self.decoder.object_reference_table.len() {
+                    return Err(Err::Error(make_error(i, ErrorKind::Digit)));
+                }
+                Ok((i, Err(Event::Reference(index))))
+            }
+            Length::Size(len) => {
+                let len: usize = len
+                    .try_into()
+                    .map_err(|_| Err::Error(make_error(i, ErrorKind::Digit)))?;
+                self.register();
+                Ok((i, Ok(len)))
+            }
+        }
+    }
+
+    fn read_block(
+        &mut self,
+        i: &'a [u8],
+        parser: impl FnOnce(&mut AMF3Decoder<'a>, &'a [u8], usize) -> AMFResult<'a, Value<'a>>,
+    ) -> AMFResult<'a, Event<'a>> {
+        match self.read_size(i)? {
+            (i, Ok(len)) => {
+                let (i, value) = parser(&mut self.decoder, i, len)?;
+                Ok((i, Event::Value(value)))
+            }
+            (i, Err(reference)) => Ok((i, reference)),
+        }
+    }
+
+    fn read_object(&mut self, i: &'a [u8]) -> AMFResult<'a, Event<'a>> {
+        let (i, length) = read_int(i)?;
+
+        if length & REFERENCE_FLAG == 0 {
+            let index = (length >> 1) as usize;
+            if index >= self.decoder.object_reference_table.len() {
+                return Err(Err::Error(make_error(i, ErrorKind::Digit)));
+            }
+            return Ok((i, Event::Reference(index)));
+        }
+        self.register();
+
+        let (i, class_def) = self.decoder.parse_class_def(length >> 1, i)?;
+
+        if class_def.attributes.contains(Attribute::External) {
+            let (i, value) = self.decoder.parse_external(i, class_def)?;
+            return Ok((i, Event::Value(value)));
+        }
+
+        self.stack.push(Frame::Object {
+            static_properties: class_def.static_properties.clone().into_iter(),
+            dynamic: class_def.attributes.contains(Attribute::Dynamic),
+        });
+        Ok((i, Event::BeginObject(class_def)))
+    }
+
+    fn read_value(&mut self, i: &'a [u8]) -> AMFResult<'a, Event<'a>> {
+        let (i, type_) = self.decoder.read_type_marker(i)?;
+
+        match type_ {
+            TypeMarker::Undefined => Ok((i, Event::Value(Value::Undefined))),
+            TypeMarker::Null => Ok((i, Event::Value(Value::Null))),
+            TypeMarker::False => Ok((i, Event::Value(Value::Bool(false)))),
+            TypeMarker::True => Ok((i, Event::Value(Value::Bool(true)))),
+            TypeMarker::Integer => {
+                let (i, v) = read_int_signed(i)?;
+                Ok((i, Event::Value(Value::Integer(v))))
+            }
+            TypeMarker::Number => {
+                let (i, v) = be_f64(i)?;
+                Ok((i, Event::Value(Value::Number(v))))
+            }
+            TypeMarker::String => {
+                let (i, s) = self.decoder.parse_string(i)?;
+                Ok((i, Event::Value(Value::String(s))))
+            }
+            TypeMarker::Xml | TypeMarker::XmlString => {
+                let string = type_ == TypeMarker::XmlString;
+                self.read_block(i, |decoder, i, len| {
+                    let (mode, surrogates) = (decoder.utf8_mode, &decoder.surrogate_strings);
+                    let (i, data) = map_res(take(len), |bytes| {
+                        mode.decode_with(bytes, surrogates).ok_or(())
+                    })(i)?;
+                    Ok((i, Value::XML(data, string)))
+                })
+            }
+            TypeMarker::Date => self.read_block(i, |_decoder, i, _len| {
+                let (i, ms) = be_f64(i)?;
+                Ok((i, Value::Date(ms, None)))
+            }),
+            TypeMarker::ByteArray => self.read_block(i, |_decoder, i, len| {
+                let (i, bytes) = take(len)(i)?;
+                Ok((i, Value::ByteArray(Cow::Borrowed(bytes))))
+            }),
+            TypeMarker::VectorInt => self.read_block(i, |_decoder, i, len| {
+                let (i, fixed_length) = be_u8(i)?;
+                let (i, ints) = read_be_block(i, len, i32::from_be_bytes)?;
+                Ok((i, Value::VectorInt(ints, fixed_length != 0)))
+            }),
+            TypeMarker::VectorUInt => self.read_block(i, |_decoder, i, len| {
+                let (i, fixed_length) = be_u8(i)?;
+                let (i, ints) = read_be_block(i, len, u32::from_be_bytes)?;
+                Ok((i, Value::VectorUInt(ints, fixed_length != 0)))
+            }),
+            TypeMarker::VectorDouble => self.read_block(i, |_decoder, i, len| {
+                let (i, fixed_length) = be_u8(i)?;
+                check_count(i, len, 8)?;
+                let (i, numbers) = many_m_n(len, len, be_f64)(i)?;
+                Ok((i, Value::VectorDouble(numbers, fixed_length != 0)))
+            }),
+            TypeMarker::Object => self.read_object(i),
+            TypeMarker::Array => match self.read_size(i)? {
+                (i, Ok(len)) => {
+                    check_count(i, len, 1)?;
+                    self.stack.push(Frame::Associative { dense: len as u32 });
+                    Ok((i, Event::BeginArray(len as u32)))
+                }
+                (i, Err(reference)) => Ok((i, reference)),
+            },
+            TypeMarker::VectorObject => match self.read_size(i)? {
+                (i, Ok(len)) => {
+                    let (i, fixed_length) = be_u8(i)?;
+                    let (i, object_type_name) = self.decoder.parse_string(i)?;
+                    check_count(i, len, 1)?;
+                    self.stack.push(Frame::Sequence {
+                        remaining: len,
+                        end: Event::EndVector,
+                    });
+                    Ok((
+                        i,
+                        Event::BeginVector(object_type_name.into_owned(), fixed_length != 0),
+                    ))
+                }
+                (i, Err(reference)) => Ok((i, reference)),
+            },
+            TypeMarker::Dictionary => match self.read_size(i)? {
+                (i, Ok(len)) => {
+                    let (i, weak_keys) = be_u8(i)?;
+                    // Each (key, value) pair is at least two type markers
+                    check_count(i, len, 2)?;
+                    self.stack.push(Frame::Sequence {
+                        remaining: len * 2,
+                        end: Event::EndDictionary,
+                    });
+                    Ok((i, Event::BeginDictionary(weak_keys != 0)))
+                }
+                (i, Err(reference)) => Ok((i, reference)),
+            },
+        }
+    }
+
+    fn read_event(&mut self) -> Result<Option<Event<'a>>, Err<Error<'a>>> {
+        loop {
+            if self.value_pending {
+                self.value_pending = false;
+                let (i, event) = self.read_value(self.i)?;
+                self.i = i;
+                return Ok(Some(event));
+            }
+
+            let i = self.i;
+            let frame = match self.stack.last_mut() {
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+
+            match frame {
+                Frame::Values => {
+                    if i.is_empty() {
+                        self.stack.pop();
+                        return Ok(None);
+                    }
+                    self.value_pending = true;
+                }
+                Frame::Body { needs_padding } => {
+                    let i = if *needs_padding {
+                        tag(PADDING)(i)?.0
+                    } else {
+                        i
+                    };
+                    *needs_padding = true;
+                    self.i = i;
+                    if i.is_empty() {
+                        self.stack.pop();
+                        return Ok(None);
+                    }
+
+                    let (i, name) = self.decoder.parse_string(i)?;
+                    self.i = i;
+                    self.value_pending = true;
+                    return Ok(Some(Event::Property(name)));
+                }
+                Frame::Object {
+                    static_properties,
+                    dynamic,
+                } => {
+                    if let Some(name) = static_properties.next() {
+                        self.value_pending = true;
+                        return Ok(Some(Event::Property(Cow::Owned(name))));
+                    }
+
+                    if *dynamic {
+                        let (i, key) = self.decoder.parse_byte_stream(i)?;
+                        self.i = i;
+                        if !key.is_empty() {
+                            let name = self.decoder.decode_string(i, key)?;
+                            self.value_pending = true;
+                            return Ok(Some(Event::Property(name)));
+                        }
+                    }
+
+                    self.stack.pop();
+                    return Ok(Some(Event::EndObject));
+                }
+                Frame::Associative { dense } => {
+                    let dense = *dense;
+                    let (i, key) = self.decoder.parse_byte_stream(i)?;
+                    self.i = i;
+                    if key.is_empty() {
+                        let frame = self
+                            .stack
+                            .last_mut()
+                            .ok_or_else(|| Err::Error(make_error(i, ErrorKind::Eof)))?;
+                        *frame = Frame::Sequence {
+                            remaining: dense as usize,
+                            end: Event::EndArray,
+                        };
+                        continue;
+                    }
+
+                    let name = self.decoder.decode_string(i, key)?;
+                    self.value_pending = true;
+                    return Ok(Some(Event::Property(name)));
+                }
+                Frame::Sequence { remaining, end } => {
+                    if *remaining == 0 {
+                        let end = end.clone();
+                        self.stack.pop();
+                        return Ok(Some(end));
+                    }
+                    *remaining -= 1;
+                    self.value_pending = true;
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for EventReader<'a> {
+    type Item = Result<Event<'a>, Err<Error<'a>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_event() {
+            Ok(event) => event.map(Ok),
+            Err(e) => {
+                // Stop after the first error, the input position is no longer meaningful
+                self.stack.clear();
+                self.value_pending = false;
+                Some(Err(e))
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::amf3::write::AMF3Encoder;
-use std::io::Write;
+use crate::io::Write;
 
 #[derive(Copy, Clone, Debug, Eq, Ord, PartialOrd, PartialEq)]
 pub(crate) enum Length {
@@ -16,32 +16,25 @@ impl Length {
         matches!(self, Length::Size(_))
     }
 
-    pub(crate) fn as_position(&self) -> Option<usize> {
-        match self {
-            Length::Reference(x) => Some(*x),
-            _ => None,
-        }
-    }
-
     pub(crate) fn write<'a, 'b: 'a, W: Write + 'a>(
         &self,
         writer: &mut W,
-        amf3: &AMF3Encoder,
-    ) -> std::io::Result<()> {
+        amf3: &AMF3Encoder<'_>,
+    ) -> crate::io::Result<()> {
         write_length(writer, amf3, self)
     }
 }
 
 fn write_length<'a, 'b: 'a, W: Write + 'a>(
     writer: &mut W,
-    amf3: &AMF3Encoder,
+    amf3: &AMF3Encoder<'_>,
     s: &Length,
-) -> std::io::Result<()> {
+) -> crate::io::Result<()> {
     match s {
         Length::Size(x) => {
             // With the last bit set
-            amf3.write_int(writer, ((x << 1) | 0b1) as i32)
+            amf3.write_u29(writer, (u64::from(*x) << 1) | 0b1)
         }
-        Length::Reference(x) => amf3.write_int(writer, (x << 1) as i32),
+        Length::Reference(x) => amf3.write_u29(writer, (*x as u64) << 1),
     }
 }
@@ -1,12 +1,37 @@
+use crate::errors::Error;
+use crate::nom_utils::AMFResult;
+use crate::prelude::*;
+use crate::types::{AMFVersion, Value};
+
 /// Support for custom encoders / decoders
 pub mod custom_encoder;
+/// Structural deduplication of values when encoding
+mod dedup;
 /// Cache pool for the 3 amf3 cache types
 pub mod element_cache;
+/// Streaming decoding of AMF3 data as events
+pub mod events;
 /// Abstraction over the AMF3 length and reference types
 pub mod length;
 /// Reading of AMF3 data
 pub mod read;
 /// AMF3 type markers
-mod type_marker;
+pub(crate) mod type_marker;
 /// Writing of AMF3 data
 pub mod write;
+
+/// Read a single AMF3 value from the start of a slice, without an lso header, returning it with the data after it
+///
+/// This is `decode_value` with `AMFVersion::AMF3`, the string, trait and object tables start empty so references can
+/// only refer to values earlier in the same value. Use `read::AMF3Decoder` to keep the tables across several values
+pub fn decode(i: &[u8]) -> AMFResult<'_, Value<'_>> {
+    crate::read::decode_value(i, AMFVersion::AMF3)
+}
+
+/// Write a single value as AMF3, without an lso header, this is the inverse of `decode`
+///
+/// This is `encode_value` with `AMFVersion::AMF3`, each call starts with empty reference tables so only values shared
+/// within this value are written as references
+pub fn encode<'a>(value: &Value<'_>) -> Result<Vec<u8>, Error<'a>> {
+    crate::write::encode_value(value, AMFVersion::AMF3)
+}
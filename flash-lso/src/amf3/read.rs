@@ -1,8 +1,12 @@
-use crate::amf3::custom_encoder::ExternalDecoderFn;
+use crate::amf3::custom_encoder::{ExternalDecoder, ExternalDecoderFn};
 use crate::amf3::type_marker::TypeMarker;
+use crate::prelude::*;
 
 use crate::amf3::length::Length;
-use crate::nom_utils::AMFResult;
+use crate::errors::{at_path, Error, PathSegment};
+use crate::nom_utils::{check_alloc, AMFResult};
+use crate::read::{DEFAULT_MAX_DEPTH, VALUE_ALLOC};
+use crate::spans::SpanRecorder;
 use crate::types::*;
 use crate::types::{Element, Value};
 use crate::PADDING;
@@ -10,16 +14,44 @@ use enumset::EnumSet;
 use nom::bytes::complete::{tag, take};
 use nom::combinator::{map, map_res};
 use nom::error::{make_error, ErrorKind};
-use nom::lib::std::collections::HashMap;
 use nom::multi::{many_m_n, separated_list0};
-use nom::number::complete::{be_f64, be_i32, be_u32, be_u8};
+use nom::number::complete::{be_f64, be_u8};
 use nom::Err;
 
-use std::convert::{TryFrom, TryInto};
-use std::ops::{Deref, DerefMut};
-use std::rc::Rc;
+use core::convert::{TryFrom, TryInto};
+use core::ops::Deref;
 
-const REFERENCE_FLAG: u32 = 0x01;
+pub(crate) const REFERENCE_FLAG: u32 = 0x01;
+
+/// Check that there is enough input left for `count` items of at least `size` bytes each
+///
+/// Every declared count must pass this before anything is allocated for it, this prevents OOM errors with v.large
+/// vecs, arrays and dicts
+pub(crate) fn check_count(i: &[u8], count: usize, size: usize) -> Result<usize, Err<Error<'_>>> {
+    count
+        .checked_mul(size)
+        .filter(|byte_len| *byte_len <= i.len())
+        .ok_or_else(|| Err::Error(make_error(i, ErrorKind::TooLarge)))
+}
+
+/// Read `len` big-endian 4 byte values in a single pass
+///
+/// The declared length is checked against the remaining input up front, see `check_count`
+pub(crate) fn read_be_block<T>(
+    i: &[u8],
+    len: usize,
+    from_be: fn([u8; 4]) -> T,
+) -> AMFResult<'_, Vec<T>> {
+    let byte_len = check_count(i, len, 4)?;
+    let (block, rest) = i.split_at(byte_len);
+
+    let values = block
+        .chunks_exact(4)
+        .map(|chunk| from_be([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect();
+
+    Ok((rest, values))
+}
 
 #[cfg(fuzzing)]
 /// For fuzzing
@@ -28,7 +60,7 @@ pub fn fuzz_read_int_signed(i: &[u8]) -> AMFResult<'_, i32> {
 }
 
 #[allow(clippy::unusual_byte_groupings)]
-fn read_int_signed(i: &[u8]) -> AMFResult<'_, i32> {
+pub(crate) fn read_int_signed(i: &[u8]) -> AMFResult<'_, i32> {
     // Read the first byte of the number
     let (mut i, num) = be_u8(i)?;
     let mut value = (num & 0b01111111) as i32;
@@ -64,7 +96,7 @@ pub fn fuzz_read_int(i: &[u8]) -> AMFResult<'_, u32> {
 }
 
 #[allow(clippy::unusual_byte_groupings)]
-fn read_int(i: &[u8]) -> AMFResult<'_, u32> {
+pub(crate) fn read_int(i: &[u8]) -> AMFResult<'_, u32> {
     // Read the first byte of the number
     let (mut i, num) = be_u8(i)?;
     let mut value = (num & 0b01111111) as u32;
@@ -85,17 +117,14 @@ fn read_int(i: &[u8]) -> AMFResult<'_, u32> {
     let (i, num) = be_u8(i)?;
     value = (value << 8) | (num as u32);
 
-    if value & 0b000_1000000_0000000_0000000_00000000 != 0 {
-        value <<= 1;
-        value += 1;
-    }
-
+    // Unlike `read_int_signed` the top bit is part of the value, not a sign
     Ok((i, value))
 }
 
 #[cfg(test)]
 mod read_number_tests {
-    use crate::amf3::read::{read_int, read_int_signed};
+    use crate::amf3::length::Length;
+    use crate::amf3::read::{read_int, read_int_signed, read_length};
 
     #[test]
     fn test_read_1byte_number() {
@@ -113,6 +142,19 @@ mod read_number_tests {
         assert_eq!(-268435455, read_int_signed(&[192, 128, 128, 1]).unwrap().1);
     }
 
+    #[test]
+    fn read_signed_boundaries() {
+        assert_eq!(-1, read_int_signed(&[0xFF, 0xFF, 0xFF, 0xFF]).unwrap().1);
+        assert_eq!(
+            -(1 << 28),
+            read_int_signed(&[0xC0, 0x80, 0x80, 0x00]).unwrap().1
+        );
+        assert_eq!(
+            (1 << 28) - 1,
+            read_int_signed(&[0xBF, 0xFF, 0xFF, 0xFF]).unwrap().1
+        );
+    }
+
     #[test]
     fn test_read_1byte_number_unsigned() {
         assert_eq!(0b00101011, read_int(&[0b00101011]).unwrap().1)
@@ -126,11 +168,32 @@ mod read_number_tests {
 
     #[test]
     fn read_neg_number_unsigned() {
-        assert_eq!(536870915, read_int(&[192, 128, 128, 1]).unwrap().1);
+        assert_eq!(268435457, read_int(&[192, 128, 128, 1]).unwrap().1);
+        assert_eq!(
+            (1 << 29) - 1,
+            read_int(&[0xFF, 0xFF, 0xFF, 0xFF]).unwrap().1
+        );
+    }
+
+    #[test]
+    fn read_length_boundaries() {
+        assert_eq!(
+            Length::Size(0x0FFF_FFFF),
+            read_length(&[0xFF, 0xFF, 0xFF, 0xFF]).unwrap().1
+        );
+        assert_eq!(
+            Length::Reference(0x0FFF_FFFF),
+            read_length(&[0xFF, 0xFF, 0xFF, 0xFE]).unwrap().1
+        );
+        // Only the first 3 bytes carry a continuation bit, so a U29 is never more than 4 bytes
+        assert_eq!(
+            (&[0x01][..], 0x1FFF_FFFF),
+            read_int(&[0xFF, 0xFF, 0xFF, 0xFF, 0x01]).unwrap()
+        );
     }
 }
 
-fn read_length(i: &[u8]) -> AMFResult<'_, Length> {
+pub(crate) fn read_length(i: &[u8]) -> AMFResult<'_, Length> {
     let (i, val) = read_int(i)?;
     Ok((
         i,
@@ -141,56 +204,222 @@ fn read_length(i: &[u8]) -> AMFResult<'_, Length> {
     ))
 }
 
-fn parse_element_int(i: &[u8]) -> AMFResult<'_, Rc<Value>> {
+fn parse_element_int(i: &[u8]) -> AMFResult<'_, Rc<Value<'_>>> {
     let (i, s) = map(read_int_signed, Value::Integer)(i)?;
     Ok((i, Rc::new(s)))
 }
 
+/// Options controlling how AMF3 is decoded
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DecoderOptions {
+    /// Decode externalizable objects, such as Flex types, into elements with their registered decoder
+    ///
+    /// When unset the decoder is only used to find the end of the object, which is kept as its original bytes in a
+    /// `Value::RawExternal` so that it is re-encoded exactly. Externalizable objects without a registered decoder are
+    /// handled as set by `unknown_external` either way, as their length can't be known
+    pub decode_flex: bool,
+
+    /// How externalizable objects without a registered decoder are handled
+    pub unknown_external: UnknownExternal,
+
+    /// Count how many times each entry in the object reference table is referenced, see
+    /// `AMF3Decoder::reference_count`
+    ///
+    /// This is diagnostic information for finding which values shared identity in the input
+    pub record_references: bool,
+
+    /// The deepest nesting of values that can be read, deeper values fail with `Error::DepthExceeded`
+    ///
+    /// Values inside externalizable objects count towards this, as their decoders read them with
+    /// `AMF3Decoder::parse_single_element`
+    pub max_depth: usize,
+
+    /// The budget in bytes for the values read, see `Reader::set_max_alloc`
+    pub max_alloc: usize,
+}
+
+impl Default for DecoderOptions {
+    fn default() -> Self {
+        Self {
+            decode_flex: true,
+            unknown_external: UnknownExternal::default(),
+            record_references: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_alloc: usize::MAX,
+        }
+    }
+}
+
+/// How to handle an externalizable object that has no registered decoder
+///
+/// The layout of an externalizable object is defined by its class, so without a decoder there is no way to know where it
+/// ends. The fallbacks here are guesses and may produce garbage or fail later in the input
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum UnknownExternal {
+    /// Stop with an error
+    #[default]
+    Error,
+
+    /// Try to read the object as the properties of a dynamic object, as many classes write themselves this way
+    ///
+    /// The guessed properties are given as the regular elements of a `Value::Custom` with no custom elements, which marks
+    /// them as uncertain. If this fails then the object is read as with `Remaining`
+    Dynamic,
+
+    /// Keep all of the remaining input as the raw bytes of the object, see `Value::raw_external_bytes`
+    ///
+    /// This is only useful when the object is the last value in the input, such as a single value read with
+    /// `parse_single_element`, anything following it can't be read
+    Remaining,
+}
+
 /// Handles decoding AMF3
+///
+/// The values read borrow from the input, so a decoder can only read from inputs that live for `'a`
 #[derive(Default)]
-pub struct AMF3Decoder {
+pub struct AMF3Decoder<'a> {
     /// The table used to cache repeated byte strings
-    pub string_reference_table: Vec<Vec<u8>>,
+    pub string_reference_table: Vec<&'a [u8]>,
 
     /// The table used to cache repeated trait definitions
     pub trait_reference_table: Vec<ClassDefinition>,
 
     /// The table used to cache repeated objects
-    pub object_reference_table: Vec<Rc<Value>>,
+    pub object_reference_table: Vec<Rc<Value<'a>>>,
 
     /// Encoders used for handling externalized types
     pub external_decoders: HashMap<String, ExternalDecoderFn>,
 
+    /// How strings are decoded
+    pub utf8_mode: Utf8Mode,
+
+    /// Where the form of strings read with `Utf8Mode::Flash` is recorded, see `AMF0Decoder::surrogate_strings`
+    pub surrogate_strings: SurrogateStrings,
+
+    /// Options controlling the decoding
+    pub options: DecoderOptions,
+
     /// Tracks the id of the last object we have read, used to generate `ObjectId`s for `Amf3Reference`
     /// Not an `ObjectId` itself as they don't impl `Default`
-    object_id: i64,
+    pub(crate) object_id: i64,
+
+    /// The number of values being read that contain the value being read now
+    pub(crate) depth: usize,
+
+    /// The bytes counted against `DecoderOptions::max_alloc` so far
+    pub(crate) allocated: usize,
+
+    /// The indices in `object_reference_table` of the values being read, which can't be referenced until they are done
+    pending: Vec<usize>,
+
+    /// The number of references to each entry of `object_reference_table`, when `DecoderOptions::record_references`
+    /// is set
+    reference_counts: Vec<usize>,
+
+    /// The path to the value that last failed to decode, innermost first
+    pub(crate) error_path: Vec<PathSegment>,
+
+    /// The error of the element that ended the body, which isn't returned as the body ends without an error there
+    pub(crate) element_error: Option<Err<Error<'a>>>,
+
+    /// Records the extent of each value read, when reading with `Reader::parse_annotated`
+    pub(crate) spans: Option<SpanRecorder>,
 }
 
-fn parse_element_number(i: &[u8]) -> AMFResult<'_, Rc<Value>> {
+fn parse_element_number(i: &[u8]) -> AMFResult<'_, Rc<Value<'_>>> {
     let (i, v) = map(be_f64, Value::Number)(i)?;
     Ok((i, Rc::new(v)))
 }
 
-impl AMF3Decoder {
-    fn parse_element_string<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+impl<'a> AMF3Decoder<'a> {
+    /// Decode objects of the class named by `decoder` with it, replacing any decoder already used for that class
+    pub fn register(&mut self, decoder: Box<dyn ExternalDecoder>) {
+        let name = decoder.name().to_string();
+        let decoder: Rc<dyn ExternalDecoder> = decoder.into();
+        let decode: ExternalDecoderFn = Rc::new(Box::new(move |i, this| decoder.decode(i, this)));
+        self.external_decoders.insert(name, decode);
+    }
+
+    /// Get the path to the value that failed to decode after an error, outermost first
+    pub fn error_path(&self) -> Vec<PathSegment> {
+        self.error_path.iter().rev().cloned().collect()
+    }
+
+    /// Parse a value, recording the given segment of the path to it if it fails
+    fn parse_at(
+        &mut self,
+        i: &'a [u8],
+        segment: impl FnOnce() -> PathSegment,
+    ) -> AMFResult<'a, Rc<Value<'a>>> {
+        if self.spans.is_none() {
+            let result = self.parse_single_element(i);
+            return at_path(&mut self.error_path, segment, result);
+        }
+
+        let segment = segment();
+        if let Some(spans) = &mut self.spans {
+            spans.push(&segment);
+        }
+        let result = self.parse_single_element(i);
+        if let Some(spans) = &mut self.spans {
+            if let Ok((j, _)) = &result {
+                spans.record(i.len(), j.len());
+            }
+            spans.pop();
+        }
+        at_path(&mut self.error_path, || segment, result)
+    }
+
+    /// Count `count` items of `size` bytes against the allocation budget
+    fn allocate(&mut self, i: &'a [u8], count: usize, size: usize) -> Result<(), Err<Error<'a>>> {
+        self.allocated = check_alloc(i, self.allocated, self.options.max_alloc, count, size)?;
+        Ok(())
+    }
+
+    /// Check that there is enough of the allocation budget left for `count` values, which are counted as they are read
+    fn check_values_alloc(&self, i: &'a [u8], count: usize) -> Result<(), Err<Error<'a>>> {
+        check_alloc(
+            i,
+            self.allocated,
+            self.options.max_alloc,
+            count,
+            VALUE_ALLOC,
+        )?;
+        Ok(())
+    }
+
+    fn parse_element_string(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         let (i, s) = map(|i| self.parse_string(i), Value::String)(i)?;
         Ok((i, Rc::new(s)))
     }
 
     #[cfg(fuzzing)]
     /// For fuzzing
-    pub fn fuzz_parse_string<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, String> {
+    pub fn fuzz_parse_string(&mut self, i: &'a [u8]) -> AMFResult<'a, Cow<'a, str>> {
         self.parse_string(i)
     }
 
-    fn parse_string<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, String> {
+    pub(crate) fn parse_string(&mut self, i: &'a [u8]) -> AMFResult<'a, Cow<'a, str>> {
         let (i, bytes) = self.parse_byte_stream(i)?;
-        let bytes_str =
-            String::from_utf8(bytes).map_err(|_| Err::Error(make_error(i, ErrorKind::Alpha)))?;
+        let bytes_str = self.decode_string(i, bytes)?;
         Ok((i, bytes_str))
     }
 
-    fn parse_class_def<'a>(&mut self, length: u32, i: &'a [u8]) -> AMFResult<'a, ClassDefinition> {
+    pub(crate) fn decode_string(
+        &self,
+        i: &'a [u8],
+        bytes: &'a [u8],
+    ) -> Result<Cow<'a, str>, Err<Error<'a>>> {
+        self.utf8_mode
+            .decode_with(bytes, &self.surrogate_strings)
+            .ok_or_else(|| Err::Error(make_error(i, ErrorKind::Alpha)))
+    }
+
+    pub(crate) fn parse_class_def(
+        &mut self,
+        length: u32,
+        i: &'a [u8],
+    ) -> AMFResult<'a, ClassDefinition> {
         if length & REFERENCE_FLAG == 0 {
             let len_usize: usize = (length >> 1)
                 .try_into()
@@ -211,7 +440,7 @@ impl AMF3Decoder {
         let name_str = if name.is_empty() {
             "".to_string()
         } else {
-            String::from_utf8(name).map_err(|_| Err::Error(make_error(i, ErrorKind::Alpha)))?
+            self.decode_string(i, name)?.into_owned()
         };
 
         let encoding = (length & 0x03) as u8;
@@ -223,8 +452,9 @@ impl AMF3Decoder {
             .map_err(|_| Err::Error(make_error(i, ErrorKind::Digit)))?;
 
         // Read static attributes if they exist
-        let (i, static_props) =
-            many_m_n(attr_count_usize, attr_count_usize, |i| self.parse_string(i))(i)?;
+        let (i, static_props) = many_m_n(attr_count_usize, attr_count_usize, |i| {
+            map(|i| self.parse_string(i), Cow::into_owned)(i)
+        })(i)?;
 
         let is_external = encoding & 0b1 == 1;
         let is_dynamic = encoding & 0b10 == 0b10;
@@ -248,20 +478,74 @@ impl AMF3Decoder {
         Ok((i, class_def))
     }
 
-    fn parse_reference_or_val<'a>(
+    /// Get the index in the object reference table of the object with the given id
+    pub fn object_index(&self, id: ObjectId) -> Option<usize> {
+        self.object_reference_table
+            .iter()
+            .position(|v| matches!(v.deref(), Value::Object(obj_id, _, _) if *obj_id == id))
+    }
+
+    /// Get the number of times the entry at `index` in the object reference table was referenced after it was read
+    ///
+    /// This is only available when `DecoderOptions::record_references` is set
+    pub fn reference_count(&self, index: usize) -> Option<usize> {
+        if !self.options.record_references || index >= self.object_reference_table.len() {
+            return None;
+        }
+        Some(self.reference_counts.get(index).copied().unwrap_or(0))
+    }
+
+    fn record_reference(&mut self, index: usize) {
+        if let Some(spans) = &mut self.spans {
+            spans.reference(index);
+        }
+        if self.options.record_references {
+            if self.reference_counts.len() <= index {
+                self.reference_counts.resize(index + 1, 0);
+            }
+            self.reference_counts[index] += 1;
+        }
+    }
+
+    fn reference_slot(&self, i: &'a [u8], index: usize) -> Result<Rc<Value<'a>>, Err<Error<'a>>> {
+        self.object_reference_table
+            .get(index)
+            .map(Rc::clone)
+            .ok_or_else(|| Err::Error(make_error(i, ErrorKind::Digit)))
+    }
+
+    /// Get the value being read into the given slot of the object reference table
+    ///
+    /// This fails if the slot is shared, a value can't be referenced while it is being read so this shouldn't happen
+    fn reference_slot_mut(
         &mut self,
         i: &'a [u8],
-        parser: impl FnOnce(&mut Self, &'a [u8], usize) -> AMFResult<'a, Value>,
-    ) -> AMFResult<'a, Rc<Value>> {
+        index: usize,
+    ) -> Result<&mut Value<'a>, Err<Error<'a>>> {
+        self.object_reference_table
+            .get_mut(index)
+            .and_then(Rc::get_mut)
+            .ok_or_else(|| Err::Error(make_error(i, ErrorKind::Verify)))
+    }
+
+    fn parse_reference_or_val(
+        &mut self,
+        i: &'a [u8],
+        parser: impl FnOnce(&mut Self, &'a [u8], usize) -> AMFResult<'a, Value<'a>>,
+    ) -> AMFResult<'a, Rc<Value<'a>>> {
         let (i, len) = read_length(i)?;
 
         match len {
+            Length::Reference(index) if self.pending.contains(&index) => {
+                Err(Err::Error(Error::CyclicReference(index)))
+            }
             Length::Reference(index) => {
                 let ref_result = Rc::clone(
                     self.object_reference_table
                         .get(index)
                         .ok_or_else(|| Err::Error(make_error(i, ErrorKind::Digit)))?,
                 );
+                self.record_reference(index);
 
                 Ok((i, ref_result))
             }
@@ -273,68 +557,59 @@ impl AMF3Decoder {
                 let initial = Rc::new(Value::Null);
                 let index = self.object_reference_table.len();
                 self.object_reference_table.push(initial);
+                if let Some(spans) = &mut self.spans {
+                    spans.define(index);
+                }
 
-                let (i, res) = parser(self, i, len_usize)?;
+                self.pending.push(index);
+                let result = parser(self, i, len_usize);
+                self.pending.pop();
+                let (i, res) = result?;
 
-                //TODO: this should be an error case and also never happen
-                let mut initial_inner = Rc::get_mut(
-                    self.object_reference_table
-                        .get_mut(index)
-                        .expect("Index not in reference table"),
-                )
-                .expect("Reference still held to rc");
-                *initial_inner.deref_mut() = res;
-
-                Ok((
-                    i,
-                    Rc::clone(
-                        self.object_reference_table
-                            .get(index)
-                            .expect("Index not in reference table"),
-                    ),
-                ))
+                *self.reference_slot_mut(i, index)? = res;
+
+                Ok((i, self.reference_slot(i, index)?))
             }
         }
     }
 
-    fn parse_byte_stream<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Vec<u8>> {
+    pub(crate) fn parse_byte_stream(&mut self, i: &'a [u8]) -> AMFResult<'a, &'a [u8]> {
         let (i, len) = read_length(i)?;
 
         match len {
             Length::Size(len) => {
                 if len == 0 {
-                    Ok((i, vec![]))
+                    Ok((i, &[]))
                 } else {
                     let (i, bytes) = take(len)(i)?;
-                    self.string_reference_table.push(bytes.to_vec());
-                    Ok((i, bytes.to_vec()))
+                    self.string_reference_table.push(bytes);
+                    Ok((i, bytes))
                 }
             }
             Length::Reference(index) => {
-                let ref_result = self
+                let ref_result = *self
                     .string_reference_table
                     .get(index)
-                    .ok_or_else(|| Err::Error(make_error(i, ErrorKind::Digit)))?
-                    .clone();
+                    .ok_or_else(|| Err::Error(make_error(i, ErrorKind::Digit)))?;
 
                 Ok((i, ref_result))
             }
         }
     }
 
-    fn parse_object_static<'a>(
+    fn parse_object_static(
         &mut self,
         i: &'a [u8],
         class_def: &ClassDefinition,
-    ) -> AMFResult<'a, Vec<Element>> {
+    ) -> AMFResult<'a, Vec<Element<'a>>> {
         let mut elements = Vec::new();
         let mut i = i;
 
         for name in class_def.static_properties.iter() {
-            let (j, e) = self.parse_single_element(i)?;
+            let (j, e) = self.parse_at(i, || PathSegment::Name(name.clone()))?;
 
             elements.push(Element {
-                name: name.clone(),
+                name: Cow::Owned(name.clone()),
                 value: e,
             });
 
@@ -344,7 +619,27 @@ impl AMF3Decoder {
         Ok((i, elements))
     }
 
-    pub(crate) fn parse_element_object<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+    fn parse_object_dynamic(&mut self, i: &'a [u8]) -> AMFResult<'a, Vec<Element<'a>>> {
+        let mut elements = Vec::new();
+
+        let (mut i, mut attr) = self.parse_byte_stream(i)?;
+        while !attr.is_empty() {
+            let attr_str = self.decode_string(i, attr)?;
+            let (j, val) = self.parse_at(i, || PathSegment::Name(attr_str.to_string()))?;
+            elements.push(Element {
+                name: attr_str,
+                value: val,
+            });
+
+            let (j, attr2) = self.parse_byte_stream(j)?;
+            i = j;
+            attr = attr2;
+        }
+
+        Ok((i, elements))
+    }
+
+    pub(crate) fn parse_element_object(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         let (i, mut length) = read_int(i)?;
 
         if length & REFERENCE_FLAG == 0 {
@@ -359,9 +654,11 @@ impl AMF3Decoder {
             let id = if let Value::Object(id, _, _) = o.deref() {
                 *id
             } else {
-                unreachable!("object_reference_table should only have objects")
+                // An object marker can only refer to an earlier object
+                return Err(Err::Error(make_error(i, ErrorKind::Digit)));
             };
 
+            self.record_reference(len_usize);
             let obj = Rc::new(Value::Amf3ObjectReference(id));
 
             return Ok((i, obj));
@@ -373,44 +670,36 @@ impl AMF3Decoder {
 
         let index = self.object_reference_table.len();
         self.object_reference_table.push(obj);
+        if let Some(spans) = &mut self.spans {
+            spans.define(index);
+        }
+
+        self.pending.push(index);
+        let result = self.parse_object_contents(i, length, index);
+        self.pending.pop();
+        result
+    }
 
+    /// Read the class definition and properties of the object being read into the given slot of the reference table
+    fn parse_object_contents(
+        &mut self,
+        i: &'a [u8],
+        length: u32,
+        index: usize,
+    ) -> AMFResult<'a, Rc<Value<'a>>> {
         // Class def
         let (i, class_def) = self.parse_class_def(length, i)?;
 
-        {
-            let mut_obj = Rc::get_mut(
-                self.object_reference_table
-                    .get_mut(index)
-                    .expect("Index invalid"),
-            )
-            .expect("Unable to get Object");
-            if let Value::Object(_, _, ref mut def) = mut_obj {
-                *def = Some(class_def.clone());
-            }
+        if let Value::Object(_, _, ref mut def) = self.reference_slot_mut(i, index)? {
+            *def = Some(class_def.clone());
         }
 
         let mut elements = Vec::new();
-        let external_elements;
 
         let mut i = i;
         if class_def.attributes.contains(Attribute::External) {
-            return if self.external_decoders.contains_key(&class_def.name) {
-                let decoder = Rc::clone(&self.external_decoders[&class_def.name]);
-                let (j, v) = decoder(i, self)?;
-                external_elements = v;
-                i = j;
-                //TODO: should it be possible to have both dynamic and external together
-                Ok((
-                    i,
-                    Rc::new(Value::Custom(
-                        external_elements,
-                        vec![],
-                        Some(class_def.clone()),
-                    )),
-                ))
-            } else {
-                Err(Err::Error(make_error(i, ErrorKind::Tag)))
-            };
+            let (i, v) = self.parse_external(i, class_def)?;
+            return Ok((i, Rc::new(v)));
         }
 
         if class_def.attributes.contains(Attribute::Dynamic) {
@@ -418,20 +707,8 @@ impl AMF3Decoder {
             elements.extend(x);
 
             // Read dynamic
-            let (mut j, mut attr) = self.parse_byte_stream(j)?;
-            while !attr.is_empty() {
-                let attr_str = String::from_utf8(attr)
-                    .map_err(|_| Err::Error(make_error(i, ErrorKind::Alpha)))?;
-                let (k, val) = self.parse_single_element(j)?;
-                elements.push(Element {
-                    name: attr_str,
-                    value: val,
-                });
-
-                let (k, attr2) = self.parse_byte_stream(k)?;
-                j = k;
-                attr = attr2;
-            }
+            let (j, x) = self.parse_object_dynamic(j)?;
+            elements.extend(x);
             i = j;
         }
         if class_def.attributes.is_empty() {
@@ -441,105 +718,180 @@ impl AMF3Decoder {
             i = j;
         }
 
-        {
-            let mut_obj = Rc::get_mut(
-                self.object_reference_table
-                    .get_mut(index)
-                    .expect("Index invalid"),
-            )
-            .expect("Unable to get Object");
-            if let Value::Object(_, ref mut elements_inner, _) = mut_obj {
-                *elements_inner = elements;
+        if let Value::Object(_, ref mut elements_inner, _) = self.reference_slot_mut(i, index)? {
+            *elements_inner = elements;
+        }
+
+        Ok((i, self.reference_slot(i, index)?))
+    }
+
+    /// The current sizes of the reference tables
+    fn table_sizes(&self) -> TableSizes {
+        TableSizes {
+            strings: self.string_reference_table.len(),
+            traits: self.trait_reference_table.len(),
+            objects: self.object_reference_table.len(),
+        }
+    }
+
+    /// Decode the body of an externalizable object with the decoder registered for its class
+    pub(crate) fn parse_external(
+        &mut self,
+        i: &'a [u8],
+        class_def: ClassDefinition,
+    ) -> AMFResult<'a, Value<'a>> {
+        let decoder = match self.external_decoders.get(&class_def.name) {
+            Some(decoder) => Rc::clone(decoder),
+            None => return self.parse_unknown_external(i, class_def),
+        };
+
+        let sizes = self.table_sizes();
+        let (j, external_elements) = decoder(i, self)?;
+
+        //TODO: should it be possible to have both dynamic and external together
+        if self.options.decode_flex {
+            return Ok((j, Value::Custom(external_elements, vec![], Some(class_def))));
+        }
+
+        let raw = RawExternal {
+            class_def,
+            bytes: i[..i.len() - j.len()].to_vec(),
+            table_sizes: Some(sizes),
+            strings: self.string_reference_table[sizes.strings..]
+                .iter()
+                .map(|s| s.to_vec())
+                .collect(),
+            traits: self.trait_reference_table[sizes.traits..].to_vec(),
+            objects: self.object_reference_table.len() - sizes.objects,
+        };
+        Ok((j, Value::RawExternal(Box::new(raw))))
+    }
+
+    fn parse_unknown_external(
+        &mut self,
+        i: &'a [u8],
+        class_def: ClassDefinition,
+    ) -> AMFResult<'a, Value<'a>> {
+        match self.options.unknown_external {
+            UnknownExternal::Error => Err(Err::Error(make_error(i, ErrorKind::Tag))),
+            UnknownExternal::Dynamic => {
+                let strings = self.string_reference_table.len();
+                let traits = self.trait_reference_table.len();
+                let objects = self.object_reference_table.len();
+                let path = self.error_path.len();
+                let spans = self.spans.as_ref().map_or(0, |spans| spans.spans.len());
+
+                if let Ok((j, elements)) = self.parse_object_dynamic(i) {
+                    return Ok((j, Value::Custom(vec![], elements, Some(class_def))));
+                }
+
+                // Undo anything the failed guess added
+                self.error_path.truncate(path);
+                if let Some(recorder) = &mut self.spans {
+                    recorder.spans.truncate(spans);
+                }
+                self.string_reference_table.truncate(strings);
+                self.trait_reference_table.truncate(traits);
+                self.object_reference_table.truncate(objects);
+                self.reference_counts.truncate(objects);
+                self.parse_remaining_external(i, class_def)
             }
+            UnknownExternal::Remaining => self.parse_remaining_external(i, class_def),
         }
+    }
 
-        Ok((
-            i,
-            Rc::clone(
-                self.object_reference_table
-                    .get(index)
-                    .expect("Index invalid"),
-            ),
-        ))
+    fn parse_remaining_external(
+        &mut self,
+        i: &'a [u8],
+        class_def: ClassDefinition,
+    ) -> AMFResult<'a, Value<'a>> {
+        let raw = RawExternal {
+            table_sizes: Some(self.table_sizes()),
+            ..RawExternal::new(class_def, i.to_vec())
+        };
+        Ok((&i[i.len()..], Value::RawExternal(Box::new(raw))))
     }
 
-    fn parse_element_byte_array<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+    fn parse_element_byte_array(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         self.parse_reference_or_val(i, |_this, i, len| {
             let (i, bytes) = take(len)(i)?;
-            Ok((i, Value::ByteArray(bytes.to_vec())))
+            Ok((i, Value::ByteArray(Cow::Borrowed(bytes))))
         })
     }
 
-    fn parse_element_vector_int<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
-        self.parse_reference_or_val(i, |_this, i, len| {
-            // There must be at least `len * 4` (i32 = 4 bytes) bytes to read this, this prevents OOM errors with v.large vecs
-            if i.len() < len * 4 {
-                return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
-            }
-
+    fn parse_element_vector_int(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
+        self.parse_reference_or_val(i, |this, i, len| {
             let (i, fixed_length) = be_u8(i)?;
 
-            let (i, ints) = many_m_n(len, len, be_i32)(i)?;
+            check_count(i, len, 4)?;
+            this.allocate(i, len, 4)?;
+            let (i, ints) = read_be_block(i, len, i32::from_be_bytes)?;
 
-            Ok((i, Value::VectorInt(ints, fixed_length == 1)))
+            Ok((i, Value::VectorInt(ints, fixed_length != 0)))
         })
     }
 
-    fn parse_element_vector_uint<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
-        self.parse_reference_or_val(i, |_this, i, len| {
-            // There must be at least `len * 4` (u32 = 4 bytes) bytes to read this, this prevents OOM errors with v.large vecs
-            if i.len() < len * 4 {
-                return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
-            }
+    fn parse_element_vector_uint(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
+        self.parse_reference_or_val(i, |this, i, len| {
             let (i, fixed_length) = be_u8(i)?;
 
-            let (i, ints) = many_m_n(len, len, be_u32)(i)?;
+            check_count(i, len, 4)?;
+            this.allocate(i, len, 4)?;
+            let (i, ints) = read_be_block(i, len, u32::from_be_bytes)?;
 
-            Ok((i, Value::VectorUInt(ints, fixed_length == 1)))
+            Ok((i, Value::VectorUInt(ints, fixed_length != 0)))
         })
     }
 
-    fn parse_element_vector_double<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
-        self.parse_reference_or_val(i, |_this, i, len| {
-            // There must be at least `len * 8` (f64 = 8 bytes) bytes to read this, this prevents OOM errors with v.large dicts
-            if i.len() < len * 8 {
-                return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
-            }
+    fn parse_element_vector_double(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
+        self.parse_reference_or_val(i, |this, i, len| {
             let (i, fixed_length) = be_u8(i)?;
 
+            // Each f64 is 8 bytes
+            check_count(i, len, 8)?;
+            this.allocate(i, len, 8)?;
             let (i, numbers) = many_m_n(len, len, be_f64)(i)?;
 
-            Ok((i, Value::VectorDouble(numbers, fixed_length == 1)))
+            Ok((i, Value::VectorDouble(numbers, fixed_length != 0)))
         })
     }
 
-    fn parse_element_object_vector<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+    fn parse_element_object_vector(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         self.parse_reference_or_val(i, |this, i, len| {
             let (i, fixed_length) = be_u8(i)?;
 
             let (i, object_type_name) = this.parse_string(i)?;
 
-            let (i, elems) = many_m_n(len, len, |i| this.parse_single_element(i))(i)?;
+            // Each element is at least a type marker
+            check_count(i, len, 1)?;
+            this.check_values_alloc(i, len)?;
+            let mut index = 0;
+            let (i, elems) = many_m_n(len, len, |i| {
+                index += 1;
+                this.parse_at(i, || PathSegment::Index(index - 1))
+            })(i)?;
 
             Ok((
                 i,
-                Value::VectorObject(elems, object_type_name, fixed_length == 1),
+                Value::VectorObject(elems, object_type_name.into_owned(), fixed_length != 0),
             ))
         })
     }
 
-    fn parse_element_array<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+    fn parse_element_array(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         self.parse_reference_or_val(i, |this, i, length_usize| {
-            // There must be at least `length_usize` bytes to read this, this prevents OOM errors with v.large dicts
-            if i.len() < length_usize {
-                return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
-            }
+            // Each dense element is at least a type marker
+            check_count(i, length_usize, 1)?;
+            this.check_values_alloc(i, length_usize)?;
 
             let (i, mut key) = this.parse_byte_stream(i)?;
 
             if key.is_empty() {
-                let (i, elements) =
-                    many_m_n(length_usize, length_usize, |i| this.parse_single_element(i))(i)?;
+                let mut index = 0;
+                let (i, elements) = many_m_n(length_usize, length_usize, |i| {
+                    index += 1;
+                    this.parse_at(i, || PathSegment::Index(index - 1))
+                })(i)?;
 
                 return Ok((i, Value::StrictArray(elements)));
             }
@@ -548,9 +900,8 @@ impl AMF3Decoder {
 
             let mut i = i;
             while !key.is_empty() {
-                let (j, e) = this.parse_single_element(i)?;
-                let key_str = String::from_utf8(key)
-                    .map_err(|_| Err::Error(make_error(i, ErrorKind::Alpha)))?;
+                let key_str = this.decode_string(i, key)?;
+                let (j, e) = this.parse_at(i, || PathSegment::Name(key_str.to_string()))?;
 
                 elements.push(Element {
                     name: key_str,
@@ -562,60 +913,92 @@ impl AMF3Decoder {
             }
 
             // Must parse `length` elements
-            let (i, el) =
-                many_m_n(length_usize, length_usize, |i| this.parse_single_element(i))(i)?;
-
-            let elements_len = elements.len() as u32;
-            Ok((i, Value::ECMAArray(el, elements, elements_len)))
+            let mut index = 0;
+            let (i, el) = many_m_n(length_usize, length_usize, |i| {
+                index += 1;
+                this.parse_at(i, || PathSegment::Index(index - 1))
+            })(i)?;
+
+            // The length of an array is the length of its dense part, as in AMF0
+            let length = el.len() as u32;
+            Ok((i, Value::ECMAArray(el, elements, length)))
         })
     }
 
-    fn parse_element_dict<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+    fn parse_element_dict(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         self.parse_reference_or_val(i, |this, i, len| {
-            //TODO: implications of this
+            // Flash Player treats any non-zero flag as weak keys, it only affects garbage collection
             let (i, weak_keys) = be_u8(i)?;
 
-            // There must be at least `len * 2` bytes (due to (key,val) pairs) to read this, this prevents OOM errors with v.large dicts
-            if i.len() < len * 2 {
-                return Err(Err::Error(make_error(i, ErrorKind::TooLarge)));
-            }
+            // Each (key, value) pair is at least two type markers
+            check_count(i, len, 2)?;
+            this.check_values_alloc(i, len.saturating_mul(2))?;
 
-            let (i, pairs) = many_m_n(len * 2, len * 2, |i| this.parse_single_element(i))(i)?;
+            let mut index = 0;
+            let (i, pairs) = many_m_n(len * 2, len * 2, |i| {
+                let (entry, part) = (index / 2, if index % 2 == 0 { "key" } else { "value" });
+                index += 1;
+                if let Some(spans) = &mut this.spans {
+                    spans.push(&PathSegment::Index(entry));
+                }
+                let result = this.parse_at(i, || PathSegment::Name(part.to_string()));
+                if let Some(spans) = &mut this.spans {
+                    spans.pop();
+                }
+                at_path(&mut this.error_path, || PathSegment::Index(entry), result)
+            })(i)?;
 
             let pairs = pairs
                 .chunks_exact(2)
                 .map(|chunk| (chunk[0].clone(), chunk[1].clone()))
                 .collect::<Vec<_>>();
 
-            Ok((i, Value::Dictionary(pairs, weak_keys == 1)))
+            Ok((i, Value::Dictionary(pairs, weak_keys != 0)))
         })
     }
 
-    fn parse_element_date<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+    fn parse_element_date(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         self.parse_reference_or_val(i, |_this, i, _len| {
             let (i, ms) = be_f64(i)?;
             Ok((i, Value::Date(ms, None)))
         })
     }
 
-    fn parse_element_xml<'a>(&mut self, i: &'a [u8], string: bool) -> AMFResult<'a, Rc<Value>> {
-        self.parse_reference_or_val(i, |_this, i, len| {
-            let (i, data) = map_res(take(len as u32), std::str::from_utf8)(i)?;
-            Ok((i, Value::XML(data.into(), string)))
+    fn parse_element_xml(&mut self, i: &'a [u8], string: bool) -> AMFResult<'a, Rc<Value<'a>>> {
+        self.parse_reference_or_val(i, |this, i, len| {
+            let (mode, surrogates) = (this.utf8_mode, &this.surrogate_strings);
+            let (i, data) = map_res(take(len as u32), |bytes| {
+                mode.decode_with(bytes, surrogates).ok_or(())
+            })(i)?;
+            Ok((i, Value::XML(data, string)))
         })
     }
 
-    fn read_type_marker<'a>(&self, i: &'a [u8]) -> AMFResult<'a, TypeMarker> {
-        let (i, type_) = be_u8(i)?;
+    pub(crate) fn read_type_marker(&self, i: &'a [u8]) -> AMFResult<'a, TypeMarker> {
+        let (rest, type_) = be_u8(i)?;
         if let Ok(type_) = TypeMarker::try_from(type_) {
-            Ok((i, type_))
+            Ok((rest, type_))
         } else {
-            Err(Err::Error(crate::errors::Error::UnsupportedType(type_)))
+            Err(Err::Error(Error::UnsupportedType(i, type_)))
         }
     }
 
     /// Parse a single AMF3 element from the input
-    pub fn parse_single_element<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value>> {
+    ///
+    /// This fails with `Error::DepthExceeded` if the value is nested deeper than `DecoderOptions::max_depth`, or
+    /// `Error::AllocationLimit` if it would go over `DecoderOptions::max_alloc`
+    pub fn parse_single_element(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
+        if self.depth >= self.options.max_depth {
+            return Err(Err::Error(Error::DepthExceeded(i)));
+        }
+        self.allocate(i, 1, VALUE_ALLOC)?;
+        self.depth += 1;
+        let result = self.parse_value(i);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_value(&mut self, i: &'a [u8]) -> AMFResult<'a, Rc<Value<'a>>> {
         let (i, type_) = self.read_type_marker(i)?;
 
         match type_ {
@@ -640,21 +1023,29 @@ impl AMF3Decoder {
         }
     }
 
-    fn parse_element<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Element> {
+    fn parse_element(&mut self, i: &'a [u8]) -> AMFResult<'a, Element<'a>> {
         let (i, name) = self.parse_string(i)?;
+        let (i, value) = self.parse_at(i, || PathSegment::Name(name.to_string()))?;
+
+        Ok((i, Element { name, value }))
+    }
 
-        map(
-            |i| self.parse_single_element(i),
-            move |v| Element {
-                name: name.clone(),
-                value: v,
-            },
-        )(i)
+    /// Parse an element of a body and the padding after it
+    pub(crate) fn parse_element_and_padding(&mut self, i: &'a [u8]) -> AMFResult<'a, Element<'a>> {
+        let (i, e) = self.parse_element(i)?;
+        let (i, _) = tag(PADDING)(i)?;
+        Ok((i, e))
     }
 
     /// Parse an AMF3 body from a slice into a list of elements
-    pub fn parse_body<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Vec<Element>> {
-        let (i, elements) = separated_list0(tag(PADDING), |i| self.parse_element(i))(i)?;
+    pub fn parse_body(&mut self, i: &'a [u8]) -> AMFResult<'a, Vec<Element<'a>>> {
+        let (i, elements) = separated_list0(tag(PADDING), |i| {
+            // The body ends at the first element that fails, so only the path to that one is kept
+            self.error_path.clear();
+            let result = self.parse_element(i);
+            self.element_error = result.as_ref().err().cloned();
+            result
+        })(i)?;
         let (i, _) = tag(PADDING)(i)?;
         Ok((i, elements))
     }
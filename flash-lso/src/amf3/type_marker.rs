@@ -1,44 +1,46 @@
+use crate::markers;
+
 /// Type markers used in AMF3
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 #[repr(u8)]
 pub(crate) enum TypeMarker {
     /// Undefined
-    Undefined = 0x00,
+    Undefined = markers::amf3::UNDEFINED,
     /// Null
-    Null = 0x01,
+    Null = markers::amf3::NULL,
     /// Boolean false
-    False = 0x02,
+    False = markers::amf3::FALSE,
     /// Boolean true
-    True = 0x03,
+    True = markers::amf3::TRUE,
     /// Variable length integer
-    Integer = 0x04,
+    Integer = markers::amf3::INTEGER,
     /// Floating point number
-    Number = 0x05,
+    Number = markers::amf3::NUMBER,
     /// String
-    String = 0x06,
+    String = markers::amf3::STRING,
     /// XML
-    Xml = 0x07,
+    Xml = markers::amf3::XML,
     /// Date (always UTC)
-    Date = 0x08,
+    Date = markers::amf3::DATE,
     /// Array
-    Array = 0x09,
+    Array = markers::amf3::ARRAY,
     /// Object
-    Object = 0x0A,
+    Object = markers::amf3::OBJECT,
     /// XML string
-    XmlString = 0x0B,
+    XmlString = markers::amf3::XML_STRING,
     /// Byte array
-    ByteArray = 0x0C,
+    ByteArray = markers::amf3::BYTE_ARRAY,
     /// Vector<Int>
-    VectorInt = 0x0D,
+    VectorInt = markers::amf3::VECTOR_INT,
     /// Vector<UInt>
-    VectorUInt = 0x0E,
+    VectorUInt = markers::amf3::VECTOR_UINT,
     /// Vector<Double>
-    VectorDouble = 0x0F,
+    VectorDouble = markers::amf3::VECTOR_DOUBLE,
     /// Vector<Object>
-    VectorObject = 0x10,
+    VectorObject = markers::amf3::VECTOR_OBJECT,
     /// Dictionary
-    Dictionary = 0x11,
+    Dictionary = markers::amf3::DICTIONARY,
 }
 
 impl TryFrom<u8> for TypeMarker {
@@ -46,24 +48,24 @@ impl TryFrom<u8> for TypeMarker {
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
-            0x00 => Ok(Self::Undefined),
-            0x01 => Ok(Self::Null),
-            0x02 => Ok(Self::False),
-            0x03 => Ok(Self::True),
-            0x04 => Ok(Self::Integer),
-            0x05 => Ok(Self::Number),
-            0x06 => Ok(Self::String),
-            0x07 => Ok(Self::Xml),
-            0x08 => Ok(Self::Date),
-            0x09 => Ok(Self::Array),
-            0x0A => Ok(Self::Object),
-            0x0B => Ok(Self::XmlString),
-            0x0C => Ok(Self::ByteArray),
-            0x0D => Ok(Self::VectorInt),
-            0x0E => Ok(Self::VectorUInt),
-            0x0F => Ok(Self::VectorDouble),
-            0x10 => Ok(Self::VectorObject),
-            0x11 => Ok(Self::Dictionary),
+            markers::amf3::UNDEFINED => Ok(Self::Undefined),
+            markers::amf3::NULL => Ok(Self::Null),
+            markers::amf3::FALSE => Ok(Self::False),
+            markers::amf3::TRUE => Ok(Self::True),
+            markers::amf3::INTEGER => Ok(Self::Integer),
+            markers::amf3::NUMBER => Ok(Self::Number),
+            markers::amf3::STRING => Ok(Self::String),
+            markers::amf3::XML => Ok(Self::Xml),
+            markers::amf3::DATE => Ok(Self::Date),
+            markers::amf3::ARRAY => Ok(Self::Array),
+            markers::amf3::OBJECT => Ok(Self::Object),
+            markers::amf3::XML_STRING => Ok(Self::XmlString),
+            markers::amf3::BYTE_ARRAY => Ok(Self::ByteArray),
+            markers::amf3::VECTOR_INT => Ok(Self::VectorInt),
+            markers::amf3::VECTOR_UINT => Ok(Self::VectorUInt),
+            markers::amf3::VECTOR_DOUBLE => Ok(Self::VectorDouble),
+            markers::amf3::VECTOR_OBJECT => Ok(Self::VectorObject),
+            markers::amf3::DICTIONARY => Ok(Self::Dictionary),
             _ => Err(()),
         }
     }
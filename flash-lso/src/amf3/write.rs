@@ -1,48 +1,129 @@
 //! Handles encoding AMF3
-use crate::amf3::custom_encoder::CustomEncoder;
+use crate::amf3::custom_encoder::{CustomEncoder, ExternalEncoder};
+use crate::amf3::dedup::{for_each_object_pair, DedupTable};
 use crate::amf3::element_cache::ElementCache;
 use crate::amf3::length::Length;
 use crate::amf3::type_marker::TypeMarker;
-use crate::types::{Attribute, ClassDefinition, Element, ObjectId, Value};
+use crate::io::Result;
+use crate::io::Write;
+use crate::prelude::*;
+use crate::types::{
+    AMFVersion, Attribute, ClassDefinition, Element, ObjectId, RawExternal, SurrogateStrings,
+    TableSizes, Utf8Mode, Value,
+};
 use crate::write::WriteExt;
 use crate::PADDING;
-use std::cell::RefCell;
-use std::collections::{BTreeMap, HashMap};
-use std::io::Result;
-use std::io::Write;
-use std::ops::Deref;
-use std::rc::Rc;
+use alloc::collections::BTreeMap;
+use core::cell::{Cell, RefCell};
+
+/// The largest value that can be written as an AMF3 integer, 2^28 - 1
+const MAX_INTEGER: i32 = 0x0FFF_FFFF;
+
+/// The smallest value that can be written as an AMF3 integer, -2^28
+const MIN_INTEGER: i32 = -0x1000_0000;
+
+/// The largest value that fits in the 29 bits of a U29
+const MAX_U29: u64 = 0x1FFF_FFFF;
+
+/// Options controlling how AMF3 is encoded
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct EncoderOptions {
+    /// The version of AMF that `Writer` encodes the body of an lso with, if `None` then the version given in its header
+    /// is used
+    ///
+    /// The lso is converted to this version with `Lso::convert_to` for writing, the lso given to the writer is left as it
+    /// is apart from `Header::length`
+    pub amf_version: Option<AMFVersion>,
+
+    /// Write complex values that are structurally equal to one written earlier as a reference to it
+    ///
+    /// This applies to objects, arrays, dictionaries, vectors, dates, byte arrays and xml. Values are compared with
+    /// `Value::structural_eq`, so distinct objects with the same contents are merged into one when read back
+    pub dedup_objects: bool,
+}
 
 /// Handles encoding AMF3
+///
+/// Values written are kept until the encoder is dropped, so it can only write values that live for `'v`
 #[derive(Default)]
-pub struct AMF3Encoder {
+pub struct AMF3Encoder<'v> {
     /// The table used to cache repeated byte strings
     string_reference_table: ElementCache<Vec<u8>>,
 
     /// The table used to cache repeated trait definitions
     trait_reference_table: RefCell<Vec<ClassDefinition>>,
 
-    /// The table used to cache repeated objects
-    object_reference_table: ElementCache<Value>,
+    /// Complex values written so far and their index in the object table, by address
+    ///
+    /// The `Rc`s are kept so that an address can't be reused by a different value while encoding
+    written_values: RefCell<HashMap<*const Value<'v>, (Rc<Value<'v>>, usize)>>,
 
     /// Encoders used for handling externalized types
     pub external_encoders: HashMap<String, Box<dyn CustomEncoder>>,
 
     object_id_to_reference: RefCell<BTreeMap<ObjectId, usize>>,
+
+    /// How strings are encoded
+    pub utf8_mode: Utf8Mode,
+
+    /// The form to write strings in with `Utf8Mode::Flash`, see `AMF0Encoder::surrogate_strings`
+    pub surrogate_strings: SurrogateStrings,
+
+    /// Options controlling the encoding
+    pub options: EncoderOptions,
+
+    /// Complex values written so far, used when `EncoderOptions::dedup_objects` is set
+    dedup_table: RefCell<DedupTable<'v>>,
+
+    /// The number of complex values written so far, which is the index the next one will have in the object table
+    reference_count: Cell<usize>,
 }
 
-impl AMF3Encoder {
+impl<'v> AMF3Encoder<'v> {
+    /// Encode objects of the class named by `encoder` with it, replacing any encoder already used for that class
+    pub fn register(&mut self, encoder: Box<dyn ExternalEncoder>) {
+        self.external_encoders
+            .insert(encoder.name().to_string(), Box::new(encoder));
+    }
+
+    /// Write a signed AMF3 integer, which must be between `MIN_INTEGER` and `MAX_INTEGER`
     #[allow(clippy::unusual_byte_groupings)]
     pub(crate) fn write_int<'a, 'b: 'a, W: Write + 'a>(
         &self,
         writer: &mut W,
         i: i32,
     ) -> Result<()> {
+        if !(MIN_INTEGER..=MAX_INTEGER).contains(&i) {
+            return Err(crate::io::Error::new(
+                crate::io::ErrorKind::InvalidInput,
+                format!("{} is out of range for an AMF3 integer", i),
+            ));
+        }
+
+        // Negative values are stored as 29 bit two's complement
         let n = if i < 0 {
             i + 0b001_0000000_0000000_0000000_00000000
         } else {
             i
         };
+        self.write_u29(writer, n as u64)
+    }
+
+    /// Write an unsigned 29 bit value, as used for lengths, references and trait headers
+    ///
+    /// Values over `MAX_U29` are an error rather than having their high bits dropped
+    pub(crate) fn write_u29<'a, 'b: 'a, W: Write + 'a>(
+        &self,
+        writer: &mut W,
+        n: u64,
+    ) -> Result<()> {
+        if n > MAX_U29 {
+            return Err(crate::io::Error::new(
+                crate::io::ErrorKind::InvalidInput,
+                format!("{} is too large for an AMF3 U29", n),
+            ));
+        }
+        let n = n as u32;
 
         if n > 0x1fffff {
             writer.write_u8(((n >> (7 * 3 + 1)) | 0b10000000) as u8)?;
@@ -68,18 +149,15 @@ impl AMF3Encoder {
         writer: &mut W,
         s: &'b [u8],
     ) -> Result<()> {
-        let len = if !s.is_empty() {
-            self.string_reference_table
-                .to_length(s.to_vec(), s.len() as u32)
-        } else {
-            Length::Size(0)
+        let len = match self.string_reference_table.get_slice_index(s) {
+            Some(index) if !s.is_empty() => Length::Reference(index),
+            _ => Length::Size(s.len() as u32),
         };
 
-        let only_length = len.is_reference() && !s.is_empty();
-        let s_vec = s.to_vec();
+        let only_length = len.is_reference();
 
-        if !s_vec.is_empty() {
-            self.string_reference_table.store(s_vec.clone());
+        if !s.is_empty() {
+            self.string_reference_table.store_slice(s);
         }
 
         len.write(writer, self)?;
@@ -91,7 +169,10 @@ impl AMF3Encoder {
     }
 
     fn write_string<'a, 'b: 'a, W: Write + 'a>(&'a self, writer: &mut W, s: &'b str) -> Result<()> {
-        self.write_byte_string(writer, s.as_bytes())
+        self.write_byte_string(
+            writer,
+            &self.utf8_mode.encode_with(s, &self.surrogate_strings),
+        )
     }
 
     fn write_type_marker<'a, 'b: 'a, W: Write + 'a>(
@@ -130,7 +211,7 @@ impl AMF3Encoder {
         s: &'b str,
     ) -> Result<()> {
         self.write_type_marker(writer, TypeMarker::String)?;
-        self.write_byte_string(writer, s.as_bytes())?;
+        self.write_string(writer, s)?;
         Ok(())
     }
 
@@ -148,20 +229,11 @@ impl AMF3Encoder {
         items: &'b [i32],
         fixed_length: bool,
     ) -> Result<()> {
-        let len = self.object_reference_table.to_length(
-            Value::VectorInt(items.to_vec(), fixed_length),
-            items.len() as u32,
-        );
-
         self.write_type_marker(writer, TypeMarker::VectorInt)?;
-        if len.is_reference() {
-            len.write(writer, self)?;
-        } else {
-            Length::Size(items.len() as u32).write(writer, self)?;
-            writer.write_u8(fixed_length as u8)?;
-            for item in items {
-                writer.write_i32(*item)?;
-            }
+        Length::Size(items.len() as u32).write(writer, self)?;
+        writer.write_u8(fixed_length as u8)?;
+        for item in items {
+            writer.write_i32(*item)?;
         }
         Ok(())
     }
@@ -172,20 +244,11 @@ impl AMF3Encoder {
         items: &'b [u32],
         fixed_length: bool,
     ) -> Result<()> {
-        let len = self.object_reference_table.to_length(
-            Value::VectorUInt(items.to_vec(), fixed_length),
-            items.len() as u32,
-        );
-
         self.write_type_marker(writer, TypeMarker::VectorUInt)?;
-        if len.is_reference() {
-            len.write(writer, self)?;
-        } else {
-            Length::Size(items.len() as u32).write(writer, self)?;
-            writer.write_u8(fixed_length as u8)?;
-            for item in items {
-                writer.write_u32(*item)?;
-            }
+        Length::Size(items.len() as u32).write(writer, self)?;
+        writer.write_u8(fixed_length as u8)?;
+        for item in items {
+            writer.write_u32(*item)?;
         }
         Ok(())
     }
@@ -196,20 +259,11 @@ impl AMF3Encoder {
         items: &'b [f64],
         fixed_length: bool,
     ) -> Result<()> {
-        let len = self.object_reference_table.to_length(
-            Value::VectorDouble(items.to_vec(), fixed_length),
-            items.len() as u32,
-        );
-
         self.write_type_marker(writer, TypeMarker::VectorDouble)?;
-        if len.is_reference() {
-            len.write(writer, self)?;
-        } else {
-            Length::Size(items.len() as u32).write(writer, self)?;
-            writer.write_u8(fixed_length as u8)?;
-            for item in items {
-                writer.write_f64(*item)?;
-            }
+        Length::Size(items.len() as u32).write(writer, self)?;
+        writer.write_u8(fixed_length as u8)?;
+        for item in items {
+            writer.write_f64(*item)?;
         }
         Ok(())
     }
@@ -219,15 +273,9 @@ impl AMF3Encoder {
         writer: &mut W,
         time: f64,
     ) -> Result<()> {
-        let len = self
-            .object_reference_table
-            .to_length(Value::Date(time, None), 0);
-
         self.write_type_marker(writer, TypeMarker::Date)?;
-        len.write(writer, self)?;
-        if len.is_size() {
-            writer.write_f64(time)?;
-        }
+        Length::Size(0).write(writer, self)?;
+        writer.write_f64(time)?;
         Ok(())
     }
 
@@ -236,6 +284,10 @@ impl AMF3Encoder {
         writer: &mut W,
         i: i32,
     ) -> Result<()> {
+        // Integers only have 29 bits, anything outside of that would be truncated so is written as a double instead
+        if !(MIN_INTEGER..=MAX_INTEGER).contains(&i) {
+            return self.write_number_element(writer, f64::from(i));
+        }
         self.write_type_marker(writer, TypeMarker::Integer)?;
         self.write_int(writer, i)?;
         Ok(())
@@ -246,15 +298,9 @@ impl AMF3Encoder {
         writer: &mut W,
         bytes: &'b [u8],
     ) -> Result<()> {
-        let len = self
-            .object_reference_table
-            .to_length(Value::ByteArray(bytes.to_vec()), bytes.len() as u32);
-
         self.write_type_marker(writer, TypeMarker::ByteArray)?;
-        len.write(writer, self)?;
-        if len.is_size() {
-            writer.write_all(bytes)?;
-        }
+        Length::Size(bytes.len() as u32).write(writer, self)?;
+        writer.write_all(bytes)?;
         Ok(())
     }
 
@@ -264,6 +310,7 @@ impl AMF3Encoder {
         bytes: &'b str,
         string: bool,
     ) -> Result<()> {
+        let bytes = self.utf8_mode.encode_with(bytes, &self.surrogate_strings);
         let len = Length::Size(bytes.len() as u32);
 
         if string {
@@ -274,7 +321,7 @@ impl AMF3Encoder {
 
         len.write(writer, self)?;
         if len.is_size() {
-            writer.write_all(bytes.as_bytes())?;
+            writer.write_all(&bytes)?;
         }
         Ok(())
     }
@@ -284,63 +331,69 @@ impl AMF3Encoder {
         writer: &mut W,
         class_def: &'b ClassDefinition,
     ) -> Result<()> {
-        self.write_byte_string(writer, class_def.name.as_bytes())?;
+        self.write_string(writer, &class_def.name)?;
         for p in &class_def.static_properties {
             self.write_string(writer, p)?;
         }
         Ok(())
     }
 
-    //TODO: conds should be common somehwere
-    fn write_trait_reference<'a, 'b: 'a, W: Write + 'a>(
+    fn write_external<'a, 'b: 'a, W: Write + 'a>(
         &'a self,
         writer: &mut W,
-        index: u32,
-        children: &'b [Element],
-        custom_props: Option<&'b [Element]>,
+        custom_props: &'b [Element<'v>],
+        children: &'b [Element<'v>],
         def: &'b ClassDefinition,
     ) -> Result<()> {
-        #[allow(clippy::identity_op)]
-        let size = (((index << 1) | 0u32) << 1) | 1u32;
-
-        self.write_int(writer, size as i32)?;
-        if def.attributes.contains(Attribute::External) {
-            if let Some(encoder) = self.external_encoders.get(&def.name) {
-                writer.write_all(&encoder.encode(
-                    custom_props.unwrap(),
-                    &Some(def.clone()),
-                    self,
-                ))?;
-            } else {
-                unimplemented!();
+        if let Some(encoder) = self.external_encoders.get(&def.name) {
+            writer.write_all(&encoder.encode(custom_props, &Some(def.clone()), self))?;
+        } else if custom_props.is_empty() {
+            // Properties guessed when decoding, see `UnknownExternal::Dynamic`
+            for c in children {
+                self.write_string(writer, &c.name)?;
+                self.write_value_element(writer, &c.value)?;
             }
+            self.write_byte_string(writer, &[])?;
+        } else {
+            return Err(crate::io::Error::new(
+                crate::io::ErrorKind::InvalidInput,
+                format!("No external encoder registered for class {:?}", def.name),
+            ));
         }
+        Ok(())
+    }
 
-        if !def.attributes.contains(Attribute::External) {
-            if def.attributes.is_empty() {
-                for c in children {
-                    if def.static_properties.contains(&c.name) {
-                        self.write_value_element(writer, &c.value)?;
-                    }
-                }
-            }
-
-            if def.attributes.contains(Attribute::Dynamic) {
-                for c in children {
-                    if def.static_properties.contains(&c.name) {
-                        self.write_value_element(writer, &c.value)?;
-                    }
-                }
+    /// Write the members of a sealed or dynamic object
+    ///
+    /// Static members are written in the order of the trait, whatever their order in `children`, followed by any
+    /// dynamic members and the empty string that ends them
+    fn write_object_members<'a, 'b: 'a, W: Write + 'a>(
+        &'a self,
+        writer: &mut W,
+        children: &'b [Element<'v>],
+        def: &'b ClassDefinition,
+    ) -> Result<()> {
+        for p in &def.static_properties {
+            let c = children.iter().find(|c| c.name == *p).ok_or_else(|| {
+                crate::io::Error::new(
+                    crate::io::ErrorKind::InvalidInput,
+                    format!(
+                        "Object of class {:?} is missing static property {:?}",
+                        def.name, p
+                    ),
+                )
+            })?;
+            self.write_value_element(writer, &c.value)?;
+        }
 
-                for c in children {
-                    if !def.static_properties.contains(&c.name) {
-                        self.write_byte_string(writer, c.name.as_bytes())?;
-                        self.write_value_element(writer, &c.value)?;
-                    }
+        if def.attributes.contains(Attribute::Dynamic) {
+            for c in children {
+                if !def.static_properties.iter().any(|p| *p == c.name) {
+                    self.write_string(writer, &c.name)?;
+                    self.write_value_element(writer, &c.value)?;
                 }
-
-                self.write_byte_string(writer, &[])?;
             }
+            self.write_byte_string(writer, &[])?;
         }
         Ok(())
     }
@@ -350,128 +403,107 @@ impl AMF3Encoder {
         writer: &mut W,
         index: u32,
     ) -> Result<()> {
-        #[allow(clippy::identity_op)]
-        let size = (index << 1) | 0u32;
-        self.write_int(writer, size as i32)
+        self.write_u29(writer, u64::from(index) << 1)
     }
 
-    fn write_object_full<'a, 'b: 'a, W: Write + 'a>(
+    /// Write the trait of an object, as a reference to an earlier one if the same trait has already been written
+    fn write_trait<'a, W: Write + 'a>(
         &'a self,
         writer: &mut W,
-        custom_props: Option<&'b [Element]>,
-        children: &'b [Element],
-        def: &'b ClassDefinition,
+        def: &ClassDefinition,
     ) -> Result<()> {
-        let is_external = def.attributes.contains(Attribute::External);
-        let is_dynamic = def.attributes.contains(Attribute::Dynamic);
+        let index = self
+            .trait_reference_table
+            .borrow()
+            .iter()
+            .position(|cd| cd == def);
+        if let Some(index) = index {
+            return self.write_u29(writer, ((index as u64) << 2) | 1);
+        }
 
         let mut encoding = 0b00;
-        if is_external {
+        if def.attributes.contains(Attribute::External) {
             encoding |= 0b01;
         }
-        if is_dynamic {
+        if def.attributes.contains(Attribute::Dynamic) {
             encoding |= 0b10;
         }
 
         // Format attribute_count[:4] | encoding[4:2] | class_def_ref flag (1 bit) | class_ref flag (1 bit)
-        let size = ((((((def.static_properties.len() as u32) << 2) | (encoding & 0xff) as u32)
-            << 1)
-            | 1u32)
-            << 1)
-            | 1u32;
+        let size =
+            ((((((def.static_properties.len() as u64) << 2) | (encoding & 0xff) as u64) << 1) | 1)
+                << 1)
+                | 1;
 
         self.trait_reference_table.borrow_mut().push(def.clone());
-        self.write_int(writer, size as i32)?;
-        self.write_class_definition(writer, def)?;
-        if def.attributes.contains(Attribute::External) {
-            if let Some(encoder) = self.external_encoders.get(&def.name) {
-                writer.write_all(&encoder.encode(
-                    custom_props.unwrap(),
-                    &Some(def.clone()),
-                    self,
-                ))?;
-            } else {
-                unimplemented!();
-            }
-        }
-        if !def.attributes.contains(Attribute::External) {
-            if def.attributes.is_empty() {
-                for c in children {
-                    if def.static_properties.contains(&c.name) {
-                        self.write_value_element(writer, &c.value)?;
-                    }
-                }
-            }
-
-            if def.attributes.contains(Attribute::Dynamic) {
-                for c in children {
-                    if def.static_properties.contains(&c.name) {
-                        self.write_value_element(writer, &c.value)?;
-                    }
-                }
-                for c in children {
-                    if !def.static_properties.contains(&c.name) {
-                        self.write_byte_string(writer, c.name.as_bytes())?;
-                        self.write_value_element(writer, &c.value)?;
-                    }
-                }
-                self.write_byte_string(writer, &[])?;
-            }
-        }
-        Ok(())
+        self.write_u29(writer, size)?;
+        self.write_class_definition(writer, def)
     }
 
     fn write_object_element<'a, 'b: 'a, W: Write + 'a>(
         &'a self,
         writer: &mut W,
-        id: ObjectId,
-        children: &'b [Element],
-        custom_props: Option<&'b [Element]>,
+        children: &'b [Element<'v>],
+        custom_props: Option<&'b [Element<'v>]>,
         class_def: &'b Option<ClassDefinition>,
     ) -> Result<()> {
-        let had_object = Length::Size(0);
+        // Objects without a class definition are plain objects, if these were written with the default definition
+        // any properties would be dropped as it has no static properties and isn't dynamic
+        let def = class_def.clone().unwrap_or_else(ClassDefinition::anonymous);
 
-        let obj = Value::Object(id, children.to_vec(), class_def.clone());
-        self.object_reference_table.store(obj.clone());
-        if let Length::Reference(r) = self.object_reference_table.to_length(obj, 0) {
-            self.object_id_to_reference.borrow_mut().insert(id, r);
+        self.write_type_marker(writer, TypeMarker::Object)?;
+        self.write_trait(writer, &def)?;
+        if def.attributes.contains(Attribute::External) {
+            self.write_external(writer, custom_props.unwrap_or(&[]), children, &def)
+        } else {
+            self.write_object_members(writer, children, &def)
         }
+    }
 
-        let def = class_def.clone().unwrap_or_default();
-        let def2 = def.clone();
-
-        let has_trait = self
-            .trait_reference_table
-            .borrow()
-            .iter()
-            .position(|cd| *cd == def);
-
+    /// Write an external object that was kept as its original bytes
+    ///
+    /// The entries that the bytes added to the reference tables when read are added again, so that later references
+    /// line up. This fails if the tables are a different size than when the bytes were read, as any references in the
+    /// bytes would then point to the wrong entries
+    fn write_raw_external_element<'a, W: Write + 'a>(
+        &'a self,
+        writer: &mut W,
+        raw: &RawExternal,
+    ) -> Result<()> {
         self.write_type_marker(writer, TypeMarker::Object)?;
-        if had_object.is_reference() {
-            self.write_object_reference(writer, had_object.as_position().unwrap() as u32)?;
-        }
-        if !had_object.is_reference() {
-            if has_trait.is_some() {
-                self.write_trait_reference(
-                    writer,
-                    has_trait.unwrap() as u32,
-                    children,
-                    custom_props,
-                    &def2,
-                )?;
-            }
-            if has_trait.is_none() {
-                self.write_object_full(writer, custom_props, children, &def)?;
-            }
+        self.write_trait(writer, &raw.class_def)?;
+
+        let sizes = TableSizes {
+            strings: self.string_reference_table.len(),
+            traits: self.trait_reference_table.borrow().len(),
+            objects: self.reference_count.get(),
+        };
+        if raw.table_sizes.is_some_and(|read| read != sizes) {
+            return Err(crate::io::Error::new(
+                crate::io::ErrorKind::InvalidInput,
+                format!(
+                    "Raw object of class {:?} was read with different reference tables, {:?} rather than {:?}",
+                    raw.class_def.name, raw.table_sizes, sizes
+                ),
+            ));
         }
 
+        writer.write_all(&raw.bytes)?;
+        for s in &raw.strings {
+            self.string_reference_table.store_slice(s);
+        }
+        self.trait_reference_table
+            .borrow_mut()
+            .extend(raw.traits.iter().cloned());
+        self.reference_count
+            .set(self.reference_count.get() + raw.objects);
         Ok(())
     }
 
     fn write_strict_array_element<'a, 'b: 'a, W: Write + 'a>(
         &'a self,
         writer: &mut W,
-        children: &'b [Rc<Value>],
+        children: &'b [Rc<Value<'v>>],
     ) -> Result<()> {
         //TODO: why is this not a reference
         let len = Length::Size(children.len() as u32);
@@ -498,13 +530,21 @@ impl AMF3Encoder {
     fn write_ecma_array_element<'a, 'b: 'a, W: Write + 'a>(
         &'a self,
         writer: &mut W,
-        dense: &'b [Rc<Value>],
-        assoc: &'b [Element],
+        dense: &'b [Rc<Value<'v>>],
+        assoc: &'b [Element<'v>],
     ) -> Result<()> {
         let len = Length::Size(dense.len() as u32);
 
         //TODO: would this also work for strict arrays if they have [] for assoc part?
 
+        // An empty name ends the associative part, so an element with one would drop it and every element after it
+        if assoc.iter().any(|e| e.name.is_empty()) {
+            return Err(crate::io::Error::new(
+                crate::io::ErrorKind::InvalidInput,
+                "The associative part of an AMF3 array can't have an element with an empty name",
+            ));
+        }
+
         self.write_type_marker(writer, TypeMarker::Array)?;
         len.write(writer, self)?;
         if len.is_size() {
@@ -522,23 +562,16 @@ impl AMF3Encoder {
     fn write_object_vector_element<'a, 'b: 'a, W: Write + 'a>(
         &'a self,
         writer: &mut W,
-        items: &'b [Rc<Value>],
+        items: &'b [Rc<Value<'v>>],
         type_name: &'b str,
         fixed_length: bool,
     ) -> Result<()> {
-        let len = self.object_reference_table.to_length(
-            Value::VectorObject(items.to_vec(), type_name.to_string(), fixed_length),
-            items.len() as u32,
-        );
-
         self.write_type_marker(writer, TypeMarker::VectorObject)?;
-        len.write(writer, self)?;
-        if len.is_size() {
-            writer.write_u8(fixed_length as u8)?;
-            self.write_string(writer, type_name)?;
-            for i in items {
-                self.write_value_element(writer, i)?;
-            }
+        Length::Size(items.len() as u32).write(writer, self)?;
+        writer.write_u8(fixed_length as u8)?;
+        self.write_string(writer, type_name)?;
+        for i in items {
+            self.write_value_element(writer, i)?;
         }
         Ok(())
     }
@@ -546,47 +579,113 @@ impl AMF3Encoder {
     fn write_dictionary_element<'a, 'b: 'a, W: Write + 'a>(
         &'a self,
         writer: &mut W,
-        items: &'b [(Rc<Value>, Rc<Value>)],
+        items: &'b [(Rc<Value<'v>>, Rc<Value<'v>>)],
         weak_keys: bool,
     ) -> Result<()> {
-        let len = self.object_reference_table.to_length(
-            Value::Dictionary(items.to_vec(), weak_keys),
-            items.len() as u32,
-        );
-        self.object_reference_table
-            .store(Value::Dictionary(items.to_vec(), weak_keys));
-
         self.write_type_marker(writer, TypeMarker::Dictionary)?;
-        len.write(writer, self)?;
-        if len.is_size() {
-            writer.write_u8(weak_keys as u8)?;
-            for i in items {
-                self.write_value_element(writer, &i.0)?;
-                self.write_value_element(writer, &i.1)?;
-            }
+        Length::Size(items.len() as u32).write(writer, self)?;
+        writer.write_u8(weak_keys as u8)?;
+        for i in items {
+            self.write_value_element(writer, &i.0)?;
+            self.write_value_element(writer, &i.1)?;
         }
         Ok(())
     }
 
-    pub(crate) fn write_value_element<'a, 'b: 'a, W: Write + 'a>(
+    /// Write a single AMF3 element, this is the inverse of `AMF3Decoder::parse_single_element`
+    ///
+    /// Values that share an `Rc` with one written earlier by this encoder are written as a reference to it
+    pub fn write_value_element<'a, 'b: 'a, W: Write + 'a>(
         &'b self,
         writer: &mut W,
-        s: &'b Rc<Value>,
+        s: &'b Rc<Value<'v>>,
     ) -> Result<()> {
-        self.write_value(writer, s.deref())
+        // The same `Rc` written twice is the same value, so the second one is written as a reference to the first
+        if !self.options.dedup_objects {
+            if let Some(marker) = reference_marker(s) {
+                let existing = self
+                    .written_values
+                    .borrow()
+                    .get(&Rc::as_ptr(s))
+                    .map(|(_, index)| *index);
+                if let Some(index) = existing {
+                    self.write_type_marker(writer, marker)?;
+                    return self.write_object_reference(writer, index as u32);
+                }
+                self.written_values
+                    .borrow_mut()
+                    .insert(Rc::as_ptr(s), (Rc::clone(s), self.reference_count.get()));
+            }
+        }
+
+        self.write_value(writer, s)
+    }
+
+    /// Write a reference to an earlier value with the same structure as `s` if there is one, otherwise record `s` as
+    /// the next entry in the object table
+    ///
+    /// Returns true if a reference was written
+    fn write_dedup_reference<'a, 'b: 'a, W: Write + 'a>(
+        &'b self,
+        writer: &mut W,
+        s: &'b Rc<Value<'v>>,
+    ) -> Result<bool> {
+        let marker = match reference_marker(s) {
+            Some(marker) => marker,
+            None => return Ok(false),
+        };
+
+        let existing = self.dedup_table.borrow().get(s);
+        if let Some((earlier, index)) = existing {
+            // Objects inside `s` are never written, so references to them need to go to the ones inside `earlier`
+            let mut object_id_to_reference = self.object_id_to_reference.borrow_mut();
+            for_each_object_pair(&earlier, s, &mut |earlier_id, id| {
+                if let Some(index) = object_id_to_reference.get(&earlier_id).copied() {
+                    object_id_to_reference.insert(id, index);
+                }
+            });
+            if let Value::Object(id, _, _) = s.as_ref() {
+                object_id_to_reference.insert(*id, index);
+            }
+            drop(object_id_to_reference);
+
+            self.write_type_marker(writer, marker)?;
+            self.write_object_reference(writer, index as u32)?;
+            Ok(true)
+        } else {
+            let index = self.reference_count.get();
+            if let Value::Object(id, _, _) = s.as_ref() {
+                self.object_id_to_reference.borrow_mut().insert(*id, index);
+            }
+            self.dedup_table.borrow_mut().store(s, index);
+            self.reference_count.set(index + 1);
+            Ok(false)
+        }
     }
 
     fn write_value<'a, 'b: 'a, W: Write + 'a>(
         &'b self,
         writer: &mut W,
-        s: &'b Value,
+        s: &'b Rc<Value<'v>>,
     ) -> Result<()> {
-        match s {
+        if self.options.dedup_objects {
+            if self.write_dedup_reference(writer, s)? {
+                return Ok(());
+            }
+        } else if reference_marker(s).is_some() {
+            let index = self.reference_count.get();
+            if let Value::Object(id, _, _) = s.as_ref() {
+                self.object_id_to_reference.borrow_mut().insert(*id, index);
+            }
+            self.reference_count.set(index + 1);
+        }
+
+        match s.as_ref() {
             Value::Number(x) => self.write_number_element(writer, *x),
             Value::Bool(b) => self.write_boolean_element(writer, *b),
             Value::String(s) => self.write_string_element(writer, s),
-            Value::Object(id, children, class_def) => {
-                self.write_object_element(writer, *id, children, None, class_def)
+            Value::Object(_, children, class_def) => {
+                self.write_object_element(writer, children, None, class_def)
             }
             Value::Null => self.write_null_element(writer),
             Value::Undefined => self.write_undefined_element(writer),
@@ -614,22 +713,27 @@ impl AMF3Encoder {
                 self.write_dictionary_element(writer, kv, *weak_keys)
             }
 
-            Value::Custom(elements, dynamic_elements, def) => self.write_object_element(
-                writer,
-                ObjectId::INVALID,
-                dynamic_elements,
-                Some(elements),
-                def,
-            ),
+            Value::Custom(elements, dynamic_elements, def) => {
+                self.write_object_element(writer, dynamic_elements, Some(elements), def)
+            }
+            Value::RawExternal(raw) => self.write_raw_external_element(writer, raw),
             Value::AMF3(e) => self.write_value_element(writer, e),
             Value::Unsupported => self.write_undefined_element(writer),
-            Value::Reference(_) => unimplemented!(),
+            Value::Reference(_) => Err(crate::io::Error::new(
+                crate::io::ErrorKind::InvalidInput,
+                "AMF0 references can't be written as AMF3",
+            )),
             Value::Amf3ObjectReference(id) => {
                 let r = *self
                     .object_id_to_reference
                     .borrow()
                     .get(id)
-                    .expect("Invalid reference");
+                    .ok_or_else(|| {
+                        crate::io::Error::new(
+                            crate::io::ErrorKind::InvalidInput,
+                            format!("Reference to {:?} before it was written", id),
+                        )
+                    })?;
                 self.write_type_marker(writer, TypeMarker::Object)?;
                 self.write_object_reference(writer, r as u32)
             }
@@ -639,7 +743,7 @@ impl AMF3Encoder {
     fn write_element<'a, 'b: 'a, W: Write + 'a>(
         &'b self,
         writer: &mut W,
-        element: &'b Element,
+        element: &'b Element<'v>,
     ) -> Result<()> {
         self.write_string(writer, &element.name)?;
         self.write_value_element(writer, &element.value)?;
@@ -649,18 +753,31 @@ impl AMF3Encoder {
     fn write_element_and_padding<'a, 'b: 'a, W: Write + 'a>(
         &'b self,
         writer: &mut W,
-        element: &'b Element,
+        element: &'b Element<'v>,
     ) -> Result<()> {
         self.write_element(writer, element)?;
         writer.write_all(&PADDING)?;
         Ok(())
     }
 
+    /// Forget every value written so far, so the next value written is the first in each reference table
+    ///
+    /// References are only valid within one body, so this is done by `write_body` before writing
+    fn reset(&self) {
+        self.string_reference_table.clear();
+        self.trait_reference_table.borrow_mut().clear();
+        self.written_values.borrow_mut().clear();
+        self.object_id_to_reference.borrow_mut().clear();
+        self.dedup_table.take();
+        self.reference_count.set(0);
+    }
+
     pub(crate) fn write_body<'a, 'b: 'a, W: Write + 'a>(
         &'b self,
         writer: &mut W,
-        elements: &'b [Element],
+        elements: &'b [Element<'v>],
     ) -> Result<()> {
+        self.reset();
         for e in elements {
             self.write_element_and_padding(writer, e)?;
         }
@@ -668,8 +785,29 @@ impl AMF3Encoder {
     }
 }
 
+/// Get the type marker of values that are stored in the object table, these can be written as references
+fn reference_marker(s: &Value<'_>) -> Option<TypeMarker> {
+    Some(match s {
+        Value::Object(_, _, _) | Value::Custom(_, _, _) | Value::RawExternal(_) => {
+            TypeMarker::Object
+        }
+        Value::ECMAArray(_, _, _) | Value::StrictArray(_) => TypeMarker::Array,
+        Value::Date(_, _) => TypeMarker::Date,
+        Value::XML(_, false) => TypeMarker::Xml,
+        Value::XML(_, true) => TypeMarker::XmlString,
+        Value::ByteArray(_) => TypeMarker::ByteArray,
+        Value::VectorInt(_, _) => TypeMarker::VectorInt,
+        Value::VectorUInt(_, _) => TypeMarker::VectorUInt,
+        Value::VectorDouble(_, _) => TypeMarker::VectorDouble,
+        Value::VectorObject(_, _, _) => TypeMarker::VectorObject,
+        Value::Dictionary(_, _) => TypeMarker::Dictionary,
+        _ => return None,
+    })
+}
+
 #[cfg(test)]
 mod write_number_tests {
+    use crate::amf3::length::Length;
     use crate::amf3::write::AMF3Encoder;
 
     #[test]
@@ -695,4 +833,42 @@ mod write_number_tests {
         e.write_int(&mut v, -268435455).unwrap();
         assert_eq!(v, &[192, 128, 128, 1]);
     }
+
+    #[test]
+    fn write_int_boundaries() {
+        let e = AMF3Encoder::default();
+        let mut v = vec![];
+        e.write_int(&mut v, 0x0FFF_FFFF).unwrap();
+        e.write_int(&mut v, -0x1000_0000).unwrap();
+        assert_eq!(v, &[0xBF, 0xFF, 0xFF, 0xFF, 0xC0, 0x80, 0x80, 0x00]);
+
+        assert!(e.write_int(&mut vec![], 0x1000_0000).is_err());
+        assert!(e.write_int(&mut vec![], -0x1000_0001).is_err());
+    }
+
+    #[test]
+    fn write_u29_boundaries() {
+        let e = AMF3Encoder::default();
+        let mut v = vec![];
+        e.write_u29(&mut v, 0x1FFF_FFFF).unwrap();
+        assert_eq!(v, &[0xFF, 0xFF, 0xFF, 0xFF]);
+
+        assert!(e.write_u29(&mut vec![], 0x1FFF_FFFF + 1).is_err());
+        assert!(e.write_u29(&mut vec![], 0x2000_0001).is_err());
+    }
+
+    #[test]
+    fn write_length_boundaries() {
+        let e = AMF3Encoder::default();
+        let mut v = vec![];
+        Length::Size(0x0FFF_FFFF).write(&mut v, &e).unwrap();
+        Length::Reference(0x0FFF_FFFF).write(&mut v, &e).unwrap();
+        assert_eq!(v, &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE]);
+
+        assert!(Length::Size(0x1000_0000).write(&mut vec![], &e).is_err());
+        assert!(Length::Reference(0x1000_0000)
+            .write(&mut vec![], &e)
+            .is_err());
+        assert!(Length::Size(u32::MAX).write(&mut vec![], &e).is_err());
+    }
 }
@@ -0,0 +1,67 @@
+use crate::prelude::*;
+
+/// Decode standard or URL-safe base64, padding is optional and whitespace is ignored
+///
+/// Returns the offset of the first invalid character on failure
+pub(crate) fn decode(input: &str) -> Result<Vec<u8>, usize> {
+    let mut out = Vec::with_capacity(input.len() / 4 * 3);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    let mut padding = 0;
+
+    for (offset, c) in input.char_indices() {
+        let sextet = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' | '-' => 62,
+            '/' | '_' => 63,
+            '=' => {
+                padding += 1;
+                continue;
+            }
+            c if c.is_ascii_whitespace() => continue,
+            _ => return Err(offset),
+        };
+        // Nothing but padding can follow padding
+        if padding > 0 {
+            return Err(offset);
+        }
+
+        buffer = (buffer << 6) | sextet;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+
+    // A single character left over can't make a whole byte
+    if bits == 6 {
+        return Err(input.len());
+    }
+    Ok(out)
+}
+
+/// Encode as standard base64 with padding
+#[cfg(feature = "json")]
+pub(crate) fn encode(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let buffer = chunk.iter().enumerate().fold(0u32, |buffer, (i, b)| {
+            buffer | (u32::from(*b) << (16 - 8 * i))
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(char::from(
+                    ALPHABET[(buffer >> (18 - 6 * i)) as usize & 0x3F],
+                ));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,193 @@
+use crate::errors::CompressionError;
+use crate::prelude::*;
+use crate::types::Value;
+use flate2::bufread::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+use std::io::{ErrorKind, Read, Write};
+
+/// A container that an lso can be wrapped in
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Compression {
+    /// A zlib stream, starting with 0x78
+    Zlib,
+
+    /// A gzip member, starting with 0x1f 0x8b
+    Gzip,
+}
+
+impl Compression {
+    /// Detect the container from the magic bytes at the start of the data, `None` if it isn't compressed
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x1f, 0x8b, ..] => Some(Compression::Gzip),
+            // The header check bits make the first two bytes a multiple of 31
+            [cmf, flg, ..]
+                if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 =>
+            {
+                Some(Compression::Zlib)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How the contents of a flash `ByteArray` were compressed, as in `flash.utils.CompressionAlgorithm`
+///
+/// Games often store data compressed with `ByteArray.compress` inside a byte array, see `uncompress`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CompressionAlgorithm {
+    /// A zlib stream, the default of `ByteArray.compress`
+    Zlib,
+
+    /// Raw deflate data without a container, from `ByteArray.deflate`
+    Deflate,
+}
+
+impl CompressionAlgorithm {
+    /// Detect zlib data from its header, `None` for anything else
+    ///
+    /// Raw deflate data has no header, and uncompressed bytes can start with a valid zlib header by chance, so this
+    /// is only a guess for data that is expected to be compressed
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        match Compression::detect(data) {
+            Some(Compression::Zlib) => Some(CompressionAlgorithm::Zlib),
+            _ => None,
+        }
+    }
+}
+
+/// Decompress data if it starts with zlib or gzip magic, data that doesn't is returned unchanged
+///
+/// The container that was removed is returned, so that `compress` can wrap the data again after it has been encoded
+pub fn decompress(data: &[u8]) -> Result<(Cow<'_, [u8]>, Option<Compression>), CompressionError> {
+    decompress_with_limit(data, usize::MAX)
+}
+
+/// Decompress data as with `decompress`, failing with `CompressionError::TooLarge` if it would decompress to more than
+/// `max_len` bytes
+///
+/// A few bytes of compressed data can expand to gigabytes, so this should be used for untrusted input
+pub fn decompress_with_limit(
+    data: &[u8],
+    max_len: usize,
+) -> Result<(Cow<'_, [u8]>, Option<Compression>), CompressionError> {
+    let Some(compression) = Compression::detect(data) else {
+        return Ok((Cow::Borrowed(data), None));
+    };
+
+    let decompressed = match compression {
+        Compression::Zlib => inflate_zlib(data, max_len)?,
+        Compression::Gzip => read_limited(GzDecoder::new(data), max_len)?,
+    };
+    Ok((Cow::Owned(decompressed), Some(compression)))
+}
+
+/// Decompress the contents of a byte array that was compressed with the given algorithm
+///
+/// Unlike `decompress`, data that isn't compressed is an error rather than being returned unchanged
+/// ```
+/// use flash_lso::compression::{compress_bytes, uncompress, CompressionAlgorithm};
+///
+/// for algorithm in [CompressionAlgorithm::Zlib, CompressionAlgorithm::Deflate] {
+///     let compressed = compress_bytes(b"data", algorithm);
+///     assert_eq!(uncompress(&compressed, algorithm).unwrap(), b"data");
+/// }
+/// ```
+pub fn uncompress(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, CompressionError> {
+    uncompress_with_limit(data, algorithm, usize::MAX)
+}
+
+/// Decompress the contents of a byte array as with `uncompress`, failing with `CompressionError::TooLarge` if it would
+/// decompress to more than `max_len` bytes
+pub fn uncompress_with_limit(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+    max_len: usize,
+) -> Result<Vec<u8>, CompressionError> {
+    match algorithm {
+        CompressionAlgorithm::Zlib if Compression::detect(data) == Some(Compression::Zlib) => {
+            inflate_zlib(data, max_len)
+        }
+        CompressionAlgorithm::Zlib => Err(CompressionError::InvalidHeader),
+        CompressionAlgorithm::Deflate => read_limited(DeflateDecoder::new(data), max_len),
+    }
+}
+
+/// Compress data with the given algorithm, to be stored in a byte array
+pub fn compress_bytes(data: &[u8], algorithm: CompressionAlgorithm) -> Vec<u8> {
+    match algorithm {
+        CompressionAlgorithm::Zlib => compress(data, Compression::Zlib),
+        CompressionAlgorithm::Deflate => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            write_all(&mut encoder, data);
+            encoder.finish().expect("Writing to a Vec can't fail")
+        }
+    }
+}
+
+impl Value<'_> {
+    /// Decompress the contents of a `ByteArray` that was compressed with the given algorithm, see `uncompress`
+    ///
+    /// The result is often AMF3 data that can be read with `amf3::decode`
+    pub fn inflate(&self, algorithm: CompressionAlgorithm) -> Result<Vec<u8>, CompressionError> {
+        let bytes = self.as_bytes().ok_or(CompressionError::NotByteArray)?;
+        uncompress(bytes, algorithm)
+    }
+
+    /// Create a `ByteArray` holding the given data compressed with the given algorithm, the reverse of `inflate`
+    pub fn deflate(data: &[u8], algorithm: CompressionAlgorithm) -> Value<'static> {
+        Value::ByteArray(Cow::Owned(compress_bytes(data, algorithm)))
+    }
+}
+
+/// Compress data and wrap it in a zlib or gzip container
+pub fn compress(data: &[u8], compression: Compression) -> Vec<u8> {
+    let level = flate2::Compression::default();
+    match compression {
+        Compression::Zlib => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), level);
+            write_all(&mut encoder, data);
+            encoder.finish()
+        }
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), level);
+            write_all(&mut encoder, data);
+            encoder.finish()
+        }
+    }
+    .expect("Writing to a Vec can't fail")
+}
+
+fn write_all(encoder: &mut impl Write, data: &[u8]) {
+    encoder
+        .write_all(data)
+        .expect("Writing to a Vec can't fail");
+}
+
+/// Decompress a zlib stream, which `ZlibDecoder` would reject without saying why if it needs a preset dictionary
+fn inflate_zlib(data: &[u8], max_len: usize) -> Result<Vec<u8>, CompressionError> {
+    if data[1] & 0x20 != 0 {
+        return Err(CompressionError::PresetDictionary);
+    }
+    read_limited(ZlibDecoder::new(data), max_len)
+}
+
+/// Read all of the output of a decoder, stopping one byte after `max_len` so that large outputs aren't held in memory
+fn read_limited(decoder: impl Read, max_len: usize) -> Result<Vec<u8>, CompressionError> {
+    let limit = u64::try_from(max_len).unwrap_or(u64::MAX).saturating_add(1);
+    let mut out = Vec::new();
+    decoder
+        .take(limit)
+        .read_to_end(&mut out)
+        .map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof => CompressionError::Truncated,
+            _ => CompressionError::InvalidData,
+        })?;
+    if out.len() > max_len {
+        return Err(CompressionError::TooLarge);
+    }
+    Ok(out)
+}
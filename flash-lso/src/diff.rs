@@ -0,0 +1,187 @@
+use crate::prelude::*;
+use crate::types::{ClassDefinition, Element, Lso, Value};
+
+/// A difference between two lsos, see `diff`
+///
+/// Paths are in the form taken by `Value::get_path`, starting with the name of an element of the body, such as
+/// `player.inventory[3].count`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change<'a> {
+    /// A value that is only in the second lso
+    Added {
+        /// Where the value is in the second lso
+        path: String,
+
+        /// The value added
+        value: Rc<Value<'a>>,
+    },
+
+    /// A value that is only in the first lso
+    Removed {
+        /// Where the value was in the first lso
+        path: String,
+
+        /// The value removed
+        old: Rc<Value<'a>>,
+    },
+
+    /// A value that is at the same path in both lsos but is different, including a change of type
+    Modified {
+        /// Where the value is in both lsos
+        path: String,
+
+        /// The value in the first lso
+        old: Rc<Value<'a>>,
+
+        /// The value in the second lso
+        new: Rc<Value<'a>>,
+    },
+}
+
+impl Change<'_> {
+    /// The path of the value that changed
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Modified { path, .. } => path,
+        }
+    }
+}
+
+/// Compare the bodies of two lsos, giving every value that was added, removed or changed going from `a` to `b`
+///
+/// Objects with the same class, `ECMAArray`s, `StrictArray`s and `VectorObject`s of the same type are compared member
+/// by member, so a change deep inside one is reported at its own path rather than as a change to the whole container.
+/// Members are matched by name and entries by index, an entry inserted into the middle of an array shows as changes to
+/// every entry after it. Any other values, including dictionaries and externalized objects, are compared as a whole
+/// with `Value::structural_eq`. Object ids aren't compared and `AMF3` wrappers are looked through
+/// ```
+/// use flash_lso::diff::{diff, Change};
+/// use flash_lso::types::{AMFVersion, Lso, Value};
+///
+/// let mut before = Lso::new_empty("save", AMFVersion::AMF3);
+/// before.insert("level", Value::Integer(3));
+/// let mut after = before.clone();
+/// after.insert("level", Value::Integer(4));
+///
+/// let changes = diff(&before, &after);
+/// assert_eq!(changes.len(), 1);
+/// assert!(matches!(&changes[0], Change::Modified { path, .. } if path == "level"));
+/// ```
+pub fn diff<'a>(a: &Lso<'a>, b: &Lso<'a>) -> Vec<Change<'a>> {
+    let mut changes = Vec::new();
+    diff_elements("", &a.body, &b.body, &mut changes);
+    changes
+}
+
+fn child_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn diff_elements<'a>(
+    path: &str,
+    a: &[Element<'a>],
+    b: &[Element<'a>],
+    changes: &mut Vec<Change<'a>>,
+) {
+    for element in a {
+        let path = child_path(path, &element.name);
+        match b.iter().find(|e| e.name == element.name) {
+            Some(other) => diff_values(path, &element.value, &other.value, changes),
+            None => changes.push(Change::Removed {
+                path,
+                old: Rc::clone(&element.value),
+            }),
+        }
+    }
+
+    for element in b {
+        if !a.iter().any(|e| e.name == element.name) {
+            changes.push(Change::Added {
+                path: child_path(path, &element.name),
+                value: Rc::clone(&element.value),
+            });
+        }
+    }
+}
+
+fn diff_indexed<'a>(
+    path: &str,
+    a: &[Rc<Value<'a>>],
+    b: &[Rc<Value<'a>>],
+    changes: &mut Vec<Change<'a>>,
+) {
+    for index in 0..a.len().max(b.len()) {
+        let path = format!("{}[{}]", path, index);
+        match (a.get(index), b.get(index)) {
+            (Some(a), Some(b)) => diff_values(path, a, b, changes),
+            (Some(old), None) => changes.push(Change::Removed {
+                path,
+                old: Rc::clone(old),
+            }),
+            (None, Some(value)) => changes.push(Change::Added {
+                path,
+                value: Rc::clone(value),
+            }),
+            (None, None) => {}
+        }
+    }
+}
+
+/// Look through any `AMF3` wrappers
+fn unwrap<'a, 'b>(value: &'b Rc<Value<'a>>) -> &'b Rc<Value<'a>> {
+    match value.as_ref() {
+        #[cfg(feature = "amf3")]
+        Value::AMF3(inner) => unwrap(inner),
+        _ => value,
+    }
+}
+
+fn diff_values<'a>(
+    path: String,
+    a: &Rc<Value<'a>>,
+    b: &Rc<Value<'a>>,
+    changes: &mut Vec<Change<'a>>,
+) {
+    let (a, b) = (unwrap(a), unwrap(b));
+    if Rc::ptr_eq(a, b) {
+        return;
+    }
+
+    match (a.as_ref(), b.as_ref()) {
+        (Value::Object(_, a_elements, a_def), Value::Object(_, b_elements, b_def))
+            if class_name(a_def.as_ref()) == class_name(b_def.as_ref()) =>
+        {
+            diff_elements(&path, a_elements, b_elements, changes)
+        }
+        (Value::ECMAArray(a_dense, a_elements, _), Value::ECMAArray(b_dense, b_elements, _)) => {
+            diff_indexed(&path, a_dense, b_dense, changes);
+            diff_elements(&path, a_elements, b_elements, changes);
+        }
+        (Value::StrictArray(a_values), Value::StrictArray(b_values)) => {
+            diff_indexed(&path, a_values, b_values, changes)
+        }
+        (
+            Value::VectorObject(a_values, a_name, a_fixed),
+            Value::VectorObject(b_values, b_name, b_fixed),
+        ) if a_name == b_name && a_fixed == b_fixed => {
+            diff_indexed(&path, a_values, b_values, changes)
+        }
+        _ if a.structural_eq(b) => {}
+        _ => changes.push(Change::Modified {
+            path,
+            old: Rc::clone(a),
+            new: Rc::clone(b),
+        }),
+    }
+}
+
+/// The class name of an object, objects without a class definition are anonymous
+fn class_name(def: Option<&ClassDefinition>) -> &str {
+    def.map_or("", |def| def.name.as_str())
+}
@@ -1,9 +1,12 @@
+use crate::prelude::*;
+use crate::types::{AMFVersion, FormatVersion};
 use nom::error::{ErrorKind, FromExternalError, ParseError};
+#[cfg(feature = "std")]
+use std::path::PathBuf;
 use thiserror::Error;
 
 // Allow the Nom variant to be large
 #[allow(variant_size_differences)]
-
 /// Enum for representing decoding errors
 #[derive(Error, Debug, Clone, Eq, PartialEq)]
 pub enum Error<'a> {
@@ -15,9 +18,13 @@ pub enum Error<'a> {
     #[error("Invalid reference")]
     InvalidReference(u16),
 
-    /// Invalid type marker
+    /// Invalid type marker, with the input starting at the marker
     #[error("Unsupported tag")]
-    UnsupportedType(u8),
+    UnsupportedType(&'a [u8], u8),
+
+    /// The format version given in a header can't be decoded
+    #[error("Unsupported format version")]
+    UnsupportedFormatVersion(u8),
 
     /// A nom internal error
     #[error("Nom internal error")]
@@ -29,7 +36,31 @@ pub enum Error<'a> {
 
     /// An unknown IO error occured
     #[error("IO error: {0}")]
-    IoError(String, std::io::ErrorKind),
+    IoError(String, crate::io::ErrorKind),
+
+    /// A string isn't valid UTF-8
+    #[error("Invalid UTF-8: {0}")]
+    InvalidUtf8(#[from] core::str::Utf8Error),
+
+    /// Values are nested deeper than the decoder allows, with the input starting at the value that is too deep
+    #[error("Maximum nesting depth exceeded")]
+    DepthExceeded(&'a [u8]),
+
+    /// An AMF3 reference to the value at this index of the object reference table, which is still being read
+    ///
+    /// Only objects can refer to themselves, as `Value::Amf3ObjectReference`, other values can't contain themselves
+    #[error("Reference to a value that is still being read")]
+    CyclicReference(usize),
+
+    /// Reading the value would take the memory used by the decoder over its budget, with the input starting at the value
+    #[error("Allocation limit exceeded")]
+    AllocationLimit(&'a [u8]),
+}
+
+impl From<crate::io::Error> for Error<'_> {
+    fn from(e: crate::io::Error) -> Self {
+        Error::IoError(e.to_string(), e.kind())
+    }
 }
 
 impl<'a> ParseError<&'a [u8]> for Error<'a> {
@@ -47,3 +78,322 @@ impl<'a, E> FromExternalError<&'a [u8], E> for Error<'a> {
         Error::Nom(input, kind)
     }
 }
+
+/// An error reading or writing an lso file, with the path of the file
+#[cfg(feature = "std")]
+#[derive(Error, Debug)]
+pub enum FileError {
+    /// The file couldn't be read or written
+    #[error("Failed to access {}: {source}", .path.display())]
+    Io {
+        /// The path of the file
+        path: PathBuf,
+
+        /// The underlying error
+        #[source]
+        source: crate::io::Error,
+    },
+
+    /// The contents of the file couldn't be decoded
+    #[error("Failed to decode {}: {message}", .path.display())]
+    Decode {
+        /// The path of the file
+        path: PathBuf,
+
+        /// A description of the decoding error
+        message: String,
+    },
+}
+
+#[cfg(feature = "std")]
+impl FileError {
+    pub(crate) fn decode(path: PathBuf, error: DecodeError) -> Self {
+        FileError::Decode {
+            path,
+            message: error.to_string(),
+        }
+    }
+}
+
+/// One step of the path from the body of an lso to a value
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PathSegment {
+    /// The element or property with this name
+    Name(String),
+
+    /// The entry at this index of an array, vector or dictionary
+    Index(usize),
+}
+
+/// A decoding error, with where it happened in the data and in the values being read
+///
+/// The offset isn't known for some errors, such as an invalid reference
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[error("{kind}{}{}", .offset.map(|o| format!(" at byte {}", o)).unwrap_or_default(), self.display_path())]
+pub struct DecodeError {
+    /// The path of the value that failed to decode, outermost first, this is empty if the error wasn't in a value
+    pub path: Vec<PathSegment>,
+
+    /// The offset of the error in the data, if known
+    pub offset: Option<usize>,
+
+    /// A description of what went wrong
+    pub kind: String,
+
+    /// What went wrong, for callers that want to handle some errors differently
+    pub reason: DecodeReason,
+}
+
+/// The broad cause of a `DecodeError`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DecodeReason {
+    /// The data doesn't start with the magic bytes of an lso header
+    BadMagic,
+
+    /// The data ended before the value being read, as it does in a truncated file
+    UnexpectedEof,
+
+    /// A type marker that isn't known or can't be decoded
+    UnknownMarker(u8),
+
+    /// A length or count that is larger than the data left
+    InvalidLength,
+
+    /// Values nested deeper than the maximum depth of the decoder, see `Reader::set_max_depth`
+    DepthExceeded,
+
+    /// A reference to an array or other value from inside itself
+    CyclicReference,
+
+    /// Decoding would use more memory than the budget of the decoder, see `Reader::set_max_alloc`
+    AllocationLimit,
+
+    /// Any other error
+    Other,
+}
+
+/// The offsets of the magic bytes in an lso header, see `Reader::parse_header`
+const MAGIC_OFFSETS: [usize; 2] = [0, 6];
+
+impl DecodeError {
+    /// Describe a decoding error without borrowing from the data it happened in
+    pub fn new(data: &[u8], error: nom::Err<Error<'_>>, path: Vec<PathSegment>) -> Self {
+        let (offset, kind, reason) = match error {
+            nom::Err::Error(Error::Nom(rest, kind)) | nom::Err::Failure(Error::Nom(rest, kind)) => {
+                let offset = data.len() - rest.len();
+                let reason = match kind {
+                    ErrorKind::Tag if path.is_empty() && MAGIC_OFFSETS.contains(&offset) => {
+                        DecodeReason::BadMagic
+                    }
+                    ErrorKind::Eof => DecodeReason::UnexpectedEof,
+                    ErrorKind::TooLarge => DecodeReason::InvalidLength,
+                    _ => DecodeReason::Other,
+                };
+                (Some(offset), format!("{:?}", kind), reason)
+            }
+            nom::Err::Error(Error::UnsupportedType(rest, marker))
+            | nom::Err::Failure(Error::UnsupportedType(rest, marker)) => (
+                Some(data.len() - rest.len()),
+                Error::UnsupportedType(rest, marker).to_string(),
+                DecodeReason::UnknownMarker(marker),
+            ),
+            nom::Err::Error(Error::DepthExceeded(rest))
+            | nom::Err::Failure(Error::DepthExceeded(rest)) => (
+                Some(data.len() - rest.len()),
+                Error::DepthExceeded(rest).to_string(),
+                DecodeReason::DepthExceeded,
+            ),
+            nom::Err::Error(Error::AllocationLimit(rest))
+            | nom::Err::Failure(Error::AllocationLimit(rest)) => (
+                Some(data.len() - rest.len()),
+                Error::AllocationLimit(rest).to_string(),
+                DecodeReason::AllocationLimit,
+            ),
+            nom::Err::Error(e @ Error::CyclicReference(_))
+            | nom::Err::Failure(e @ Error::CyclicReference(_)) => {
+                (None, e.to_string(), DecodeReason::CyclicReference)
+            }
+            nom::Err::Error(e) | nom::Err::Failure(e) => (None, e.to_string(), DecodeReason::Other),
+            nom::Err::Incomplete(_) => (
+                None,
+                "Unexpected end of file".to_string(),
+                DecodeReason::UnexpectedEof,
+            ),
+        };
+        Self {
+            path,
+            offset,
+            kind,
+            reason,
+        }
+    }
+
+    /// Format the path as names separated by dots and indices in brackets, such as `inventory.items[12]`
+    pub fn path_string(&self) -> String {
+        let mut out = String::new();
+        for segment in &self.path {
+            match segment {
+                PathSegment::Name(name) if out.is_empty() => out.push_str(name),
+                PathSegment::Name(name) => {
+                    out.push('.');
+                    out.push_str(name);
+                }
+                PathSegment::Index(index) => out.push_str(&format!("[{}]", index)),
+            }
+        }
+        out
+    }
+
+    fn display_path(&self) -> String {
+        if self.path.is_empty() {
+            String::new()
+        } else {
+            format!(" in {}", self.path_string())
+        }
+    }
+}
+
+/// Record a segment of the path to a failed value while its error is returned through the decoder
+///
+/// Segments are pushed innermost first and nothing is done on success, so tracking the path costs nothing unless
+/// decoding fails
+pub(crate) fn at_path<'a, T>(
+    path: &mut Vec<PathSegment>,
+    segment: impl FnOnce() -> PathSegment,
+    result: crate::nom_utils::AMFResult<'a, T>,
+) -> crate::nom_utils::AMFResult<'a, T> {
+    if result.is_err() {
+        path.push(segment());
+    }
+    result
+}
+
+/// An error reading an lso from a data url
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum DataUrlError {
+    /// The url is a data url, but its contents aren't base64
+    #[error("Data url is not base64 encoded")]
+    NotBase64,
+
+    /// The base64 contents are invalid
+    #[error("Invalid base64 at offset {0}")]
+    InvalidBase64(usize),
+
+    /// The decoded contents couldn't be decoded as an lso
+    #[error("Failed to decode data url: {0}")]
+    Decode(String),
+}
+
+impl DataUrlError {
+    pub(crate) fn decode(error: DecodeError) -> Self {
+        DataUrlError::Decode(error.to_string())
+    }
+}
+
+/// An error reading a stream of length-framed values with `FramedReader`
+#[cfg(feature = "std")]
+#[derive(Error, Debug)]
+pub enum FrameError {
+    /// The stream couldn't be read, or ended inside a frame
+    #[error("Failed to read frame: {0}")]
+    Io(#[from] crate::io::Error),
+
+    /// The value in a frame couldn't be decoded
+    #[error("Failed to decode frame: {0}")]
+    Decode(DecodeError),
+
+    /// A frame has bytes left over after its value
+    #[error("Frame has {0} bytes after its value")]
+    TrailingBytes(usize),
+}
+
+/// An error reading an lso from a stream with `stream::Reader`
+#[cfg(feature = "std")]
+#[derive(Error, Debug)]
+pub enum StreamError {
+    /// The stream couldn't be read
+    #[error("Failed to read stream: {0}")]
+    Io(#[from] crate::io::Error),
+
+    /// The header or an element couldn't be decoded, offsets are from the start of the stream
+    #[error("Failed to decode stream: {0}")]
+    Decode(DecodeError),
+}
+
+/// An error removing the zlib or gzip container from an lso
+#[cfg(feature = "compression")]
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum CompressionError {
+    /// The data ended before the container did
+    #[error("Compressed data is truncated")]
+    Truncated,
+
+    /// The container header is invalid
+    #[error("Invalid compression header")]
+    InvalidHeader,
+
+    /// The zlib stream needs a preset dictionary, these aren't supported
+    #[error("Compressed data uses a preset dictionary")]
+    PresetDictionary,
+
+    /// The compressed data is invalid
+    #[error("Invalid compressed data")]
+    InvalidData,
+
+    /// The data decompresses to more than the given limit, see `decompress_with_limit`
+    #[error("Decompressed data is larger than the limit")]
+    TooLarge,
+
+    /// The value given to `Value::inflate` isn't a `ByteArray`
+    #[error("Value is not a byte array")]
+    NotByteArray,
+}
+
+/// A problem found by `Lso::validate` that would stop an lso from being encoded correctly
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum ValidationError {
+    /// The header gives a format version that can't be encoded
+    #[error("Unsupported format version {0}")]
+    UnsupportedFormatVersion(FormatVersion),
+
+    /// A name is longer than its length prefix can hold
+    #[error("Name of {path} is too long ({length} bytes)")]
+    NameTooLong {
+        /// Where the name is, such as `body.foo.bar`
+        path: String,
+
+        /// The length of the name in bytes
+        length: usize,
+    },
+
+    /// A value that can't be represented in the AMF version it is written with
+    #[error("{path} can't be encoded with {version}")]
+    UnsupportedValue {
+        /// Where the value is
+        path: String,
+
+        /// The AMF version the value would be written with
+        version: AMFVersion,
+    },
+
+    /// An element of a `VectorObject` that doesn't have the type of the vector
+    #[error("{path} is not a {type_name}")]
+    WrongVectorElement {
+        /// Where the element is
+        path: String,
+
+        /// The type name of the vector
+        type_name: String,
+    },
+}
+
+/// Values that `Lso::to_amf0` would lose converting an lso
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[error("{} values can't be converted to {version}: {}", .paths.len(), .paths.join(", "))]
+pub struct ConversionError {
+    /// The version being converted to
+    pub version: AMFVersion,
+
+    /// Where each value that can't be converted is, such as `body.foo[1]`
+    pub paths: Vec<String>,
+}
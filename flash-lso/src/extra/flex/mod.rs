@@ -16,5 +16,139 @@ const CORRELATION_ID_BYTES_FLAG: u8 = 2;
 
 const OPERATION_FLAG: u8 = 1;
 
+const IDENTITY_FLAG: u8 = 1;
+const DATA_OPERATION_FLAG: u8 = 2;
+
+const CAUSE_FLAG: u8 = 1;
+const PROPERTY_NAMES_FLAG: u8 = 2;
+
 pub mod read;
 pub mod write;
+
+use crate::prelude::*;
+use crate::types::{Attribute, ClassDefinition, Element, Value};
+
+/// The class of an `ArrayCollection`
+pub const ARRAY_COLLECTION: &str = "flex.messaging.io.ArrayCollection";
+
+/// The class of an `ArrayList`, which is read and written in the same way as an `ArrayCollection`
+pub const ARRAY_LIST: &str = "flex.messaging.io.ArrayList";
+
+/// The class of an `ObjectProxy`
+pub const OBJECT_PROXY: &str = "flex.messaging.io.ObjectProxy";
+
+/// The class of a `ManagedObjectProxy`, which is read and written in the same way as an `ObjectProxy`
+pub const MANAGED_OBJECT_PROXY: &str = "flex.messaging.io.ManagedObjectProxy";
+
+/// The class of a `SerializationProxy`, which is read and written in the same way as an `ObjectProxy`
+pub const SERIALIZATION_PROXY: &str = "flex.messaging.io.SerializationProxy";
+
+/// A Flex collection or proxy wrapping another value
+///
+/// These are read by the decoders in `read` as a `Value::Custom` with the class of the wrapper and the wrapped value as
+/// its only custom element, so they are written back as the same wrapper. This gives a typed view of one. Wrappers read
+/// with `DecoderOptions::decode_flex` unset are kept as raw bytes, and aren't recognised
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlexValue<'v, 'a> {
+    /// An `ArrayCollection` or `ArrayList`
+    ArrayCollection {
+        /// The class of the collection, `ARRAY_COLLECTION` or `ARRAY_LIST`
+        class: &'v str,
+
+        /// The array wrapped by the collection
+        source: &'v Rc<Value<'a>>,
+    },
+
+    /// An `ObjectProxy`, `ManagedObjectProxy` or `SerializationProxy`
+    ObjectProxy {
+        /// The class of the proxy, such as `OBJECT_PROXY`
+        class: &'v str,
+
+        /// The object wrapped by the proxy
+        object: &'v Rc<Value<'a>>,
+    },
+}
+
+impl<'v, 'a> FlexValue<'v, 'a> {
+    /// Get the Flex wrapper that a value is, if it is one
+    pub fn from_value(value: &'v Value<'a>) -> Option<Self> {
+        let (custom_elements, def) = match value {
+            Value::Custom(custom_elements, elements, Some(def)) if elements.is_empty() => {
+                (custom_elements, def)
+            }
+            _ => return None,
+        };
+        let [element] = custom_elements.as_slice() else {
+            return None;
+        };
+        let class = def.name.as_str();
+        match (class, element.name()) {
+            (ARRAY_COLLECTION | ARRAY_LIST, "data") => Some(FlexValue::ArrayCollection {
+                class,
+                source: &element.value,
+            }),
+            (OBJECT_PROXY | MANAGED_OBJECT_PROXY | SERIALIZATION_PROXY, "object") => {
+                Some(FlexValue::ObjectProxy {
+                    class,
+                    object: &element.value,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Wrap an array in an `ArrayCollection`, as it is read by the decoders in `read`
+pub fn array_collection<'a>(source: impl Into<Rc<Value<'a>>>) -> Value<'a> {
+    external(ARRAY_COLLECTION, "data", source.into())
+}
+
+/// Wrap an object in an `ObjectProxy`, as it is read by the decoders in `read`
+pub fn object_proxy<'a>(object: impl Into<Rc<Value<'a>>>) -> Value<'a> {
+    external(OBJECT_PROXY, "object", object.into())
+}
+
+fn external<'a>(class: &str, name: &'static str, value: Rc<Value<'a>>) -> Value<'a> {
+    Value::Custom(
+        vec![Element::new(name, value)],
+        vec![],
+        Some(ClassDefinition {
+            name: class.to_string(),
+            attributes: Attribute::External.into(),
+            static_properties: vec![],
+        }),
+    )
+}
+
+/// Format a 16 byte Flex UID as a string in the canonical `8-4-4-4-12` hex form
+///
+/// Returns `None` if `bytes` is not 16 bytes long
+pub fn uid_to_string(bytes: &[u8]) -> Option<String> {
+    if bytes.len() != 16 {
+        return None;
+    }
+
+    let mut s = String::with_capacity(36);
+    for (index, byte) in bytes.iter().enumerate() {
+        if matches!(index, 4 | 6 | 8 | 10) {
+            s.push('-');
+        }
+        s.push_str(&format!("{:02X}", byte));
+    }
+    Some(s)
+}
+
+/// Parse a UID string in the `8-4-4-4-12` hex form back into its bytes, this is the inverse of `uid_to_string`
+pub fn string_to_uid(s: &str) -> Option<[u8; 16]> {
+    let groups: Vec<&str> = s.split('-').collect();
+    if groups.iter().map(|g| g.len()).collect::<Vec<_>>() != [8, 4, 4, 4, 12] {
+        return None;
+    }
+
+    let hex = groups.concat();
+    let mut bytes = [0; 16];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(index * 2..index * 2 + 2)?, 16).ok()?;
+    }
+    Some(bytes)
+}
@@ -1,24 +1,26 @@
 //! Handles decoding of flex types
 
 use crate::amf3::read::AMF3Decoder;
+use crate::extra::flex::uid_to_string;
 use crate::extra::flex::{
-    BODY_FLAG, CLIENT_ID_BYTES_FLAG, CLIENT_ID_FLAG, CORRELATION_ID_BYTES_FLAG,
-    CORRELATION_ID_FLAG, DESTINATION_ID_FLAG, HEADERS_FLAG, MESSAGE_ID_BYTES_FLAG, MESSAGE_ID_FLAG,
-    NEXT_FLAG, OPERATION_FLAG, TIMESTAMP_FLAG, TTL_FLAG,
+    ARRAY_COLLECTION, ARRAY_LIST, BODY_FLAG, CAUSE_FLAG, CLIENT_ID_BYTES_FLAG, CLIENT_ID_FLAG,
+    CORRELATION_ID_BYTES_FLAG, CORRELATION_ID_FLAG, DATA_OPERATION_FLAG, DESTINATION_ID_FLAG,
+    HEADERS_FLAG, IDENTITY_FLAG, MANAGED_OBJECT_PROXY, MESSAGE_ID_BYTES_FLAG, MESSAGE_ID_FLAG,
+    NEXT_FLAG, OBJECT_PROXY, OPERATION_FLAG, PROPERTY_NAMES_FLAG, SERIALIZATION_PROXY,
+    TIMESTAMP_FLAG, TTL_FLAG,
 };
 use crate::nom_utils::AMFResult;
-use crate::types::Element;
+use crate::prelude::*;
+use crate::types::{Element, Value};
 use nom::number::complete::be_u8;
 
-use std::rc::Rc;
-
 fn parse_abstract_message_flags(i: &[u8]) -> AMFResult<'_, Vec<u8>> {
     let mut next_flag = true;
     let mut flags = Vec::new();
 
     let mut k = i;
     while next_flag {
-        let (i, flag) = be_u8(i)?;
+        let (i, flag) = be_u8(k)?;
         flags.push(flag);
         if flag & NEXT_FLAG == 0 {
             next_flag = false
@@ -29,7 +31,20 @@ fn parse_abstract_message_flags(i: &[u8]) -> AMFResult<'_, Vec<u8>> {
     Ok((k, flags))
 }
 
-fn parse_abstract_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'a, Vec<Element>> {
+/// Show a UID byte array as a readable string, any other value is kept as it is
+fn uid_as_string(value: Rc<Value<'_>>) -> Rc<Value<'_>> {
+    match value.as_ref() {
+        Value::ByteArray(bytes) => {
+            uid_to_string(bytes).map_or(value, |s| Rc::new(Value::String(s.into())))
+        }
+        _ => value,
+    }
+}
+
+fn parse_abstract_message<'a>(
+    i: &'a [u8],
+    amf3: &mut AMF3Decoder<'a>,
+) -> AMFResult<'a, Vec<Element<'a>>> {
     let (i, flags) = parse_abstract_message_flags(i)?;
 
     let mut elements = Vec::new();
@@ -42,7 +57,7 @@ fn parse_abstract_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<
             if flags & BODY_FLAG != 0 {
                 let (j, value) = amf3.parse_single_element(k)?;
                 elements.push(Element {
-                    name: "body".to_string(),
+                    name: "body".into(),
                     value,
                 });
                 k = j;
@@ -50,7 +65,7 @@ fn parse_abstract_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<
             if flags & CLIENT_ID_FLAG != 0 {
                 let (j, value) = amf3.parse_single_element(k)?;
                 elements.push(Element {
-                    name: "client_id".to_string(),
+                    name: "client_id".into(),
                     value,
                 });
                 k = j;
@@ -58,7 +73,7 @@ fn parse_abstract_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<
             if flags & DESTINATION_ID_FLAG != 0 {
                 let (j, value) = amf3.parse_single_element(k)?;
                 elements.push(Element {
-                    name: "destination".to_string(),
+                    name: "destination".into(),
                     value,
                 });
                 k = j;
@@ -66,7 +81,7 @@ fn parse_abstract_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<
             if flags & HEADERS_FLAG != 0 {
                 let (j, value) = amf3.parse_single_element(k)?;
                 elements.push(Element {
-                    name: "headers".to_string(),
+                    name: "headers".into(),
                     value,
                 });
                 k = j;
@@ -74,7 +89,7 @@ fn parse_abstract_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<
             if flags & MESSAGE_ID_FLAG != 0 {
                 let (j, value) = amf3.parse_single_element(k)?;
                 elements.push(Element {
-                    name: "message_id".to_string(),
+                    name: "message_id".into(),
                     value,
                 });
                 k = j;
@@ -82,7 +97,7 @@ fn parse_abstract_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<
             if flags & TIMESTAMP_FLAG != 0 {
                 let (j, value) = amf3.parse_single_element(k)?;
                 elements.push(Element {
-                    name: "timestamp".to_string(),
+                    name: "timestamp".into(),
                     value,
                 });
                 k = j;
@@ -90,7 +105,7 @@ fn parse_abstract_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<
             if flags & TTL_FLAG != 0 {
                 let (j, value) = amf3.parse_single_element(k)?;
                 elements.push(Element {
-                    name: "ttl".to_string(),
+                    name: "ttl".into(),
                     value,
                 });
                 k = j;
@@ -100,16 +115,16 @@ fn parse_abstract_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<
             if (flags & CLIENT_ID_BYTES_FLAG) != 0 {
                 let (j, value) = amf3.parse_single_element(k)?;
                 elements.push(Element {
-                    name: "client_id_bytes".to_string(),
-                    value,
+                    name: "client_id_bytes".into(),
+                    value: uid_as_string(value),
                 });
                 k = j;
             }
             if (flags & MESSAGE_ID_BYTES_FLAG) != 0 {
                 let (j, value) = amf3.parse_single_element(k)?;
                 elements.push(Element {
-                    name: "message_id_bytes".to_string(),
-                    value,
+                    name: "message_id_bytes".into(),
+                    value: uid_as_string(value),
                 });
                 k = j;
             }
@@ -119,10 +134,10 @@ fn parse_abstract_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<
 
         if (flags >> reserved) != 0 {
             for j in reserved..6 {
-                if (flags >> j) != 0 {
+                if (flags >> j) & 1 != 0 {
                     let (jj, value) = amf3.parse_single_element(k)?;
                     elements.push(Element {
-                        name: format!("children_{}", j),
+                        name: format!("children_{}", j).into(),
                         value,
                     });
                     k = jj;
@@ -131,10 +146,13 @@ fn parse_abstract_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<
         }
     }
 
-    Ok((i, elements))
+    Ok((k, elements))
 }
 
-fn parse_async_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'a, Vec<Element>> {
+fn parse_async_message<'a>(
+    i: &'a [u8],
+    amf3: &mut AMF3Decoder<'a>,
+) -> AMFResult<'a, Vec<Element<'a>>> {
     let (i, msg) = parse_abstract_message(i, amf3)?;
 
     let (i, flags) = parse_abstract_message_flags(i)?;
@@ -148,7 +166,7 @@ fn parse_async_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'a,
             if (flags & CORRELATION_ID_FLAG) != 0u8 {
                 let (j, value) = amf3.parse_single_element(k)?;
                 elements.push(Element {
-                    name: "correlation_id".to_string(),
+                    name: "correlation_id".into(),
                     value,
                 });
                 k = j;
@@ -156,8 +174,8 @@ fn parse_async_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'a,
             if (flags & CORRELATION_ID_BYTES_FLAG) != 0u8 {
                 let (j, value) = amf3.parse_single_element(k)?;
                 elements.push(Element {
-                    name: "correlation_id_bytes".to_string(),
-                    value,
+                    name: "correlation_id_bytes".into(),
+                    value: uid_as_string(value),
                 });
                 k = j;
             }
@@ -169,7 +187,7 @@ fn parse_async_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'a,
                 if (flags >> j) & 1 != 0u8 {
                     let (jj, value) = amf3.parse_single_element(k)?;
                     elements.push(Element {
-                        name: format!("children_async_{}", j),
+                        name: format!("children_async_{}", j).into(),
                         value,
                     });
                     k = jj;
@@ -183,8 +201,8 @@ fn parse_async_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'a,
 
 fn parse_acknowledge_message<'a>(
     i: &'a [u8],
-    amf3: &mut AMF3Decoder,
-) -> AMFResult<'a, Vec<Element>> {
+    amf3: &mut AMF3Decoder<'a>,
+) -> AMFResult<'a, Vec<Element<'a>>> {
     let (i, msg) = parse_async_message(i, amf3)?;
 
     let (i, flags) = parse_abstract_message_flags(i)?;
@@ -198,7 +216,7 @@ fn parse_acknowledge_message<'a>(
                 if (flags >> j) & 1 != 0 {
                     let (jj, value) = amf3.parse_single_element(k)?;
                     elements.push(Element {
-                        name: format!("children_acknowledge_{}", j),
+                        name: format!("children_acknowledge_{}", j).into(),
                         value,
                     });
                     k = jj;
@@ -210,7 +228,10 @@ fn parse_acknowledge_message<'a>(
     Ok((k, elements))
 }
 
-fn parse_command_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'a, Vec<Element>> {
+fn parse_command_message<'a>(
+    i: &'a [u8],
+    amf3: &mut AMF3Decoder<'a>,
+) -> AMFResult<'a, Vec<Element<'a>>> {
     let (i, msg) = parse_async_message(i, amf3)?;
 
     let (i, flags) = parse_abstract_message_flags(i)?;
@@ -223,9 +244,9 @@ fn parse_command_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'
 
         if pos == 0 {
             if (flags & OPERATION_FLAG) != 0 {
-                let (j, value) = amf3.parse_single_element(i)?;
+                let (j, value) = amf3.parse_single_element(k)?;
                 elements.push(Element {
-                    name: "operation".to_string(),
+                    name: "operation".into(),
                     value,
                 });
                 k = j;
@@ -238,7 +259,109 @@ fn parse_command_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'
                 if (flags >> j) & 1 != 0 {
                     let (jj, value) = amf3.parse_single_element(k)?;
                     elements.push(Element {
-                        name: format!("children_command_{}", j),
+                        name: format!("children_command_{}", j).into(),
+                        value,
+                    });
+                    k = jj;
+                }
+            }
+        }
+    }
+
+    Ok((k, elements))
+}
+
+fn parse_data_message<'a>(
+    i: &'a [u8],
+    amf3: &mut AMF3Decoder<'a>,
+) -> AMFResult<'a, Vec<Element<'a>>> {
+    let (i, msg) = parse_async_message(i, amf3)?;
+
+    let (i, flags) = parse_abstract_message_flags(i)?;
+
+    let mut elements = msg;
+
+    let mut k = i;
+    for (pos, flags) in flags.iter().enumerate() {
+        let mut reserved = 0;
+
+        if pos == 0 {
+            if (flags & IDENTITY_FLAG) != 0 {
+                let (j, value) = amf3.parse_single_element(k)?;
+                elements.push(Element {
+                    name: "identity".into(),
+                    value,
+                });
+                k = j;
+            }
+            if (flags & DATA_OPERATION_FLAG) != 0 {
+                let (j, value) = amf3.parse_single_element(k)?;
+                elements.push(Element {
+                    name: "operation".into(),
+                    value,
+                });
+                k = j;
+            }
+            reserved = 2;
+        }
+
+        if (flags >> reserved) != 0 {
+            for j in reserved..6 {
+                if (flags >> j) & 1 != 0 {
+                    let (jj, value) = amf3.parse_single_element(k)?;
+                    elements.push(Element {
+                        name: format!("children_data_{}", j).into(),
+                        value,
+                    });
+                    k = jj;
+                }
+            }
+        }
+    }
+
+    Ok((k, elements))
+}
+
+fn parse_data_error_message<'a>(
+    i: &'a [u8],
+    amf3: &mut AMF3Decoder<'a>,
+) -> AMFResult<'a, Vec<Element<'a>>> {
+    let (i, msg) = parse_acknowledge_message(i, amf3)?;
+
+    let (i, flags) = parse_abstract_message_flags(i)?;
+
+    let mut elements = msg;
+
+    let mut k = i;
+    for (pos, flags) in flags.iter().enumerate() {
+        let mut reserved = 0;
+
+        if pos == 0 {
+            if (flags & CAUSE_FLAG) != 0 {
+                let (j, value) = amf3.parse_single_element(k)?;
+                elements.push(Element {
+                    name: "cause".into(),
+                    value,
+                });
+                k = j;
+            }
+            if (flags & PROPERTY_NAMES_FLAG) != 0 {
+                let (j, value) = amf3.parse_single_element(k)?;
+                elements.push(Element {
+                    name: "property_names".into(),
+                    value,
+                });
+                k = j;
+            }
+            reserved = 2;
+        }
+
+        if (flags >> reserved) != 0 {
+            for j in reserved..6 {
+                if (flags >> j) & 1 != 0 {
+                    let (jj, value) = amf3.parse_single_element(k)?;
+                    elements.push(Element {
+                        name: format!("children_data_error_{}", j).into(),
                         value,
                     });
                     k = jj;
@@ -251,11 +374,14 @@ fn parse_command_message<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'
 }
 
 // all arrays
-fn parse_array_collection<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'a, Vec<Element>> {
+fn parse_array_collection<'a>(
+    i: &'a [u8],
+    amf3: &mut AMF3Decoder<'a>,
+) -> AMFResult<'a, Vec<Element<'a>>> {
     let (i, value) = amf3.parse_single_element(i)?;
 
     let el = vec![Element {
-        name: "data".to_string(),
+        name: "data".into(),
         value,
     }];
 
@@ -263,11 +389,14 @@ fn parse_array_collection<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<
 }
 
 // all proxies
-fn parse_object_proxy<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'a, Vec<Element>> {
+fn parse_object_proxy<'a>(
+    i: &'a [u8],
+    amf3: &mut AMF3Decoder<'a>,
+) -> AMFResult<'a, Vec<Element<'a>>> {
     let (i, value) = amf3.parse_single_element(i)?;
 
     let el = vec![Element {
-        name: "object".to_string(),
+        name: "object".into(),
         value,
     }];
 
@@ -276,7 +405,7 @@ fn parse_object_proxy<'a>(i: &'a [u8], amf3: &mut AMF3Decoder) -> AMFResult<'a,
 
 /// Register the flex decoders into the given AMF3Decoder
 #[inline]
-pub fn register_decoders(decoder: &mut AMF3Decoder) {
+pub fn register_decoders(decoder: &mut AMF3Decoder<'_>) {
     decoder.external_decoders.insert(
         "flex.messaging.io.AbstractMessage".to_string(),
         Rc::new(Box::new(parse_abstract_message)),
@@ -289,6 +418,21 @@ pub fn register_decoders(decoder: &mut AMF3Decoder) {
         "flex.messaging.io.AsyncMessageExt".to_string(),
         Rc::new(Box::new(parse_async_message)),
     );
+    decoder.external_decoders.insert(
+        "flex.messaging.messages.AsyncMessage".to_string(),
+        Rc::new(Box::new(parse_async_message)),
+    );
+    decoder
+        .external_decoders
+        .insert("DSA".to_string(), Rc::new(Box::new(parse_async_message)));
+    decoder.external_decoders.insert(
+        "flex.data.messages.DataMessage".to_string(),
+        Rc::new(Box::new(parse_data_message)),
+    );
+    decoder.external_decoders.insert(
+        "flex.data.messages.DataErrorMessage".to_string(),
+        Rc::new(Box::new(parse_data_error_message)),
+    );
     decoder.external_decoders.insert(
         "flex.messaging.io.AcknowledgeMessage".to_string(),
         Rc::new(Box::new(parse_acknowledge_message)),
@@ -311,23 +455,23 @@ pub fn register_decoders(decoder: &mut AMF3Decoder) {
     );
 
     decoder.external_decoders.insert(
-        "flex.messaging.io.ArrayCollection".to_string(),
+        ARRAY_COLLECTION.to_string(),
         Rc::new(Box::new(parse_array_collection)),
     );
     decoder.external_decoders.insert(
-        "flex.messaging.io.ArrayList".to_string(),
+        ARRAY_LIST.to_string(),
         Rc::new(Box::new(parse_array_collection)),
     );
     decoder.external_decoders.insert(
-        "flex.messaging.io.ObjectProxy".to_string(),
+        OBJECT_PROXY.to_string(),
         Rc::new(Box::new(parse_object_proxy)),
     );
     decoder.external_decoders.insert(
-        "flex.messaging.io.ManagedObjectProxy".to_string(),
+        MANAGED_OBJECT_PROXY.to_string(),
         Rc::new(Box::new(parse_object_proxy)),
     );
     decoder.external_decoders.insert(
-        "flex.messaging.io.SerializationProxy".to_string(),
+        SERIALIZATION_PROXY.to_string(),
         Rc::new(Box::new(parse_object_proxy)),
     );
 }
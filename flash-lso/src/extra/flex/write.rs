@@ -2,23 +2,27 @@
 
 use crate::amf3::custom_encoder::CustomEncoder;
 use crate::amf3::write::AMF3Encoder;
+use crate::extra::flex::string_to_uid;
 use crate::extra::flex::{
-    BODY_FLAG, CLIENT_ID_BYTES_FLAG, CLIENT_ID_FLAG, CORRELATION_ID_BYTES_FLAG,
-    CORRELATION_ID_FLAG, DESTINATION_ID_FLAG, HEADERS_FLAG, MESSAGE_ID_BYTES_FLAG, MESSAGE_ID_FLAG,
-    NEXT_FLAG, OPERATION_FLAG, TIMESTAMP_FLAG, TTL_FLAG,
+    ARRAY_COLLECTION, ARRAY_LIST, BODY_FLAG, CAUSE_FLAG, CLIENT_ID_BYTES_FLAG, CLIENT_ID_FLAG,
+    CORRELATION_ID_BYTES_FLAG, CORRELATION_ID_FLAG, DATA_OPERATION_FLAG, DESTINATION_ID_FLAG,
+    HEADERS_FLAG, IDENTITY_FLAG, MANAGED_OBJECT_PROXY, MESSAGE_ID_BYTES_FLAG, MESSAGE_ID_FLAG,
+    NEXT_FLAG, OBJECT_PROXY, OPERATION_FLAG, PROPERTY_NAMES_FLAG, SERIALIZATION_PROXY,
+    TIMESTAMP_FLAG, TTL_FLAG,
 };
-use crate::types::{ClassDefinition, Element};
+use crate::io::Write;
+use crate::prelude::*;
+use crate::types::{ClassDefinition, Element, Value};
 use crate::write::WriteExt;
-use std::io::Write;
 
 struct ArrayCollection;
 
 impl CustomEncoder for ArrayCollection {
-    fn encode(
+    fn encode<'v>(
         &self,
-        elements: &[Element],
+        elements: &[Element<'v>],
         _class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
+        encoder: &AMF3Encoder<'v>,
     ) -> Vec<u8> {
         let mut v = Vec::new();
         self.do_encode(&mut v, elements, encoder).unwrap();
@@ -27,12 +31,12 @@ impl CustomEncoder for ArrayCollection {
 }
 
 impl ArrayCollection {
-    fn do_encode<'a, 'b: 'a, W: Write + 'a>(
+    fn do_encode<'a, 'b: 'a, 'v, W: Write + 'a>(
         &'a self,
         writer: &mut W,
-        elements: &'b [Element],
-        encoder: &'a AMF3Encoder,
-    ) -> std::io::Result<()> {
+        elements: &'b [Element<'v>],
+        encoder: &'a AMF3Encoder<'v>,
+    ) -> crate::io::Result<()> {
         let data = elements.first().unwrap();
         encoder.write_value_element(writer, &data.value)
     }
@@ -41,11 +45,11 @@ impl ArrayCollection {
 struct ObjectProxy;
 
 impl CustomEncoder for ObjectProxy {
-    fn encode(
+    fn encode<'v>(
         &self,
-        elements: &[Element],
+        elements: &[Element<'v>],
         _class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
+        encoder: &AMF3Encoder<'v>,
     ) -> Vec<u8> {
         let mut v = Vec::new();
         self.do_encode(&mut v, elements, encoder).unwrap();
@@ -54,20 +58,34 @@ impl CustomEncoder for ObjectProxy {
 }
 
 impl ObjectProxy {
-    fn do_encode<'a, 'b: 'a, W: Write + 'a>(
+    fn do_encode<'a, 'b: 'a, 'v, W: Write + 'a>(
         &'a self,
         writer: &mut W,
-        elements: &'b [Element],
-        encoder: &'a AMF3Encoder,
-    ) -> std::io::Result<()> {
+        elements: &'b [Element<'v>],
+        encoder: &'a AMF3Encoder<'v>,
+    ) -> crate::io::Result<()> {
         let data = elements.first().unwrap();
         encoder.write_value_element(writer, &data.value)
     }
 }
 
-fn write_flags<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, flags: &'a [u8]) -> std::io::Result<()> {
+/// Write a UID that was shown as a string by the decoder back as its bytes
+fn uid_as_bytes<'v>(value: &Rc<Value<'v>>) -> Rc<Value<'v>> {
+    match value.as_ref() {
+        Value::String(s) => string_to_uid(s).map_or_else(
+            || value.clone(),
+            |bytes| Rc::new(Value::ByteArray(bytes.to_vec().into())),
+        ),
+        _ => value.clone(),
+    }
+}
+
+fn write_flags<'a, 'b: 'a, W: Write + 'a>(
+    writer: &mut W,
+    flags: &'a [u8],
+) -> crate::io::Result<()> {
     for (index, flag) in flags.iter().enumerate() {
-        if index == flags.len() {
+        if index == flags.len() - 1 {
             writer.write_u8(*flag & !NEXT_FLAG)?;
         } else {
             writer.write_u8(*flag | NEXT_FLAG)?;
@@ -79,11 +97,11 @@ fn write_flags<'a, 'b: 'a, W: Write + 'a>(writer: &mut W, flags: &'a [u8]) -> st
 struct AbstractMessage;
 
 impl CustomEncoder for AbstractMessage {
-    fn encode(
+    fn encode<'v>(
         &self,
-        elements: &[Element],
+        elements: &[Element<'v>],
         _class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
+        encoder: &AMF3Encoder<'v>,
     ) -> Vec<u8> {
         let mut v = Vec::new();
         write_abstract_message(&mut v, elements, encoder).unwrap();
@@ -91,11 +109,11 @@ impl CustomEncoder for AbstractMessage {
     }
 }
 
-fn write_abstract_message<'a, 'b: 'a, W: Write + 'a>(
+fn write_abstract_message<'a, 'b: 'a, 'v, W: Write + 'a>(
     writer: &mut W,
-    elements: &'b [Element],
-    encoder: &'a AMF3Encoder,
-) -> std::io::Result<()> {
+    elements: &'b [Element<'v>],
+    encoder: &'a AMF3Encoder<'v>,
+) -> crate::io::Result<()> {
     let mut flags = Vec::new();
     let mut new_elements = Vec::new();
     {
@@ -175,7 +193,7 @@ fn write_abstract_message<'a, 'b: 'a, W: Write + 'a>(
         if let Some(v) = elements
             .iter()
             .find(|e| e.name == "client_id_bytes")
-            .map(|e| e.value.clone())
+            .map(|e| uid_as_bytes(&e.value))
         {
             flag |= CLIENT_ID_BYTES_FLAG;
             new_elements.push(v);
@@ -183,7 +201,7 @@ fn write_abstract_message<'a, 'b: 'a, W: Write + 'a>(
         if let Some(v) = elements
             .iter()
             .find(|e| e.name == "message_id_bytes")
-            .map(|e| e.value.clone())
+            .map(|e| uid_as_bytes(&e.value))
         {
             flag |= MESSAGE_ID_BYTES_FLAG;
             new_elements.push(v);
@@ -243,11 +261,11 @@ fn write_abstract_message<'a, 'b: 'a, W: Write + 'a>(
 struct AsyncMessage;
 
 impl CustomEncoder for AsyncMessage {
-    fn encode(
+    fn encode<'v>(
         &self,
-        elements: &[Element],
+        elements: &[Element<'v>],
         _class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
+        encoder: &AMF3Encoder<'v>,
     ) -> Vec<u8> {
         let mut v = Vec::new();
         write_async_message(&mut v, elements, encoder).unwrap();
@@ -255,11 +273,11 @@ impl CustomEncoder for AsyncMessage {
     }
 }
 
-fn write_async_message<'a, 'b: 'a, W: Write + 'a>(
+fn write_async_message<'a, 'b: 'a, 'v, W: Write + 'a>(
     writer: &mut W,
-    elements: &'b [Element],
-    encoder: &'a AMF3Encoder,
-) -> std::io::Result<()> {
+    elements: &'b [Element<'v>],
+    encoder: &'a AMF3Encoder<'v>,
+) -> crate::io::Result<()> {
     let mut flags = Vec::new();
     let mut new_elements = Vec::new();
     {
@@ -277,7 +295,7 @@ fn write_async_message<'a, 'b: 'a, W: Write + 'a>(
         if let Some(v) = elements
             .iter()
             .find(|e| e.name == "correlation_id_bytes")
-            .map(|e| e.value.clone())
+            .map(|e| uid_as_bytes(&e.value))
         {
             flag |= CORRELATION_ID_BYTES_FLAG;
             new_elements.push(v);
@@ -337,11 +355,11 @@ fn write_async_message<'a, 'b: 'a, W: Write + 'a>(
 struct AcknowledgeMessage;
 
 impl CustomEncoder for AcknowledgeMessage {
-    fn encode(
+    fn encode<'v>(
         &self,
-        elements: &[Element],
+        elements: &[Element<'v>],
         _class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
+        encoder: &AMF3Encoder<'v>,
     ) -> Vec<u8> {
         let mut v = Vec::new();
         write_acknowledge_message(&mut v, elements, encoder).unwrap();
@@ -349,11 +367,11 @@ impl CustomEncoder for AcknowledgeMessage {
     }
 }
 
-fn write_acknowledge_message<'a, 'b: 'a, W: Write + 'a>(
+fn write_acknowledge_message<'a, 'b: 'a, 'v, W: Write + 'a>(
     writer: &mut W,
-    elements: &'b [Element],
-    encoder: &'a AMF3Encoder,
-) -> std::io::Result<()> {
+    elements: &'b [Element<'v>],
+    encoder: &'a AMF3Encoder<'v>,
+) -> crate::io::Result<()> {
     let mut flags = Vec::new();
     let mut new_elements = Vec::new();
     {
@@ -396,11 +414,11 @@ fn write_acknowledge_message<'a, 'b: 'a, W: Write + 'a>(
 struct CommandMessage;
 
 impl CustomEncoder for CommandMessage {
-    fn encode(
+    fn encode<'v>(
         &self,
-        elements: &[Element],
+        elements: &[Element<'v>],
         _class_def: &Option<ClassDefinition>,
-        encoder: &AMF3Encoder,
+        encoder: &AMF3Encoder<'v>,
     ) -> Vec<u8> {
         let mut v = Vec::new();
         write_command_message(&mut v, elements, encoder).unwrap();
@@ -408,11 +426,11 @@ impl CustomEncoder for CommandMessage {
     }
 }
 
-fn write_command_message<'a, 'b: 'a, W: Write + 'a>(
+fn write_command_message<'a, 'b: 'a, 'v, W: Write + 'a>(
     writer: &mut W,
-    elements: &'b [Element],
-    encoder: &'a AMF3Encoder,
-) -> std::io::Result<()> {
+    elements: &'b [Element<'v>],
+    encoder: &'a AMF3Encoder<'v>,
+) -> crate::io::Result<()> {
     let mut flags = Vec::new();
     let mut new_elements = Vec::new();
     {
@@ -477,18 +495,124 @@ fn write_command_message<'a, 'b: 'a, W: Write + 'a>(
     Ok(())
 }
 
+/// Write the flag byte and values of the fields added by a message subclass
+///
+/// `fields` gives the element name of each flag in order, any remaining bits are written from `children_{prefix}_{n}`
+fn write_subclass_fields<'a, 'b: 'a, 'v, W: Write + 'a>(
+    writer: &mut W,
+    elements: &'b [Element<'v>],
+    encoder: &'a AMF3Encoder<'v>,
+    fields: &[(&str, u8)],
+    prefix: &str,
+) -> crate::io::Result<()> {
+    let mut flag = 0;
+    let mut new_elements = Vec::new();
+
+    for (name, field_flag) in fields {
+        if let Some(v) = elements
+            .iter()
+            .find(|e| e.name == *name)
+            .map(|e| e.value.clone())
+        {
+            flag |= field_flag;
+            new_elements.push(v);
+        }
+    }
+
+    for n in fields.len()..6 {
+        if let Some(v) = elements
+            .iter()
+            .find(|e| e.name == format!("children_{}_{}", prefix, n))
+            .map(|e| e.value.clone())
+        {
+            flag |= 0b1 << n;
+            new_elements.push(v);
+        }
+    }
+
+    write_flags(writer, &[flag])?;
+    for v in new_elements {
+        encoder.write_value_element(writer, &v)?;
+    }
+    Ok(())
+}
+
+struct DataMessage;
+
+impl CustomEncoder for DataMessage {
+    fn encode<'v>(
+        &self,
+        elements: &[Element<'v>],
+        _class_def: &Option<ClassDefinition>,
+        encoder: &AMF3Encoder<'v>,
+    ) -> Vec<u8> {
+        let mut v = Vec::new();
+        write_data_message(&mut v, elements, encoder).unwrap();
+        v
+    }
+}
+
+fn write_data_message<'a, 'b: 'a, 'v, W: Write + 'a>(
+    writer: &mut W,
+    elements: &'b [Element<'v>],
+    encoder: &'a AMF3Encoder<'v>,
+) -> crate::io::Result<()> {
+    write_async_message(writer, elements, encoder)?;
+    write_subclass_fields(
+        writer,
+        elements,
+        encoder,
+        &[
+            ("identity", IDENTITY_FLAG),
+            ("operation", DATA_OPERATION_FLAG),
+        ],
+        "data",
+    )
+}
+
+struct DataErrorMessage;
+
+impl CustomEncoder for DataErrorMessage {
+    fn encode<'v>(
+        &self,
+        elements: &[Element<'v>],
+        _class_def: &Option<ClassDefinition>,
+        encoder: &AMF3Encoder<'v>,
+    ) -> Vec<u8> {
+        let mut v = Vec::new();
+        write_data_error_message(&mut v, elements, encoder).unwrap();
+        v
+    }
+}
+
+fn write_data_error_message<'a, 'b: 'a, 'v, W: Write + 'a>(
+    writer: &mut W,
+    elements: &'b [Element<'v>],
+    encoder: &'a AMF3Encoder<'v>,
+) -> crate::io::Result<()> {
+    write_acknowledge_message(writer, elements, encoder)?;
+    write_subclass_fields(
+        writer,
+        elements,
+        encoder,
+        &[
+            ("cause", CAUSE_FLAG),
+            ("property_names", PROPERTY_NAMES_FLAG),
+        ],
+        "data_error",
+    )
+}
+
 /// Register the flex encoders into the given AMF3Encoder
 #[inline]
-pub fn register_encoders(encoder: &mut AMF3Encoder) {
-    encoder.external_encoders.insert(
-        "flex.messaging.io.ArrayCollection".to_string(),
-        Box::new(ArrayCollection {}),
-    );
+pub fn register_encoders<'v>(encoder: &mut AMF3Encoder<'v>) {
+    encoder
+        .external_encoders
+        .insert(ARRAY_COLLECTION.to_string(), Box::new(ArrayCollection {}));
 
-    encoder.external_encoders.insert(
-        "flex.messaging.io.ArrayList".to_string(),
-        Box::new(ArrayCollection {}),
-    );
+    encoder
+        .external_encoders
+        .insert(ARRAY_LIST.to_string(), Box::new(ArrayCollection {}));
 
     encoder.external_encoders.insert(
         "flex.messaging.io.AbstractMessage".to_string(),
@@ -505,6 +629,25 @@ pub fn register_encoders(encoder: &mut AMF3Encoder) {
         Box::new(AsyncMessage {}),
     );
 
+    encoder.external_encoders.insert(
+        "flex.messaging.messages.AsyncMessage".to_string(),
+        Box::new(AsyncMessage {}),
+    );
+
+    encoder
+        .external_encoders
+        .insert("DSA".to_string(), Box::new(AsyncMessage {}));
+
+    encoder.external_encoders.insert(
+        "flex.data.messages.DataMessage".to_string(),
+        Box::new(DataMessage {}),
+    );
+
+    encoder.external_encoders.insert(
+        "flex.data.messages.DataErrorMessage".to_string(),
+        Box::new(DataErrorMessage {}),
+    );
+
     encoder.external_encoders.insert(
         "flex.messaging.io.AcknowledgeMessage".to_string(),
         Box::new(AcknowledgeMessage {}),
@@ -530,18 +673,15 @@ pub fn register_encoders(encoder: &mut AMF3Encoder) {
         Box::new(AcknowledgeMessage {}),
     );
 
-    encoder.external_encoders.insert(
-        "flex.messaging.io.ObjectProxy".to_string(),
-        Box::new(ObjectProxy {}),
-    );
+    encoder
+        .external_encoders
+        .insert(OBJECT_PROXY.to_string(), Box::new(ObjectProxy {}));
 
-    encoder.external_encoders.insert(
-        "flex.messaging.io.ManagedObjectProxy".to_string(),
-        Box::new(ObjectProxy {}),
-    );
+    encoder
+        .external_encoders
+        .insert(MANAGED_OBJECT_PROXY.to_string(), Box::new(ObjectProxy {}));
 
-    encoder.external_encoders.insert(
-        "flex.messaging.io.SerializationProxy".to_string(),
-        Box::new(ObjectProxy {}),
-    );
+    encoder
+        .external_encoders
+        .insert(SERIALIZATION_PROXY.to_string(), Box::new(ObjectProxy {}));
 }
@@ -0,0 +1,86 @@
+use crate::errors::{DecodeError, FrameError};
+use crate::read::decode_value;
+use crate::types::{AMFVersion, OwnedValue};
+use std::io::Read;
+
+/// Read a stream of AMF values that are each preceded by their length as a big-endian `u32`
+///
+/// Only one frame is held in memory at a time. Each frame must contain exactly one value, and each value is decoded
+/// with a new decoder, so references can't reach into an earlier frame. The stream may end between frames, but not
+/// inside one. Iteration stops after the first error
+/// ```
+/// use flash_lso::types::{AMFVersion, Value};
+/// use flash_lso::FramedReader;
+///
+/// let data = [0, 0, 0, 2, 0x01, 0x01, 0, 0, 0, 1, 0x05];
+/// let values: Vec<Value> = FramedReader::new(&data[..], AMFVersion::AMF0)
+///     .collect::<Result<_, _>>()
+///     .expect("Failed to read values");
+/// assert_eq!(values, vec![Value::Bool(true), Value::Null]);
+/// ```
+pub struct FramedReader<R> {
+    reader: R,
+    version: AMFVersion,
+    done: bool,
+}
+
+impl<R: Read> FramedReader<R> {
+    /// Read frames of the given AMF version from `reader`
+    pub fn new(reader: R, version: AMFVersion) -> Self {
+        Self {
+            reader,
+            version,
+            done: false,
+        }
+    }
+
+    /// Get the underlying reader back
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_frame(&mut self) -> Result<Option<OwnedValue>, FrameError> {
+        let mut length = [0; 4];
+        let mut read = 0;
+        while read < length.len() {
+            match self.reader.read(&mut length[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let length = u32::from_be_bytes(length);
+
+        // The frame is read as it arrives rather than allocated up front, so a bad length can't exhaust memory
+        let mut frame = Vec::new();
+        (&mut self.reader)
+            .take(length.into())
+            .read_to_end(&mut frame)?;
+        if frame.len() != length as usize {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+
+        match decode_value(&frame, self.version) {
+            Ok(([], value)) => Ok(Some(value.into_owned())),
+            Ok((rest, _)) => Err(FrameError::TrailingBytes(rest.len())),
+            Err(e) => Err(FrameError::Decode(DecodeError::new(&frame, e, Vec::new()))),
+        }
+    }
+}
+
+impl<R: Read> Iterator for FramedReader<R> {
+    type Item = Result<OwnedValue, FrameError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.read_frame();
+        if !matches!(result, Ok(Some(_))) {
+            self.done = true;
+        }
+        result.transpose()
+    }
+}
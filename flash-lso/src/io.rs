@@ -0,0 +1,134 @@
+//! The writing half of `std::io`, or a minimal replacement for it when the `std` feature is disabled
+//!
+//! Encoders write to any `Write`, without `std` this is implemented for `Vec<u8>` and can be implemented for other
+//! sinks, such as a fixed buffer on a device
+
+#[cfg(feature = "std")]
+pub use std::io::{Error, ErrorKind, Result, Write};
+
+#[cfg(not(feature = "std"))]
+pub use self::no_std::{Error, ErrorKind, Result, Write};
+
+#[cfg(not(feature = "std"))]
+mod no_std {
+    use crate::prelude::*;
+    use core::fmt;
+
+    /// The result of a write
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// The kind of a write error, a subset of `std::io::ErrorKind`
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        /// A value can't be encoded, such as a string that is too long for its length prefix
+        InvalidInput,
+
+        /// Data that is invalid for what it is used for
+        InvalidData,
+
+        /// The sink couldn't accept all of the data
+        WriteZero,
+
+        /// The data ended early
+        UnexpectedEof,
+
+        /// Any other error
+        Other,
+    }
+
+    impl fmt::Display for ErrorKind {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(match self {
+                ErrorKind::InvalidInput => "invalid input parameter",
+                ErrorKind::InvalidData => "invalid data",
+                ErrorKind::WriteZero => "write zero",
+                ErrorKind::UnexpectedEof => "unexpected end of file",
+                ErrorKind::Other => "other error",
+            })
+        }
+    }
+
+    /// A write error, with a kind and an optional description
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct Error {
+        kind: ErrorKind,
+        message: Option<String>,
+    }
+
+    impl Error {
+        /// Create an error of the given kind with a description
+        pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+            Self {
+                kind,
+                message: Some(message.into()),
+            }
+        }
+
+        /// Get the kind of this error
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Self {
+            Self {
+                kind,
+                message: None,
+            }
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match &self.message {
+                Some(message) => f.write_str(message),
+                None => fmt::Display::fmt(&self.kind, f),
+            }
+        }
+    }
+
+    impl core::error::Error for Error {}
+
+    /// A sink for bytes, the subset of `std::io::Write` used by the encoders
+    pub trait Write {
+        /// Write some of `buf`, returning how many bytes were written
+        fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+        /// Flush any buffered data
+        fn flush(&mut self) -> Result<()>;
+
+        /// Write all of `buf`
+        fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.write(buf)? {
+                    0 => return Err(ErrorKind::WriteZero.into()),
+                    n => buf = &buf[n..],
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            (**self).write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            (**self).flush()
+        }
+    }
+}
@@ -0,0 +1,606 @@
+use crate::types::{Element, Lso, ObjectId, OwnedValue, Value};
+use serde::de::{DeserializeOwned, Error as _};
+use serde_json::{json, Error, Map, Value as Json};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The key of the marker object that replaces a value that has already been written
+const REF_KEY: &str = "$ref";
+
+/// The key naming the kind of value in the objects written by `value_to_json`
+const TYPE_KEY: &str = "__type";
+
+/// How byte arrays are written by `to_json_with_options`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ByteArrayRepr {
+    /// An array of integers, such as `[1, 2, 255]`, as serde writes them
+    #[default]
+    IntArray,
+
+    /// A hex string, such as `{"hex": "0102ff"}`
+    Hex,
+
+    /// A standard base64 string, such as `{"base64": "AQL/"}`
+    Base64,
+}
+
+/// Options for `to_json_with_options`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JsonOptions {
+    /// How byte arrays are written, `from_json` accepts any of them
+    pub byte_array: ByteArrayRepr,
+}
+
+/// Convert an Lso to JSON, writing every value that has already been written as a reference marker
+///
+/// The output has the same shape as serializing the `Lso` with serde, except that a container that is shared with an
+/// earlier one, or an `Amf3ObjectReference` to an earlier object, is written as `{"$ref": "<path>"}`. Paths are the
+/// dotted paths used by `Lso::flatten`, so the result is finite even when the values contain cycles
+pub fn to_json(lso: &Lso<'_>) -> Result<Json, Error> {
+    to_json_with_options(lso, JsonOptions::default())
+}
+
+/// Convert an Lso to JSON as `to_json` does, with the given options
+pub fn to_json_with_options(lso: &Lso<'_>, options: JsonOptions) -> Result<Json, Error> {
+    let mut exporter = Exporter {
+        options,
+        ..Exporter::default()
+    };
+    let mut body = Vec::with_capacity(lso.body.len());
+    for element in &lso.body {
+        body.push(exporter.element(element, element.name.to_string())?);
+    }
+
+    let mut json = json!({
+        "header": serde_json::to_value(&lso.header)?,
+        "body": body,
+    });
+    if !lso.trailing.is_empty() {
+        json["trailing"] = serde_json::to_value(&lso.trailing)?;
+    }
+    if !lso.surrogate_strings.is_empty() {
+        json["surrogate_strings"] = serde_json::to_value(&lso.surrogate_strings)?;
+    }
+    Ok(json)
+}
+
+/// Convert JSON created by `to_json` back into an Lso, restoring the values that were written as references
+///
+/// A marker pointing at an object becomes an `Amf3ObjectReference` to that object, as the decoder would produce, any
+/// other marker becomes an `Rc` shared with the value it points at
+pub fn from_json(json: Json) -> Result<Lso<'static>, Error> {
+    let Json::Object(mut fields) = json else {
+        return Err(Error::custom("expected an lso object"));
+    };
+    let header = serde_json::from_value(fields.remove("header").unwrap_or_default())?;
+    let Some(Json::Array(body)) = fields.remove("body") else {
+        return Err(Error::custom("expected an lso body"));
+    };
+
+    let mut importer = Importer::default();
+    let body = importer.elements(body, None)?;
+    let trailing =
+        serde_json::from_value(fields.remove("trailing").unwrap_or(Json::Array(Vec::new())))?;
+    let surrogate_strings = fields
+        .remove("surrogate_strings")
+        .map(serde_json::from_value)
+        .transpose()?
+        .unwrap_or_default();
+
+    Ok(Lso {
+        header,
+        body,
+        trailing,
+        surrogate_strings,
+    })
+}
+
+/// Convert a single value to plain JSON
+///
+/// Strings, booleans, null, integers, finite numbers and strict arrays are written as the JSON equivalent, everything
+/// else is written as an object with a `"__type"` key naming the kind of value, such as
+/// `{"__type": "bytearray", "data": "<base64>"}` or `{"__type": "date", "epoch": 0.0, "tz": null}`. Numbers are
+/// always written with a fraction or exponent so they are read back as numbers rather than integers, non-finite ones
+/// are written as `{"__type": "number", "bits": <u64>}`.
+///
+/// Unlike `to_json`, values shared through an `Rc` are written in full each time, so a value must not contain
+/// itself
+pub fn value_to_json(value: &Value<'_>) -> Json {
+    let elements = |elements: &[Element<'_>]| -> Vec<Json> {
+        elements
+            .iter()
+            .map(|e| json!({ "name": e.name, "value": value_to_json(&e.value) }))
+            .collect()
+    };
+    let values = |values: &[Rc<Value<'_>>]| -> Vec<Json> {
+        values.iter().map(|v| value_to_json(v)).collect()
+    };
+
+    match value {
+        Value::Number(n) => number_to_json(*n),
+        Value::Bool(b) => json!(b),
+        Value::String(s) => json!(s),
+        Value::Null => Json::Null,
+        Value::Integer(i) => json!(i),
+        Value::StrictArray(array) => Json::Array(values(array)),
+        Value::Undefined => json!({ TYPE_KEY: "undefined" }),
+        Value::Unsupported => json!({ TYPE_KEY: "unsupported" }),
+        Value::Object(id, properties, def) => json!({
+            TYPE_KEY: "object",
+            "id": id,
+            "properties": elements(properties),
+            "class": def,
+        }),
+        Value::Custom(custom_elements, properties, def) => json!({
+            TYPE_KEY: "custom",
+            "custom": elements(custom_elements),
+            "properties": elements(properties),
+            "class": def,
+        }),
+        Value::ECMAArray(dense, properties, length) => json!({
+            TYPE_KEY: "ecma_array",
+            "dense": values(dense),
+            "properties": elements(properties),
+            "length": length,
+        }),
+        Value::Date(epoch, tz) => {
+            json!({ TYPE_KEY: "date", "epoch": number_to_json(*epoch), "tz": tz })
+        }
+        Value::XML(content, string) => {
+            json!({ TYPE_KEY: "xml", "data": content, "string": string })
+        }
+        #[cfg(feature = "amf3")]
+        Value::AMF3(inner) => json!({ TYPE_KEY: "amf3", "value": value_to_json(inner) }),
+        Value::ByteArray(bytes) => {
+            json!({ TYPE_KEY: "bytearray", "data": crate::base64::encode(bytes) })
+        }
+        Value::VectorInt(items, fixed) => {
+            json!({ TYPE_KEY: "vector_int", "values": items, "fixed": fixed })
+        }
+        Value::VectorUInt(items, fixed) => {
+            json!({ TYPE_KEY: "vector_uint", "values": items, "fixed": fixed })
+        }
+        Value::VectorDouble(items, fixed) => json!({
+            TYPE_KEY: "vector_double",
+            "values": items.iter().map(|n| number_to_json(*n)).collect::<Vec<_>>(),
+            "fixed": fixed,
+        }),
+        Value::VectorObject(items, type_name, fixed) => json!({
+            TYPE_KEY: "vector_object",
+            "values": values(items),
+            "type_name": type_name,
+            "fixed": fixed,
+        }),
+        Value::Dictionary(entries, weak_keys) => json!({
+            TYPE_KEY: "dictionary",
+            "entries": entries
+                .iter()
+                .map(|(k, v)| json!([value_to_json(k), value_to_json(v)]))
+                .collect::<Vec<_>>(),
+            "weak_keys": weak_keys,
+        }),
+        Value::RawExternal(raw) => json!({ TYPE_KEY: "raw_external", "raw": raw }),
+        Value::Reference(reference) => json!({ TYPE_KEY: "reference", "index": reference }),
+        Value::Amf3ObjectReference(id) => json!({ TYPE_KEY: "object_reference", "id": id }),
+    }
+}
+
+/// Convert JSON created by `value_to_json` back into a value
+pub fn value_from_json(json: &Json) -> Result<OwnedValue, Error> {
+    let elements = |json: &Json| -> Result<Vec<Element<'static>>, Error> {
+        array_ref(json)?
+            .iter()
+            .map(|e| {
+                let name: String = field(e, "name")?;
+                Ok(Element::new(name, value_from_json(&e["value"])?))
+            })
+            .collect()
+    };
+    let values = |json: &Json| -> Result<Vec<Rc<OwnedValue>>, Error> {
+        array_ref(json)?
+            .iter()
+            .map(|v| value_from_json(v).map(Rc::new))
+            .collect()
+    };
+
+    let map = match json {
+        Json::Null => return Ok(Value::Null),
+        Json::Bool(b) => return Ok(Value::Bool(*b)),
+        Json::String(s) => return Ok(Value::String(s.clone().into())),
+        Json::Number(n) if n.is_f64() => return number_from_json(json).map(Value::Number),
+        Json::Number(n) => {
+            return n
+                .as_i64()
+                .and_then(|i| i32::try_from(i).ok())
+                .map(Value::Integer)
+                .ok_or_else(|| Error::custom("integer out of range"))
+        }
+        Json::Array(_) => return Ok(Value::StrictArray(values(json)?)),
+        Json::Object(map) => map,
+    };
+
+    let Some(Json::String(kind)) = map.get(TYPE_KEY) else {
+        return Err(Error::custom("expected a value"));
+    };
+    Ok(match kind.as_str() {
+        "number" => Value::Number(number_from_json(json)?),
+        "undefined" => Value::Undefined,
+        "unsupported" => Value::Unsupported,
+        "object" => Value::Object(
+            field(json, "id")?,
+            elements(&json["properties"])?,
+            field(json, "class")?,
+        ),
+        "custom" => Value::Custom(
+            elements(&json["custom"])?,
+            elements(&json["properties"])?,
+            field(json, "class")?,
+        ),
+        "ecma_array" => Value::ECMAArray(
+            values(&json["dense"])?,
+            elements(&json["properties"])?,
+            field(json, "length")?,
+        ),
+        "date" => Value::Date(number_from_json(&json["epoch"])?, field(json, "tz")?),
+        "xml" => Value::XML(
+            field::<String>(json, "data")?.into(),
+            field(json, "string")?,
+        ),
+        #[cfg(feature = "amf3")]
+        "amf3" => Value::AMF3(Rc::new(value_from_json(&json["value"])?)),
+        "bytearray" => {
+            let data: String = field(json, "data")?;
+            let bytes = crate::base64::decode(&data)
+                .map_err(|offset| Error::custom(format!("invalid base64 at offset {}", offset)))?;
+            Value::ByteArray(bytes.into())
+        }
+        "vector_int" => Value::VectorInt(field(json, "values")?, field(json, "fixed")?),
+        "vector_uint" => Value::VectorUInt(field(json, "values")?, field(json, "fixed")?),
+        "vector_double" => Value::VectorDouble(
+            array_ref(&json["values"])?
+                .iter()
+                .map(number_from_json)
+                .collect::<Result<_, _>>()?,
+            field(json, "fixed")?,
+        ),
+        "vector_object" => Value::VectorObject(
+            values(&json["values"])?,
+            field(json, "type_name")?,
+            field(json, "fixed")?,
+        ),
+        "dictionary" => Value::Dictionary(
+            array_ref(&json["entries"])?
+                .iter()
+                .map(|entry| match array_ref(entry)?.as_slice() {
+                    [key, value] => Ok((
+                        Rc::new(value_from_json(key)?),
+                        Rc::new(value_from_json(value)?),
+                    )),
+                    _ => Err(Error::custom("expected a key and a value")),
+                })
+                .collect::<Result<_, Error>>()?,
+            field(json, "weak_keys")?,
+        ),
+        "raw_external" => Value::RawExternal(Box::new(field(json, "raw")?)),
+        "reference" => Value::Reference(field(json, "index")?),
+        "object_reference" => Value::Amf3ObjectReference(field(json, "id")?),
+        other => return Err(Error::custom(format!("unknown value type {}", other))),
+    })
+}
+
+/// Write a number for `value_to_json`, finite numbers are written as JSON numbers which serde_json always writes with
+/// a fraction or exponent
+fn number_to_json(n: f64) -> Json {
+    serde_json::Number::from_f64(n)
+        .map(Json::Number)
+        .unwrap_or_else(|| json!({ TYPE_KEY: "number", "bits": n.to_bits() }))
+}
+
+/// Read a number written by `number_to_json`
+fn number_from_json(json: &Json) -> Result<f64, Error> {
+    match json {
+        Json::Number(n) => n.as_f64().ok_or_else(|| Error::custom("expected a number")),
+        _ => field(json, "bits").map(f64::from_bits),
+    }
+}
+
+#[derive(Default)]
+struct Exporter<'a> {
+    options: JsonOptions,
+
+    /// The path of every container that has been written, by pointer
+    paths: HashMap<*const Value<'a>, String>,
+
+    /// The path of every object that has been written, by id
+    objects: HashMap<ObjectId, String>,
+}
+
+impl<'a> Exporter<'a> {
+    fn element(&mut self, element: &Element<'a>, path: String) -> Result<Json, Error> {
+        Ok(json!({
+            "name": element.name,
+            "value": self.value(&element.value, path)?,
+        }))
+    }
+
+    fn elements(&mut self, elements: &[Element<'a>], path: &str) -> Result<Json, Error> {
+        elements
+            .iter()
+            .map(|e| self.element(e, format!("{}.{}", path, e.name)))
+            .collect()
+    }
+
+    fn values(&mut self, values: &[Rc<Value<'a>>], path: &str) -> Result<Json, Error> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, v)| self.value(v, format!("{}.{}", path, index)))
+            .collect()
+    }
+
+    fn value(&mut self, value: &Rc<Value<'a>>, path: String) -> Result<Json, Error> {
+        if let Value::Amf3ObjectReference(id) = value.as_ref() {
+            if let Some(target) = self.objects.get(id) {
+                return Ok(json!({ REF_KEY: target }));
+            }
+        }
+        if is_container(value) {
+            if let Some(target) = self.paths.get(&Rc::as_ptr(value)) {
+                return Ok(json!({ REF_KEY: target }));
+            }
+            self.paths.insert(Rc::as_ptr(value), path.clone());
+        }
+
+        Ok(match value.as_ref() {
+            Value::Object(id, elements, def) => {
+                if *id != ObjectId::INVALID {
+                    self.objects.entry(*id).or_insert_with(|| path.clone());
+                }
+                json!({ "Object": [id, self.elements(elements, &path)?, def] })
+            }
+            Value::Custom(custom_elements, elements, def) => json!({
+                "Custom": [
+                    self.elements(custom_elements, &path)?,
+                    self.elements(elements, &path)?,
+                    def,
+                ]
+            }),
+            Value::ECMAArray(dense, elements, length) => json!({
+                "ECMAArray": [
+                    self.values(dense, &path)?,
+                    self.elements(elements, &path)?,
+                    length,
+                ]
+            }),
+            Value::StrictArray(values) => json!({ "StrictArray": self.values(values, &path)? }),
+            Value::VectorObject(values, type_name, fixed_length) => json!({
+                "VectorObject": [self.values(values, &path)?, type_name, fixed_length]
+            }),
+            Value::Dictionary(entries, weak_keys) => {
+                let mut pairs = Vec::with_capacity(entries.len());
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    pairs.push(json!([
+                        self.value(key, format!("{}.{}.key", path, index))?,
+                        self.value(value, format!("{}.{}.value", path, index))?,
+                    ]));
+                }
+                json!({ "Dictionary": [pairs, weak_keys] })
+            }
+            Value::ByteArray(bytes) => match self.options.byte_array {
+                ByteArrayRepr::IntArray => json!({ "ByteArray": bytes.as_ref() }),
+                ByteArrayRepr::Hex => json!({ "ByteArray": { "hex": to_hex(bytes) } }),
+                ByteArrayRepr::Base64 => {
+                    json!({ "ByteArray": { "base64": crate::base64::encode(bytes) } })
+                }
+            },
+            #[cfg(feature = "amf3")]
+            Value::AMF3(inner) => json!({ "AMF3": self.value(inner, path)? }),
+            other => serde_json::to_value(other)?,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Importer {
+    /// Every container that has been read, by path
+    values: HashMap<String, Rc<OwnedValue>>,
+
+    /// The id of every object that has been read, by path
+    ///
+    /// Objects are added before their children are read, so that references to an enclosing object can be resolved
+    objects: HashMap<String, ObjectId>,
+}
+
+impl Importer {
+    fn elements(
+        &mut self,
+        json: Vec<Json>,
+        path: Option<&str>,
+    ) -> Result<Vec<Element<'static>>, Error> {
+        json.into_iter()
+            .map(|element| {
+                let [name, value] = fields(element, ["name", "value"])?;
+                let name: String = serde_json::from_value(name)?;
+                let element_path = match path {
+                    Some(path) => format!("{}.{}", path, name),
+                    None => name.clone(),
+                };
+                let value = self.value(value, element_path)?;
+                Ok(Element::new(name, value))
+            })
+            .collect()
+    }
+
+    fn values(&mut self, json: Json, path: &str) -> Result<Vec<Rc<OwnedValue>>, Error> {
+        array(json)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, v)| self.value(v, format!("{}.{}", path, index)))
+            .collect()
+    }
+
+    fn value(&mut self, json: Json, path: String) -> Result<Rc<OwnedValue>, Error> {
+        let Json::Object(map) = json else {
+            return Ok(Rc::new(serde_json::from_value(json)?));
+        };
+        let Some((tag, content)) = single_entry(map)? else {
+            return Err(Error::custom("expected a value"));
+        };
+
+        let value = match tag.as_str() {
+            REF_KEY => return self.resolve(content),
+            "Object" => {
+                let [id, elements, def] = tuple(content)?;
+                let id: ObjectId = serde_json::from_value(id)?;
+                if id != ObjectId::INVALID {
+                    self.objects.entry(path.clone()).or_insert(id);
+                }
+                let elements = self.elements(array(elements)?, Some(&path))?;
+                Value::Object(id, elements, serde_json::from_value(def)?)
+            }
+            "Custom" => {
+                let [custom_elements, elements, def] = tuple(content)?;
+                Value::Custom(
+                    self.elements(array(custom_elements)?, Some(&path))?,
+                    self.elements(array(elements)?, Some(&path))?,
+                    serde_json::from_value(def)?,
+                )
+            }
+            "ECMAArray" => {
+                let [dense, elements, length] = tuple(content)?;
+                Value::ECMAArray(
+                    self.values(dense, &path)?,
+                    self.elements(array(elements)?, Some(&path))?,
+                    serde_json::from_value(length)?,
+                )
+            }
+            "StrictArray" => Value::StrictArray(self.values(content, &path)?),
+            "VectorObject" => {
+                let [values, type_name, fixed_length] = tuple(content)?;
+                Value::VectorObject(
+                    self.values(values, &path)?,
+                    serde_json::from_value(type_name)?,
+                    serde_json::from_value(fixed_length)?,
+                )
+            }
+            "Dictionary" => {
+                let [pairs, weak_keys] = tuple(content)?;
+                let mut entries = Vec::new();
+                for (index, pair) in array(pairs)?.into_iter().enumerate() {
+                    let [key, value] = tuple(pair)?;
+                    entries.push((
+                        self.value(key, format!("{}.{}.key", path, index))?,
+                        self.value(value, format!("{}.{}.value", path, index))?,
+                    ));
+                }
+                Value::Dictionary(entries, serde_json::from_value(weak_keys)?)
+            }
+            "ByteArray" => return Ok(Rc::new(Value::ByteArray(byte_array(content)?.into()))),
+            #[cfg(feature = "amf3")]
+            "AMF3" => return Ok(Rc::new(Value::AMF3(self.value(content, path)?))),
+            _ => {
+                let mut map = Map::new();
+                map.insert(tag, content);
+                return Ok(Rc::new(serde_json::from_value(Json::Object(map))?));
+            }
+        };
+
+        let value = Rc::new(value);
+        self.values.entry(path).or_insert_with(|| Rc::clone(&value));
+        Ok(value)
+    }
+
+    fn resolve(&self, target: Json) -> Result<Rc<OwnedValue>, Error> {
+        let target: String = serde_json::from_value(target)?;
+        if let Some(id) = self.objects.get(&target) {
+            Ok(Rc::new(Value::Amf3ObjectReference(*id)))
+        } else if let Some(value) = self.values.get(&target) {
+            Ok(Rc::clone(value))
+        } else {
+            Err(Error::custom(format!("unknown reference to {}", target)))
+        }
+    }
+}
+
+/// Check if a value can contain other values, these are the only values that are written as references
+fn is_container(value: &Value<'_>) -> bool {
+    matches!(
+        value,
+        Value::Object(_, _, _)
+            | Value::Custom(_, _, _)
+            | Value::ECMAArray(_, _, _)
+            | Value::StrictArray(_)
+            | Value::VectorObject(_, _, _)
+            | Value::Dictionary(_, _)
+    )
+}
+
+/// Read a byte array written with any `ByteArrayRepr`
+fn byte_array(json: Json) -> Result<Vec<u8>, Error> {
+    let Json::Object(map) = json else {
+        return serde_json::from_value(json);
+    };
+    match single_entry(map)? {
+        Some((encoding, Json::String(s))) if encoding == "hex" => from_hex(&s),
+        Some((encoding, Json::String(s))) if encoding == "base64" => crate::base64::decode(&s)
+            .map_err(|offset| Error::custom(format!("invalid base64 at offset {}", offset))),
+        _ => Err(Error::custom("expected a byte array")),
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>, Error> {
+    if !s.len().is_multiple_of(2) {
+        return Err(Error::custom("hex byte array has an odd length"));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            s.get(i..i + 2)
+                .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| Error::custom(format!("invalid hex at offset {}", i)))
+        })
+        .collect()
+}
+
+fn single_entry(map: Map<String, Json>) -> Result<Option<(String, Json)>, Error> {
+    if map.len() > 1 {
+        return Err(Error::custom("expected a single value"));
+    }
+    Ok(map.into_iter().next())
+}
+
+fn array(json: Json) -> Result<Vec<Json>, Error> {
+    match json {
+        Json::Array(values) => Ok(values),
+        _ => Err(Error::custom("expected an array")),
+    }
+}
+
+fn field<T: DeserializeOwned>(json: &Json, name: &str) -> Result<T, Error> {
+    serde_json::from_value(json[name].clone())
+}
+
+fn array_ref(json: &Json) -> Result<&Vec<Json>, Error> {
+    match json {
+        Json::Array(values) => Ok(values),
+        _ => Err(Error::custom("expected an array")),
+    }
+}
+
+fn tuple<const N: usize>(json: Json) -> Result<[Json; N], Error> {
+    array(json)?
+        .try_into()
+        .map_err(|_| Error::custom(format!("expected an array of length {}", N)))
+}
+
+fn fields<const N: usize>(json: Json, names: [&str; N]) -> Result<[Json; N], Error> {
+    let Json::Object(mut map) = json else {
+        return Err(Error::custom("expected an object"));
+    };
+    Ok(names.map(|name| map.remove(name).unwrap_or_default()))
+}
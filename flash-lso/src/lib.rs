@@ -14,6 +14,10 @@
     variant_size_differences,
     missing_docs
 )]
+#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 const HEADER_VERSION: [u8; 2] = [0x00, 0xbf];
 const HEADER_SIGNATURE: [u8; 10] = [0x54, 0x43, 0x53, 0x4f, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00];
@@ -21,7 +25,6 @@ const PADDING: [u8; 1] = [0x00];
 
 const FORMAT_VERSION_AMF0: u8 = 0x0;
 
-#[cfg(feature = "amf3")]
 const FORMAT_VERSION_AMF3: u8 = 0x3;
 
 #[cfg(feature = "serde")]
@@ -41,6 +44,19 @@ pub mod errors;
 /// Private internal utils for reading
 mod nom_utils;
 
+/// Private imports of `alloc` types, so the crate builds without `std`
+#[allow(unused_imports)]
+mod prelude;
+
+/// The byte sinks encoders write to
+pub mod io;
+
+/// Private base64 decoding, for data urls
+mod base64;
+
+/// The marker bytes that precede every value, for tools that inspect raw AMF data
+pub mod markers;
+
 /// Reading of the Lso container format
 pub mod read;
 
@@ -50,8 +66,50 @@ pub mod types;
 /// Writing of the Lso container format
 pub mod write;
 
+/// Reading of streams of length-framed AMF values
+#[cfg(feature = "std")]
+pub mod framed;
+
+/// Byte ranges of the values read from an lso
+pub mod spans;
+
+/// Reading of lso files an element at a time from a stream
+#[cfg(feature = "std")]
+pub mod stream;
+
+#[cfg(feature = "std")]
+pub use framed::FramedReader;
+#[cfg(feature = "std")]
+pub use read::read_file;
+pub use read::{decode_value, from_data_url};
+pub use write::encode_value;
+#[cfg(feature = "std")]
+pub use write::write_file;
+
 /// Extra functionality such as decoders for popular external class formats
 pub mod extra;
 
+/// Conversion to and from JSON that keeps shared and cyclic values as references
+#[cfg(feature = "json")]
+pub mod json;
+
+/// Transparent handling of lso files wrapped in zlib or gzip
+#[cfg(feature = "compression")]
+pub mod compression;
+
+/// Conversion to and from MessagePack
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+
+/// Decoding of many files in parallel
+#[cfg(feature = "rayon")]
+pub mod par;
+
+/// Visualisation of value graphs
+pub mod viz;
+
+/// Comparison of two lsos value by value
+pub mod diff;
+
 /// Reading and Writing of AMF Self Contained Packets
 pub mod packet;
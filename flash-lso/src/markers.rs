@@ -0,0 +1,96 @@
+/// Type markers used in AMF0
+pub mod amf0 {
+    /// Number
+    pub const NUMBER: u8 = 0x00;
+
+    /// Boolean
+    pub const BOOLEAN: u8 = 0x01;
+
+    /// String
+    pub const STRING: u8 = 0x02;
+
+    /// Object start
+    pub const OBJECT: u8 = 0x03;
+
+    /// MovieClip (unused)
+    pub const MOVIE_CLIP: u8 = 0x04;
+
+    /// Null
+    pub const NULL: u8 = 0x05;
+
+    /// Undefined
+    pub const UNDEFINED: u8 = 0x06;
+
+    /// Reference (unused)
+    pub const REFERENCE: u8 = 0x07;
+
+    /// Start of a mixed array
+    pub const MIXED_ARRAY_START: u8 = 0x08;
+
+    /// Object end
+    pub const OBJECT_END: u8 = 0x09;
+
+    /// Array start
+    pub const ARRAY: u8 = 0x0A;
+
+    /// Date with timezone
+    pub const DATE: u8 = 0x0B;
+
+    /// Long string (length > 65535)
+    pub const LONG_STRING: u8 = 0x0C;
+
+    /// Unsupported
+    pub const UNSUPPORTED: u8 = 0x0D;
+
+    /// Recordset
+    pub const RECORD_SET: u8 = 0x0E;
+
+    /// XML
+    pub const XML: u8 = 0x0F;
+
+    /// Typed object start
+    pub const TYPED_OBJECT: u8 = 0x10;
+
+    /// Embedded AMF3 element
+    pub const AMF3: u8 = 0x11;
+}
+
+/// Type markers used in AMF3
+pub mod amf3 {
+    /// Undefined
+    pub const UNDEFINED: u8 = 0x00;
+    /// Null
+    pub const NULL: u8 = 0x01;
+    /// Boolean false
+    pub const FALSE: u8 = 0x02;
+    /// Boolean true
+    pub const TRUE: u8 = 0x03;
+    /// Variable length integer
+    pub const INTEGER: u8 = 0x04;
+    /// Floating point number
+    pub const NUMBER: u8 = 0x05;
+    /// String
+    pub const STRING: u8 = 0x06;
+    /// XML
+    pub const XML: u8 = 0x07;
+    /// Date (always UTC)
+    pub const DATE: u8 = 0x08;
+    /// Array
+    pub const ARRAY: u8 = 0x09;
+    /// Object
+    pub const OBJECT: u8 = 0x0A;
+    /// XML string
+    pub const XML_STRING: u8 = 0x0B;
+    /// Byte array
+    pub const BYTE_ARRAY: u8 = 0x0C;
+    /// Vector<Int>
+    pub const VECTOR_INT: u8 = 0x0D;
+    /// Vector<UInt>
+    pub const VECTOR_UINT: u8 = 0x0E;
+    /// Vector<Double>
+    pub const VECTOR_DOUBLE: u8 = 0x0F;
+    /// Vector<Object>
+    pub const VECTOR_OBJECT: u8 = 0x10;
+    /// Dictionary
+    pub const DICTIONARY: u8 = 0x11;
+}
@@ -0,0 +1,40 @@
+use crate::types::{OwnedValue, Value};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// An error converting a value to or from MessagePack
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The value couldn't be written
+    #[error(transparent)]
+    Encode(#[from] rmp_serde::encode::Error),
+
+    /// The data isn't valid MessagePack or doesn't have the shape of a value
+    #[error(transparent)]
+    Decode(#[from] rmp_serde::decode::Error),
+
+    /// Data was left over after the value
+    #[error("{0} bytes left over after the value")]
+    TrailingBytes(usize),
+}
+
+impl Value<'_> {
+    /// Convert this value to MessagePack
+    ///
+    /// The output has the same shape as the JSON created with serde, except that byte arrays are written as binary
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::new();
+        self.serialize(&mut rmp_serde::Serializer::new(&mut out).with_struct_map())?;
+        Ok(out)
+    }
+}
+
+/// Read a value from MessagePack created by `Value::to_msgpack`
+pub fn from_msgpack(data: &[u8]) -> Result<OwnedValue, Error> {
+    let mut deserializer = rmp_serde::Deserializer::new(data);
+    let value = OwnedValue::deserialize(&mut deserializer)?;
+    match deserializer.get_ref().len() {
+        0 => Ok(value),
+        left => Err(Error::TrailingBytes(left)),
+    }
+}
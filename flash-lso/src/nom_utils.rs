@@ -1,22 +1,44 @@
 use crate::errors::Error;
+use crate::prelude::*;
 use nom::bytes::complete::take;
 use nom::combinator::map_res;
 
+use crate::io::Write;
 use crate::write::WriteExt;
 use nom::IResult;
-use std::io::Write;
 
 pub(crate) type AMFResult<'a, T> = IResult<&'a [u8], T, Error<'a>>;
 
-pub(crate) fn write_string<'a, 'b: 'a, W: Write + 'a>(
-    writer: &mut W,
-    s: &'b str,
-) -> std::io::Result<()> {
-    writer.write_u16(s.len() as u16)?;
-    writer.write_all(s.as_bytes())?;
+/// Add `count` items of `size` bytes to the `allocated` bytes of a decoder, giving the new total
+///
+/// This fails with `Error::AllocationLimit` if the total would be more than `max_alloc`
+pub(crate) fn check_alloc(
+    i: &[u8],
+    allocated: usize,
+    max_alloc: usize,
+    count: usize,
+    size: usize,
+) -> Result<usize, nom::Err<Error<'_>>> {
+    count
+        .checked_mul(size)
+        .and_then(|bytes| allocated.checked_add(bytes))
+        .filter(|total| *total <= max_alloc)
+        .ok_or(nom::Err::Error(Error::AllocationLimit(i)))
+}
+
+/// Write a string that has been encoded into `bytes`, with its length as a u16
+pub(crate) fn write_string<W: Write>(writer: &mut W, bytes: &[u8]) -> crate::io::Result<()> {
+    let length = u16::try_from(bytes.len()).map_err(|_| {
+        crate::io::Error::new(
+            crate::io::ErrorKind::InvalidInput,
+            format!("String is too long ({} bytes)", bytes.len()),
+        )
+    })?;
+    writer.write_u16(length)?;
+    writer.write_all(bytes)?;
     Ok(())
 }
 
 pub(crate) fn take_str(i: &[u8], length: u16) -> AMFResult<'_, &str> {
-    map_res(take(length), std::str::from_utf8)(i)
+    map_res(take(length), core::str::from_utf8)(i)
 }
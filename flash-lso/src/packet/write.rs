@@ -3,10 +3,11 @@
 use crate::amf0;
 use crate::errors::Error;
 use crate::packet::{Header, Message, Packet};
+use crate::prelude::*;
 use crate::types::AMFVersion;
 
 fn write_header(
-    header: &Header,
+    header: &Header<'_>,
     out: &mut Vec<u8>,
     exact_lengths: bool,
 ) -> Result<(), Error<'static>> {
@@ -24,8 +25,7 @@ fn write_header(
 
     // Value
     let mut value = vec![];
-    amf0::write::write_value(&mut value, &header.value)
-        .map_err(|e| Error::IoError(e.to_string(), e.kind()))?;
+    amf0::write::write_value(&mut value, &header.value)?;
     if exact_lengths {
         let value_length = u32::try_from(value.len()).map_err(|_| Error::PacketTooLarge)?;
         out.extend(value_length.to_be_bytes());
@@ -38,7 +38,7 @@ fn write_header(
 }
 
 fn write_message(
-    message: &Message,
+    message: &Message<'_>,
     out: &mut Vec<u8>,
     exact_lengths: bool,
 ) -> Result<(), Error<'static>> {
@@ -56,8 +56,7 @@ fn write_message(
 
     // Contents
     let mut contents = vec![];
-    amf0::write::write_value(&mut contents, &message.contents)
-        .map_err(|e| Error::IoError(e.to_string(), e.kind()))?;
+    amf0::write::write_value(&mut contents, &message.contents)?;
     if exact_lengths {
         let contents_length = u32::try_from(contents.len()).map_err(|_| Error::PacketTooLarge)?;
         out.extend(contents_length.to_be_bytes());
@@ -70,7 +69,7 @@ fn write_message(
 }
 
 /// Write a packet to a vec of bytes
-pub fn write_to_bytes(packet: &Packet, exact_lengths: bool) -> Result<Vec<u8>, Error<'static>> {
+pub fn write_to_bytes(packet: &Packet<'_>, exact_lengths: bool) -> Result<Vec<u8>, Error<'static>> {
     let mut buffer = vec![];
 
     // Version
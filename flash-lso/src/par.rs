@@ -0,0 +1,29 @@
+use crate::errors::Error;
+use crate::read::Reader;
+use crate::types::Lso;
+use rayon::prelude::*;
+
+/// Decode many lso files across threads, passing each result to `f` on the thread that decoded it
+///
+/// Each file is read with its own default `Reader`, so nothing is shared between them. Decoded values use `Rc` and
+/// can't be sent between threads, so `f` turns each one into something that can, such as a summary or JSON. The
+/// results are in the same order as `files`
+/// ```
+/// let files = vec![
+///     std::fs::read("tests/sol/AS2-Demo.sol").unwrap(),
+///     std::fs::read("tests/sol/AS3-Demo.sol").unwrap(),
+/// ];
+/// let names = flash_lso::par::parse_many(&files, |lso| lso.map(|lso| lso.header.name).ok());
+/// assert_eq!(names, vec![Some("AS2-Demo".to_string()), Some("AS3-Demo".to_string())]);
+/// ```
+pub fn parse_many<B, T, F>(files: &[B], f: F) -> Vec<T>
+where
+    B: AsRef<[u8]> + Sync,
+    T: Send,
+    F: for<'a> Fn(Result<Lso<'a>, nom::Err<Error<'a>>>) -> T + Sync,
+{
+    files
+        .par_iter()
+        .map(|data| f(Reader::default().parse(data.as_ref())))
+        .collect()
+}
@@ -0,0 +1,15 @@
+//! Types from the standard prelude and `std` that come from `alloc` when the `std` feature is disabled
+//!
+//! Without `std` a `BTreeMap` stands in for `HashMap`, the crate only uses the methods they share
+
+pub(crate) use alloc::borrow::{Cow, ToOwned};
+pub(crate) use alloc::boxed::Box;
+pub(crate) use alloc::rc::Rc;
+pub(crate) use alloc::string::{String, ToString};
+pub(crate) use alloc::vec::Vec;
+pub(crate) use alloc::{format, vec};
+
+#[cfg(not(feature = "std"))]
+pub(crate) use alloc::collections::BTreeMap as HashMap;
+#[cfg(feature = "std")]
+pub(crate) use std::collections::HashMap;
@@ -1,26 +1,99 @@
-use std::convert::TryInto;
-
-use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::number::complete::be_u32;
+use nom::number::complete::{be_u16, be_u32, be_u8};
 
-use crate::amf0;
 use crate::amf0::read::AMF0Decoder;
 #[cfg(feature = "amf3")]
+use crate::amf3::length::Length;
+#[cfg(feature = "amf3")]
 use crate::amf3::read::AMF3Decoder;
-use crate::errors::Error;
+#[cfg(feature = "compression")]
+use crate::compression::{decompress_with_limit, Compression};
+#[cfg(feature = "std")]
+use crate::errors::FileError;
+use crate::errors::{DataUrlError, DecodeError, Error, PathSegment};
 use crate::nom_utils::AMFResult;
-use crate::types::{AMFVersion, Header, Lso};
+use crate::prelude::*;
+use crate::spans::{SpanRecorder, Spans};
+use crate::types::{
+    AMFVersion, Element, FormatVersion, Header, Lso, SurrogateStrings, Utf8Mode, Value,
+};
 use nom::combinator::all_consuming;
+#[cfg(feature = "std")]
+use std::path::Path;
 
 const HEADER_VERSION: [u8; 2] = [0x00, 0xbf];
 const HEADER_SIGNATURE: [u8; 10] = [0x54, 0x43, 0x53, 0x4f, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00];
 const PADDING: [u8; 1] = [0x00];
 
-const FORMAT_VERSION_AMF0: u8 = 0x0;
+/// The deepest nesting of values decoders allow by default, see `Reader::set_max_depth`
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// The memory counted against the allocation budget of a decoder for each value read, see `Reader::set_max_alloc`
+///
+/// This is the value, the counts of the `Rc` holding it and the element or entry it is in
+pub(crate) const VALUE_ALLOC: usize =
+    size_of::<Value<'static>>() + 2 * size_of::<usize>() + size_of::<Element<'static>>();
+
+/// Guess the AMF version of an lso body from its first element
+///
+/// The first element is checked as both an AMF0 and an AMF3 element, a name in bounds and valid UTF-8 followed by a known
+/// type marker. A version is only given if the element is plausible for exactly one of them, otherwise this returns
+/// `None`, as does an empty body
+pub fn sniff_amf_version(body: &[u8]) -> Option<AMFVersion> {
+    #[cfg(feature = "amf3")]
+    let amf3 = plausible_amf3_element(body);
+    #[cfg(not(feature = "amf3"))]
+    let amf3 = false;
+
+    match (plausible_amf0_element(body), amf3) {
+        (true, false) => Some(AMFVersion::AMF0),
+        #[cfg(feature = "amf3")]
+        (false, true) => Some(AMFVersion::AMF3),
+        _ => None,
+    }
+}
+
+fn plausible_amf0_element(i: &[u8]) -> bool {
+    match be_u16::<_, ()>(i) {
+        Ok((i, name_length)) => plausible_element(i, name_length as usize, |marker| {
+            crate::amf0::type_marker::TypeMarker::try_from(marker).is_ok()
+        }),
+        Err(_) => false,
+    }
+}
 
 #[cfg(feature = "amf3")]
-const FORMAT_VERSION_AMF3: u8 = 0x3;
+fn plausible_amf3_element(i: &[u8]) -> bool {
+    // The string table is empty at the start of the body, so the name can't be a reference
+    match crate::amf3::read::read_length(i) {
+        Ok((i, Length::Size(name_length))) if name_length > 0 => {
+            plausible_element(i, name_length as usize, |marker| {
+                crate::amf3::type_marker::TypeMarker::try_from(marker).is_ok()
+            })
+        }
+        _ => false,
+    }
+}
+
+fn plausible_element(i: &[u8], name_length: usize, is_marker: impl Fn(u8) -> bool) -> bool {
+    match (i.get(..name_length), i.get(name_length)) {
+        (Some(name), Some(marker)) => core::str::from_utf8(name).is_ok() && is_marker(*marker),
+        _ => false,
+    }
+}
+
+/// An Lso read from the start of a slice by `Reader::parse_prefix`, with where it ended
+#[derive(Debug)]
+pub struct ParseOutcome<'a> {
+    /// The Lso that was read
+    pub lso: Lso<'a>,
+
+    /// The number of bytes at the start of the input the Lso was read from
+    pub consumed: usize,
+
+    /// The rest of the input after the Lso
+    pub remaining: &'a [u8],
+}
 
 /// The main entry point of decoding an LSO file
 /// Example of use
@@ -36,36 +109,103 @@ const FORMAT_VERSION_AMF3: u8 = 0x3;
 /// ```
 /// }
 #[derive(Default)]
-pub struct Reader {
+pub struct Reader<'a> {
     #[cfg(feature = "amf3")]
     /// Handles reading Amf3 data
-    pub amf3_decoder: AMF3Decoder,
+    pub amf3_decoder: AMF3Decoder<'a>,
 
     /// Handles reading Amf0 data
-    pub amf0_decoder: AMF0Decoder,
+    pub amf0_decoder: AMF0Decoder<'a>,
+
+    /// Choose the AMF version of the body with `sniff_amf_version` when the header gives one it doesn't look like
+    ///
+    /// This only overrides the header when its version is clearly wrong for the first element and the other version
+    /// clearly fits, a body that fits both or neither is read with the version from the header
+    pub detect_amf_version: bool,
+
+    /// The format version given in the header of the last Lso read, if `detect_amf_version` read its body with a
+    /// different version
+    ///
+    /// The header of the returned Lso gives the version that was used, so this is the only record of the mismatch
+    pub header_version_mismatch: Option<FormatVersion>,
+
+    /// Keep any data after the body in `Lso::trailing` instead of failing to parse
+    ///
+    /// A body ends at the first element that can't be read, so in this mode a damaged element is kept as trailing data
+    /// along with everything after it
+    pub preserve_trailing: bool,
+
+    /// The container the last file read with `parse_file` was wrapped in, if it was compressed
+    ///
+    /// Set `Writer::compression` to this to write the file back the same way
+    #[cfg(feature = "compression")]
+    pub compression: Option<Compression>,
 }
 
-impl Reader {
+impl<'a> Reader<'a> {
+    /// Set how strings are decoded, for both AMF0 and AMF3 data
+    pub fn set_utf8_mode(&mut self, utf8_mode: Utf8Mode) {
+        self.amf0_decoder.utf8_mode = utf8_mode;
+        #[cfg(feature = "amf3")]
+        {
+            self.amf3_decoder.utf8_mode = utf8_mode;
+        }
+    }
+
+    /// Set a decoder for AMF0 strings that aren't valid in the `Utf8Mode`, such as Latin-1 strings in old files, see
+    /// `AMF0Decoder::string_decoder`
+    pub fn set_string_decoder(
+        &mut self,
+        decoder: impl for<'b> Fn(&'b [u8]) -> Cow<'b, str> + 'static,
+    ) {
+        self.amf0_decoder.string_decoder = Some(Rc::new(decoder));
+    }
+
+    /// Set how deeply values can be nested, for both AMF0 and AMF3 data, the elements of the body have a depth of 1
+    ///
+    /// Decoding recurses into each nested value, so this stops crafted files from overflowing the stack. Deeper values
+    /// fail with `Error::DepthExceeded`, the default is `DEFAULT_MAX_DEPTH`
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.amf0_decoder.max_depth = max_depth;
+        #[cfg(feature = "amf3")]
+        {
+            self.amf3_decoder.options.max_depth = max_depth;
+        }
+    }
+
+    /// Set a budget in bytes for the memory used by the values of each Lso read, for both AMF0 and AMF3 data
+    ///
+    /// Each value counts as `VALUE_ALLOC` bytes, plus 4 or 8 bytes for each entry of an int, uint or double vector.
+    /// Strings and byte arrays borrow from the input so they aren't counted. Counts declared by arrays, vectors and
+    /// dictionaries are checked against the budget before anything is allocated for them, so a few bytes claiming to
+    /// hold millions of values fail straight away. Values that would go over the budget fail with
+    /// `Error::AllocationLimit`, by default there is no limit
+    ///
+    /// This also limits the size of compressed files after decompression, in `parse_file`
+    pub fn set_max_alloc(&mut self, max_alloc: usize) {
+        self.amf0_decoder.max_alloc = max_alloc;
+        #[cfg(feature = "amf3")]
+        {
+            self.amf3_decoder.options.max_alloc = max_alloc;
+        }
+    }
+
     /// Read a Lso header from the given slice
-    pub fn parse_header<'a>(&self, i: &'a [u8]) -> AMFResult<'a, Header> {
+    ///
+    /// Any format version marker is accepted here, a marker that can't be decoded is given as `FormatVersion::Unknown`
+    pub fn parse_header(&self, i: &'a [u8]) -> AMFResult<'a, Header> {
         let (i, _) = tag(HEADER_VERSION)(i)?;
         let (i, l) = be_u32(i)?;
         let (i, _) = tag(HEADER_SIGNATURE)(i)?;
 
-        let (i, name) = amf0::read::parse_string(i)?;
+        let (i, name) = self.amf0_decoder.parse_string(i)?;
 
         let (i, _) = tag(PADDING)(i)?;
         let (i, _) = tag(PADDING)(i)?;
         let (i, _) = tag(PADDING)(i)?;
 
-        let (i, version) = alt((
-            tag(&[FORMAT_VERSION_AMF0]),
-            #[cfg(feature = "amf3")]
-            tag(&[FORMAT_VERSION_AMF3]),
-        ))(i)?;
-
-        // This unwrap can't fail because of the alt above
-        let format_version: AMFVersion = version[0].try_into().unwrap();
+        let (i, version) = be_u8(i)?;
+        let format_version = FormatVersion::from(version);
 
         Ok((
             i,
@@ -77,32 +217,352 @@ impl Reader {
         ))
     }
 
+    /// Clear the state left in the decoders by the last Lso read
+    fn reset(&mut self) {
+        let surrogate_strings = SurrogateStrings::default();
+        self.amf0_decoder.surrogate_strings = surrogate_strings.clone();
+        self.amf0_decoder.error_path.clear();
+        self.amf0_decoder.element_error = None;
+        self.amf0_decoder.allocated = 0;
+        #[cfg(feature = "amf3")]
+        {
+            self.amf3_decoder.surrogate_strings = surrogate_strings;
+            self.amf3_decoder.error_path.clear();
+            self.amf3_decoder.element_error = None;
+            self.amf3_decoder.allocated = 0;
+        }
+    }
+
+    /// Read the header of an Lso and choose the AMF version to read its body with
+    fn parse_header_and_version(&mut self, i: &'a [u8]) -> AMFResult<'a, (Header, AMFVersion)> {
+        let (i, mut header) = self.parse_header(i)?;
+        self.header_version_mismatch = None;
+
+        let mut version = header.format_version.amf_version();
+        if self.detect_amf_version {
+            if let Some(detected) = sniff_amf_version(i).filter(|v| Some(*v) != version) {
+                self.header_version_mismatch = Some(header.format_version);
+                header.format_version = detected.into();
+                version = Some(detected);
+            }
+        }
+
+        let version = version.ok_or_else(|| {
+            nom::Err::Error(Error::UnsupportedFormatVersion(
+                header.format_version.as_u8(),
+            ))
+        })?;
+        Ok((i, (header, version)))
+    }
+
     /// Read a given buffer as an Lso
     ///
     /// Unlike parse, this function will not error if the entire slice isn't consumed
     /// and will return the data that was not parsed
-    pub fn parse_incomplete<'a>(&mut self, i: &'a [u8]) -> AMFResult<'a, Lso> {
-        let (i, header) = self.parse_header(i)?;
-        match header.format_version {
-            AMFVersion::AMF0 => {
-                let (i, body) = self.amf0_decoder.parse_body(i)?;
-                Ok((i, Lso { header, body }))
-            }
+    pub fn parse_incomplete(&mut self, i: &'a [u8]) -> AMFResult<'a, Lso<'a>> {
+        self.reset();
+        let (i, (header, version)) = self.parse_header_and_version(i)?;
 
+        let (i, body) = match version {
+            AMFVersion::AMF0 => self.amf0_decoder.parse_body(i)?,
             #[cfg(feature = "amf3")]
-            AMFVersion::AMF3 => {
-                let (i, body) = self.amf3_decoder.parse_body(i)?;
-                Ok((i, Lso { header, body }))
+            AMFVersion::AMF3 => self.amf3_decoder.parse_body(i)?,
+        };
+        Ok((
+            i,
+            Lso {
+                header,
+                body,
+                trailing: Vec::new(),
+                surrogate_strings: self.amf0_decoder.surrogate_strings.clone(),
+            },
+        ))
+    }
+
+    /// Read as much as possible of a damaged Lso, such as a truncated file
+    ///
+    /// The header must be readable, then the elements of the body are read in order until one can't be. The elements
+    /// before it are the same as `parse` gives for an undamaged file, and the error for the first element that couldn't
+    /// be read is returned alongside them, with its path starting from the name of the element if that could be read.
+    /// If the error has no offset of its own, such as an invalid reference, the offset of the start of the element is
+    /// given. Nothing after that element is read, it isn't kept in `Lso::trailing`
+    /// ```
+    /// use flash_lso::read::Reader;
+    ///
+    /// let data = include_bytes!("../tests/sol/AS2-Demo.sol");
+    /// let (lso, error) = Reader::default().parse_lenient(&data[..data.len() - 10]).unwrap();
+    /// assert!(error.is_some());
+    /// assert_eq!(lso.body[..], Reader::default().parse(data).unwrap().body[..lso.body.len()]);
+    /// ```
+    pub fn parse_lenient(
+        &mut self,
+        i: &'a [u8],
+    ) -> Result<(Lso<'a>, Option<DecodeError>), nom::Err<Error<'a>>> {
+        self.reset();
+        let (mut rest, (header, version)) = self.parse_header_and_version(i)?;
+
+        // The body itself is the first entry of the AMF0 reference table, see `AMF0Decoder::parse_body`
+        if version == AMFVersion::AMF0 {
+            self.amf0_decoder.cache.push(Rc::new(Value::Undefined));
+        }
+
+        let mut body = Vec::new();
+        let mut error = None;
+        while !rest.is_empty() {
+            let result = match version {
+                AMFVersion::AMF0 => {
+                    self.amf0_decoder.error_path.clear();
+                    self.amf0_decoder.parse_element_and_padding(rest)
+                }
+                #[cfg(feature = "amf3")]
+                AMFVersion::AMF3 => {
+                    self.amf3_decoder.error_path.clear();
+                    self.amf3_decoder.parse_element_and_padding(rest)
+                }
+            };
+            match result {
+                Ok((next, element)) => {
+                    body.push(element);
+                    rest = next;
+                }
+                Err(e) => {
+                    let mut e = DecodeError::new(i, e, self.error_path());
+                    e.offset.get_or_insert(i.len() - rest.len());
+                    error = Some(e);
+                    break;
+                }
             }
         }
+
+        let lso = Lso {
+            header,
+            body,
+            trailing: Vec::new(),
+            surrogate_strings: self.amf0_decoder.surrogate_strings.clone(),
+        };
+        Ok((lso, error))
+    }
+
+    /// Read an Lso from the start of a given slice, like `parse_incomplete`, giving how much of the slice was used
+    pub fn parse_prefix(&mut self, i: &'a [u8]) -> Result<ParseOutcome<'a>, nom::Err<Error<'a>>> {
+        let (remaining, lso) = self.parse_incomplete(i)?;
+        Ok(ParseOutcome {
+            lso,
+            consumed: i.len() - remaining.len(),
+            remaining,
+        })
+    }
+
+    /// Read every Lso from a slice holding one or more of them back to back
+    ///
+    /// The body of an Lso can't mark its own end, so each one is assumed to end where the length in its header says,
+    /// or at the end of the slice if that is past it. Each Lso is then read as with `parse`, so any data left over in
+    /// one is an error unless `preserve_trailing` is set. An empty slice gives no Lsos
+    pub fn parse_all(&mut self, i: &'a [u8]) -> Result<Vec<Lso<'a>>, nom::Err<Error<'a>>> {
+        let mut out = Vec::new();
+        let mut i = i;
+        while !i.is_empty() {
+            let (_, header) = self.parse_header(i)?;
+            // The length doesn't count the version marker and itself
+            let end = (header.length as usize)
+                .saturating_add(HEADER_VERSION.len() + 4)
+                .min(i.len());
+            out.push(self.parse(&i[..end])?);
+            i = &i[end..];
+        }
+        Ok(out)
     }
 
     /// Read a given slice as an Lso
     ///
     /// This function will return an error if the slice could not be parsed or if the entire slice
-    /// was not consumed
-    pub fn parse<'a>(&mut self, i: &'a [u8]) -> Result<Lso, nom::Err<Error<'a>>> {
+    /// was not consumed, unless `preserve_trailing` is set
+    pub fn parse(&mut self, i: &'a [u8]) -> Result<Lso<'a>, nom::Err<Error<'a>>> {
+        if self.preserve_trailing {
+            let (rest, mut lso) = self.parse_incomplete(i)?;
+            lso.trailing = rest.to_vec();
+            return Ok(lso);
+        }
         let (_, lso) = all_consuming(|i| self.parse_incomplete(i))(i)?;
         Ok(lso)
     }
+
+    /// Read a given slice as an Lso, like `parse`, along with the byte range of every value in it and the AMF version
+    /// each top level element was decoded with
+    ///
+    /// Offsets in the `Spans` are from the start of the slice, see `Spans` for how values are named
+    pub fn parse_annotated(
+        &mut self,
+        i: &'a [u8],
+    ) -> Result<(Lso<'a>, Spans), nom::Err<Error<'a>>> {
+        self.amf0_decoder.spans = Some(SpanRecorder::default());
+        #[cfg(feature = "amf3")]
+        {
+            self.amf3_decoder.spans = Some(SpanRecorder::default());
+        }
+
+        let result = self.parse(i);
+
+        let amf0 = self.amf0_decoder.spans.take().unwrap_or_default();
+        let (recorded, references) = (amf0.spans, amf0.references);
+        #[cfg(feature = "amf3")]
+        let (recorded, references) = {
+            let amf3 = self.amf3_decoder.spans.take().unwrap_or_default();
+            (
+                [recorded, amf3.spans].concat(),
+                [references, amf3.references].concat(),
+            )
+        };
+
+        let lso = result?;
+        let spans = Spans::from_recorded(i.len(), recorded, references, &lso);
+        Ok((lso, spans))
+    }
+
+    /// Get the path to the value that failed to decode after `parse` or `parse_incomplete` returns an error, outermost
+    /// first
+    ///
+    /// This is empty if the error wasn't in a value, such as an invalid header
+    pub fn error_path(&self) -> Vec<PathSegment> {
+        #[cfg(feature = "amf3")]
+        if !self.amf3_decoder.error_path.is_empty() {
+            return self.amf3_decoder.error_path();
+        }
+        self.amf0_decoder.error_path()
+    }
+
+    /// Describe an error returned by `parse` for the given data, with both its offset and the path to the failed value
+    ///
+    /// A body ends at the first element that can't be decoded, so `parse` fails after the body rather than in that
+    /// element. The error from the element is described instead when there is one, as it is where decoding went wrong
+    pub fn decode_error(&self, data: &[u8], error: nom::Err<Error<'_>>) -> DecodeError {
+        #[cfg(feature = "amf3")]
+        let element_error = self
+            .amf3_decoder
+            .element_error
+            .as_ref()
+            .or(self.amf0_decoder.element_error.as_ref());
+        #[cfg(not(feature = "amf3"))]
+        let element_error = self.amf0_decoder.element_error.as_ref();
+
+        match element_error {
+            Some(element_error) => DecodeError::new(data, element_error.clone(), self.error_path()),
+            None => DecodeError::new(data, error, self.error_path()),
+        }
+    }
+
+    /// Read the file at the given path as an Lso
+    ///
+    /// As with `parse`, the entire file must be consumed. The file is read with a new `Reader` with the same settings
+    /// as this one, as the values read can't outlive the file contents, and the result is detached from it
+    #[cfg(feature = "std")]
+    pub fn parse_file(&mut self, path: impl AsRef<Path>) -> Result<Lso<'static>, FileError> {
+        let path = path.as_ref();
+        #[allow(unused_mut)]
+        let mut data = std::fs::read(path).map_err(|source| FileError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        #[cfg(feature = "compression")]
+        {
+            self.compression = Compression::detect(&data);
+            if self.compression.is_some() {
+                data = decompress_with_limit(&data, self.amf0_decoder.max_alloc)
+                    .map_err(|e| FileError::Decode {
+                        path: path.to_path_buf(),
+                        message: e.to_string(),
+                    })?
+                    .0
+                    .into_owned();
+            }
+        }
+
+        let mut reader = self.with_same_settings();
+        let lso = match reader.parse(&data) {
+            Ok(lso) => Ok(lso.into_owned()),
+            Err(e) => Err(FileError::decode(
+                path.to_path_buf(),
+                reader.decode_error(&data, e),
+            )),
+        };
+        self.header_version_mismatch = reader.header_version_mismatch;
+        lso
+    }
+
+    /// Create a new `Reader` with the settings of this one, but none of the values it has read
+    #[cfg(feature = "std")]
+    fn with_same_settings<'b>(&self) -> Reader<'b> {
+        let mut reader = Reader {
+            detect_amf_version: self.detect_amf_version,
+            preserve_trailing: self.preserve_trailing,
+            ..Reader::default()
+        };
+        reader.amf0_decoder.utf8_mode = self.amf0_decoder.utf8_mode;
+        reader.amf0_decoder.string_decoder = self.amf0_decoder.string_decoder.clone();
+        reader.amf0_decoder.max_depth = self.amf0_decoder.max_depth;
+        reader.amf0_decoder.max_alloc = self.amf0_decoder.max_alloc;
+        #[cfg(feature = "amf3")]
+        {
+            reader.amf3_decoder.external_decoders = self.amf3_decoder.external_decoders.clone();
+            reader.amf3_decoder.utf8_mode = self.amf3_decoder.utf8_mode;
+            reader.amf3_decoder.options = self.amf3_decoder.options;
+        }
+        reader
+    }
+}
+
+/// Read a single value of the given AMF version from the start of a slice, returning it with the data after it
+///
+/// This is the lowest level entry point, for protocols that frame AMF values themselves. A new decoder is used, so
+/// references in the data can only refer to values earlier in the same value
+pub fn decode_value(i: &[u8], version: AMFVersion) -> AMFResult<'_, Value<'_>> {
+    let (i, value) = match version {
+        AMFVersion::AMF0 => AMF0Decoder::default().parse_single_element(i)?,
+        #[cfg(feature = "amf3")]
+        AMFVersion::AMF3 => AMF3Decoder::default().parse_single_element(i)?,
+    };
+    Ok((i, Rc::unwrap_or_clone(value)))
+}
+
+/// Read an Lso from a base64 `data:` url, such as `data:application/octet-stream;base64,...`, using the default `Reader`
+///
+/// Plain base64 without the `data:` prefix is also accepted, so a shared save can be pasted in either form
+pub fn from_data_url(url: &str) -> Result<Lso<'static>, DataUrlError> {
+    let url = url.trim();
+    let encoded = match url.get(..5) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("data:") => {
+            let (media_type, encoded) = url[5..].split_once(',').ok_or(DataUrlError::NotBase64)?;
+            let is_base64 = media_type
+                .rsplit(';')
+                .next()
+                .is_some_and(|p| p.trim().eq_ignore_ascii_case("base64"));
+            if !is_base64 {
+                return Err(DataUrlError::NotBase64);
+            }
+            encoded
+        }
+        _ => url,
+    };
+
+    let mut reader = Reader::default();
+    #[allow(unused_mut)]
+    let mut data = crate::base64::decode(encoded).map_err(DataUrlError::InvalidBase64)?;
+    #[cfg(feature = "compression")]
+    if Compression::detect(&data).is_some() {
+        data = decompress_with_limit(&data, reader.amf0_decoder.max_alloc)
+            .map_err(|e| DataUrlError::Decode(e.to_string()))?
+            .0
+            .into_owned();
+    }
+    match reader.parse(&data) {
+        Ok(lso) => Ok(lso.into_owned()),
+        Err(e) => Err(DataUrlError::decode(reader.decode_error(&data, e))),
+    }
+}
+
+/// Read the file at the given path as an Lso, using the default `Reader`
+#[cfg(feature = "std")]
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Lso<'static>, FileError> {
+    Reader::default().parse_file(path)
 }
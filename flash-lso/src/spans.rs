@@ -0,0 +1,137 @@
+use crate::errors::PathSegment;
+use crate::prelude::*;
+use crate::types::{AMFVersion, Lso};
+use core::ops::Range;
+
+/// The byte range of every value read by `Reader::parse_annotated`, by its dotted path, and the AMF version of each top
+/// level element
+///
+/// Paths are the ones used by `Lso::flatten`, such as `player.items.0.name`, except that containers have a range as
+/// well as leaves. Ranges cover the value including its type marker, but not the name of the element holding it.
+/// Values inside externalizable objects aren't included. A value read from a reference to an earlier one has the range
+/// of the reference, see `definition` for the range of the value it refers to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Spans {
+    ranges: HashMap<String, Range<usize>>,
+    versions: HashMap<String, AMFVersion>,
+    references: HashMap<String, String>,
+}
+
+impl Spans {
+    /// Get the byte range of the value at the given path
+    pub fn get(&self, path: &str) -> Option<Range<usize>> {
+        self.ranges.get(path).cloned()
+    }
+
+    /// Get the byte range of the definition of the value at the given path
+    ///
+    /// For a value read from a reference this is the range of the value it refers to, where it was first read, rather
+    /// than that of the reference. For any other value it is the same as `get`
+    pub fn definition(&self, path: &str) -> Option<Range<usize>> {
+        let path = self.references.get(path).map_or(path, String::as_str);
+        self.get(path)
+    }
+
+    /// Iterate over every path and its byte range, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Range<usize>)> {
+        self.ranges
+            .iter()
+            .map(|(path, range)| (path.as_str(), range))
+    }
+
+    /// The number of values with a range
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Check if no values have a range
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Get the AMF version the top level element with the given name was decoded with
+    ///
+    /// In an AMF0 body this is `AMF3` for an element whose value was switched to AMF3 with the `AMF3` type marker,
+    /// elements that only switch to AMF3 further in are `AMF0`. Every element of an AMF3 body is `AMF3`
+    pub fn version(&self, name: &str) -> Option<AMFVersion> {
+        self.versions.get(name).copied()
+    }
+
+    /// Convert the spans recorded while reading `lso` from an input of `length` bytes into ranges
+    pub(crate) fn from_recorded(
+        length: usize,
+        recorded: Vec<(String, usize, usize)>,
+        references: Vec<(String, String)>,
+        lso: &Lso<'_>,
+    ) -> Self {
+        let body_version = lso.header.format_version.amf_version();
+        let versions = lso
+            .body
+            .iter()
+            .filter_map(|element| {
+                let version = match element.value() {
+                    #[cfg(feature = "amf3")]
+                    crate::types::Value::AMF3(_) => AMFVersion::AMF3,
+                    _ => body_version?,
+                };
+                Some((element.name.to_string(), version))
+            })
+            .collect();
+
+        Self {
+            ranges: recorded
+                .into_iter()
+                .map(|(path, start, end)| (path, length - start..length - end))
+                .collect(),
+            versions,
+            references: references.into_iter().collect(),
+        }
+    }
+}
+
+/// Records the path of the value being read and the extent of each one as it's read
+///
+/// Extents are recorded as the number of bytes remaining in the input at the start and end of the value, as the
+/// decoders don't know where their input starts, `Spans::from_recorded` turns them into ranges
+#[derive(Debug, Default)]
+pub(crate) struct SpanRecorder {
+    path: Vec<String>,
+    pub(crate) spans: Vec<(String, usize, usize)>,
+
+    /// The path of the value in each slot of the reference table of the decoder using the recorder
+    pub(crate) definitions: HashMap<usize, String>,
+
+    /// The path of each value read from a reference, with the path of the value it refers to
+    pub(crate) references: Vec<(String, String)>,
+}
+
+impl SpanRecorder {
+    pub(crate) fn push(&mut self, segment: &PathSegment) {
+        self.path.push(match segment {
+            PathSegment::Name(name) => name.clone(),
+            PathSegment::Index(index) => index.to_string(),
+        });
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.path.pop();
+    }
+
+    /// Record that the value at the current path fills the given slot of the reference table
+    pub(crate) fn define(&mut self, index: usize) {
+        self.definitions.insert(index, self.path.join("."));
+    }
+
+    /// Record that the value at the current path was read from a reference to the given slot of the reference table
+    pub(crate) fn reference(&mut self, index: usize) {
+        if let Some(definition) = self.definitions.get(&index) {
+            self.references
+                .push((self.path.join("."), definition.clone()));
+        }
+    }
+
+    /// Record the extent of the value at the current path
+    pub(crate) fn record(&mut self, start: usize, end: usize) {
+        self.spans.push((self.path.join("."), start, end));
+    }
+}
@@ -0,0 +1,306 @@
+use crate::amf0::read::AMF0Decoder;
+#[cfg(feature = "amf3")]
+use crate::amf3::read::AMF3Decoder;
+use crate::errors::{DecodeError, Error, StreamError};
+use crate::read;
+#[cfg(feature = "amf3")]
+use crate::types::ClassDefinition;
+use crate::types::{owned_elements, owned_rc, OwnedValues};
+use crate::types::{AMFVersion, Element, Header, OwnedValue, Reference, Utf8Mode, Value};
+use std::io::Read;
+use std::rc::Rc;
+
+/// The smallest number of bytes read from the stream at a time
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Read an lso from a stream, decoding the elements of the body one at a time as they are needed
+///
+/// The header is read by `new`, then each element is read as the iterator reaches it, so only the element being
+/// decoded is held in memory, along with the reference tables. Elements are detached from the stream as they are
+/// read, see `Element::into_owned`. Iteration stops after the first error
+///
+/// References in AMF0 and AMF3 can only point backwards, to values read earlier in the body, so the reference tables
+/// are kept between elements as owned copies of the values in them. References are resolved as they are when reading
+/// the whole body: an AMF3 reference in a later element gives the same `Rc` that was in the earlier one, or an
+/// `Amf3ObjectReference` with its id for an object, and AMF0 references are left as `Value::Reference`s, see
+/// `resolve_reference`
+/// ```
+/// use flash_lso::stream::Reader;
+/// use std::fs::File;
+///
+/// let file = File::open("tests/sol/AS2-Demo.sol").expect("Couldn't open file");
+/// let mut reader = Reader::new(file).expect("Failed to read header");
+/// assert_eq!(reader.header().name, "AS2-Demo");
+/// for element in reader {
+///     println!("{:?}", element.expect("Failed to read element"));
+/// }
+/// ```
+pub struct Reader<R> {
+    reader: R,
+    header: Header,
+
+    /// Bytes read from the stream that haven't been decoded yet
+    buffer: Vec<u8>,
+
+    /// The offset in the stream of the start of `buffer`
+    position: usize,
+
+    /// Set once the stream has no more bytes
+    eof: bool,
+
+    tables: Tables,
+    utf8_mode: Utf8Mode,
+    done: bool,
+}
+
+/// The reference tables of the decoder for the body, detached from the bytes they were read from
+enum Tables {
+    Amf0 {
+        cache: Vec<Rc<OwnedValue>>,
+    },
+    #[cfg(feature = "amf3")]
+    Amf3 {
+        strings: Vec<Vec<u8>>,
+        traits: Vec<ClassDefinition>,
+        objects: Vec<Rc<OwnedValue>>,
+        object_id: i64,
+    },
+}
+
+impl<R: Read> Reader<R> {
+    /// Read the header of an lso from `reader`, strings are decoded with `Utf8Mode::Strict`
+    pub fn new(reader: R) -> Result<Self, StreamError> {
+        Self::with_utf8_mode(reader, Utf8Mode::default())
+    }
+
+    /// Read the header of an lso from `reader`, decoding strings with the given mode
+    pub fn with_utf8_mode(reader: R, utf8_mode: Utf8Mode) -> Result<Self, StreamError> {
+        let mut stream = Self {
+            reader,
+            header: Header::new("", AMFVersion::AMF0),
+            buffer: Vec::new(),
+            position: 0,
+            eof: false,
+            tables: Tables::Amf0 { cache: Vec::new() },
+            utf8_mode,
+            done: false,
+        };
+
+        let (consumed, header) = stream.read_with(|i| {
+            let mut header_reader = read::Reader::default();
+            header_reader.set_utf8_mode(utf8_mode);
+            match header_reader.parse_header(i) {
+                Ok((rest, header)) => Ok((i.len() - rest.len(), header)),
+                Err(e) => Err(DecodeError::new(i, e, Vec::new())),
+            }
+        })?;
+        stream.advance(consumed);
+
+        let version = header.format_version.amf_version().ok_or_else(|| {
+            let error = nom::Err::Error(Error::UnsupportedFormatVersion(
+                header.format_version.as_u8(),
+            ));
+            StreamError::Decode(DecodeError::new(&[], error, Vec::new()))
+        })?;
+        stream.tables = match version {
+            // The body is the first entry of the AMF0 reference table, see `AMF0Decoder::parse_body`
+            AMFVersion::AMF0 => Tables::Amf0 {
+                cache: vec![Rc::new(Value::Undefined)],
+            },
+            #[cfg(feature = "amf3")]
+            AMFVersion::AMF3 => Tables::Amf3 {
+                strings: Vec::new(),
+                traits: Vec::new(),
+                objects: Vec::new(),
+                object_id: 0,
+            },
+        };
+        stream.header = header;
+        Ok(stream)
+    }
+
+    /// The header of the lso
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Get the value an AMF0 reference read so far refers to
+    ///
+    /// The first entry of the reference table is the body itself, which isn't known until the whole body has been
+    /// read, so a reference to it gives `None`, as does a reference in an AMF3 body
+    pub fn resolve_reference(&self, reference: Reference) -> Option<Rc<OwnedValue>> {
+        match &self.tables {
+            Tables::Amf0 { cache } => match usize::from(reference.0) {
+                0 => None,
+                index => cache.get(index).cloned(),
+            },
+            #[cfg(feature = "amf3")]
+            Tables::Amf3 { .. } => None,
+        }
+    }
+
+    /// Get the underlying reader back
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_element(&mut self) -> Result<Option<Element<'static>>, StreamError> {
+        if self.buffer.is_empty() && !self.fill()? {
+            return Ok(None);
+        }
+
+        let utf8_mode = self.utf8_mode;
+        let mut tables = std::mem::replace(&mut self.tables, Tables::Amf0 { cache: Vec::new() });
+        let result = self.read_with(|i| tables.read_element(i, utf8_mode));
+        self.tables = tables;
+
+        let (consumed, element) = result?;
+        self.advance(consumed);
+        Ok(Some(element))
+    }
+
+    /// Decode from the start of the buffer, reading more of the stream until it succeeds or the stream ends
+    ///
+    /// Decoding fails in the same way whether the data is invalid or just incomplete, so the whole stream may be read
+    /// before an invalid element is reported
+    fn read_with<T>(
+        &mut self,
+        mut decode: impl FnMut(&[u8]) -> Result<(usize, T), DecodeError>,
+    ) -> Result<(usize, T), StreamError> {
+        loop {
+            let mut error = match decode(&self.buffer) {
+                Ok(decoded) => return Ok(decoded),
+                Err(error) => error,
+            };
+            if !self.fill()? {
+                error.offset = error.offset.map(|offset| offset + self.position);
+                return Err(StreamError::Decode(error));
+            }
+        }
+    }
+
+    /// Read at least as many bytes as are already buffered, returns false if the stream has ended
+    fn fill(&mut self) -> Result<bool, StreamError> {
+        if self.eof {
+            return Ok(false);
+        }
+        let want = self.buffer.len().max(CHUNK_SIZE);
+        let read = (&mut self.reader)
+            .take(want as u64)
+            .read_to_end(&mut self.buffer)?;
+        self.eof = read < want;
+        Ok(read > 0)
+    }
+
+    fn advance(&mut self, consumed: usize) {
+        self.buffer.drain(..consumed);
+        self.position += consumed;
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Element<'static>, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.read_element();
+        if !matches!(result, Ok(Some(_))) {
+            self.done = true;
+        }
+        result.transpose()
+    }
+}
+
+impl Tables {
+    /// Read the next element and its padding from `i` with a decoder that has these tables, then add the values it
+    /// added to them
+    ///
+    /// The tables are only changed if the element is read, so a failed read can be tried again with more data. Error
+    /// offsets are from the start of `i`
+    fn read_element(
+        &mut self,
+        i: &[u8],
+        utf8_mode: Utf8Mode,
+    ) -> Result<(usize, Element<'static>), DecodeError> {
+        match self {
+            Tables::Amf0 { cache } => {
+                let mut decoder = AMF0Decoder {
+                    utf8_mode,
+                    ..AMF0Decoder::default()
+                };
+                decoder.cache = cache.iter().map(shared).collect();
+
+                let (rest, element) = decoder
+                    .parse_element_and_padding(i)
+                    .map_err(|e| DecodeError::new(i, e, decoder.error_path()))?;
+                let (element, added) = detach(&element, &decoder.cache, cache);
+                cache.extend(added);
+                Ok((i.len() - rest.len(), element))
+            }
+            #[cfg(feature = "amf3")]
+            Tables::Amf3 {
+                strings,
+                traits,
+                objects,
+                object_id,
+            } => {
+                // The decoder borrows the string table, so everything it added is taken from it before changing that
+                let (consumed, element, added, added_strings) = {
+                    let mut decoder = AMF3Decoder::default();
+                    decoder.utf8_mode = utf8_mode;
+                    decoder.string_reference_table = strings.iter().map(Vec::as_slice).collect();
+                    decoder.trait_reference_table = traits.clone();
+                    decoder.object_reference_table = objects.iter().map(shared).collect();
+                    decoder.object_id = *object_id;
+
+                    let (rest, element) = decoder
+                        .parse_element_and_padding(i)
+                        .map_err(|e| DecodeError::new(i, e, decoder.error_path()))?;
+                    let (element, added) =
+                        detach(&element, &decoder.object_reference_table, objects);
+                    let added_strings: Vec<Vec<u8>> = decoder.string_reference_table
+                        [strings.len()..]
+                        .iter()
+                        .map(|s| s.to_vec())
+                        .collect();
+                    *traits = std::mem::take(&mut decoder.trait_reference_table);
+                    *object_id = decoder.object_id;
+                    (i.len() - rest.len(), element, added, added_strings)
+                };
+
+                objects.extend(added);
+                strings.extend(added_strings);
+                Ok((consumed, element))
+            }
+        }
+    }
+}
+
+/// Use an owned value in a decoder for borrowed ones
+fn shared<'a>(value: &Rc<OwnedValue>) -> Rc<Value<'a>> {
+    Rc::clone(value)
+}
+
+/// Detach an element and the entries a decoder added to its reference table after the `known` ones it was given
+///
+/// The known entries are the same `Rc`s as those given to the decoder, so they are used as they are, keeping values
+/// that are shared with earlier elements shared
+fn detach<'a>(
+    element: &Element<'a>,
+    table: &[Rc<Value<'a>>],
+    known: &[Rc<OwnedValue>],
+) -> (Element<'static>, Vec<Rc<OwnedValue>>) {
+    let mut seen = OwnedValues::new();
+    for (value, owned) in table.iter().zip(known) {
+        seen.insert(Rc::as_ptr(value), Rc::clone(owned));
+    }
+
+    let element = owned_elements(std::slice::from_ref(element), &mut seen).remove(0);
+    let added = table[known.len()..]
+        .iter()
+        .map(|v| owned_rc(v, &mut seen))
+        .collect();
+    (element, added)
+}
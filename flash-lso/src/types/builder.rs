@@ -0,0 +1,62 @@
+use super::{AMFVersion, Element, Lso, Value};
+use crate::prelude::*;
+use crate::write::{ByteCounter, Writer};
+
+/// Builds an lso from scratch, one element at a time
+///
+/// Values are given as they would be in AMF3, such as an `i32` as an `Integer`, and are converted with `Lso::convert_to`
+/// when building an AMF0 lso
+/// ```
+/// use flash_lso::types::{AMFVersion, LsoBuilder};
+/// use flash_lso::write::write_to_bytes;
+///
+/// let mut lso = LsoBuilder::new("save")
+///     .version(AMFVersion::AMF0)
+///     .add("name", "bob")
+///     .add("level", 3)
+///     .add("alive", true)
+///     .build();
+/// let bytes = write_to_bytes(&mut lso).expect("Failed to write lso");
+/// assert_eq!(lso.header.length as usize, bytes.len() - 6);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LsoBuilder<'a> {
+    name: String,
+    version: AMFVersion,
+    body: Vec<Element<'a>>,
+}
+
+impl<'a> LsoBuilder<'a> {
+    /// Start building an AMF3 lso with the given name and no elements
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: AMFVersion::AMF3,
+            body: Vec::new(),
+        }
+    }
+
+    /// Set the AMF version the body is encoded with
+    pub fn version(&mut self, version: AMFVersion) -> &mut Self {
+        self.version = version;
+        self
+    }
+
+    /// Add an element with the given name and value to the end of the body
+    pub fn add(&mut self, name: impl Into<Cow<'a, str>>, value: impl Into<Value<'a>>) -> &mut Self {
+        self.body.push(Element::new(name, value.into()));
+        self
+    }
+
+    /// Build the lso, with the header length set to the length it is written with by the default `Writer`
+    ///
+    /// The length is left as 0 if the body can't be encoded, writing it gives the error
+    pub fn build(&self) -> Lso<'a> {
+        let mut lso = Lso::new(self.body.clone(), self.name.clone(), AMFVersion::AMF3);
+        if self.version != AMFVersion::AMF3 {
+            lso = lso.convert_to(self.version);
+        }
+        let _ = Writer::default().write_full(&mut ByteCounter::default(), &mut lso);
+        lso
+    }
+}
@@ -1,4 +1,5 @@
 use super::Attribute;
+use crate::prelude::*;
 use enumset::EnumSet;
 
 /// A class definition (trait) used in AMF3
@@ -34,4 +35,12 @@ impl ClassDefinition {
             static_properties: Vec::new(),
         }
     }
+    /// Creates the dynamic, unnamed class definition that AMF3 uses for plain objects
+    pub fn anonymous() -> Self {
+        Self {
+            name: String::new(),
+            attributes: Attribute::Dynamic.into(),
+            static_properties: Vec::new(),
+        }
+    }
 }
@@ -0,0 +1,158 @@
+use super::{AMFVersion, Element, ObjectId, Value};
+use crate::prelude::*;
+use core::fmt::Write;
+
+/// Converts a body read with one AMF version into values that can be written with another, see `Lso::convert_to`
+pub(crate) struct Converter<'a> {
+    /// The version being converted to
+    version: AMFVersion,
+
+    /// Values already converted, by the address of the original, so values shared before are still shared after
+    converted: HashMap<*const Value<'a>, Rc<Value<'a>>>,
+
+    /// The AMF0 reference table, in the order a reader fills it, with `None` for values still being converted
+    ///
+    /// The body itself is the first entry, see `AMF0Decoder::parse_body`
+    references: Vec<Option<Rc<Value<'a>>>>,
+
+    /// The number of `AMF3` wrappers around the value being converted, values in them aren't in `references`
+    amf3_depth: usize,
+
+    /// Objects that have been converted, by id, for resolving `Amf3ObjectReference`s
+    objects: HashMap<ObjectId, Rc<Value<'a>>>,
+
+    /// Where the value being converted is, such as `body.foo[1]`
+    path: String,
+
+    /// The paths of references that couldn't be kept and were replaced by `Null`
+    pub(crate) lost: Vec<String>,
+}
+
+impl<'a> Converter<'a> {
+    pub(crate) fn new(version: AMFVersion) -> Self {
+        Self {
+            version,
+            converted: HashMap::new(),
+            references: vec![None],
+            amf3_depth: 0,
+            objects: HashMap::new(),
+            path: "body".to_string(),
+            lost: Vec::new(),
+        }
+    }
+
+    pub(crate) fn elements(&mut self, elements: &[Element<'a>]) -> Vec<Element<'a>> {
+        elements
+            .iter()
+            .map(|e| {
+                let len = self.path.len();
+                self.path.push('.');
+                self.path.push_str(&e.name);
+                let value = self.value(&e.value);
+                self.path.truncate(len);
+                Element::new(e.name.clone(), value)
+            })
+            .collect()
+    }
+
+    fn values(&mut self, values: &[Rc<Value<'a>>]) -> Vec<Rc<Value<'a>>> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let len = self.path.len();
+                let _ = write!(self.path, "[{}]", i);
+                let value = self.value(v);
+                self.path.truncate(len);
+                value
+            })
+            .collect()
+    }
+
+    /// Record the current value as lost, giving the `Null` that replaces it
+    fn lose(&mut self) -> Rc<Value<'a>> {
+        self.lost.push(self.path.clone());
+        Rc::new(Value::Null)
+    }
+
+    fn value(&mut self, value: &Rc<Value<'a>>) -> Rc<Value<'a>> {
+        if let Some(converted) = self.converted.get(&Rc::as_ptr(value)) {
+            return Rc::clone(converted);
+        }
+
+        // Objects and arrays take their slot in the AMF0 reference table before their contents
+        let slot = (self.amf3_depth == 0
+            && matches!(
+                value.as_ref(),
+                Value::Object(..) | Value::ECMAArray(..) | Value::StrictArray(_)
+            ))
+        .then(|| {
+            self.references.push(None);
+            self.references.len() - 1
+        });
+
+        let converted = self.convert(value);
+
+        if let Some(slot) = slot {
+            self.references[slot] = Some(Rc::clone(&converted));
+        }
+        if let Value::Object(id, _, _) = converted.as_ref() {
+            if *id != ObjectId::INVALID {
+                self.objects.insert(*id, Rc::clone(&converted));
+            }
+        }
+        self.converted
+            .insert(Rc::as_ptr(value), Rc::clone(&converted));
+        converted
+    }
+
+    fn convert(&mut self, value: &Rc<Value<'a>>) -> Rc<Value<'a>> {
+        let converted = match (self.version, value.as_ref()) {
+            #[cfg(feature = "amf3")]
+            (AMFVersion::AMF3, Value::AMF3(inner)) => {
+                self.amf3_depth += 1;
+                let inner = self.value(inner);
+                self.amf3_depth -= 1;
+                return inner;
+            }
+            // References to a value that contains them can't be made in AMF3 without an object id
+            #[cfg(feature = "amf3")]
+            (AMFVersion::AMF3, Value::Reference(reference)) => {
+                return match self.references.get(usize::from(reference.0)) {
+                    Some(Some(value)) => Rc::clone(value),
+                    _ => self.lose(),
+                };
+            }
+
+            (AMFVersion::AMF0, Value::Integer(i)) => Value::Number(f64::from(*i)),
+            (AMFVersion::AMF0, Value::Amf3ObjectReference(id)) => {
+                return match self.objects.get(id) {
+                    Some(value) => Rc::clone(value),
+                    None => self.lose(),
+                };
+            }
+            #[cfg(feature = "amf3")]
+            (
+                AMFVersion::AMF0,
+                Value::ByteArray(_)
+                | Value::VectorInt(..)
+                | Value::VectorUInt(..)
+                | Value::VectorDouble(..)
+                | Value::VectorObject(..)
+                | Value::Dictionary(..)
+                | Value::Custom(..)
+                | Value::RawExternal(_),
+            ) => Value::AMF3(Rc::clone(value)),
+
+            (_, Value::Object(id, elements, class_def)) => {
+                Value::Object(*id, self.elements(elements), class_def.clone())
+            }
+            (_, Value::ECMAArray(dense, elements, length)) => {
+                Value::ECMAArray(self.values(dense), self.elements(elements), *length)
+            }
+            (_, Value::StrictArray(values)) => Value::StrictArray(self.values(values)),
+            _ => return Rc::clone(value),
+        };
+        Rc::new(converted)
+    }
+}
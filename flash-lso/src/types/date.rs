@@ -0,0 +1,58 @@
+use super::Value;
+use chrono::{DateTime, FixedOffset, Offset, TimeDelta, TimeZone, Utc};
+
+const NANOS_PER_MILLI: f64 = 1_000_000.0;
+
+impl<'a> Value<'a> {
+    /// Create a `Date` at the time of `dt`, with the offset of its timezone in minutes east of UTC as the timezone field
+    ///
+    /// The time is stored as milliseconds since the epoch in an `f64`, which can only hold fractions of a millisecond to
+    /// a few hundred nanoseconds for current dates, anything finer is lost. The timezone field is only written in AMF0,
+    /// Flash Player writes it as 0 and ignores it when reading so it doesn't change the time
+    /// ```
+    /// use chrono::{FixedOffset, TimeZone};
+    /// use flash_lso::types::Value;
+    ///
+    /// let dt = FixedOffset::east_opt(3600).unwrap().with_ymd_and_hms(2020, 1, 1, 1, 0, 0).unwrap();
+    /// let date = Value::date_from_chrono(dt);
+    /// assert_eq!(date, Value::Date(1_577_836_800_000.0, Some(60)));
+    /// assert_eq!(date.to_datetime(), Some(dt.to_utc()));
+    /// ```
+    pub fn date_from_chrono<Tz: TimeZone>(dt: DateTime<Tz>) -> Self {
+        let millis = dt.timestamp() as f64 * 1000.0
+            + f64::from(dt.timestamp_subsec_nanos()) / NANOS_PER_MILLI;
+        let offset_minutes = dt.offset().fix().local_minus_utc() / 60;
+        Value::Date(millis, Some(offset_minutes as i16 as u16))
+    }
+
+    /// Get the time of a `Date`, `None` for other values and dates that are out of range or not finite
+    ///
+    /// The time of a date is always in UTC, the timezone field is ignored as it is by Flash Player, see
+    /// `to_datetime_with_offset` to use it
+    pub fn to_datetime(&self) -> Option<DateTime<Utc>> {
+        let Value::Date(millis, _) = self else {
+            return None;
+        };
+        if !millis.is_finite() {
+            return None;
+        }
+        let whole = millis.floor();
+        // Rounding can give a whole millisecond, which is added as nanoseconds all the same
+        let nanos = ((millis - whole) * NANOS_PER_MILLI).round() as i64;
+        DateTime::from_timestamp_millis(whole as i64)?
+            .checked_add_signed(TimeDelta::nanoseconds(nanos))
+    }
+
+    /// Get the time of a `Date` in the timezone given by its timezone field, as written by `date_from_chrono`
+    ///
+    /// Dates without a timezone, as in AMF3, or with one that isn't a valid offset are given in UTC
+    pub fn to_datetime_with_offset(&self) -> Option<DateTime<FixedOffset>> {
+        let Value::Date(_, time_zone) = self else {
+            return None;
+        };
+        let offset = time_zone
+            .and_then(|minutes| FixedOffset::east_opt(i32::from(minutes as i16) * 60))
+            .unwrap_or_else(|| Utc.fix());
+        Some(self.to_datetime()?.with_timezone(&offset))
+    }
+}
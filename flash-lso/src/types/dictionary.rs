@@ -0,0 +1,81 @@
+use super::value::Value;
+use crate::prelude::*;
+
+/// A view of the entries of a `Value::Dictionary`, see `Value::as_dictionary`
+///
+/// Keys are compared by value, if there is more than one matching key the first one is used
+#[derive(Debug, Clone, Copy)]
+pub struct Dictionary<'v, 'a> {
+    entries: &'v [(Rc<Value<'a>>, Rc<Value<'a>>)],
+}
+
+impl<'v, 'a> Dictionary<'v, 'a> {
+    /// Get the value stored under the given key
+    pub fn get(&self, key: &Value<'_>) -> Option<&'v Value<'a>> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k.as_ref() == key)
+            .map(|(_, v)| v.as_ref())
+    }
+
+    /// Get the number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over the key value pairs in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = (&'v Value<'a>, &'v Value<'a>)> {
+        self.entries.iter().map(|(k, v)| (k.as_ref(), v.as_ref()))
+    }
+}
+
+/// A mutable view of the entries of a `Value::Dictionary`, see `Value::as_dictionary_mut`
+#[derive(Debug)]
+pub struct DictionaryMut<'v, 'a> {
+    entries: &'v mut Vec<(Rc<Value<'a>>, Rc<Value<'a>>)>,
+}
+
+impl<'a> DictionaryMut<'_, 'a> {
+    /// Get a read only view of the entries
+    pub fn as_dictionary(&self) -> Dictionary<'_, 'a> {
+        Dictionary {
+            entries: self.entries,
+        }
+    }
+
+    /// Insert a value under the given key
+    ///
+    /// If the key is already present its value is replaced, keeping its position, otherwise the entry is added to the
+    /// end. The previous value is returned
+    pub fn insert(&mut self, key: Rc<Value<'a>>, value: Rc<Value<'a>>) -> Option<Rc<Value<'a>>> {
+        if let Some((_, v)) = self.entries.iter_mut().find(|(k, _)| *k == key) {
+            Some(core::mem::replace(v, value))
+        } else {
+            self.entries.push((key, value));
+            None
+        }
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Get a view of the entries, if this is a `Dictionary`
+    pub fn as_dictionary(&self) -> Option<Dictionary<'_, 'a>> {
+        match self {
+            Value::Dictionary(entries, _) => Some(Dictionary { entries }),
+            _ => None,
+        }
+    }
+
+    /// Get a mutable view of the entries, if this is a `Dictionary`
+    pub fn as_dictionary_mut(&mut self) -> Option<DictionaryMut<'_, 'a>> {
+        match self {
+            Value::Dictionary(entries, _) => Some(DictionaryMut { entries }),
+            _ => None,
+        }
+    }
+}
@@ -1,35 +1,56 @@
-use super::Value;
+use super::value::{owned_elements, Value};
+use crate::prelude::*;
 use core::ops::Deref;
-use std::rc::Rc;
 
 /// Represent a named element
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq)]
-pub struct Element {
+pub struct Element<'a> {
     /// The name of the element
-    pub name: String,
+    pub name: Cow<'a, str>,
 
     /// The value of the element
-    pub value: Rc<Value>,
+    pub value: Rc<Value<'a>>,
 }
 
-impl Element {
+impl<'a> Element<'a> {
     /// Create a new Element
+    ///
+    /// The value can be given as a `Value`, which is wrapped in a new `Rc`, or as an `Rc` to share it
     #[inline]
-    pub fn new(name: impl Into<String>, value: Rc<Value>) -> Self {
+    pub fn new(name: impl Into<Cow<'a, str>>, value: impl Into<Rc<Value<'a>>>) -> Self {
         Self {
             name: name.into(),
-            value,
+            value: value.into(),
         }
     }
 
     /// Get the Value of this element
-    pub fn value(&self) -> &Value {
+    pub fn value(&self) -> &Value<'a> {
         self.value.deref()
     }
 
     /// Get the name of this element
     pub fn name(&self) -> &str {
-        self.name.as_str()
+        &self.name
+    }
+
+    /// Check if two elements have the same name and value, see `Value::structural_eq`
+    pub fn structural_eq(&self, other: &Element<'_>) -> bool {
+        self.name == other.name && self.value.structural_eq(&other.value)
+    }
+
+    /// Detach this element from the input it was read from, see `Value::into_owned`
+    pub fn into_owned(self) -> Element<'static> {
+        owned_elements(core::slice::from_ref(&self), &mut HashMap::new()).remove(0)
+    }
+}
+
+/// An element can be used as its value, such as `element.get_property("name")`
+impl<'a> Deref for Element<'a> {
+    type Target = Value<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
     }
 }
@@ -0,0 +1,81 @@
+use super::AMFVersion;
+use crate::{FORMAT_VERSION_AMF0, FORMAT_VERSION_AMF3};
+use core::fmt;
+
+/// The format version marker stored in the header of a lso file
+///
+/// Unlike `AMFVersion` this can represent any marker found in a file, so that a header can be read
+/// even if its body can't be decoded
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum FormatVersion {
+    /// The body is encoded with AMF0
+    #[cfg_attr(feature = "serde", serde(rename = "AMF0"))]
+    Amf0,
+
+    /// The body is encoded with AMF3
+    #[cfg_attr(feature = "serde", serde(rename = "AMF3"))]
+    Amf3,
+
+    /// An unrecognised format version marker
+    Unknown(u8),
+}
+
+impl FormatVersion {
+    /// Get the numeric value of this version, as stored in the header
+    #[inline]
+    pub fn as_u8(self) -> u8 {
+        match self {
+            FormatVersion::Amf0 => FORMAT_VERSION_AMF0,
+            FormatVersion::Amf3 => FORMAT_VERSION_AMF3,
+            FormatVersion::Unknown(v) => v,
+        }
+    }
+
+    /// Get the AMF version used to encode the body, if it is one that can be handled
+    #[inline]
+    pub fn amf_version(self) -> Option<AMFVersion> {
+        match self {
+            FormatVersion::Amf0 => Some(AMFVersion::AMF0),
+            #[cfg(feature = "amf3")]
+            FormatVersion::Amf3 => Some(AMFVersion::AMF3),
+            _ => None,
+        }
+    }
+}
+
+impl From<u8> for FormatVersion {
+    fn from(value: u8) -> Self {
+        match value {
+            FORMAT_VERSION_AMF0 => FormatVersion::Amf0,
+            FORMAT_VERSION_AMF3 => FormatVersion::Amf3,
+            v => FormatVersion::Unknown(v),
+        }
+    }
+}
+
+impl From<FormatVersion> for u8 {
+    fn from(value: FormatVersion) -> Self {
+        value.as_u8()
+    }
+}
+
+impl From<AMFVersion> for FormatVersion {
+    fn from(value: AMFVersion) -> Self {
+        match value {
+            AMFVersion::AMF0 => FormatVersion::Amf0,
+            #[cfg(feature = "amf3")]
+            AMFVersion::AMF3 => FormatVersion::Amf3,
+        }
+    }
+}
+
+impl fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatVersion::Amf0 => f.write_str("AMF0"),
+            FormatVersion::Amf3 => f.write_str("AMF3"),
+            FormatVersion::Unknown(v) => write!(f, "Unknown ({})", v),
+        }
+    }
+}
@@ -1,38 +1,718 @@
-use super::{AMFVersion, Element, Header};
+use super::convert::Converter;
+use super::value::{elements_structural_eq, owned_elements};
+use super::{AMFVersion, Element, FormatVersion, Header, Stats, SurrogateStrings, Value};
+use crate::errors::{ConversionError, ValidationError};
+use crate::prelude::*;
+
+/// The longest name that can be written in AMF0, which uses a u16 length
+const AMF0_MAX_NAME_LENGTH: usize = u16::MAX as usize;
+
+#[cfg(feature = "amf3")]
+/// The longest string that can be written in AMF3, which uses a u29 length with one bit used as the reference flag
+const AMF3_MAX_NAME_LENGTH: usize = (1 << 28) - 1;
 
 /// A container for lso files
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[derive(Debug, PartialEq, Clone)]
-pub struct Lso {
+///
+/// With the `serde` feature an lso can be serialized and read back to write the same bytes. Values shared through an
+/// `Rc` are written out in full each time, and listed under `shared` by the path of each later copy and of the first
+/// one, so they are shared again when read and written as references
+#[derive(Debug, Clone)]
+pub struct Lso<'a> {
     /// The header of this lso
     pub header: Header,
 
     /// The elements at the root level of this lso
-    pub body: Vec<Element>,
+    pub body: Vec<Element<'a>>,
+
+    /// Data after the body that isn't part of the format, such as metadata appended by other tools
+    ///
+    /// This is only read when `Reader::preserve_trailing` is set, and is written back unchanged after the body
+    pub trailing: Vec<u8>,
+
+    /// The form that strings with characters outside of the basic multilingual plane were read in, with
+    /// `Utf8Mode::Flash`
+    ///
+    /// `Writer` writes these strings back in the same form, so that the file re-encodes to identical bytes
+    pub surrogate_strings: SurrogateStrings,
 }
 
-impl Lso {
+/// Lsos are compared by their header, body and trailing data, `surrogate_strings` only changes how they are encoded
+impl PartialEq for Lso<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.header == other.header && self.body == other.body && self.trailing == other.trailing
+    }
+}
+
+impl<'a> Lso<'a> {
     /// Create a new Lso with a header with the given name and version and an empty body
     #[inline]
-    pub fn new_empty(name: impl Into<String>, version: AMFVersion) -> Self {
+    pub fn new_empty(name: impl Into<String>, version: impl Into<FormatVersion>) -> Self {
         Self::new(Vec::new(), name, version)
     }
 
     /// Crate a new Lso with a header with the given name, version and body
     #[inline]
-    pub fn new(body: Vec<Element>, name: impl Into<String>, version: AMFVersion) -> Self {
+    pub fn new(
+        body: Vec<Element<'a>>,
+        name: impl Into<String>,
+        version: impl Into<FormatVersion>,
+    ) -> Self {
         Self {
             header: Header::new(name, version),
             body,
+            trailing: Vec::new(),
+            surrogate_strings: SurrogateStrings::default(),
+        }
+    }
+
+    /// Iterate over the elements of the body, in order
+    pub fn iter(&self) -> core::slice::Iter<'_, Element<'a>> {
+        self.body.iter()
+    }
+
+    /// Get the value of the element of the body with the given name
+    ///
+    /// If more than one element has the name, the first one is used
+    /// ```
+    /// use flash_lso::types::{AMFVersion, Lso, Value};
+    ///
+    /// let mut lso = Lso::new_empty("save", AMFVersion::AMF3);
+    /// lso.insert("level", Value::Integer(3));
+    /// assert_eq!(lso.get("level"), Some(&Value::Integer(3)));
+    ///
+    /// let old = lso.insert("level", Value::Integer(4));
+    /// assert_eq!(old.as_deref(), Some(&Value::Integer(3)));
+    /// assert_eq!(lso.iter().count(), 1);
+    /// ```
+    pub fn get(&self, name: &str) -> Option<&Value<'a>> {
+        self.body
+            .iter()
+            .find(|e| e.name == name)
+            .map(Element::value)
+    }
+
+    /// Get the value at the given path in the body, such as `player.inventory[3].count`
+    ///
+    /// The path starts with the name of an element and the rest is as for `Value::get_path`, these are the paths given
+    /// by `diff::diff`. A name containing `.` or `[` can't be reached this way, use `get` and `Value::get_path`
+    pub fn get_path(&self, path: &str) -> Option<&Value<'a>> {
+        let (name, rest) = path.split_at(path.find(['.', '[']).unwrap_or(path.len()));
+        self.get(name)?
+            .get_path(rest.strip_prefix('.').unwrap_or(rest))
+    }
+
+    /// Set the value of the element of the body with the given name, returning its old value, or add a new element at
+    /// the end of the body if there isn't one
+    ///
+    /// If more than one element has the name, the first one is replaced
+    pub fn insert(
+        &mut self,
+        name: impl Into<Cow<'a, str>>,
+        value: impl Into<Rc<Value<'a>>>,
+    ) -> Option<Rc<Value<'a>>> {
+        let name = name.into();
+        let value = value.into();
+        match self.body.iter_mut().find(|e| e.name == name) {
+            Some(element) => Some(core::mem::replace(&mut element.value, value)),
+            None => {
+                self.body.push(Element { name, value });
+                None
+            }
+        }
+    }
+
+    /// Check if two lsos have the same header, body and trailing data, see `Value::structural_eq`
+    pub fn structural_eq(&self, other: &Lso<'_>) -> bool {
+        self.header == other.header
+            && self.trailing == other.trailing
+            && elements_structural_eq(&self.body, &other.body)
+    }
+
+    /// Detach this lso from the input it was read from, see `Value::into_owned`
+    ///
+    /// Values shared between elements of the body are still shared in the result
+    pub fn into_owned(self) -> Lso<'static> {
+        Lso {
+            header: self.header,
+            body: owned_elements(&self.body, &mut HashMap::new()),
+            trailing: self.trailing,
+            surrogate_strings: self.surrogate_strings,
+        }
+    }
+
+    /// Convert this lso so that it can be written with the given AMF version, setting the version in the header
+    ///
+    /// Values are changed where the two versions differ:
+    /// - Writing AMF3, `AMF3` wrappers are removed and AMF0 `Reference`s are replaced by the value they refer to, which
+    ///   is then shared. A reference to a value that contains it can't be kept and becomes `Null`
+    /// - Writing AMF0, an `Integer` becomes a `Number` and an `Amf3ObjectReference` is replaced by the object it refers
+    ///   to, or `Null` if it refers to an object that contains it or one inside an AMF3-only value. Values that only exist
+    ///   in AMF3 (byte arrays, vectors, dictionaries and externalizable objects) are kept in an `AMF3` wrapper, so they
+    ///   read back wrapped
+    ///
+    /// Both directions lose the difference between sealed and dynamic properties of typed objects, which AMF0 doesn't
+    /// have. Converting to the version the lso already has leaves its values as they are
+    pub fn convert_to(&self, version: AMFVersion) -> Lso<'a> {
+        self.convert(version).0
+    }
+
+    /// Convert this lso to AMF3, see `convert_to`
+    ///
+    /// This can't fail, though an AMF0 `Reference` to a value that contains it becomes `Null`
+    /// ```
+    /// use flash_lso::types::{AMFVersion, Lso};
+    ///
+    /// let lso = Lso::new_empty("example", AMFVersion::AMF0);
+    /// assert_eq!(lso.to_amf3().header.format_version.amf_version(), Some(AMFVersion::AMF3));
+    /// ```
+    #[cfg(feature = "amf3")]
+    pub fn to_amf3(&self) -> Lso<'a> {
+        self.convert_to(AMFVersion::AMF3)
+    }
+
+    /// Convert this lso to AMF0, see `convert_to`, failing if any value would be lost
+    ///
+    /// Byte arrays, vectors, dictionaries and externalizable objects have no AMF0 form, they are kept in an `AMF3`
+    /// wrapper rather than failing. An `Amf3ObjectReference` to an object that contains it can't be written in AMF0,
+    /// the paths of all such references are given in the error
+    pub fn to_amf0(&self) -> Result<Lso<'a>, ConversionError> {
+        let (lso, paths) = self.convert(AMFVersion::AMF0);
+        if paths.is_empty() {
+            Ok(lso)
+        } else {
+            Err(ConversionError {
+                version: AMFVersion::AMF0,
+                paths,
+            })
+        }
+    }
+
+    /// Convert this lso as `convert_to` does, also giving the paths of values that were replaced by `Null`
+    fn convert(&self, version: AMFVersion) -> (Lso<'a>, Vec<String>) {
+        let mut header = self.header.clone();
+        header.format_version = version.into();
+
+        let (body, lost) = if self.header.format_version.amf_version() == Some(version) {
+            (self.body.clone(), Vec::new())
+        } else {
+            let mut converter = Converter::new(version);
+            let body = converter.elements(&self.body);
+            (body, converter.lost)
+        };
+
+        let lso = Lso {
+            header,
+            body,
+            trailing: self.trailing.clone(),
+            surrogate_strings: self.surrogate_strings.clone(),
+        };
+        (lso, lost)
+    }
+
+    /// Get the value at the given JSON pointer (RFC 6901), such as `/player/inventory/0/name`
+    ///
+    /// The first segment names an element of the body, and the rest name the same children as the dotted paths of
+    /// `flatten`. Entries of arrays and vectors are numbered from 0, dictionary entries are `/<index>/key` and
+    /// `/<index>/value`, and `AMF3` wrappers don't add a segment. `~1` and `~0` in a segment stand for `/` and `~`. The
+    /// body isn't a value itself, so the empty pointer gives `None`, as does any invalid pointer
+    pub fn pointer(&self, pointer: &str) -> Option<&Value<'a>> {
+        let segments = pointer_segments(pointer)?;
+        let (name, rest) = segments.split_first()?;
+        let element = self.body.iter().find(|e| e.name == *name)?;
+        element.value().pointer_segments(rest)
+    }
+
+    /// Get a mutable reference to the value at the given JSON pointer, see `pointer`
+    ///
+    /// Values on the way to it that are shared with other parts of the body are copied, so changes only appear at this
+    /// pointer
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value<'a>> {
+        let segments = pointer_segments(pointer)?;
+        let (name, rest) = segments.split_first()?;
+        let element = self.body.iter_mut().find(|e| e.name == *name)?;
+        Rc::make_mut(&mut element.value).pointer_segments_mut(rest)
+    }
+
+    /// Get every leaf value in the body with its dotted path, such as `player.items.0.name`
+    ///
+    /// Elements are named by their name and array or vector entries by their index, dictionary entries are given as
+    /// `<index>.key` and `<index>.value`. `AMF3` wrappers don't add a segment. Containers aren't included themselves,
+    /// so empty ones don't appear at all, and int, uint and double vectors are leaves
+    pub fn flatten(&self) -> Vec<(String, &Value<'a>)> {
+        let mut out = Vec::new();
+        for element in &self.body {
+            flatten_value(&element.value, element.name.to_string(), false, &mut out);
+        }
+        out
+    }
+
+    /// Get the dotted path of every value in the body that matches the predicate, in the order they appear
+    ///
+    /// Paths are the same as those from `flatten`, but containers are checked as well as leaves, before their children.
+    /// A value reached through more than one path, such as a shared `Rc`, is checked once for each path
+    pub fn find_paths(&self, mut predicate: impl FnMut(&Value<'a>) -> bool) -> Vec<String> {
+        let mut values = Vec::new();
+        for element in &self.body {
+            flatten_value(&element.value, element.name.to_string(), true, &mut values);
+        }
+        values
+            .into_iter()
+            .filter(|(_, value)| predicate(value))
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// Count the values in the body by type, along with the total number of values and the deepest nesting
+    ///
+    /// Every value is counted, including containers, dictionary keys and `AMF3` wrappers. Entries of int, uint and
+    /// double vectors aren't values of their own so they aren't counted
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        stats.add_elements(&self.body, 1);
+        stats
+    }
+
+    /// Check for problems that would stop this lso from being encoded correctly
+    ///
+    /// This finds names too long for their length prefix, values that can't be represented in the format version
+    /// from the header (such as AMF3 only types in an AMF0 body, which would be written as `Unsupported`) and
+    /// `VectorObject` elements that don't match a primitive vector type. Lengths are of the UTF-8 encoding of a name.
+    /// All problems found are returned
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+
+        if self.header.name.len() > AMF0_MAX_NAME_LENGTH {
+            errors.push(ValidationError::NameTooLong {
+                path: "header".to_string(),
+                length: self.header.name.len(),
+            });
+        }
+
+        match self.header.format_version.amf_version() {
+            Some(version) => validate_elements(&self.body, "body", version, &mut errors),
+            None => errors.push(ValidationError::UnsupportedFormatVersion(
+                self.header.format_version,
+            )),
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
     }
 }
 
-impl IntoIterator for Lso {
-    type Item = Element;
-    type IntoIter = std::vec::IntoIter<Self::Item>;
+/// Serde support for lsos, which records the values in the body that share an `Rc` so they are shared again when read
+#[cfg(feature = "serde")]
+mod shared_values {
+    use super::Lso;
+    use crate::prelude::*;
+    use crate::types::{Element, Header, SurrogateStrings, Value};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// A value that is the same `Rc` as one earlier in the body
+    ///
+    /// Paths are the index of an element in the body followed by the index of each child on the way to the value, in
+    /// the order they are written
+    #[derive(Serialize, Deserialize)]
+    struct SharedValue {
+        path: Vec<usize>,
+        same_as: Vec<usize>,
+    }
+
+    #[derive(Serialize)]
+    struct LsoRef<'r, 'a> {
+        header: &'r Header,
+        body: &'r [Element<'a>],
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        shared: Vec<SharedValue>,
+        #[serde(skip_serializing_if = "<[u8]>::is_empty")]
+        trailing: &'r [u8],
+        #[serde(skip_serializing_if = "SurrogateStrings::is_empty")]
+        surrogate_strings: &'r SurrogateStrings,
+    }
+
+    #[derive(Deserialize)]
+    struct LsoRepr<'a> {
+        header: Header,
+        body: Vec<Element<'a>>,
+        #[serde(default)]
+        shared: Vec<SharedValue>,
+        #[serde(default)]
+        trailing: Vec<u8>,
+        #[serde(default)]
+        surrogate_strings: SurrogateStrings,
+    }
+
+    impl Serialize for Lso<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut first_paths = HashMap::new();
+            let mut shared = Vec::new();
+            for (index, element) in self.body.iter().enumerate() {
+                find_shared(
+                    &element.value,
+                    &mut vec![index],
+                    &mut first_paths,
+                    &mut shared,
+                );
+            }
+
+            LsoRef {
+                header: &self.header,
+                body: &self.body,
+                shared,
+                trailing: &self.trailing,
+                surrogate_strings: &self.surrogate_strings,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, 'a> Deserialize<'de> for Lso<'a> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let mut repr = LsoRepr::deserialize(deserializer)?;
+
+            // Shared values are listed in the order they are found, so the values on the way to each one haven't been
+            // shared yet and can be changed in place
+            for SharedValue { path, same_as } in &repr.shared {
+                let invalid = || serde::de::Error::custom("invalid shared value path");
+                let value = Rc::clone(value_at(&repr.body, same_as).ok_or_else(invalid)?);
+                *value_at_mut(&mut repr.body, path).ok_or_else(invalid)? = value;
+            }
+
+            Ok(Lso {
+                header: repr.header,
+                body: repr.body,
+                trailing: repr.trailing,
+                surrogate_strings: repr.surrogate_strings,
+            })
+        }
+    }
+
+    /// Record every value in `value` that is the same `Rc` as one seen before, children of those aren't visited
+    fn find_shared<'a>(
+        value: &Rc<Value<'a>>,
+        path: &mut Vec<usize>,
+        first_paths: &mut HashMap<*const Value<'a>, Vec<usize>>,
+        shared: &mut Vec<SharedValue>,
+    ) {
+        if let Some(first) = first_paths.get(&Rc::as_ptr(value)) {
+            shared.push(SharedValue {
+                path: path.clone(),
+                same_as: first.clone(),
+            });
+            return;
+        }
+        if is_complex(value) {
+            first_paths.insert(Rc::as_ptr(value), path.clone());
+        }
+
+        for (index, child) in children(value).into_iter().enumerate() {
+            path.push(index);
+            find_shared(child, path, first_paths, shared);
+            path.pop();
+        }
+    }
+
+    /// Whether a value can be written as a reference to an earlier one, scalars are always written in full
+    fn is_complex(value: &Value<'_>) -> bool {
+        !matches!(
+            value,
+            Value::Number(_)
+                | Value::Bool(_)
+                | Value::String(_)
+                | Value::Null
+                | Value::Undefined
+                | Value::Unsupported
+                | Value::Integer(_)
+                | Value::Reference(_)
+                | Value::Amf3ObjectReference(_)
+        )
+    }
+
+    fn children<'r, 'a>(value: &'r Value<'a>) -> Vec<&'r Rc<Value<'a>>> {
+        match value {
+            #[cfg(feature = "amf3")]
+            Value::AMF3(inner) => vec![inner],
+            Value::Object(_, elements, _) => elements.iter().map(|e| &e.value).collect(),
+            Value::Custom(custom_elements, elements, _) => custom_elements
+                .iter()
+                .chain(elements)
+                .map(|e| &e.value)
+                .collect(),
+            Value::ECMAArray(dense, elements, _) => dense
+                .iter()
+                .chain(elements.iter().map(|e| &e.value))
+                .collect(),
+            Value::StrictArray(values) | Value::VectorObject(values, _, _) => {
+                values.iter().collect()
+            }
+            Value::Dictionary(entries, _) => entries.iter().flat_map(|(k, v)| [k, v]).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn children_mut<'r, 'a>(value: &'r mut Value<'a>) -> Vec<&'r mut Rc<Value<'a>>> {
+        match value {
+            #[cfg(feature = "amf3")]
+            Value::AMF3(inner) => vec![inner],
+            Value::Object(_, elements, _) => elements.iter_mut().map(|e| &mut e.value).collect(),
+            Value::Custom(custom_elements, elements, _) => custom_elements
+                .iter_mut()
+                .chain(elements)
+                .map(|e| &mut e.value)
+                .collect(),
+            Value::ECMAArray(dense, elements, _) => dense
+                .iter_mut()
+                .chain(elements.iter_mut().map(|e| &mut e.value))
+                .collect(),
+            Value::StrictArray(values) | Value::VectorObject(values, _, _) => {
+                values.iter_mut().collect()
+            }
+            Value::Dictionary(entries, _) => entries.iter_mut().flat_map(|(k, v)| [k, v]).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn value_at<'r, 'a>(body: &'r [Element<'a>], path: &[usize]) -> Option<&'r Rc<Value<'a>>> {
+        let (first, rest) = path.split_first()?;
+        let mut value = &body.get(*first)?.value;
+        for index in rest {
+            value = children(value).into_iter().nth(*index)?;
+        }
+        Some(value)
+    }
+
+    fn value_at_mut<'r, 'a>(
+        body: &'r mut [Element<'a>],
+        path: &[usize],
+    ) -> Option<&'r mut Rc<Value<'a>>> {
+        let (first, rest) = path.split_first()?;
+        let mut value = &mut body.get_mut(*first)?.value;
+        for index in rest {
+            value = children_mut(Rc::make_mut(value)).into_iter().nth(*index)?;
+        }
+        Some(value)
+    }
+}
+
+/// Split a JSON pointer into its unescaped segments, `None` if it doesn't start with `/` or has an invalid escape
+fn pointer_segments(pointer: &str) -> Option<Vec<String>> {
+    let rest = pointer.strip_prefix('/')?;
+    rest.split('/')
+        .map(|segment| {
+            let mut out = String::with_capacity(segment.len());
+            let mut chars = segment.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '~' => match chars.next()? {
+                        '0' => out.push('~'),
+                        '1' => out.push('/'),
+                        _ => return None,
+                    },
+                    c => out.push(c),
+                }
+            }
+            Some(out)
+        })
+        .collect()
+}
+
+/// Add the values in `value` to `out` with their dotted paths, including containers only if `containers` is set
+fn flatten_value<'a, 'b>(
+    value: &'b Value<'a>,
+    path: String,
+    containers: bool,
+    out: &mut Vec<(String, &'b Value<'a>)>,
+) {
+    let flatten_elements = |elements: &'b [Element<'a>], out: &mut Vec<(String, &'b Value<'a>)>| {
+        for element in elements {
+            flatten_value(
+                &element.value,
+                format!("{}.{}", path, element.name),
+                containers,
+                out,
+            );
+        }
+    };
+    let flatten_values = |values: &'b [Rc<Value<'a>>], out: &mut Vec<(String, &'b Value<'a>)>| {
+        for (index, value) in values.iter().enumerate() {
+            flatten_value(value, format!("{}.{}", path, index), containers, out);
+        }
+    };
+
+    match value {
+        #[cfg(feature = "amf3")]
+        Value::AMF3(value) => return flatten_value(value, path, containers, out),
+        Value::Object(..)
+        | Value::Custom(..)
+        | Value::ECMAArray(..)
+        | Value::StrictArray(_)
+        | Value::VectorObject(..)
+        | Value::Dictionary(..) => {
+            if containers {
+                out.push((path.clone(), value));
+            }
+        }
+        _ => return out.push((path, value)),
+    }
+
+    match value {
+        Value::Object(_, elements, _) => flatten_elements(elements, out),
+        Value::Custom(custom_elements, elements, _) => {
+            flatten_elements(custom_elements, out);
+            flatten_elements(elements, out);
+        }
+        Value::ECMAArray(dense, elements, _) => {
+            flatten_values(dense, out);
+            flatten_elements(elements, out);
+        }
+        Value::StrictArray(values) | Value::VectorObject(values, _, _) => {
+            flatten_values(values, out)
+        }
+        Value::Dictionary(entries, _) => {
+            for (index, (key, value)) in entries.iter().enumerate() {
+                flatten_value(key, format!("{}.{}.key", path, index), containers, out);
+                flatten_value(value, format!("{}.{}.value", path, index), containers, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn validate_elements(
+    elements: &[Element<'_>],
+    path: &str,
+    version: AMFVersion,
+    errors: &mut Vec<ValidationError>,
+) {
+    let max_length = match version {
+        AMFVersion::AMF0 => AMF0_MAX_NAME_LENGTH,
+        #[cfg(feature = "amf3")]
+        AMFVersion::AMF3 => AMF3_MAX_NAME_LENGTH,
+    };
+
+    for element in elements {
+        let element_path = format!("{}.{}", path, element.name);
+        if element.name.len() > max_length {
+            errors.push(ValidationError::NameTooLong {
+                path: element_path.clone(),
+                length: element.name.len(),
+            });
+        }
+        validate_value(&element.value, &element_path, version, errors);
+    }
+}
+
+fn validate_values(
+    values: &[Rc<Value<'_>>],
+    path: &str,
+    version: AMFVersion,
+    errors: &mut Vec<ValidationError>,
+) {
+    for (index, value) in values.iter().enumerate() {
+        validate_value(value, &format!("{}[{}]", path, index), version, errors);
+    }
+}
+
+fn validate_value(
+    value: &Value<'_>,
+    path: &str,
+    version: AMFVersion,
+    errors: &mut Vec<ValidationError>,
+) {
+    let amf0 = version == AMFVersion::AMF0;
+    match value {
+        Value::Object(_, elements, _) => validate_elements(elements, path, version, errors),
+        Value::ECMAArray(dense, elements, _) => {
+            validate_values(dense, path, version, errors);
+            validate_elements(elements, path, version, errors);
+        }
+        Value::StrictArray(values) => validate_values(values, path, version, errors),
+        #[cfg(feature = "amf3")]
+        Value::AMF3(value) => validate_value(value, path, AMFVersion::AMF3, errors),
+        Value::Integer(_)
+        | Value::ByteArray(_)
+        | Value::VectorInt(_, _)
+        | Value::VectorUInt(_, _)
+        | Value::VectorDouble(_, _)
+        | Value::VectorObject(_, _, _)
+        | Value::Dictionary(_, _)
+        | Value::Custom(_, _, _)
+        | Value::RawExternal(_)
+        | Value::Amf3ObjectReference(_)
+            if amf0 =>
+        {
+            errors.push(ValidationError::UnsupportedValue {
+                path: path.to_string(),
+                version,
+            })
+        }
+        Value::Reference(_) if !amf0 => errors.push(ValidationError::UnsupportedValue {
+            path: path.to_string(),
+            version,
+        }),
+        Value::VectorObject(values, type_name, _) => {
+            for (index, value) in values.iter().enumerate() {
+                if !vector_accepts(type_name, value) {
+                    errors.push(ValidationError::WrongVectorElement {
+                        path: format!("{}[{}]", path, index),
+                        type_name: type_name.clone(),
+                    });
+                }
+            }
+            validate_values(values, path, version, errors);
+        }
+        Value::Dictionary(entries, _) => {
+            for (index, (key, value)) in entries.iter().enumerate() {
+                validate_value(key, &format!("{}[{}].key", path, index), version, errors);
+                validate_value(
+                    value,
+                    &format!("{}[{}].value", path, index),
+                    version,
+                    errors,
+                );
+            }
+        }
+        Value::Custom(custom_elements, elements, _) => {
+            validate_elements(custom_elements, path, version, errors);
+            validate_elements(elements, path, version, errors);
+        }
+        _ => {}
+    }
+}
+
+/// Check if a value can be stored in a vector with the given type name
+///
+/// Only vectors of primitive types and objects are checked, as the class hierarchy of other type names isn't known
+fn vector_accepts(type_name: &str, value: &Value<'_>) -> bool {
+    match type_name {
+        "String" => matches!(value, Value::String(_) | Value::Null),
+        "Boolean" => matches!(value, Value::Bool(_)),
+        "Number" | "int" | "uint" => matches!(value, Value::Number(_) | Value::Integer(_)),
+        "" | "*" | "Object" => true,
+        _ => !matches!(
+            value,
+            Value::String(_) | Value::Bool(_) | Value::Number(_) | Value::Integer(_)
+        ),
+    }
+}
+
+impl<'a> IntoIterator for Lso<'a> {
+    type Item = Element<'a>;
+    type IntoIter = vec::IntoIter<Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.body.into_iter()
     }
 }
+
+impl<'l, 'a> IntoIterator for &'l Lso<'a> {
+    type Item = &'l Element<'a>;
+    type IntoIter = core::slice::Iter<'l, Element<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.body.iter()
+    }
+}
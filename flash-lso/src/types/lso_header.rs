@@ -1,4 +1,5 @@
-use super::AMFVersion;
+use super::FormatVersion;
+use crate::prelude::*;
 
 /// The header of a lso file
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -11,17 +12,17 @@ pub struct Header {
     pub name: String,
 
     /// The version of AMF used to encode the data
-    pub format_version: AMFVersion,
+    pub format_version: FormatVersion,
 }
 
 impl Header {
     /// Create a new header with the given name and version, will have a size of 0 by default
     #[inline]
-    pub fn new(name: impl Into<String>, version: AMFVersion) -> Self {
+    pub fn new(name: impl Into<String>, version: impl Into<FormatVersion>) -> Self {
         Self {
             length: 0,
             name: name.into(),
-            format_version: version,
+            format_version: version.into(),
         }
     }
 }
@@ -1,20 +1,45 @@
 mod amf_version;
 mod attribute;
+#[cfg(feature = "amf3")]
+mod builder;
 mod class_definition;
+mod convert;
+#[cfg(feature = "chrono")]
+mod date;
+mod dictionary;
 mod element;
+mod format_version;
 mod lso;
 mod lso_header;
+mod pretty;
+mod raw_external;
 mod reference;
+mod stats;
+mod utf8_mode;
 mod value;
+mod visit;
 
 mod object_id;
 
 pub use amf_version::AMFVersion;
 pub use attribute::Attribute;
+#[cfg(feature = "amf3")]
+pub use builder::LsoBuilder;
 pub use class_definition::ClassDefinition;
+pub use dictionary::{Dictionary, DictionaryMut};
 pub use element::Element;
+pub use format_version::FormatVersion;
 pub use lso::Lso;
 pub use lso_header::Header;
 pub use object_id::ObjectId;
+pub use raw_external::RawExternal;
+#[cfg(feature = "amf3")]
+pub(crate) use raw_external::TableSizes;
 pub use reference::Reference;
-pub use value::Value;
+pub use stats::Stats;
+pub use utf8_mode::{SurrogateStrings, Utf8Mode};
+pub use value::{OwnedValue, StructuralKey, Value};
+pub use visit::{Visitor, VisitorMut};
+
+#[cfg(feature = "std")]
+pub(crate) use value::{owned_elements, owned_rc, OwnedValues};
@@ -0,0 +1,193 @@
+use super::{ClassDefinition, Element, Value};
+use crate::prelude::*;
+use core::fmt::Write;
+
+/// Byte arrays longer than this are shown by their length rather than their contents
+const MAX_SHOWN_BYTES: usize = 16;
+
+/// Renders a value tree over multiple lines, see `Value::pretty`
+pub(crate) struct Printer<'v, 'a> {
+    out: String,
+
+    /// The number of spaces each level of nesting is indented by
+    indent: usize,
+
+    /// The current level of nesting
+    depth: usize,
+
+    /// The values being printed, outermost first, so a value containing itself is printed as `<ref>`
+    ancestors: Vec<&'v Value<'a>>,
+}
+
+impl<'v, 'a> Printer<'v, 'a> {
+    pub(crate) fn new(indent: usize) -> Self {
+        Self {
+            out: String::new(),
+            indent,
+            depth: 0,
+            ancestors: Vec::new(),
+        }
+    }
+
+    pub(crate) fn finish(self) -> String {
+        self.out
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.out
+            .extend(core::iter::repeat_n(' ', self.indent * self.depth));
+    }
+
+    /// Write `open`, then each entry on a line of its own one level deeper, then `close` on a new line
+    ///
+    /// Nothing is written between the brackets when there are no entries
+    fn block<T>(
+        &mut self,
+        open: &str,
+        entries: impl IntoIterator<Item = T>,
+        close: &str,
+        mut entry: impl FnMut(&mut Self, T),
+    ) {
+        self.out.push_str(open);
+        let mut any = false;
+        self.depth += 1;
+        for item in entries {
+            self.newline();
+            entry(self, item);
+            self.out.push(',');
+            any = true;
+        }
+        self.depth -= 1;
+        if any {
+            self.newline();
+        }
+        self.out.push_str(close);
+    }
+
+    fn element(&mut self, element: &'v Element<'a>) {
+        self.out.push_str(element.name());
+        self.out.push_str(": ");
+        self.value(&element.value);
+    }
+
+    fn elements(&mut self, open: &str, elements: impl IntoIterator<Item = &'v Element<'a>>) {
+        self.block(open, elements, "}", Self::element);
+    }
+
+    fn rc_values(&mut self, open: &str, values: &'v [Rc<Value<'a>>]) {
+        self.block(open, values, "]", |p, v| p.value(v));
+    }
+
+    fn class_prefix(class: &Option<ClassDefinition>) -> String {
+        match class {
+            Some(class) if !class.name.is_empty() && class.name != "Object" => {
+                format!("{} {{", class.name)
+            }
+            _ => "{".to_string(),
+        }
+    }
+
+    fn list<T: core::fmt::Display>(&mut self, name: &str, values: &[T]) {
+        self.out.push_str(name);
+        self.out.push('[');
+        for (i, v) in values.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            let _ = write!(self.out, "{}", v);
+        }
+        self.out.push(']');
+    }
+
+    pub(crate) fn value(&mut self, value: &'v Value<'a>) {
+        if self.ancestors.iter().any(|a| core::ptr::eq(*a, value)) {
+            self.out.push_str("<ref>");
+            return;
+        }
+        self.ancestors.push(value);
+
+        match value {
+            Value::Number(n) => {
+                let _ = write!(self.out, "{}", n);
+            }
+            Value::Integer(i) => {
+                let _ = write!(self.out, "{}", i);
+            }
+            Value::Bool(b) => {
+                let _ = write!(self.out, "{}", b);
+            }
+            Value::String(s) => {
+                let _ = write!(self.out, "{:?}", s);
+            }
+            Value::XML(s, _) => {
+                let _ = write!(self.out, "XML({:?})", s);
+            }
+            Value::Date(time, _) => {
+                let _ = write!(self.out, "Date({})", time);
+            }
+            Value::Null => self.out.push_str("null"),
+            Value::Undefined => self.out.push_str("undefined"),
+            Value::Unsupported => self.out.push_str("unsupported"),
+            Value::ByteArray(bytes) if bytes.len() > MAX_SHOWN_BYTES => {
+                let _ = write!(self.out, "ByteArray({} bytes)", bytes.len());
+            }
+            Value::ByteArray(bytes) => {
+                let _ = write!(self.out, "ByteArray({:?})", bytes);
+            }
+            Value::VectorInt(values, _) => self.list("Vector<Int>", values),
+            Value::VectorUInt(values, _) => self.list("Vector<UInt>", values),
+            Value::VectorDouble(values, _) => self.list("Vector<Double>", values),
+            Value::VectorObject(values, name, _) => {
+                self.rc_values(&format!("Vector<{}> [", name), values)
+            }
+            Value::StrictArray(values) => self.rc_values("[", values),
+            Value::ECMAArray(dense, elements, _) => {
+                self.out.push('[');
+                self.depth += 1;
+                for v in dense {
+                    self.newline();
+                    self.value(v);
+                    self.out.push(',');
+                }
+                for element in elements {
+                    self.newline();
+                    self.element(element);
+                    self.out.push(',');
+                }
+                self.depth -= 1;
+                if !dense.is_empty() || !elements.is_empty() {
+                    self.newline();
+                }
+                self.out.push(']');
+            }
+            Value::Object(_, elements, class) => {
+                self.elements(&Self::class_prefix(class), elements)
+            }
+            Value::Custom(custom_elements, elements, class) => self.elements(
+                &Self::class_prefix(class),
+                custom_elements.iter().chain(elements),
+            ),
+            Value::RawExternal(raw) => {
+                let _ = write!(
+                    self.out,
+                    "{}({} bytes)",
+                    raw.class_def.name,
+                    raw.bytes.len()
+                );
+            }
+            Value::Dictionary(entries, _) => {
+                self.block("Dictionary {", entries, "}", |p, (k, v)| {
+                    p.value(k);
+                    p.out.push_str(" => ");
+                    p.value(v);
+                })
+            }
+            #[cfg(feature = "amf3")]
+            Value::AMF3(inner) => self.value(inner),
+            Value::Reference(_) | Value::Amf3ObjectReference(_) => self.out.push_str("<ref>"),
+        }
+
+        self.ancestors.pop();
+    }
+}
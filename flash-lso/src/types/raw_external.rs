@@ -0,0 +1,53 @@
+use super::ClassDefinition;
+use crate::prelude::*;
+
+/// An externalizable object kept as the bytes that follow its class definition, see `Value::RawExternal`
+///
+/// The bytes can refer to strings, class definitions and objects that came before them by their index in the reference
+/// tables, so they are only valid in the position they were read from. When encoding, the reference tables must be the
+/// same size as they were when the bytes were read, and the entries that the bytes added to them are added again so
+/// that later references line up
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct RawExternal {
+    /// The class of the object
+    pub class_def: ClassDefinition,
+
+    /// The body of the object, written unchanged when encoding
+    pub bytes: Vec<u8>,
+
+    /// The sizes of the string, trait and object reference tables before the bytes, if they were read from a file
+    pub(crate) table_sizes: Option<TableSizes>,
+
+    /// The strings that the bytes add to the string reference table
+    pub(crate) strings: Vec<Vec<u8>>,
+
+    /// The class definitions that the bytes add to the trait reference table
+    pub(crate) traits: Vec<ClassDefinition>,
+
+    /// The number of entries that the bytes add to the object reference table
+    pub(crate) objects: usize,
+}
+
+/// The sizes of the AMF3 reference tables at some point in the input
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub(crate) struct TableSizes {
+    pub(crate) strings: usize,
+    pub(crate) traits: usize,
+    pub(crate) objects: usize,
+}
+
+impl RawExternal {
+    /// Create an object of the given class with a body that doesn't refer to or add any reference table entries
+    pub fn new(class_def: ClassDefinition, bytes: Vec<u8>) -> Self {
+        Self {
+            class_def,
+            bytes,
+            table_sizes: None,
+            strings: Vec::new(),
+            traits: Vec::new(),
+            objects: 0,
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use super::{Element, Value};
+use crate::prelude::*;
+
+/// Counts of the values in an lso, see `Lso::stats`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of values of each type, by `Value::type_name`
+    pub types: HashMap<&'static str, usize>,
+
+    /// The total number of values
+    pub nodes: usize,
+
+    /// The deepest nesting of values, the elements of the body have a depth of 1
+    pub max_depth: usize,
+}
+
+impl Stats {
+    pub(crate) fn add_elements(&mut self, elements: &[Element<'_>], depth: usize) {
+        for element in elements {
+            self.add_value(&element.value, depth);
+        }
+    }
+
+    fn add_values(&mut self, values: &[Rc<Value<'_>>], depth: usize) {
+        for value in values {
+            self.add_value(value, depth);
+        }
+    }
+
+    fn add_value(&mut self, value: &Value<'_>, depth: usize) {
+        *self.types.entry(value.type_name()).or_default() += 1;
+        self.nodes += 1;
+        self.max_depth = self.max_depth.max(depth);
+
+        match value {
+            Value::Object(_, elements, _) => self.add_elements(elements, depth + 1),
+            Value::Custom(custom_elements, elements, _) => {
+                self.add_elements(custom_elements, depth + 1);
+                self.add_elements(elements, depth + 1);
+            }
+            Value::ECMAArray(dense, elements, _) => {
+                self.add_values(dense, depth + 1);
+                self.add_elements(elements, depth + 1);
+            }
+            Value::StrictArray(values) | Value::VectorObject(values, _, _) => {
+                self.add_values(values, depth + 1)
+            }
+            Value::Dictionary(entries, _) => {
+                for (key, value) in entries {
+                    self.add_value(key, depth + 1);
+                    self.add_value(value, depth + 1);
+                }
+            }
+            #[cfg(feature = "amf3")]
+            Value::AMF3(value) => self.add_value(value, depth + 1),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use core::cell::RefCell;
+
+/// How strings are converted between bytes and `String`s
+///
+/// Flash Player encodes characters outside of the basic multilingual plane as a pair of UTF-16 surrogates,
+/// each one written as its own three byte sequence (sometimes called CESU-8 or modified UTF-8), which is not valid UTF-8
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Default)]
+pub enum Utf8Mode {
+    /// Strings must be valid UTF-8, anything else is a decoding error
+    #[default]
+    Strict,
+
+    /// Invalid UTF-8 sequences are replaced with U+FFFD when decoding, strings are written as UTF-8
+    Lossy,
+
+    /// Surrogate pairs are decoded into the characters they represent, as well as characters written as standard UTF-8
+    ///
+    /// Characters outside of the basic multilingual plane are written with surrogate pairs, as Flash Player does, unless
+    /// the `SurrogateStrings` of the encoder record that the string was read as standard UTF-8. `Reader` records the
+    /// form of each string it reads in `Lso::surrogate_strings` and `Writer` writes them back the same way in any mode,
+    /// so a file read in this mode re-encodes to identical bytes
+    ///
+    /// Unpaired surrogates can't be represented in a `String` and are replaced with U+FFFD
+    Flash,
+}
+
+/// The form that strings with characters outside of the basic multilingual plane were read in with `Utf8Mode::Flash`
+///
+/// Each time such a string is read, whether it held surrogate pairs is recorded under its content. Each time it is
+/// written the next form recorded for it is used, so a string read in both forms is written back in both forms in the
+/// same order, and one written more times than it was read keeps the last form. Strings that weren't read are written
+/// as the `Utf8Mode` of the encoder writes them
+///
+/// Clones share the same record, so a decoder and any decoders it creates for embedded data all add to one record
+#[derive(Debug, Clone, Default)]
+pub struct SurrogateStrings(Rc<RefCell<StringForms>>);
+
+#[derive(Debug, Default)]
+struct StringForms {
+    /// Whether each time a string was read it held surrogate pairs, by its content
+    read: BTreeMap<String, Vec<bool>>,
+
+    /// The number of times each string has been written since the last `rewind`
+    written: BTreeMap<String, usize>,
+}
+
+impl SurrogateStrings {
+    /// Check if the given string was read with surrogate pairs at least once
+    pub fn contains(&self, s: &str) -> bool {
+        self.0
+            .borrow()
+            .read
+            .get(s)
+            .is_some_and(|forms| forms.contains(&true))
+    }
+
+    /// Record that the given string was read, with surrogate pairs if `pairs` is set
+    pub fn insert(&self, s: impl Into<String>, pairs: bool) {
+        self.0
+            .borrow_mut()
+            .read
+            .entry(s.into())
+            .or_default()
+            .push(pairs);
+    }
+
+    /// Start writing from the first form recorded for each string again, such as before writing a file a second time
+    pub fn rewind(&self) {
+        self.0.borrow_mut().written.clear();
+    }
+
+    /// The form to write the given string in, `None` if it wasn't read
+    fn next_form(&self, s: &str) -> Option<bool> {
+        let mut forms = self.0.borrow_mut();
+        let StringForms { read, written } = &mut *forms;
+        let read = read.get(s)?;
+        let count = match written.get_mut(s) {
+            Some(count) => count,
+            None => written.entry(s.to_string()).or_default(),
+        };
+        let form = read[(*count).min(read.len() - 1)];
+        *count += 1;
+        Some(form)
+    }
+
+    /// The number of different strings recorded
+    pub fn len(&self) -> usize {
+        self.0.borrow().read.len()
+    }
+
+    /// Check if no strings were recorded
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().read.is_empty()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SurrogateStrings {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&self.0.borrow().read, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SurrogateStrings {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let read = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self(Rc::new(RefCell::new(StringForms {
+            read,
+            written: BTreeMap::new(),
+        }))))
+    }
+}
+
+/// Check if a string has characters outside of the basic multilingual plane, which take four bytes in UTF-8
+fn has_supplementary(s: &str) -> bool {
+    s.bytes().any(|b| b >= 0xF0)
+}
+
+const REPLACEMENT_CHARACTER: char = '\u{FFFD}';
+
+/// Read a three byte encoded surrogate from the start of `bytes`, if there is one
+fn read_surrogate(bytes: &[u8]) -> Option<u16> {
+    match bytes {
+        [0xED, b1 @ 0xA0..=0xBF, b2 @ 0x80..=0xBF, ..] => {
+            Some(0xD000 | (u16::from(b1 & 0x3F) << 6) | u16::from(b2 & 0x3F))
+        }
+        _ => None,
+    }
+}
+
+/// Decode a string that isn't valid UTF-8, returning it with whether it held any surrogate pairs
+fn decode_flash(bytes: &[u8]) -> Option<(String, bool)> {
+    let mut out = String::with_capacity(bytes.len());
+    let mut rest = bytes;
+    let mut pairs = false;
+
+    loop {
+        match core::str::from_utf8(rest) {
+            Ok(s) => {
+                out.push_str(s);
+                return Some((out, pairs));
+            }
+            Err(e) => {
+                let (valid, invalid) = rest.split_at(e.valid_up_to());
+                // This range has already been validated, so this will always succeed
+                out.push_str(core::str::from_utf8(valid).ok()?);
+
+                let high = read_surrogate(invalid)?;
+                let low = read_surrogate(&invalid[3..]).filter(|low| *low >= 0xDC00);
+
+                match low {
+                    Some(low) if high < 0xDC00 => {
+                        let c = 0x10000
+                            + ((u32::from(high) - 0xD800) << 10)
+                            + (u32::from(low) - 0xDC00);
+                        out.push(char::from_u32(c)?);
+                        rest = &invalid[6..];
+                        pairs = true;
+                    }
+                    _ => {
+                        out.push(REPLACEMENT_CHARACTER);
+                        rest = &invalid[3..];
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn encode_flash(s: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(s.len() + 2);
+    for c in s.chars() {
+        if (c as u32) < 0x10000 {
+            let mut buf = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        } else {
+            let mut buf = [0; 2];
+            for unit in c.encode_utf16(&mut buf) {
+                let unit = *unit;
+                out.push(0xE0 | (unit >> 12) as u8);
+                out.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                out.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    out
+}
+
+impl Utf8Mode {
+    /// Decode the given bytes into a string, returns `None` if the bytes are not valid in this mode
+    pub fn decode(self, bytes: &[u8]) -> Option<Cow<'_, str>> {
+        self.decode_with(bytes, &SurrogateStrings::default())
+    }
+
+    /// See `decode`, in `Utf8Mode::Flash` the form of strings with characters outside of the basic multilingual plane
+    /// is recorded in `surrogates`
+    pub fn decode_with<'b>(
+        self,
+        bytes: &'b [u8],
+        surrogates: &SurrogateStrings,
+    ) -> Option<Cow<'b, str>> {
+        match self {
+            Utf8Mode::Strict => core::str::from_utf8(bytes).ok().map(Cow::Borrowed),
+            Utf8Mode::Lossy => Some(String::from_utf8_lossy(bytes)),
+            Utf8Mode::Flash => match core::str::from_utf8(bytes) {
+                Ok(s) => {
+                    if has_supplementary(s) {
+                        surrogates.insert(s, false);
+                    }
+                    Some(Cow::Borrowed(s))
+                }
+                Err(_) => {
+                    let (s, pairs) = decode_flash(bytes)?;
+                    if has_supplementary(&s) {
+                        surrogates.insert(s.clone(), pairs);
+                    }
+                    Some(Cow::Owned(s))
+                }
+            },
+        }
+    }
+
+    /// Encode the given string into bytes, in `Utf8Mode::Flash` characters outside of the basic multilingual plane are
+    /// written with surrogate pairs
+    pub fn encode(self, s: &str) -> Cow<'_, [u8]> {
+        self.encode_with(s, &SurrogateStrings::default())
+    }
+
+    /// See `encode`, strings recorded in `surrogates` are written in the next form recorded for them whatever the mode,
+    /// as only `Utf8Mode::Flash` records strings
+    pub fn encode_with<'s>(self, s: &'s str, surrogates: &SurrogateStrings) -> Cow<'s, [u8]> {
+        if !has_supplementary(s) {
+            return Cow::Borrowed(s.as_bytes());
+        }
+        match surrogates.next_form(s) {
+            Some(true) => Cow::Owned(encode_flash(s)),
+            None if self == Utf8Mode::Flash => Cow::Owned(encode_flash(s)),
+            _ => Cow::Borrowed(s.as_bytes()),
+        }
+    }
+}
@@ -1,22 +1,38 @@
-use super::{ClassDefinition, Element, ObjectId, Reference};
-use std::rc::Rc;
+use super::pretty::Printer;
+use super::{ClassDefinition, Element, ObjectId, RawExternal, Reference};
+use crate::errors::PathSegment;
+use crate::prelude::*;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::mem::discriminant;
+
+/// A `Value` that doesn't borrow from the input it was read from, see `Value::into_owned`
+pub type OwnedValue = Value<'static>;
 
 //TODO: should amf3 assoc arrays be their own type with a dense and assoc section
 /// A single or compound value
+///
+/// Strings and byte arrays borrow from the input they were read from where possible, use `into_owned` to detach a
+/// value from its input
+///
+/// Values are equal when their contents are, values held by `Rc` are compared by the value they point to rather than
+/// by address. Numbers are compared as floats, so a NaN isn't equal to anything, see `structural_eq` for a comparison
+/// where it is
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
-pub enum Value {
+pub enum Value<'a> {
     /// Represent the type number (amf0) and double (amf3)
-    Number(f64),
+    Number(#[cfg_attr(feature = "serde", serde(with = "number"))] f64),
 
     /// Represents the type boolean (amf0) and both the true/false type (amf3)
     Bool(bool),
 
     /// Represent both the string (amf0/3) and long string type (amf0)
-    String(String),
+    String(Cow<'a, str>),
 
     /// Represents the object type in both amf0 and amf3, class definition are only available with amf3
-    Object(ObjectId, Vec<Element>, Option<ClassDefinition>),
+    Object(ObjectId, Vec<Element<'a>>, Option<ClassDefinition>),
 
     /// Represent the null type
     Null,
@@ -25,31 +41,40 @@ pub enum Value {
     Undefined,
 
     /// Represent ECMA-Arrays (amf0) and associative arrays (amf3, even if they contain a dense part)
-    /// Final value represents the length of the array in amf0, this can differ from the actual number of elements
-    ECMAArray(Vec<Rc<Value>>, Vec<Element>, u32),
+    /// Final value represents the length of the array, this can differ from the actual number of elements in amf0. In
+    /// amf3 it is the length of the dense part. The order of the associative elements is kept when read and written
+    ECMAArray(Vec<Rc<Value<'a>>>, Vec<Element<'a>>, u32),
 
     /// Represent a strict array (amf0) or a dense array (amf3)
-    StrictArray(Vec<Rc<Value>>),
+    StrictArray(Vec<Rc<Value<'a>>>),
 
-    /// Represent a timezone in the format (seconds since epoch, timezone or UTC if missing (amf3) )
-    Date(f64, Option<u16>),
+    /// Represent a date as (milliseconds since the epoch in UTC, timezone offset in minutes or `None` in amf3)
+    ///
+    /// The timezone is only written in amf0 and is usually 0, see `Value::to_datetime` with the `chrono` feature
+    Date(
+        #[cfg_attr(feature = "serde", serde(with = "number"))] f64,
+        Option<u16>,
+    ),
 
     /// Represent the unsupported type
     Unsupported,
 
     /// Represent the XML type, (value, is_string)
-    XML(String, bool),
+    ///
+    /// In AMF3 `is_string` picks the marker: `true` for an E4X `XML` value and `false` for a legacy `XMLDocument`.
+    /// AMF0 only has `XMLDocument`, which reads with `is_string` set
+    XML(Cow<'a, str>, bool),
 
     #[cfg(feature = "amf3")]
     /// Represent an amf3 element embedded in an AMF0 file
-    AMF3(Rc<Value>),
+    AMF3(Rc<Value<'a>>),
 
     // AMF3
     /// Represent the integer type (u29) (amf3)
     Integer(i32),
 
     /// Represent the bytearray type (amf3)
-    ByteArray(Vec<u8>),
+    ByteArray(#[cfg_attr(feature = "serde", serde(with = "byte_array"))] Cow<'a, [u8]>),
 
     /// Represent the int vector type (amf3)
     /// Format is (values, is_fixed_length)
@@ -61,19 +86,30 @@ pub enum Value {
 
     /// Represent the double vector type (amf3)
     /// Format is (values, is_fixed_length)
-    VectorDouble(Vec<f64>, bool),
+    VectorDouble(
+        #[cfg_attr(feature = "serde", serde(with = "number::vec"))] Vec<f64>,
+        bool,
+    ),
 
     /// Represent the object vector type (amf3)
     /// Format is (values, is_fixed_length)
-    VectorObject(Vec<Rc<Value>>, String, bool),
+    VectorObject(Vec<Rc<Value<'a>>>, String, bool),
 
     /// Represent the dictionary type (amf3)
     /// Format is ((key, value), has_weak_keys)
-    Dictionary(Vec<(Rc<Value>, Rc<Value>)>, bool),
+    ///
+    /// Entries are kept in the order they were read, which is the order Flash iterates them in, and are written in order
+    Dictionary(Vec<(Rc<Value<'a>>, Rc<Value<'a>>)>, bool),
 
     /// Represent a external object, such as from flex
     /// (custom_elements, regular elements, class def)
-    Custom(Vec<Element>, Vec<Element>, Option<ClassDefinition>),
+    Custom(Vec<Element<'a>>, Vec<Element<'a>>, Option<ClassDefinition>),
+
+    /// Represent an external object kept as its original bytes (amf3)
+    ///
+    /// This is read for classes without a decoder with `UnknownExternal::Remaining`, and for every class when
+    /// `DecoderOptions::decode_flex` isn't set
+    RawExternal(Box<RawExternal>),
 
     /// Represent an existing value, stored by reference, the value here should be considered opaque
     Reference(Reference),
@@ -88,8 +124,1061 @@ pub enum Value {
     Amf3ObjectReference(ObjectId),
 }
 
-impl FromIterator<Value> for Vec<Rc<Value>> {
-    fn from_iter<T: IntoIterator<Item = Value>>(iter: T) -> Self {
+impl<'a> Value<'a> {
+    /// Get the name of the type of this value, such as `Number` or `Vector<Int>`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "Number",
+            Value::Bool(_) => "Boolean",
+            Value::String(_) => "String",
+            Value::Object(_, _, _) => "Object",
+            Value::Null => "Null",
+            Value::Undefined => "Undefined",
+            Value::ECMAArray(_, _, _) => "ECMAArray",
+            Value::StrictArray(_) => "StrictArray",
+            Value::Date(_, _) => "Date",
+            Value::Unsupported => "Unsupported",
+            Value::XML(_, _) => "XML",
+            #[cfg(feature = "amf3")]
+            Value::AMF3(_) => "AMF3",
+            Value::Integer(_) => "Integer",
+            Value::ByteArray(_) => "ByteArray",
+            Value::VectorInt(_, _) => "Vector<Int>",
+            Value::VectorUInt(_, _) => "Vector<UInt>",
+            Value::VectorDouble(_, _) => "Vector<Double>",
+            Value::VectorObject(_, _, _) => "Vector<Object>",
+            Value::Dictionary(_, _) => "Dictionary",
+            Value::Custom(_, _, _) => "Custom",
+            Value::RawExternal(_) => "RawExternal",
+            Value::Reference(_) => "Reference",
+            Value::Amf3ObjectReference(_) => "ObjectReference",
+        }
+    }
+
+    /// Render this value over multiple lines, with nested objects and arrays indented by `indent` spaces per level
+    ///
+    /// Typed objects are shown with their class name and byte arrays of more than 16 bytes by their length. References
+    /// to other values, and values that contain themselves, are shown as `<ref>`. `Display` uses an indent of 2
+    /// ```
+    /// use flash_lso::types::{ClassDefinition, Element, ObjectId, Value};
+    ///
+    /// let value = Value::Object(
+    ///     ObjectId::INVALID,
+    ///     vec![Element::new("hp", Value::Integer(10))],
+    ///     Some(ClassDefinition::default_with_name("Player".to_string())),
+    /// );
+    /// assert_eq!(value.pretty(4), "Player {\n    hp: 10,\n}");
+    /// ```
+    pub fn pretty(&self, indent: usize) -> String {
+        let mut printer = Printer::new(indent);
+        printer.value(self);
+        printer.finish()
+    }
+
+    /// Get the string, if this is a `String`
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Get the number, if this is a `Number` or an `Integer`
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Integer(i) => Some(f64::from(*i)),
+            _ => None,
+        }
+    }
+
+    /// Get the number, if this is an `Integer` or a `Number` with a whole value that fits in an `i32`
+    ///
+    /// AMF0 has no integer type, so whole numbers in it are always read as `Number`s
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            // Converting back checks for a fractional part without `f64::fract`, which needs `std`
+            Value::Number(n)
+                if (f64::from(i32::MIN)..=f64::from(i32::MAX)).contains(n)
+                    && f64::from(*n as i32) == *n =>
+            {
+                Some(*n as i32)
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the boolean, if this is a `Bool`
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Get the bytes, if this is a `ByteArray`
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::ByteArray(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Get the original bytes of an externalizable object that was kept raw, as with `UnknownExternal::Remaining`
+    ///
+    /// Classes without a registered decoder can be parsed from these, they are written back unchanged when encoding
+    pub fn raw_external_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::RawExternal(raw) => Some(&raw.bytes),
+            _ => None,
+        }
+    }
+
+    /// Get the properties and class definition, if this is an `Object`
+    pub fn as_object(&self) -> Option<(&[Element<'a>], Option<&ClassDefinition>)> {
+        match self {
+            Value::Object(_, elements, def) => Some((elements, def.as_ref())),
+            _ => None,
+        }
+    }
+
+    /// Check if this is `Null` or `Undefined`
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null | Value::Undefined)
+    }
+
+    /// Check if two values have the same structure
+    ///
+    /// This is as with `==` except that any NaN is equal to any other NaN, and the ids of objects aren't compared so the
+    /// same object read from two different files is equal. This is useful when comparing a decoded value against an
+    /// expected one in tests, which would otherwise never be equal if they contain a NaN. `structural_hash` agrees with
+    /// this
+    pub fn structural_eq(&self, other: &Value<'_>) -> bool {
+        fn values_eq(a: &[Rc<Value<'_>>], b: &[Rc<Value<'_>>]) -> bool {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.structural_eq(b))
+        }
+
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => f64_eq(*a, *b),
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Object(_, a, a_def), Value::Object(_, b, b_def)) => {
+                a_def == b_def && elements_structural_eq(a, b)
+            }
+            (Value::Null, Value::Null)
+            | (Value::Undefined, Value::Undefined)
+            | (Value::Unsupported, Value::Unsupported) => true,
+            (Value::ECMAArray(a_dense, a, a_len), Value::ECMAArray(b_dense, b, b_len)) => {
+                a_len == b_len && values_eq(a_dense, b_dense) && elements_structural_eq(a, b)
+            }
+            (Value::StrictArray(a), Value::StrictArray(b)) => values_eq(a, b),
+            (Value::Date(a, a_tz), Value::Date(b, b_tz)) => f64_eq(*a, *b) && a_tz == b_tz,
+            (Value::XML(a, a_string), Value::XML(b, b_string)) => a == b && a_string == b_string,
+            #[cfg(feature = "amf3")]
+            (Value::AMF3(a), Value::AMF3(b)) => a.structural_eq(b),
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::ByteArray(a), Value::ByteArray(b)) => a == b,
+            (Value::VectorInt(a, a_fixed), Value::VectorInt(b, b_fixed)) => {
+                a == b && a_fixed == b_fixed
+            }
+            (Value::VectorUInt(a, a_fixed), Value::VectorUInt(b, b_fixed)) => {
+                a == b && a_fixed == b_fixed
+            }
+            (Value::VectorDouble(a, a_fixed), Value::VectorDouble(b, b_fixed)) => {
+                a_fixed == b_fixed
+                    && a.len() == b.len()
+                    && a.iter().zip(b).all(|(a, b)| f64_eq(*a, *b))
+            }
+            (Value::VectorObject(a, a_name, a_fixed), Value::VectorObject(b, b_name, b_fixed)) => {
+                a_name == b_name && a_fixed == b_fixed && values_eq(a, b)
+            }
+            (Value::Dictionary(a, a_weak), Value::Dictionary(b, b_weak)) => {
+                a_weak == b_weak
+                    && a.len() == b.len()
+                    && a.iter()
+                        .zip(b)
+                        .all(|((ak, av), (bk, bv))| ak.structural_eq(bk) && av.structural_eq(bv))
+            }
+            (Value::Custom(a_custom, a, a_def), Value::Custom(b_custom, b, b_def)) => {
+                a_def == b_def
+                    && elements_structural_eq(a_custom, b_custom)
+                    && elements_structural_eq(a, b)
+            }
+            (Value::RawExternal(a), Value::RawExternal(b)) => a == b,
+            (Value::Reference(a), Value::Reference(b)) => a == b,
+            (Value::Amf3ObjectReference(a), Value::Amf3ObjectReference(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Hash the structure of a value, so that values that are equal with `structural_eq` have the same hash
+    ///
+    /// Strings, byte arrays and vectors are hashed by their contents, which can be slow for large ones
+    pub fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        fn hash_values<H: Hasher>(values: &[Rc<Value<'_>>], state: &mut H) {
+            values.len().hash(state);
+            values.iter().for_each(|v| v.structural_hash(state));
+        }
+        fn hash_elements<H: Hasher>(elements: &[Element<'_>], state: &mut H) {
+            elements.len().hash(state);
+            for e in elements {
+                e.name.hash(state);
+                e.value.structural_hash(state);
+            }
+        }
+
+        discriminant(self).hash(state);
+        match self {
+            Value::Number(n) => f64_bits(*n).hash(state),
+            Value::Bool(b) => b.hash(state),
+            Value::String(s) => s.hash(state),
+            Value::Object(_, elements, def) => {
+                hash_elements(elements, state);
+                def.hash(state);
+            }
+            Value::ECMAArray(dense, elements, length) => {
+                hash_values(dense, state);
+                hash_elements(elements, state);
+                length.hash(state);
+            }
+            Value::StrictArray(values) => hash_values(values, state),
+            Value::Date(time, tz) => {
+                f64_bits(*time).hash(state);
+                tz.hash(state);
+            }
+            Value::XML(content, string) => {
+                content.hash(state);
+                string.hash(state);
+            }
+            #[cfg(feature = "amf3")]
+            Value::AMF3(value) => value.structural_hash(state),
+            Value::Integer(i) => i.hash(state),
+            Value::ByteArray(bytes) => bytes.hash(state),
+            Value::VectorInt(values, fixed) => {
+                values.hash(state);
+                fixed.hash(state);
+            }
+            Value::VectorUInt(values, fixed) => {
+                values.hash(state);
+                fixed.hash(state);
+            }
+            Value::VectorDouble(values, fixed) => {
+                values.len().hash(state);
+                values.iter().for_each(|n| f64_bits(*n).hash(state));
+                fixed.hash(state);
+            }
+            Value::VectorObject(values, type_name, fixed) => {
+                hash_values(values, state);
+                type_name.hash(state);
+                fixed.hash(state);
+            }
+            Value::Dictionary(entries, weak_keys) => {
+                entries.len().hash(state);
+                for (k, v) in entries {
+                    k.structural_hash(state);
+                    v.structural_hash(state);
+                }
+                weak_keys.hash(state);
+            }
+            Value::Custom(custom_elements, elements, def) => {
+                hash_elements(custom_elements, state);
+                hash_elements(elements, state);
+                def.hash(state);
+            }
+            Value::RawExternal(raw) => raw.hash(state),
+            Value::Reference(reference) => reference.hash(state),
+            Value::Amf3ObjectReference(id) => id.hash(state),
+            Value::Null | Value::Undefined | Value::Unsupported => {}
+        }
+    }
+
+    /// Get the value of the property with the given name, if this is an `Object` that has one
+    ///
+    /// If the object has more than one property with this name, the first one is returned
+    pub fn get_property(&self, name: &str) -> Option<&Value<'a>> {
+        match self {
+            Value::Object(_, elements, _) => {
+                elements.iter().find(|e| e.name == name).map(|e| e.value())
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the members of an `Object` that are static properties of its class, sealed members declared by the class
+    ///
+    /// When read, these come first in the order of `ClassDefinition::static_properties`, an object without a class
+    /// definition has none
+    pub fn static_members(&self) -> impl Iterator<Item = &Element<'a>> + '_ {
+        self.object_members(true)
+    }
+
+    /// Get the members of an `Object` that were added dynamically, rather than being static properties of its class
+    ///
+    /// When read, these follow the static members in the order they were written. Only objects whose class has
+    /// `Attribute::Dynamic`, or that have no class definition, can be written with dynamic members
+    pub fn dynamic_members(&self) -> impl Iterator<Item = &Element<'a>> + '_ {
+        self.object_members(false)
+    }
+
+    fn object_members(&self, is_static: bool) -> impl Iterator<Item = &Element<'a>> + '_ {
+        let (elements, def) = match self {
+            Value::Object(_, elements, def) => (elements.as_slice(), def.as_ref()),
+            _ => (&[][..], None),
+        };
+        elements.iter().filter(move |e| {
+            def.is_some_and(|def| def.static_properties.iter().any(|p| *p == e.name)) == is_static
+        })
+    }
+
+    /// Set the value of the property with the given name, if this is an `Object`
+    ///
+    /// If the object has more than one property with this name, only the first one is replaced. If it has none then
+    /// the property is added to the end. The previous value is returned, values other than objects are left unchanged
+    pub fn set_property(
+        &mut self,
+        name: impl Into<Cow<'a, str>>,
+        value: Rc<Value<'a>>,
+    ) -> Option<Rc<Value<'a>>> {
+        match self {
+            Value::Object(_, elements, _) => {
+                let name = name.into();
+                if let Some(element) = elements.iter_mut().find(|e| e.name == name) {
+                    Some(core::mem::replace(&mut element.value, value))
+                } else {
+                    elements.push(Element::new(name, value));
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the depth of the deepest value in this value, a value without children has a depth of 1
+    ///
+    /// `Amf3ObjectReference`s are not followed, so this is finite even for cyclic graphs
+    pub fn max_depth(&self) -> usize {
+        let mut depth = 0;
+        self.for_each_child(&mut |child| depth = depth.max(child.max_depth()));
+        depth + 1
+    }
+
+    /// Check if this value contains an `Amf3ObjectReference` to an object that contains it, including itself
+    ///
+    /// Cycles can only be made through `Amf3ObjectReference`, as values hold their children by `Rc` without interior
+    /// mutability. Graphs with cycles can't be represented by formats such as JSON without handling these references
+    pub fn has_cycle(&self) -> bool {
+        self.has_cycle_within(&mut Vec::new())
+    }
+
+    fn has_cycle_within(&self, ancestors: &mut Vec<ObjectId>) -> bool {
+        match self {
+            Value::Amf3ObjectReference(id) => return ancestors.contains(id),
+            Value::Object(id, _, _) if *id != ObjectId::INVALID => ancestors.push(*id),
+            _ => {}
+        }
+
+        let mut found = false;
+        self.for_each_child(&mut |child| found = found || child.has_cycle_within(ancestors));
+
+        if matches!(self, Value::Object(id, _, _) if *id != ObjectId::INVALID) {
+            ancestors.pop();
+        }
+        found
+    }
+
+    fn for_each_child(&self, f: &mut dyn FnMut(&Value<'a>)) {
+        let mut elements = |elements: &[Element<'a>]| elements.iter().for_each(|e| f(&e.value));
+        match self {
+            Value::Object(_, children, _) => elements(children),
+            Value::Custom(custom_elements, children, _) => {
+                elements(custom_elements);
+                elements(children);
+            }
+            Value::ECMAArray(dense, children, _) => {
+                dense.iter().for_each(|v| f(v));
+                children.iter().for_each(|e| f(&e.value));
+            }
+            Value::StrictArray(values) | Value::VectorObject(values, _, _) => {
+                values.iter().for_each(|v| f(v))
+            }
+            Value::Dictionary(entries, _) => entries.iter().for_each(|(k, v)| {
+                f(k);
+                f(v);
+            }),
+            #[cfg(feature = "amf3")]
+            Value::AMF3(value) => f(value),
+            _ => {}
+        }
+    }
+
+    /// Detach this value from the input it was read from, copying any strings and byte arrays that borrow from it
+    ///
+    /// Children that are shared through the same `Rc` are still shared in the result
+    pub fn into_owned(self) -> OwnedValue {
+        self.to_owned_with(&mut HashMap::new())
+    }
+
+    fn to_owned_with(&self, seen: &mut OwnedValues<'a>) -> OwnedValue {
+        match self {
+            Value::Number(n) => Value::Number(*n),
+            Value::Bool(b) => Value::Bool(*b),
+            Value::String(s) => Value::String(owned_cow(s)),
+            Value::Object(id, elements, def) => {
+                Value::Object(*id, owned_elements(elements, seen), def.clone())
+            }
+            Value::Null => Value::Null,
+            Value::Undefined => Value::Undefined,
+            Value::ECMAArray(dense, elements, length) => Value::ECMAArray(
+                owned_values(dense, seen),
+                owned_elements(elements, seen),
+                *length,
+            ),
+            Value::StrictArray(values) => Value::StrictArray(owned_values(values, seen)),
+            Value::Date(time, time_zone) => Value::Date(*time, *time_zone),
+            Value::Unsupported => Value::Unsupported,
+            Value::XML(content, string) => Value::XML(owned_cow(content), *string),
+            #[cfg(feature = "amf3")]
+            Value::AMF3(value) => Value::AMF3(owned_rc(value, seen)),
+            Value::Integer(i) => Value::Integer(*i),
+            Value::ByteArray(bytes) => Value::ByteArray(owned_cow(bytes)),
+            Value::VectorInt(items, fixed_length) => Value::VectorInt(items.clone(), *fixed_length),
+            Value::VectorUInt(items, fixed_length) => {
+                Value::VectorUInt(items.clone(), *fixed_length)
+            }
+            Value::VectorDouble(items, fixed_length) => {
+                Value::VectorDouble(items.clone(), *fixed_length)
+            }
+            Value::VectorObject(items, type_name, fixed_length) => {
+                Value::VectorObject(owned_values(items, seen), type_name.clone(), *fixed_length)
+            }
+            Value::Dictionary(entries, weak_keys) => Value::Dictionary(
+                entries
+                    .iter()
+                    .map(|(k, v)| (owned_rc(k, seen), owned_rc(v, seen)))
+                    .collect(),
+                *weak_keys,
+            ),
+            Value::Custom(custom_elements, elements, def) => Value::Custom(
+                owned_elements(custom_elements, seen),
+                owned_elements(elements, seen),
+                def.clone(),
+            ),
+            Value::RawExternal(raw) => Value::RawExternal(raw.clone()),
+            Value::Reference(reference) => Value::Reference(*reference),
+            Value::Amf3ObjectReference(id) => Value::Amf3ObjectReference(*id),
+        }
+    }
+
+    /// Apply `f` to every `String` value in this value and all of its children, including dictionary keys
+    ///
+    /// Children are reached through `Rc::make_mut`, so any child that is shared with another `Rc` is cloned before it is
+    /// visited, even if `f` leaves it unchanged. Property names and `XML` values are not visited
+    pub fn map_strings(&mut self, mut f: impl FnMut(&mut String)) {
+        self.walk_mut(&mut |value| {
+            if let Value::String(s) = value {
+                f(s.to_mut());
+            }
+        });
+    }
+
+    /// Apply `f` to every `Number` value and `VectorDouble` entry in this value and all of its children
+    ///
+    /// This has the same clone-on-write cost as `map_strings`
+    pub fn map_numbers(&mut self, mut f: impl FnMut(&mut f64)) {
+        self.walk_mut(&mut |value| match value {
+            Value::Number(n) => f(n),
+            Value::VectorDouble(numbers, _) => numbers.iter_mut().for_each(&mut f),
+            _ => {}
+        });
+    }
+
+    fn walk_mut(&mut self, f: &mut impl FnMut(&mut Value<'a>)) {
+        f(self);
+
+        match self {
+            Value::Object(_, elements, _) => walk_elements(elements, f),
+            Value::Custom(custom_elements, elements, _) => {
+                walk_elements(custom_elements, f);
+                walk_elements(elements, f);
+            }
+            Value::ECMAArray(dense, elements, _) => {
+                walk_values(dense.iter_mut(), f);
+                walk_elements(elements, f);
+            }
+            Value::StrictArray(values) | Value::VectorObject(values, _, _) => {
+                walk_values(values.iter_mut(), f)
+            }
+            Value::Dictionary(entries, _) => {
+                walk_values(entries.iter_mut().flat_map(|(k, v)| [k, v]), f)
+            }
+            #[cfg(feature = "amf3")]
+            Value::AMF3(value) => Rc::make_mut(value).walk_mut(f),
+            _ => {}
+        }
+    }
+
+    /// Get the value at the given path from this value, such as `inventory[3].count`
+    ///
+    /// Names separated by `.` are properties of an `Object`, elements of an `ECMAArray` or string keys of a
+    /// `Dictionary`, and `[index]` is an entry of a `StrictArray`, `VectorObject` or the dense part of an `ECMAArray`, or
+    /// a numeric key of a `Dictionary`. `AMF3` wrappers are looked through. The empty path gives this value, a missing
+    /// entry or a path that can't be parsed gives `None`
+    pub fn get_path(&self, path: &str) -> Option<&Value<'a>> {
+        parse_path(path)?
+            .iter()
+            .try_fold(self, |value, segment| value.path_child(segment))
+    }
+
+    /// Get a mutable reference to the value at the given path from this value, see `get_path`
+    ///
+    /// Values on the way to it that are shared with other values are copied, so changes only appear at this path
+    pub fn get_path_mut(&mut self, path: &str) -> Option<&mut Value<'a>> {
+        parse_path(path)?
+            .iter()
+            .try_fold(self, |value, segment| value.path_child_mut(segment))
+    }
+
+    fn path_child(&self, segment: &PathSegment) -> Option<&Value<'a>> {
+        let child: &Rc<Value<'a>> = match (self, segment) {
+            #[cfg(feature = "amf3")]
+            (Value::AMF3(value), _) => return value.path_child(segment),
+            (Value::Object(_, elements, _), PathSegment::Name(name))
+            | (Value::ECMAArray(_, elements, _), PathSegment::Name(name)) => {
+                &elements.iter().find(|e| e.name == *name)?.value
+            }
+            (Value::ECMAArray(values, _, _), PathSegment::Index(index))
+            | (Value::StrictArray(values), PathSegment::Index(index))
+            | (Value::VectorObject(values, _, _), PathSegment::Index(index)) => {
+                values.get(*index)?
+            }
+            (Value::Dictionary(entries, _), segment) => {
+                &entries.iter().find(|(k, _)| is_path_key(k, segment))?.1
+            }
+            _ => return None,
+        };
+        Some(child)
+    }
+
+    fn path_child_mut(&mut self, segment: &PathSegment) -> Option<&mut Value<'a>> {
+        let child: &mut Rc<Value<'a>> = match (self, segment) {
+            #[cfg(feature = "amf3")]
+            (Value::AMF3(value), _) => return Rc::make_mut(value).path_child_mut(segment),
+            (Value::Object(_, elements, _), PathSegment::Name(name))
+            | (Value::ECMAArray(_, elements, _), PathSegment::Name(name)) => {
+                &mut elements.iter_mut().find(|e| e.name == *name)?.value
+            }
+            (Value::ECMAArray(values, _, _), PathSegment::Index(index))
+            | (Value::StrictArray(values), PathSegment::Index(index))
+            | (Value::VectorObject(values, _, _), PathSegment::Index(index)) => {
+                values.get_mut(*index)?
+            }
+            (Value::Dictionary(entries, _), segment) => {
+                &mut entries.iter_mut().find(|(k, _)| is_path_key(k, segment))?.1
+            }
+            _ => return None,
+        };
+        Some(Rc::make_mut(child))
+    }
+
+    /// Follow the unescaped segments of a JSON pointer from this value, see `Lso::pointer`
+    pub(crate) fn pointer_segments(&self, segments: &[String]) -> Option<&Value<'a>> {
+        let Some((segment, rest)) = segments.split_first() else {
+            return Some(self);
+        };
+
+        let child: &Value<'a> = match self {
+            #[cfg(feature = "amf3")]
+            Value::AMF3(value) => return value.pointer_segments(segments),
+            Value::Object(_, elements, _) => find_element(elements.iter(), segment)?,
+            Value::Custom(custom_elements, elements, _) => {
+                find_element(custom_elements.iter().chain(elements), segment)?
+            }
+            Value::ECMAArray(dense, elements, _) => match pointer_index(segment) {
+                Some(index) if index < dense.len() => &dense[index],
+                _ => find_element(elements.iter(), segment)?,
+            },
+            Value::StrictArray(values) | Value::VectorObject(values, _, _) => {
+                values.get(pointer_index(segment)?)?
+            }
+            Value::Dictionary(entries, _) => {
+                let (key, value) = entries.get(pointer_index(segment)?)?;
+                let (part, rest) = rest.split_first()?;
+                return match part.as_str() {
+                    "key" => key.pointer_segments(rest),
+                    "value" => value.pointer_segments(rest),
+                    _ => None,
+                };
+            }
+            _ => return None,
+        };
+        child.pointer_segments(rest)
+    }
+
+    /// Follow the unescaped segments of a JSON pointer from this value, see `Lso::pointer_mut`
+    pub(crate) fn pointer_segments_mut(&mut self, segments: &[String]) -> Option<&mut Value<'a>> {
+        let Some((segment, rest)) = segments.split_first() else {
+            return Some(self);
+        };
+
+        let child: &mut Rc<Value<'a>> = match self {
+            #[cfg(feature = "amf3")]
+            Value::AMF3(value) => return Rc::make_mut(value).pointer_segments_mut(segments),
+            Value::Object(_, elements, _) => find_element_mut(elements.iter_mut(), segment)?,
+            Value::Custom(custom_elements, elements, _) => {
+                find_element_mut(custom_elements.iter_mut().chain(elements), segment)?
+            }
+            Value::ECMAArray(dense, elements, _) => match pointer_index(segment) {
+                Some(index) if index < dense.len() => &mut dense[index],
+                _ => find_element_mut(elements.iter_mut(), segment)?,
+            },
+            Value::StrictArray(values) | Value::VectorObject(values, _, _) => {
+                values.get_mut(pointer_index(segment)?)?
+            }
+            Value::Dictionary(entries, _) => {
+                let (key, value) = entries.get_mut(pointer_index(segment)?)?;
+                let (part, rest) = rest.split_first()?;
+                return match part.as_str() {
+                    "key" => Rc::make_mut(key).pointer_segments_mut(rest),
+                    "value" => Rc::make_mut(value).pointer_segments_mut(rest),
+                    _ => None,
+                };
+            }
+            _ => return None,
+        };
+        Rc::make_mut(child).pointer_segments_mut(rest)
+    }
+}
+
+fn find_element<'a, 'b>(
+    mut elements: impl Iterator<Item = &'b Element<'a>>,
+    name: &str,
+) -> Option<&'b Value<'a>>
+where
+    'a: 'b,
+{
+    elements.find(|e| e.name == name).map(|e| e.value())
+}
+
+fn find_element_mut<'a, 'b>(
+    mut elements: impl Iterator<Item = &'b mut Element<'a>>,
+    name: &str,
+) -> Option<&'b mut Rc<Value<'a>>>
+where
+    'a: 'b,
+{
+    elements.find(|e| e.name == name).map(|e| &mut e.value)
+}
+
+/// Check if two numbers are equal for `Value::structural_eq`
+fn f64_eq(a: f64, b: f64) -> bool {
+    a == b || (a.is_nan() && b.is_nan())
+}
+
+/// The bits of a number for `Value::structural_hash`, with every NaN the same, as are `0.0` and `-0.0`
+fn f64_bits(n: f64) -> u64 {
+    if n.is_nan() {
+        f64::NAN.to_bits()
+    } else if n == 0.0 {
+        0
+    } else {
+        n.to_bits()
+    }
+}
+
+/// Check if two lists of elements are equal, see `Value::structural_eq`
+pub(crate) fn elements_structural_eq(a: &[Element<'_>], b: &[Element<'_>]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.structural_eq(b))
+}
+
+/// Split a path for `Value::get_path` into its segments, `None` if it isn't valid
+///
+/// Each `.` separated part is a name followed by any number of `[index]`s, only the first part may have no name
+fn parse_path(path: &str) -> Option<Vec<PathSegment>> {
+    let mut segments = Vec::new();
+    if path.is_empty() {
+        return Some(segments);
+    }
+    for (i, part) in path.split('.').enumerate() {
+        let (name, mut indices) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if !name.is_empty() {
+            segments.push(PathSegment::Name(name.to_string()));
+        } else if i > 0 || indices.is_empty() {
+            return None;
+        }
+        while !indices.is_empty() {
+            let (index, rest) = indices.strip_prefix('[')?.split_once(']')?;
+            segments.push(PathSegment::Index(pointer_index(index)?));
+            indices = rest;
+        }
+    }
+    Some(segments)
+}
+
+/// Check if a dictionary key is the one named by a path segment, a string for a name or a number for an index
+fn is_path_key(key: &Value<'_>, segment: &PathSegment) -> bool {
+    match (key, segment) {
+        (Value::String(key), PathSegment::Name(name)) => key == name,
+        (Value::Integer(key), PathSegment::Index(index)) => usize::try_from(*key) == Ok(*index),
+        (Value::Number(key), PathSegment::Index(index)) => *key == *index as f64,
+        _ => false,
+    }
+}
+
+/// Parse an array index from a JSON pointer, which is either `0` or digits without a leading zero
+fn pointer_index(segment: &str) -> Option<usize> {
+    let digits = !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit());
+    if !digits || (segment.len() > 1 && segment.starts_with('0')) {
+        return None;
+    }
+    segment.parse().ok()
+}
+
+/// Serde support for byte arrays, these are written as bytes so that formats with a binary type can use it
+///
+/// Formats without one, such as JSON, write bytes as an array of numbers, which is also accepted when reading
+#[cfg(feature = "serde")]
+mod byte_array {
+    use crate::prelude::*;
+    use core::fmt;
+    use serde::de::{SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub(super) fn deserialize<'de, 'a, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Cow<'a, [u8]>, D::Error> {
+        deserializer
+            .deserialize_byte_buf(BytesVisitor)
+            .map(Cow::Owned)
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("a byte array")
+        }
+
+        fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+/// Serde support for numbers that keeps `NaN` and infinities in formats that can't represent them, such as JSON
+///
+/// In human readable formats these are written as the strings `"NaN"`, `"-NaN"`, `"Infinity"` and `"-Infinity"`, or
+/// `"NaN:0x<bits>"` for a `NaN` with a payload, so the exact bits are written back. Other numbers and all numbers in
+/// binary formats are written as normal
+#[cfg(feature = "serde")]
+mod number {
+    use core::fmt;
+    use serde::de::{Error, SeqAccess, Visitor};
+    use serde::ser::SerializeSeq;
+    use serde::{Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(n: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if n.is_finite() || !serializer.is_human_readable() {
+            serializer.serialize_f64(*n)
+        } else if n.to_bits() == f64::NAN.to_bits() {
+            serializer.serialize_str("NaN")
+        } else if n.to_bits() == (-f64::NAN).to_bits() {
+            serializer.serialize_str("-NaN")
+        } else if n.is_nan() {
+            serializer.serialize_str(&format!("NaN:0x{:016x}", n.to_bits()))
+        } else if n.is_sign_positive() {
+            serializer.serialize_str("Infinity")
+        } else {
+            serializer.serialize_str("-Infinity")
+        }
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        deserializer.deserialize_any(NumberVisitor)
+    }
+
+    struct NumberVisitor;
+
+    impl Visitor<'_> for NumberVisitor {
+        type Value = f64;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("a number, NaN or an infinity")
+        }
+
+        fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+            Ok(v as f64)
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+            Ok(v as f64)
+        }
+
+        fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+            let bits = v
+                .strip_prefix("NaN:0x")
+                .and_then(|bits| u64::from_str_radix(bits, 16).ok());
+            match v {
+                "NaN" => Ok(f64::NAN),
+                "-NaN" => Ok(-f64::NAN),
+                "Infinity" => Ok(f64::INFINITY),
+                "-Infinity" => Ok(f64::NEG_INFINITY),
+                _ => match bits.map(f64::from_bits) {
+                    Some(n) if n.is_nan() => Ok(n),
+                    _ => Err(E::invalid_value(serde::de::Unexpected::Str(v), &self)),
+                },
+            }
+        }
+    }
+
+    /// The same for each number of a `Vec`
+    pub(super) mod vec {
+        use super::*;
+
+        struct Number(f64);
+
+        impl<'de> serde::Deserialize<'de> for Number {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                super::deserialize(deserializer).map(Number)
+            }
+        }
+
+        struct NumberRef<'a>(&'a f64);
+
+        impl serde::Serialize for NumberRef<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                super::serialize(self.0, serializer)
+            }
+        }
+
+        pub(crate) fn serialize<S: Serializer>(
+            numbers: &[f64],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(numbers.len()))?;
+            for n in numbers {
+                seq.serialize_element(&NumberRef(n))?;
+            }
+            seq.end()
+        }
+
+        pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<f64>, D::Error> {
+            deserializer.deserialize_seq(NumbersVisitor)
+        }
+
+        struct NumbersVisitor;
+
+        impl<'de> Visitor<'de> for NumbersVisitor {
+            type Value = Vec<f64>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a sequence of numbers")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut numbers = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+                while let Some(Number(n)) = seq.next_element()? {
+                    numbers.push(n);
+                }
+                Ok(numbers)
+            }
+        }
+    }
+}
+
+/// Owned copies of the values that have been detached so far, by the pointer of the original
+pub(crate) type OwnedValues<'a> = HashMap<*const Value<'a>, Rc<OwnedValue>>;
+
+fn owned_cow<B: ?Sized + ToOwned>(borrowed: &B) -> Cow<'static, B> {
+    Cow::Owned(borrowed.to_owned())
+}
+
+pub(crate) fn owned_rc<'a>(value: &Rc<Value<'a>>, seen: &mut OwnedValues<'a>) -> Rc<OwnedValue> {
+    if let Some(owned) = seen.get(&Rc::as_ptr(value)) {
+        return Rc::clone(owned);
+    }
+    let owned = Rc::new(value.to_owned_with(seen));
+    seen.insert(Rc::as_ptr(value), Rc::clone(&owned));
+    owned
+}
+
+fn owned_values<'a>(values: &[Rc<Value<'a>>], seen: &mut OwnedValues<'a>) -> Vec<Rc<OwnedValue>> {
+    values.iter().map(|v| owned_rc(v, seen)).collect()
+}
+
+pub(crate) fn owned_elements<'a>(
+    elements: &[Element<'a>],
+    seen: &mut OwnedValues<'a>,
+) -> Vec<Element<'static>> {
+    elements
+        .iter()
+        .map(|e| Element::new(owned_cow(e.name.as_ref()), owned_rc(&e.value, seen)))
+        .collect()
+}
+
+fn walk_elements<'a>(elements: &mut [Element<'a>], f: &mut impl FnMut(&mut Value<'a>)) {
+    walk_values(elements.iter_mut().map(|e| &mut e.value), f);
+}
+
+fn walk_values<'a: 'b, 'b>(
+    values: impl Iterator<Item = &'b mut Rc<Value<'a>>>,
+    f: &mut impl FnMut(&mut Value<'a>),
+) {
+    for value in values {
+        Rc::make_mut(value).walk_mut(f);
+    }
+}
+
+/// A value that can be used as a key of a `HashMap` or a member of a `HashSet`
+///
+/// Keys are compared with `Value::structural_eq` and hashed with `Value::structural_hash`, so a value containing a NaN
+/// is equal to itself and the same object read from two different files is the same key. Strings, byte arrays and
+/// vectors are hashed by their contents, which can be slow for large ones
+/// ```
+/// use flash_lso::types::{StructuralKey, Value};
+/// use std::collections::HashSet;
+/// use std::rc::Rc;
+///
+/// let mut set = HashSet::new();
+/// set.insert(StructuralKey(Rc::new(Value::Number(f64::NAN))));
+/// assert!(set.contains(&StructuralKey(Rc::new(Value::Number(f64::NAN)))));
+/// ```
+#[derive(Clone, Debug)]
+pub struct StructuralKey<'a>(pub Rc<Value<'a>>);
+
+impl PartialEq for StructuralKey<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.structural_eq(&other.0)
+    }
+}
+
+impl Eq for StructuralKey<'_> {}
+
+impl Hash for StructuralKey<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.structural_hash(state);
+    }
+}
+
+/// Only `Integer` and `Number` values are ordered, by their numeric value, every other comparison gives `None` unless
+/// the values are equal
+///
+/// This agrees with `PartialEq`, so a `NaN` isn't ordered with anything and an `Integer` is never equal to a `Number`,
+/// an `Integer` is ordered before a `Number` with the same value instead. Sort with `sort_by` and `partial_cmp`, as
+/// `Value` isn't `Ord`
+impl PartialOrd for Value<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::Integer(a), Value::Integer(b)) => a.partial_cmp(b),
+            (Value::Integer(a), Value::Number(b)) => f64::from(*a)
+                .partial_cmp(b)
+                .map(|ordering| ordering.then(Ordering::Less)),
+            (Value::Number(a), Value::Integer(b)) => a
+                .partial_cmp(&f64::from(*b))
+                .map(|ordering| ordering.then(Ordering::Greater)),
+            _ => (self == other).then_some(Ordering::Equal),
+        }
+    }
+}
+
+impl<'a> FromIterator<Value<'a>> for Vec<Rc<Value<'a>>> {
+    fn from_iter<T: IntoIterator<Item = Value<'a>>>(iter: T) -> Self {
         iter.into_iter().map(Rc::new).collect()
     }
 }
+
+impl From<String> for Value<'_> {
+    fn from(s: String) -> Self {
+        Value::String(Cow::Owned(s))
+    }
+}
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(s: &'a str) -> Self {
+        Value::String(Cow::Borrowed(s))
+    }
+}
+
+/// Floats are always `Number`, even when they hold a whole number, so the value written is the one given
+impl From<f64> for Value<'_> {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+/// Integers are always `Integer` rather than `Number`, use an `f64` to get a `Number`
+///
+/// Integers only exist in AMF3, use `Lso::convert_to` to write them in AMF0
+impl From<i32> for Value<'_> {
+    fn from(i: i32) -> Self {
+        Value::Integer(i)
+    }
+}
+
+impl From<bool> for Value<'_> {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+/// Byte arrays only exist in AMF3, use `Lso::convert_to` to write them in AMF0
+impl From<Vec<u8>> for Value<'_> {
+    fn from(bytes: Vec<u8>) -> Self {
+        Value::ByteArray(Cow::Owned(bytes))
+    }
+}
+
+/// Byte arrays only exist in AMF3, use `Lso::convert_to` to write them in AMF0
+impl<'a> From<&'a [u8]> for Value<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        Value::ByteArray(Cow::Borrowed(bytes))
+    }
+}
+
+impl<'a> From<Vec<Value<'a>>> for Value<'a> {
+    fn from(values: Vec<Value<'a>>) -> Self {
+        Value::StrictArray(values.into_iter().collect())
+    }
+}
+
+/// An anonymous object with the given members, in order, with an `ObjectId::INVALID` id
+impl<'a> From<Vec<(String, Value<'a>)>> for Value<'a> {
+    fn from(members: Vec<(String, Value<'a>)>) -> Self {
+        let elements = members
+            .into_iter()
+            .map(|(name, value)| Element::new(name, value))
+            .collect();
+        Value::Object(ObjectId::INVALID, elements, None)
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pretty(2))
+    }
+}
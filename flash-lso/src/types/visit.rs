@@ -0,0 +1,205 @@
+use super::{Element, Lso, Value};
+use crate::errors::PathSegment;
+use crate::prelude::*;
+
+/// Called for each value in a tree by `Value::accept` or `Lso::accept`
+///
+/// The path of a value is the same as the path of a decoding error: the names of elements and properties, the indices
+/// of array and vector entries, and for a dictionary the index of the entry followed by `key` or `value`
+pub trait Visitor<'a> {
+    /// Called for a value before any of its children
+    ///
+    /// An `AMF3` wrapper is visited and then the value in it with the same path. A value shared between several places
+    /// is visited once for each place, references aren't followed
+    fn visit_value(&mut self, path: &[PathSegment], value: &Value<'a>);
+
+    /// Called for a value after all of its children
+    fn leave_value(&mut self, _path: &[PathSegment], _value: &Value<'a>) {}
+}
+
+/// Called for each value in a tree by `Value::accept_mut` or `Lso::accept_mut`, which may change it in place
+///
+/// Paths are given as for `Visitor`. Values shared with other values are copied before they are visited, so a change
+/// only appears at the path it was made at
+pub trait VisitorMut<'a> {
+    /// Called for a value before any of its children, the children of the value as changed are visited
+    fn visit_value(&mut self, path: &[PathSegment], value: &mut Value<'a>);
+
+    /// Called for a value after all of its children
+    fn leave_value(&mut self, _path: &[PathSegment], _value: &mut Value<'a>) {}
+}
+
+impl<'a> Value<'a> {
+    /// Visit this value and all of its children, see `Visitor`
+    /// ```
+    /// use flash_lso::errors::PathSegment;
+    /// use flash_lso::types::{Element, ObjectId, Value, Visitor};
+    ///
+    /// struct Strings(Vec<String>);
+    ///
+    /// impl<'a> Visitor<'a> for Strings {
+    ///     fn visit_value(&mut self, _path: &[PathSegment], value: &Value<'a>) {
+    ///         if let Value::String(s) = value {
+    ///             self.0.push(s.to_string());
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let value = Value::Object(
+    ///     ObjectId::INVALID,
+    ///     vec![Element::new("name", Value::String("bob".into()))],
+    ///     None,
+    /// );
+    /// let mut strings = Strings(Vec::new());
+    /// value.accept(&mut strings);
+    /// assert_eq!(strings.0, ["bob"]);
+    /// ```
+    pub fn accept(&self, visitor: &mut impl Visitor<'a>) {
+        accept_value(self, &mut Vec::new(), visitor);
+    }
+
+    /// Visit this value and all of its children, allowing them to be changed, see `VisitorMut`
+    pub fn accept_mut(&mut self, visitor: &mut impl VisitorMut<'a>) {
+        accept_value_mut(self, &mut Vec::new(), visitor);
+    }
+}
+
+impl<'a> Lso<'a> {
+    /// Visit every value in the body, see `Visitor`
+    ///
+    /// The path of each value starts with the name of the element it is in
+    pub fn accept(&self, visitor: &mut impl Visitor<'a>) {
+        accept_elements(&self.body, &mut Vec::new(), visitor);
+    }
+
+    /// Visit every value in the body, allowing them to be changed, see `VisitorMut`
+    pub fn accept_mut(&mut self, visitor: &mut impl VisitorMut<'a>) {
+        accept_elements_mut(&mut self.body, &mut Vec::new(), visitor);
+    }
+}
+
+fn accept_value<'a>(
+    value: &Value<'a>,
+    path: &mut Vec<PathSegment>,
+    visitor: &mut impl Visitor<'a>,
+) {
+    visitor.visit_value(path, value);
+
+    match value {
+        Value::Object(_, elements, _) => accept_elements(elements, path, visitor),
+        Value::Custom(custom_elements, elements, _) => {
+            accept_elements(custom_elements, path, visitor);
+            accept_elements(elements, path, visitor);
+        }
+        Value::ECMAArray(dense, elements, _) => {
+            accept_values(dense, path, visitor);
+            accept_elements(elements, path, visitor);
+        }
+        Value::StrictArray(values) | Value::VectorObject(values, _, _) => {
+            accept_values(values, path, visitor)
+        }
+        Value::Dictionary(entries, _) => {
+            for (index, (key, value)) in entries.iter().enumerate() {
+                path.push(PathSegment::Index(index));
+                for (part, value) in [("key", key), ("value", value)] {
+                    path.push(PathSegment::Name(part.to_string()));
+                    accept_value(value, path, visitor);
+                    path.pop();
+                }
+                path.pop();
+            }
+        }
+        #[cfg(feature = "amf3")]
+        Value::AMF3(inner) => accept_value(inner, path, visitor),
+        _ => {}
+    }
+
+    visitor.leave_value(path, value);
+}
+
+fn accept_elements<'a>(
+    elements: &[Element<'a>],
+    path: &mut Vec<PathSegment>,
+    visitor: &mut impl Visitor<'a>,
+) {
+    for element in elements {
+        path.push(PathSegment::Name(element.name.to_string()));
+        accept_value(&element.value, path, visitor);
+        path.pop();
+    }
+}
+
+fn accept_values<'a>(
+    values: &[Rc<Value<'a>>],
+    path: &mut Vec<PathSegment>,
+    visitor: &mut impl Visitor<'a>,
+) {
+    for (index, value) in values.iter().enumerate() {
+        path.push(PathSegment::Index(index));
+        accept_value(value, path, visitor);
+        path.pop();
+    }
+}
+
+fn accept_value_mut<'a>(
+    value: &mut Value<'a>,
+    path: &mut Vec<PathSegment>,
+    visitor: &mut impl VisitorMut<'a>,
+) {
+    visitor.visit_value(path, value);
+
+    match value {
+        Value::Object(_, elements, _) => accept_elements_mut(elements, path, visitor),
+        Value::Custom(custom_elements, elements, _) => {
+            accept_elements_mut(custom_elements, path, visitor);
+            accept_elements_mut(elements, path, visitor);
+        }
+        Value::ECMAArray(dense, elements, _) => {
+            accept_values_mut(dense, path, visitor);
+            accept_elements_mut(elements, path, visitor);
+        }
+        Value::StrictArray(values) | Value::VectorObject(values, _, _) => {
+            accept_values_mut(values, path, visitor)
+        }
+        Value::Dictionary(entries, _) => {
+            for (index, (key, value)) in entries.iter_mut().enumerate() {
+                path.push(PathSegment::Index(index));
+                for (part, value) in [("key", key), ("value", value)] {
+                    path.push(PathSegment::Name(part.to_string()));
+                    accept_value_mut(Rc::make_mut(value), path, visitor);
+                    path.pop();
+                }
+                path.pop();
+            }
+        }
+        #[cfg(feature = "amf3")]
+        Value::AMF3(inner) => accept_value_mut(Rc::make_mut(inner), path, visitor),
+        _ => {}
+    }
+
+    visitor.leave_value(path, value);
+}
+
+fn accept_elements_mut<'a>(
+    elements: &mut [Element<'a>],
+    path: &mut Vec<PathSegment>,
+    visitor: &mut impl VisitorMut<'a>,
+) {
+    for element in elements {
+        path.push(PathSegment::Name(element.name.to_string()));
+        accept_value_mut(Rc::make_mut(&mut element.value), path, visitor);
+        path.pop();
+    }
+}
+
+fn accept_values_mut<'a>(
+    values: &mut [Rc<Value<'a>>],
+    path: &mut Vec<PathSegment>,
+    visitor: &mut impl VisitorMut<'a>,
+) {
+    for (index, value) in values.iter_mut().enumerate() {
+        path.push(PathSegment::Index(index));
+        accept_value_mut(Rc::make_mut(value), path, visitor);
+        path.pop();
+    }
+}
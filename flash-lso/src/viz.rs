@@ -0,0 +1,177 @@
+use crate::prelude::*;
+use crate::types::{Element, ObjectId, Value};
+use core::fmt::Write;
+
+/// Convert a value to a Graphviz DOT graph
+///
+/// Every value becomes a node and every property, array index or dictionary entry becomes an edge labelled with its
+/// name. A value that is reached through more than one `Rc` is a single node with several incoming edges, and an
+/// `Amf3ObjectReference` is drawn as an edge to the object it refers to, so shared values and cycles are visible.
+/// `AMF3` wrappers don't get a node of their own
+pub fn to_dot(value: &Value<'_>) -> String {
+    let mut graph = Graph::default();
+    graph.node(value);
+    graph.finish()
+}
+
+#[derive(Default)]
+struct Graph<'a> {
+    out: String,
+
+    /// The node of every value that has been drawn, by pointer
+    nodes: HashMap<*const Value<'a>, usize>,
+
+    /// The node of every object that has been drawn, by id
+    objects: HashMap<ObjectId, usize>,
+
+    /// Edges to referenced objects, these are added at the end as the object may not have been drawn yet
+    references: Vec<(usize, String, ObjectId)>,
+}
+
+impl<'a> Graph<'a> {
+    fn node(&mut self, value: &Value<'a>) -> usize {
+        #[cfg(feature = "amf3")]
+        if let Value::AMF3(inner) = value {
+            return self.node(inner);
+        }
+
+        let ptr: *const Value<'a> = value;
+        if let Some(node) = self.nodes.get(&ptr) {
+            return *node;
+        }
+        let node = self.nodes.len();
+        self.nodes.insert(ptr, node);
+        if let Value::Object(id, _, _) = value {
+            if *id != ObjectId::INVALID {
+                self.objects.entry(*id).or_insert(node);
+            }
+        }
+        let _ = writeln!(
+            self.out,
+            "  n{} [label=\"{}\"];",
+            node,
+            escape(&label(value))
+        );
+
+        for (name, child) in children(value) {
+            if let Value::Amf3ObjectReference(id) = child {
+                self.references.push((node, name, *id));
+            } else {
+                let target = self.node(child);
+                self.edge(node, target, &name);
+            }
+        }
+        node
+    }
+
+    fn edge(&mut self, from: usize, to: usize, name: &str) {
+        let _ = writeln!(
+            self.out,
+            "  n{} -> n{} [label=\"{}\"];",
+            from,
+            to,
+            escape(name)
+        );
+    }
+
+    fn finish(mut self) -> String {
+        let mut next_node = self.nodes.len();
+        for (from, name, id) in core::mem::take(&mut self.references) {
+            let to = match self.objects.get(&id) {
+                Some(node) => *node,
+                None => {
+                    // The referenced object isn't part of this graph
+                    let node = next_node;
+                    next_node += 1;
+                    self.objects.insert(id, node);
+                    let _ = writeln!(
+                        self.out,
+                        "  n{} [label=\"Object #{}\", style=dashed];",
+                        node, id.0
+                    );
+                    node
+                }
+            };
+            self.edge(from, to, &name);
+        }
+        format!("digraph {{\n{}}}\n", self.out)
+    }
+}
+
+/// Get the children of a value, with the name of the edge to each one
+fn children<'a, 'b>(value: &'b Value<'a>) -> Vec<(String, &'b Value<'a>)> {
+    match value {
+        Value::Object(_, elements, _) => named(elements),
+        Value::Custom(custom_elements, elements, _) => {
+            let mut children = named(custom_elements);
+            children.extend(named(elements));
+            children
+        }
+        Value::ECMAArray(dense, elements, _) => {
+            let mut children: Vec<_> = dense
+                .iter()
+                .enumerate()
+                .map(|(index, v)| (index.to_string(), v.as_ref()))
+                .collect();
+            children.extend(named(elements));
+            children
+        }
+        Value::StrictArray(values) | Value::VectorObject(values, _, _) => values
+            .iter()
+            .enumerate()
+            .map(|(index, v)| (index.to_string(), v.as_ref()))
+            .collect(),
+        Value::Dictionary(entries, _) => entries
+            .iter()
+            .enumerate()
+            .flat_map(|(index, (k, v))| {
+                [
+                    (format!("{}.key", index), k.as_ref()),
+                    (format!("{}.value", index), v.as_ref()),
+                ]
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn named<'a, 'b>(elements: &'b [Element<'a>]) -> Vec<(String, &'b Value<'a>)> {
+    elements
+        .iter()
+        .map(|e| (e.name.to_string(), e.value()))
+        .collect()
+}
+
+/// Get the label of the node for a value, this is its type along with its content for simple values
+fn label(value: &Value<'_>) -> String {
+    match value {
+        Value::Object(id, _, def) => {
+            let name = def
+                .as_ref()
+                .map(|def| def.name.as_str())
+                .filter(|name| !name.is_empty())
+                .unwrap_or("Object");
+            if *id == ObjectId::INVALID {
+                name.to_string()
+            } else {
+                format!("{} #{}", name, id.0)
+            }
+        }
+        Value::Custom(_, _, Some(def)) if !def.name.is_empty() => def.name.clone(),
+        Value::RawExternal(raw) if !raw.class_def.name.is_empty() => raw.class_def.name.clone(),
+        Value::Number(n) => format!("Number: {}", n),
+        Value::Integer(i) => format!("Integer: {}", i),
+        Value::Bool(b) => format!("Boolean: {}", b),
+        Value::String(s) => format!("String: \"{}\"", s),
+        Value::Date(time, _) => format!("Date: {}", time),
+        Value::VectorObject(_, type_name, _) => format!("Vector<{}>", type_name),
+        other => other.type_name().to_string(),
+    }
+}
+
+/// Escape a string for use in a quoted DOT label
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -1,110 +1,274 @@
 //! Handles writing of LSO files
-use std::io::Write;
+use crate::io::Write;
+use crate::prelude::*;
+#[cfg(feature = "std")]
+use std::path::Path;
 
+use crate::amf0::write::AMF0Encoder;
 #[cfg(feature = "amf3")]
 use crate::amf3::write::AMF3Encoder;
+#[cfg(feature = "compression")]
+use crate::compression::{compress, Compression};
 use crate::errors::Error;
+#[cfg(feature = "std")]
+use crate::errors::FileError;
 use crate::nom_utils::write_string;
-use crate::types::{AMFVersion, Header, Lso};
-#[cfg(feature = "amf3")]
-use crate::FORMAT_VERSION_AMF3;
-use crate::{FORMAT_VERSION_AMF0, HEADER_SIGNATURE, HEADER_VERSION, PADDING};
+use crate::types::{AMFVersion, Header, Lso, Value};
+use crate::{HEADER_SIGNATURE, HEADER_VERSION, PADDING};
 
 /// Handles writing a given LSO
 #[derive(Default)]
-pub struct Writer {
+pub struct Writer<'v> {
+    /// The encoder used for writing AMF0 bodies
+    pub amf0_encoder: AMF0Encoder,
+
     /// The encoder used for writing Value::AMF3() wrapped types
     #[cfg(feature = "amf3")]
-    pub amf3_encoder: AMF3Encoder,
+    pub amf3_encoder: AMF3Encoder<'v>,
+
+    /// The container to wrap the written file in, if any, see `Reader::compression`
+    #[cfg(feature = "compression")]
+    pub compression: Option<Compression>,
+
+    #[cfg(not(feature = "amf3"))]
+    _values: core::marker::PhantomData<&'v ()>,
 }
 
-impl Writer {
+impl<'v> Writer<'v> {
     /// Write a given LSO
+    ///
+    /// The body is encoded with the AMF version given by `Header::format_version` unless overridden by
+    /// `EncoderOptions::amf_version`, an error is returned if the header gives a version that can't be encoded. Only
+    /// `Header::length` of the given LSO is changed, to the length that was written. Strings are written in the form
+    /// given by `Lso::surrogate_strings`
+    ///
+    /// The header gives the length of the body, so the body is encoded twice, once to count its length and once to
+    /// write it, rather than being held in memory. When `compression` is set the file is compressed as a whole, so it is
+    /// encoded once into a buffer instead
     pub fn write_full<'a, 'b: 'a, W: Write + 'a>(
         &'a mut self,
         writer: &mut W,
-        lso: &'b mut Lso,
-    ) -> std::io::Result<()> {
-        let mut buffer = vec![];
-        if lso.header.format_version == AMFVersion::AMF0 {
-            crate::amf0::write::write_body(&mut buffer, &lso.body)?;
+        lso: &'b mut Lso<'v>,
+    ) -> crate::io::Result<()> {
+        #[cfg(feature = "amf3")]
+        let amf_version = self.amf3_encoder.options.amf_version;
+        #[cfg(not(feature = "amf3"))]
+        let amf_version = None;
+
+        let version = amf_version
+            .or_else(|| lso.header.format_version.amf_version())
+            .ok_or_else(|| {
+                crate::io::Error::new(
+                    crate::io::ErrorKind::InvalidInput,
+                    format!("Unsupported format version {}", lso.header.format_version),
+                )
+            })?;
+        let converted;
+        let source = if lso.header.format_version.amf_version() == Some(version) {
+            &*lso
         } else {
+            converted = lso.convert_to(version);
+            &converted
+        };
+        self.amf0_encoder.surrogate_strings = source.surrogate_strings.clone();
+        #[cfg(feature = "amf3")]
+        {
+            self.amf3_encoder.surrogate_strings = source.surrogate_strings.clone();
+        }
+        let mut header = source.header.clone();
+
+        #[cfg(feature = "compression")]
+        if let Some(compression) = self.compression {
+            let name = self.encode_name(source);
+            let mut body = vec![];
+            self.write_body(&mut body, source, version)?;
+
+            header.length = body.len() as u32 + header_length_with_name(&name) as u32;
+            let mut plain = vec![];
+            write_header(&mut plain, &header, &name)?;
+            plain.extend_from_slice(&body);
+            writer.write_all(&compress(&plain, compression))?;
+            lso.header.length = header.length;
+            return Ok(());
+        }
+
+        let name = self.encode_name(source);
+        let mut counter = ByteCounter::default();
+        self.write_body(&mut counter, source, version)?;
+
+        header.length = counter.0 as u32 + header_length_with_name(&name) as u32;
+        let name = self.encode_name(source);
+        write_header(writer, &header, &name)?;
+        self.write_body(writer, source, version)?;
+        lso.header.length = header.length;
+        Ok(())
+    }
+
+    /// Encode the name of the LSO, starting from the first recorded form of each string in `Lso::surrogate_strings`
+    ///
+    /// The name is read before the body, so it is also encoded before the body each time the body is written
+    fn encode_name(&self, lso: &Lso<'_>) -> Vec<u8> {
+        lso.surrogate_strings.rewind();
+        self.amf0_encoder
+            .encode_string(&lso.header.name)
+            .into_owned()
+    }
+
+    /// Write the body of the LSO and its trailing bytes
+    fn write_body<'a, 'b: 'a, W: Write + 'a>(
+        &'a self,
+        writer: &mut W,
+        lso: &'b Lso<'v>,
+        version: AMFVersion,
+    ) -> crate::io::Result<()> {
+        match version {
+            AMFVersion::AMF0 => self.amf0_encoder.write_body(writer, &lso.body)?,
             #[cfg(feature = "amf3")]
-            self.amf3_encoder.write_body(&mut buffer, &lso.body)?;
+            AMFVersion::AMF3 => self.amf3_encoder.write_body(writer, &lso.body)?,
         }
+        writer.write_all(&lso.trailing)
+    }
 
-        lso.header.length = buffer.len() as u32 + header_length(&lso.header) as u32;
+    /// Write a given LSO to the file at the given path, replacing it if it exists
+    ///
+    /// As with `write_full`, the header of the LSO is updated to match what was written
+    #[cfg(feature = "std")]
+    pub fn write_file(
+        &mut self,
+        lso: &mut Lso<'v>,
+        path: impl AsRef<Path>,
+    ) -> Result<(), FileError> {
+        let path = path.as_ref();
+        let mut buffer = vec![];
+        self.write_full(&mut buffer, lso)
+            .and_then(|_| std::fs::write(path, buffer))
+            .map_err(|source| FileError::Io {
+                path: path.to_path_buf(),
+                source,
+            })
+    }
+}
+
+/// A sink that counts the bytes written to it
+#[derive(Default)]
+pub(crate) struct ByteCounter(pub(crate) usize);
 
-        write_header(writer, &lso.header)?;
-        writer.write_all(&buffer)?;
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> crate::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> crate::io::Result<()> {
         Ok(())
     }
 }
 
+/// Write a header, with its name already encoded into `name`
 fn write_header<'a, 'b: 'a, W: Write + 'a>(
     writer: &mut W,
     header: &'b Header,
-) -> std::io::Result<()> {
+    name: &[u8],
+) -> crate::io::Result<()> {
     writer.write_all(&HEADER_VERSION)?;
     writer.write_u32(header.length)?;
     writer.write_all(&HEADER_SIGNATURE)?;
-    write_string(writer, &header.name)?;
+    write_string(writer, name)?;
     writer.write_all(&PADDING)?;
     writer.write_all(&PADDING)?;
     writer.write_all(&PADDING)?;
-    match header.format_version {
-        AMFVersion::AMF0 => writer.write_all(&[FORMAT_VERSION_AMF0])?,
-        #[cfg(feature = "amf3")]
-        AMFVersion::AMF3 => writer.write_all(&[FORMAT_VERSION_AMF3])?,
-    };
+    writer.write_u8(header.format_version.as_u8())?;
     Ok(())
 }
 
 /// Get the serialized length of the header in bytes, this does not include the size of the header length field or the lso version marker
 pub fn header_length(header: &Header) -> usize {
+    header_length_with_name(header.name.as_bytes())
+}
+
+/// See `header_length`, with the name already encoded into `name`
+fn header_length_with_name(name: &[u8]) -> usize {
     // signature + (name size u16 + name_len) + 3*padding + amf_version_marker
-    10 + (2 + header.name.len() + 3 + 1)
+    10 + (2 + name.len() + 3 + 1)
+}
+
+/// Write a LSO to any writer, using the default `Writer`
+///
+/// Nothing is buffered, so `out` should be buffered itself if small writes to it are slow, see `Writer::write_full`
+pub fn write_to<W: Write>(lso: &mut Lso<'_>, out: &mut W) -> crate::io::Result<()> {
+    Writer::default().write_full(out, lso)
 }
 
 /// Write a LSO to a vec of bytes
-pub fn write_to_bytes<'a>(lso: &mut Lso) -> Result<Vec<u8>, Error<'a>> {
+pub fn write_to_bytes<'a>(lso: &mut Lso<'_>) -> Result<Vec<u8>, Error<'a>> {
+    let mut v = vec![];
+    write_to(lso, &mut v)?;
+    Ok(v)
+}
+
+/// Write a LSO to a vec of bytes with the given `Writer`
+///
+/// Set `Writer::amf_version` to convert the body to a different AMF version than it was read with
+pub fn write_to_bytes_with<'a, 'v>(
+    lso: &mut Lso<'v>,
+    mut writer: Writer<'v>,
+) -> Result<Vec<u8>, Error<'a>> {
     let mut v = vec![];
+    writer.write_full(&mut v, lso)?;
+    Ok(v)
+}
 
-    let mut s = Writer::default();
-    s.write_full(&mut v, lso)
-        .map_err(|e| Error::IoError(e.to_string(), e.kind()))?;
+/// Write a single value with the given AMF version, this is the inverse of `decode_value`
+///
+/// A new encoder is used, so only values shared within this value are written as references
+pub fn encode_value<'a>(value: &Value<'_>, version: AMFVersion) -> Result<Vec<u8>, Error<'a>> {
+    let mut v = vec![];
+    let value = Rc::new(value.clone());
+    match version {
+        AMFVersion::AMF0 => AMF0Encoder::default().write_value(&mut v, &value),
+        #[cfg(feature = "amf3")]
+        AMFVersion::AMF3 => AMF3Encoder::default().write_value_element(&mut v, &value),
+    }?;
     Ok(v)
 }
 
+/// Write a LSO to the file at the given path, using the default `Writer`
+#[cfg(feature = "std")]
+pub fn write_file<P: AsRef<Path>>(lso: &mut Lso<'_>, path: P) -> Result<(), FileError> {
+    Writer::default().write_file(lso, path)
+}
+
 pub(crate) trait WriteExt {
-    fn write_u8(&mut self, value: u8) -> std::io::Result<()>;
-    fn write_u16(&mut self, value: u16) -> std::io::Result<()>;
-    fn write_u32(&mut self, value: u32) -> std::io::Result<()>;
-    fn write_i32(&mut self, value: i32) -> std::io::Result<()>;
-    fn write_f64(&mut self, value: f64) -> std::io::Result<()>;
+    fn write_u8(&mut self, value: u8) -> crate::io::Result<()>;
+    fn write_u16(&mut self, value: u16) -> crate::io::Result<()>;
+    fn write_u32(&mut self, value: u32) -> crate::io::Result<()>;
+    #[cfg(feature = "amf3")]
+    fn write_i32(&mut self, value: i32) -> crate::io::Result<()>;
+    fn write_f64(&mut self, value: f64) -> crate::io::Result<()>;
 }
 
 impl<T> WriteExt for T
 where
     T: Write,
 {
-    fn write_u8(&mut self, value: u8) -> std::io::Result<()> {
+    fn write_u8(&mut self, value: u8) -> crate::io::Result<()> {
         self.write_all(&[value])
     }
 
-    fn write_u16(&mut self, value: u16) -> std::io::Result<()> {
+    fn write_u16(&mut self, value: u16) -> crate::io::Result<()> {
         self.write_all(&value.to_be_bytes())
     }
 
-    fn write_u32(&mut self, value: u32) -> std::io::Result<()> {
+    fn write_u32(&mut self, value: u32) -> crate::io::Result<()> {
         self.write_all(&value.to_be_bytes())
     }
 
-    fn write_i32(&mut self, value: i32) -> std::io::Result<()> {
+    #[cfg(feature = "amf3")]
+    fn write_i32(&mut self, value: i32) -> crate::io::Result<()> {
         self.write_all(&value.to_be_bytes())
     }
 
-    fn write_f64(&mut self, value: f64) -> std::io::Result<()> {
+    fn write_f64(&mut self, value: f64) -> crate::io::Result<()> {
         self.write_all(&value.to_be_bytes())
     }
 }
@@ -1,10 +1,17 @@
 use core::fmt;
+use flash_lso::amf3::events::{Event, EventReader};
+use flash_lso::amf3::write::EncoderOptions;
 use flash_lso::errors::Error;
 use flash_lso::read::Reader;
-use flash_lso::types::Value;
+use flash_lso::types::{
+    AMFVersion, ClassDefinition, Element, FormatVersion, Lso, ObjectId, RawExternal,
+    SurrogateStrings, Utf8Mode, Value,
+};
+use flash_lso::write::Writer;
 use nom::error::ErrorKind;
 use std::borrow::Borrow;
 use std::ops::Deref;
+use std::rc::Rc;
 
 // #[cfg(test)]
 // use pretty_assertions::assert_eq;
@@ -176,7 +183,7 @@ macro_rules! json_test {
             use serde_json;
 
             let data = include_bytes!(concat!("sol/", $path, ".sol"));
-            let parse_res = Reader::default().parse(data)?;
+            let mut parse_res = Reader::default().parse(data)?;
             let output_json = serde_json::to_string(&parse_res)?;
 
 
@@ -184,6 +191,13 @@ macro_rules! json_test {
 
             assert_eq!(json_expected.trim(), output_json);
 
+            // Reading the json back should write the same bytes
+            let mut round_trip: Lso<'_> = serde_json::from_str(&output_json)?;
+            assert_eq!(
+                flash_lso::write::write_to_bytes(&mut parse_res)?,
+                flash_lso::write::write_to_bytes(&mut round_trip)?
+            );
+
             Ok(())
         }
         )*
@@ -210,7 +224,7 @@ macro_rules! json_test_bytearray_object_amf3 {
             assert_eq!(json_expected.trim(), output_json);
 
             let mut lso = flash_lso::types::Lso::new(vec![flash_lso::types::Element {
-                name: "".to_string(),
+                name: "".into(),
                 value: parse_res,
             }], "", flash_lso::types::AMFVersion::AMF3);
 
@@ -354,6 +368,7 @@ auto_test! {
     [as2_null, "AS2-Null-Demo"],
     [as2_number, "AS2-Number-Demo"],
     [as2_object, "AS2-Object-Demo"],
+    [as2_reference, "AS2-Reference-Demo"],
     [as2_string, "AS2-String-Demo"],
     [as2_typed_object, "AS2-TypedObject-Demo"],
     [as2_undefined, "AS2-Undefined-Demo"],
@@ -381,7 +396,8 @@ auto_test! {
     [as3_vector_number, "AS3-VectorNumber-Demo"],
     [as3_vector_object, "AS3-VectorObject-Demo"],
     [as3_vector_typed_object, "AS3-VectorTypedObject-Demo"],
-    [as3_dictionary, "AS3-Dictionary-Demo"]
+    [as3_dictionary, "AS3-Dictionary-Demo"],
+    [as3_mixed_array, "AS3-MixedArray-Demo"]
 }
 
 // Other tests, mixed
@@ -453,7 +469,11 @@ should_fail! {
     // Corrupt/invalid file
     [two, "2",  nom::Err::Error(Error::Nom(vec![17, 112, 99, 95, 112, 97, 114, 116, 121, 10, 130, 51, 21, 80, 97, 114, 116, 121, 65, 108, 105, 97, 115, 0, 13, 98, 97, 116, 116, 108, 101, 2, 0].as_slice(), ErrorKind::Tag))],
     // OOB read
-    [zero_four, "00000004", nom::Err::Error(Error::Nom(vec![0, 255, 0, 0, 0, 86, 0, 84, 47, 117, 112, 108, 111, 97, 100, 115, 46, 117, 110, 103, 114, 111, 117, 110, 100, 101, 100, 46, 110, 101, 116, 47, 53, 57, 50, 48, 48, 48, 47, 53, 57, 50, 52, 55, 51, 95, 77, 97, 100, 110, 101, 115, 115, 71, 97, 109, 101, 95, 85, 76, 84, 73, 77, 65, 84, 69, 46, 115, 119, 102, 47, 97, 114, 101, 110, 97, 77, 97, 100, 110, 101, 115, 115, 71, 97, 109, 101, 50, 46, 115, 111, 108].as_slice(), ErrorKind::Eof))]
+    [zero_four, "00000004", nom::Err::Error(Error::Nom(vec![0, 255, 0, 0, 0, 86, 0, 84, 47, 117, 112, 108, 111, 97, 100, 115, 46, 117, 110, 103, 114, 111, 117, 110, 100, 101, 100, 46, 110, 101, 116, 47, 53, 57, 50, 48, 48, 48, 47, 53, 57, 50, 52, 55, 51, 95, 77, 97, 100, 110, 101, 115, 115, 71, 97, 109, 101, 95, 85, 76, 84, 73, 77, 65, 84, 69, 46, 115, 119, 102, 47, 97, 114, 101, 110, 97, 77, 97, 100, 110, 101, 115, 115, 71, 97, 109, 101, 50, 46, 115, 111, 108].as_slice(), ErrorKind::Eof))],
+    // ECMA array that ends before its terminator
+    [ecma_array_unterminated, "AMF0-ECMAArray-Unterminated", nom::Err::Error(Error::Nom(vec![0, 3, 97, 114, 114, 8, 0, 0, 0, 1, 0, 1, 97, 0, 63, 240, 0, 0, 0, 0, 0, 0].as_slice(), ErrorKind::Eof))],
+    // ECMA array ended by an object end marker with a non-empty name
+    [ecma_array_bad_terminator, "AMF0-ECMAArray-BadTerminator", nom::Err::Error(Error::Nom(vec![0, 3, 97, 114, 114, 8, 0, 0, 0, 1, 0, 1, 97, 0, 63, 240, 0, 0, 0, 0, 0, 0, 0, 1, 98, 9, 0, 0, 4, 110, 101, 120, 116, 0, 63, 240, 0, 0, 0, 0, 0, 0, 0].as_slice(), ErrorKind::Eof))]
 }
 
 #[test]
@@ -468,3 +488,4445 @@ pub fn test_recursive_object() {
         panic!("Expected object");
     }
 }
+
+#[test]
+pub fn test_format_version_0_writes_amf0_body() {
+    let mut lso = Lso::new(
+        vec![Element::new("name", Rc::new(Value::String("value".into())))],
+        "test",
+        AMFVersion::AMF0,
+    );
+
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+    assert!(bytes.ends_with(&[
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x04, b'n', b'a', b'm', b'e', 0x02, 0x00, 0x05, b'v', b'a',
+        b'l', b'u', b'e', 0x00
+    ]));
+
+    let read = Reader::default()
+        .parse(&bytes)
+        .expect("Failed to parse lso");
+    assert_eq!(read, lso);
+}
+
+#[test]
+pub fn test_format_version_3_writes_amf3_body() {
+    let mut lso = Lso::new(
+        vec![Element::new("name", Rc::new(Value::String("value".into())))],
+        "test",
+        AMFVersion::AMF3,
+    );
+
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+    assert!(bytes.ends_with(&[
+        0x00, 0x00, 0x00, 0x03, 0x09, b'n', b'a', b'm', b'e', 0x06, 0x0b, b'v', b'a', b'l', b'u',
+        b'e', 0x00
+    ]));
+
+    let read = Reader::default()
+        .parse(&bytes)
+        .expect("Failed to parse lso");
+    assert_eq!(read, lso);
+}
+
+#[test]
+pub fn test_writer_amf_version_override() {
+    let mut lso = Lso::new(
+        vec![Element::new("name", Rc::new(Value::Number(1.0)))],
+        "test",
+        AMFVersion::AMF3,
+    );
+
+    let before = lso.clone();
+
+    let mut bytes = vec![];
+    let mut writer = Writer::default();
+    writer.amf3_encoder.options = EncoderOptions {
+        amf_version: Some(AMFVersion::AMF0),
+        ..EncoderOptions::default()
+    };
+    writer
+        .write_full(&mut bytes, &mut lso)
+        .expect("Failed to write lso");
+    assert_eq!(lso.header.format_version, FormatVersion::Amf3);
+    assert_eq!(lso.header.length as usize, bytes.len() - 6);
+    assert_eq!(lso.body, before.body);
+
+    let read = Reader::default()
+        .parse(&bytes)
+        .expect("Failed to parse lso");
+    assert_eq!(read.header.format_version, FormatVersion::Amf0);
+    assert_eq!(read.body, lso.body);
+}
+
+#[test]
+pub fn test_unknown_format_version() {
+    let mut lso = Lso::new_empty("test", FormatVersion::Unknown(2));
+    assert!(flash_lso::write::write_to_bytes(&mut lso).is_err());
+
+    let mut bytes = flash_lso::write::write_to_bytes(&mut Lso::new_empty("test", AMFVersion::AMF0))
+        .expect("Failed to write lso");
+    *bytes.last_mut().unwrap() = 2;
+
+    let (_, header) = Reader::default()
+        .parse_header(&bytes)
+        .expect("Failed to parse header");
+    assert_eq!(header.format_version, FormatVersion::Unknown(2));
+    assert_eq!(header.format_version.as_u8(), 2);
+
+    let err = Reader::default().parse(&bytes).unwrap_err();
+    assert_eq!(err, nom::Err::Error(Error::UnsupportedFormatVersion(2)));
+}
+
+// "😀 𠀀" as written by Flash Player, each character is encoded as a pair of three byte surrogates
+const FLASH_SURROGATE_STRING: [u8; 13] = [
+    0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80, b' ', 0xED, 0xA1, 0x80, 0xED, 0xB0, 0x80,
+];
+
+fn flash_surrogate_lso(format_version: u8) -> Vec<u8> {
+    string_lso(format_version, &[(b's', &FLASH_SURROGATE_STRING)])
+}
+
+// A lso named "test" holding a string element for each of the given names and encoded strings
+fn string_lso(format_version: u8, strings: &[(u8, &[u8])]) -> Vec<u8> {
+    let mut body = vec![];
+    for (name, string) in strings {
+        let len = string.len() as u8;
+        if format_version == 0 {
+            body.extend_from_slice(&[0x00, 0x01, *name, 0x02, 0x00, len]);
+        } else {
+            body.extend_from_slice(&[0x03, *name, 0x06, (len << 1) | 1]);
+        }
+        body.extend_from_slice(string);
+        body.push(0x00);
+    }
+
+    let mut data = vec![0x54, 0x43, 0x53, 0x4f, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00];
+    data.extend_from_slice(&[0x00, 0x04, b't', b'e', b's', b't', 0x00, 0x00, 0x00]);
+    data.push(format_version);
+    data.extend(body);
+
+    let mut lso = vec![0x00, 0xbf];
+    lso.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    lso.extend(data);
+    lso
+}
+
+#[test]
+pub fn test_utf8_mode_strict_rejects_surrogates() {
+    for version in [0, 3] {
+        let bytes = flash_surrogate_lso(version);
+        assert!(Reader::default().parse(&bytes).is_err());
+    }
+}
+
+#[test]
+pub fn test_utf8_mode_lossy_replaces_surrogates() {
+    for version in [0, 3] {
+        let bytes = flash_surrogate_lso(version);
+        let mut reader = Reader::default();
+        reader.set_utf8_mode(Utf8Mode::Lossy);
+        let lso = reader.parse(&bytes).expect("Failed to parse lso");
+
+        if let Value::String(s) = lso.body[0].value.deref() {
+            assert!(s.contains('\u{FFFD}'));
+            assert!(!s.contains('😀'));
+        } else {
+            panic!("Expected string");
+        }
+    }
+}
+
+#[test]
+pub fn test_utf8_mode_flash_round_trip() {
+    for version in [0, 3] {
+        let bytes = flash_surrogate_lso(version);
+        let mut reader = Reader::default();
+        reader.set_utf8_mode(Utf8Mode::Flash);
+        let mut lso = reader.parse(&bytes).expect("Failed to parse lso");
+
+        assert_eq!(lso.body[0].value.deref(), &Value::String("😀 𠀀".into()));
+
+        let written = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+        assert_eq!(written, bytes);
+    }
+}
+
+#[test]
+pub fn test_utf8_mode_flash_keeps_the_form_read() {
+    // A different string as standard UTF-8, next to the surrogate pair form
+    let utf8 = "𠀀 😀".as_bytes();
+    for version in [0, 3] {
+        let bytes = string_lso(version, &[(b'a', &FLASH_SURROGATE_STRING), (b'b', utf8)]);
+        let mut reader = Reader::default();
+        reader.set_utf8_mode(Utf8Mode::Flash);
+        let mut lso = reader.parse(&bytes).expect("Failed to parse lso");
+        assert_eq!(lso.body[1].value.deref(), &Value::String("𠀀 😀".into()));
+        assert_eq!(lso.surrogate_strings.len(), 2);
+        assert!(lso.surrogate_strings.contains("😀 𠀀"));
+        assert!(!lso.surrogate_strings.contains("𠀀 😀"));
+
+        let written = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+        assert_eq!(written, bytes);
+
+        // Without the record, strings are written as the mode of the encoder writes them
+        lso.surrogate_strings = SurrogateStrings::default();
+        let plain = string_lso(version, &[(b'a', "😀 𠀀".as_bytes()), (b'b', utf8)]);
+        assert_eq!(flash_lso::write::write_to_bytes(&mut lso).unwrap(), plain);
+
+        let mut writer = Writer::default();
+        writer.amf0_encoder.utf8_mode = Utf8Mode::Flash;
+        writer.amf3_encoder.utf8_mode = Utf8Mode::Flash;
+        let mut written = vec![];
+        writer.write_full(&mut written, &mut lso).unwrap();
+        let pairs = string_lso(
+            version,
+            &[
+                (b'a', &FLASH_SURROGATE_STRING),
+                (b'b', &Utf8Mode::Flash.encode("𠀀 😀")),
+            ],
+        );
+        assert_eq!(written, pairs);
+    }
+}
+
+#[test]
+pub fn test_utf8_mode_flash_unpaired_surrogate() {
+    assert_eq!(
+        Utf8Mode::Flash
+            .decode(&[b'a', 0xED, 0xA0, 0xBD, b'b'])
+            .as_deref(),
+        Some("a\u{FFFD}b")
+    );
+    assert_eq!(
+        Utf8Mode::Flash
+            .decode(&[0xED, 0xB8, 0x80, 0xED, 0xA0, 0xBD])
+            .as_deref(),
+        Some("\u{FFFD}\u{FFFD}")
+    );
+    assert_eq!(Utf8Mode::Flash.decode(&[0xFF]), None);
+    assert_eq!(Utf8Mode::Flash.encode("abc").as_ref(), b"abc");
+    assert_eq!(
+        Utf8Mode::Flash.encode("😀").as_ref(),
+        [0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]
+    );
+    assert_eq!(Utf8Mode::Strict.encode("😀").as_ref(), "😀".as_bytes());
+}
+
+#[test]
+pub fn test_utf8_mode_flash_fixture() {
+    // Strings with characters outside of the basic multilingual plane as surrogate pairs, in element names, values,
+    // object keys and string references, and one string written both as standard UTF-8 and with surrogate pairs
+    let data = include_bytes!("sol/AS3-Surrogate-Demo.sol");
+    assert!(Reader::default().parse(data).is_err());
+
+    let mut reader = Reader::default();
+    reader.set_utf8_mode(Utf8Mode::Flash);
+    let mut lso = reader.parse(data).expect("Failed to parse lso");
+    assert_eq!(lso.get("myEmoji").and_then(Value::as_str), Some("😀 smile"));
+    assert_eq!(lso.get("myCJK").and_then(Value::as_str), Some("𠀀𠀁 漢字"));
+    assert_eq!(
+        lso.get_path("myObject.名前").and_then(Value::as_str),
+        Some("🎮 player")
+    );
+    assert_eq!(
+        lso.get("myObject")
+            .and_then(|o| o.get_path("😀 key"))
+            .and_then(Value::as_str),
+        Some("😀 smile")
+    );
+    assert_eq!(
+        lso.get_path("myArray[0]").and_then(Value::as_str),
+        Some("🎮 player")
+    );
+    for name in ["myUtf8", "mySurrogates", "myUtf8Again"] {
+        assert_eq!(lso.get(name).and_then(Value::as_str), Some("🍣 sushi"));
+    }
+    assert!(lso.surrogate_strings.contains("🍣 sushi"));
+
+    let before = lso.clone();
+    let written = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+    assert_eq!(
+        crate::PrettyArray(&written),
+        crate::PrettyArray(&data.to_vec())
+    );
+    assert_eq!(lso, before);
+
+    // Writing twice gives the same bytes, and so does writing a copy read back from serde
+    assert_eq!(flash_lso::write::write_to_bytes(&mut lso).unwrap(), written);
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&lso).unwrap();
+        let mut copy: Lso<'_> = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            flash_lso::write::write_to_bytes(&mut copy).unwrap(),
+            written
+        );
+    }
+}
+
+#[test]
+pub fn test_utf8_mode_flash_real_files() {
+    // Saves written by Flash Player with Chinese and Japanese text, which are in the basic multilingual plane and so
+    // are the same in every mode
+    for data in [
+        &include_bytes!("sol/JY1.sol")[..],
+        &include_bytes!("sol/flash.viewer.sol")[..],
+    ] {
+        let mut reader = Reader::default();
+        reader.set_utf8_mode(Utf8Mode::Flash);
+        let mut lso = reader.parse(data).expect("Failed to parse lso");
+        assert_eq!(lso, Reader::default().parse(data).unwrap());
+        assert!(lso.surrogate_strings.is_empty());
+        assert_eq!(flash_lso::write::write_to_bytes(&mut lso).unwrap(), data);
+    }
+
+    let mut reader = Reader::default();
+    reader.set_utf8_mode(Utf8Mode::Flash);
+    let lso = reader
+        .parse(include_bytes!("sol/JY1.sol"))
+        .expect("Failed to parse lso");
+    assert!(!lso
+        .find_paths(|v| v.as_str() == Some("无名小卒"))
+        .is_empty());
+}
+
+fn empty_values_lso(version: AMFVersion) -> Lso<'static> {
+    let dictionary = Rc::new(Value::Dictionary(Vec::new(), false));
+    Lso::new(
+        vec![
+            Element::new("array", Rc::new(Value::StrictArray(Vec::new()))),
+            Element::new(
+                "object",
+                Rc::new(Value::Object(ObjectId::INVALID, Vec::new(), None)),
+            ),
+            Element::new(
+                "dictionary",
+                match version {
+                    AMFVersion::AMF0 => Rc::new(Value::AMF3(dictionary)),
+                    AMFVersion::AMF3 => dictionary,
+                },
+            ),
+        ],
+        "empty",
+        version,
+    )
+}
+
+#[test]
+pub fn test_empty_values_amf0() {
+    let mut lso = empty_values_lso(AMFVersion::AMF0);
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+
+    let mut body = vec![0x00, 0x05];
+    body.extend_from_slice(b"array");
+    body.extend_from_slice(&[0x0a, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    body.extend_from_slice(&[0x00, 0x06]);
+    body.extend_from_slice(b"object");
+    body.extend_from_slice(&[0x03, 0x00, 0x00, 0x09, 0x00]);
+    body.extend_from_slice(&[0x00, 0x0a]);
+    body.extend_from_slice(b"dictionary");
+    body.extend_from_slice(&[0x11, 0x11, 0x01, 0x00, 0x00]);
+    assert!(bytes.ends_with(&body));
+
+    let read = Reader::default()
+        .parse(&bytes)
+        .expect("Failed to parse lso");
+    assert_eq!(read, lso);
+    assert_eq!(
+        flash_lso::write::write_to_bytes(&mut read.clone()).expect("Failed to write lso"),
+        bytes
+    );
+}
+
+#[test]
+pub fn test_empty_values_amf3() {
+    let mut lso = empty_values_lso(AMFVersion::AMF3);
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+
+    let mut body = vec![0x0b];
+    body.extend_from_slice(b"array");
+    body.extend_from_slice(&[0x09, 0x01, 0x01, 0x00]);
+    body.push(0x0d);
+    body.extend_from_slice(b"object");
+    body.extend_from_slice(&[0x0a, 0x0b, 0x01, 0x01, 0x00]);
+    body.push(0x15);
+    body.extend_from_slice(b"dictionary");
+    body.extend_from_slice(&[0x11, 0x01, 0x00, 0x00]);
+    assert!(bytes.ends_with(&body));
+
+    // Plain objects are read back with the anonymous class definition they were written with
+    let read = Reader::default()
+        .parse(&bytes)
+        .expect("Failed to parse lso");
+    assert_eq!(read.body[0], lso.body[0]);
+    assert_eq!(
+        read.body[1].value.deref(),
+        &Value::Object(ObjectId(1), Vec::new(), Some(ClassDefinition::anonymous()))
+    );
+    assert_eq!(read.body[2], lso.body[2]);
+    assert_eq!(
+        flash_lso::write::write_to_bytes(&mut read.clone()).expect("Failed to write lso"),
+        bytes
+    );
+}
+
+#[test]
+pub fn test_anonymous_object_keeps_properties_amf3() {
+    let mut lso = Lso::new(
+        vec![Element::new(
+            "object",
+            Rc::new(Value::Object(
+                ObjectId::INVALID,
+                vec![Element::new("a", Rc::new(Value::Integer(1)))],
+                None,
+            )),
+        )],
+        "test",
+        AMFVersion::AMF3,
+    );
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+    let read = Reader::default()
+        .parse(&bytes)
+        .expect("Failed to parse lso");
+
+    if let Value::Object(_, elements, _) = read.body[0].value.deref() {
+        assert_eq!(
+            elements,
+            &vec![Element::new("a", Rc::new(Value::Integer(1)))]
+        );
+    } else {
+        panic!("Expected object");
+    }
+}
+
+#[test]
+pub fn test_object_properties() {
+    let mut object = Value::Object(
+        ObjectId::INVALID,
+        vec![
+            Element::new("a", Rc::new(Value::Number(1.0))),
+            Element::new("b", Rc::new(Value::Number(2.0))),
+            Element::new("a", Rc::new(Value::Number(3.0))),
+        ],
+        None,
+    );
+
+    assert_eq!(object.get_property("a"), Some(&Value::Number(1.0)));
+    assert_eq!(object.get_property("b"), Some(&Value::Number(2.0)));
+    assert_eq!(object.get_property("c"), None);
+
+    assert_eq!(
+        object.set_property("a", Rc::new(Value::Bool(true))),
+        Some(Rc::new(Value::Number(1.0)))
+    );
+    assert_eq!(object.set_property("c", Rc::new(Value::Null)), None);
+    assert_eq!(
+        object,
+        Value::Object(
+            ObjectId::INVALID,
+            vec![
+                Element::new("a", Rc::new(Value::Bool(true))),
+                Element::new("b", Rc::new(Value::Number(2.0))),
+                Element::new("a", Rc::new(Value::Number(3.0))),
+                Element::new("c", Rc::new(Value::Null)),
+            ],
+            None,
+        )
+    );
+
+    let mut number = Value::Number(1.0);
+    assert_eq!(number.get_property("a"), None);
+    assert_eq!(number.set_property("a", Rc::new(Value::Null)), None);
+    assert_eq!(number, Value::Number(1.0));
+}
+
+#[test]
+pub fn test_dictionary_helpers() {
+    let object_key = Rc::new(Value::Object(
+        ObjectId(1),
+        vec![Element::new("a", Rc::new(Value::Integer(1)))],
+        None,
+    ));
+    let mut value = Value::Dictionary(Vec::new(), false);
+    assert_eq!(value.as_dictionary().map(|d| d.len()), Some(0));
+    assert!(value.as_dictionary().unwrap().is_empty());
+
+    let mut dictionary = value.as_dictionary_mut().unwrap();
+
+    assert_eq!(
+        dictionary.insert(
+            Rc::new(Value::String("b".into())),
+            Rc::new(Value::Integer(2))
+        ),
+        None
+    );
+    assert_eq!(
+        dictionary.insert(Rc::clone(&object_key), Rc::new(Value::Integer(3))),
+        None
+    );
+    assert_eq!(
+        dictionary.insert(
+            Rc::new(Value::String("b".into())),
+            Rc::new(Value::Integer(4))
+        ),
+        Some(Rc::new(Value::Integer(2)))
+    );
+
+    let dictionary = value.as_dictionary().unwrap();
+    assert_eq!(dictionary.len(), 2);
+    assert_eq!(
+        dictionary.get(&Value::String("b".into())),
+        Some(&Value::Integer(4))
+    );
+    assert_eq!(
+        dictionary.get(&object_key.as_ref().clone()),
+        Some(&Value::Integer(3))
+    );
+    assert_eq!(dictionary.get(&Value::Null), None);
+    assert_eq!(
+        dictionary.iter().collect::<Vec<_>>(),
+        vec![
+            (&Value::String("b".into()), &Value::Integer(4)),
+            (object_key.as_ref(), &Value::Integer(3)),
+        ]
+    );
+
+    let mut number = Value::Number(1.0);
+    assert!(number.as_dictionary().is_none());
+    assert!(number.as_dictionary_mut().is_none());
+}
+
+#[test]
+pub fn test_large_int_vectors() {
+    let mut lso = Lso::new(
+        vec![
+            Element::new(
+                "ints",
+                Rc::new(Value::VectorInt(vec![i32::MIN, -1, 0, 1, i32::MAX], true)),
+            ),
+            Element::new(
+                "uints",
+                Rc::new(Value::VectorUInt((0..10_000).collect(), false)),
+            ),
+        ],
+        "vectors",
+        AMFVersion::AMF3,
+    );
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+
+    let read = Reader::default()
+        .parse(&bytes)
+        .expect("Failed to parse lso");
+    assert_eq!(read, lso);
+
+    // A vector that declares more elements than are present must be rejected
+    assert!(Reader::default().parse(&bytes[..bytes.len() - 2]).is_err());
+}
+
+macro_rules! event_test {
+    ($([$name: ident, $path: expr]),*) => {
+        $(
+        #[test]
+        pub fn $name() {
+            let data = include_bytes!(concat!("sol/", $path, ".sol"));
+            let sol = Reader::default().parse(data).expect("Failed to parse lso");
+
+            let (body, _) = Reader::default().parse_header(data).expect("Failed to parse header");
+            let mut reader = EventReader::body(body);
+            let mut depth = 0;
+            let mut names = Vec::new();
+            for event in reader.by_ref() {
+                match event.expect("Failed to read event") {
+                    Event::Property(name) if depth == 0 => names.push(name),
+                    Event::BeginObject(_) | Event::BeginArray(_) | Event::BeginVector(_, _) | Event::BeginDictionary(_) => depth += 1,
+                    Event::EndObject | Event::EndArray | Event::EndVector | Event::EndDictionary => depth -= 1,
+                    _ => {}
+                }
+            }
+
+            assert_eq!(depth, 0);
+            assert!(reader.remaining().is_empty());
+            assert_eq!(names, sol.body.iter().map(|e| e.name.clone()).collect::<Vec<_>>());
+        }
+        )*
+    }
+}
+
+event_test! {
+    [events_as3_array, "AS3-Array-Demo"],
+    [events_as3_byte_array, "AS3-ByteArray-Demo"],
+    [events_as3_date, "AS3-Date-Demo"],
+    [events_as3_demo, "AS3-Demo"],
+    [events_as3_dictionary, "AS3-Dictionary-Demo"],
+    [events_as3_object, "AS3-Object-Demo"],
+    [events_as3_typed_object, "AS3-TypedObject-Demo"],
+    [events_as3_vector_int, "AS3-VectorInt-Demo"],
+    [events_as3_vector_number, "AS3-VectorNumber-Demo"],
+    [events_as3_vector_object, "AS3-VectorObject-Demo"],
+    [events_as3_vector_typed_object, "AS3-VectorTypedObject-Demo"],
+    [events_as3_xml, "AS3-XML-Demo"]
+}
+
+#[test]
+pub fn test_event_reader_sequence() {
+    let object = Rc::new(Value::Object(
+        ObjectId(1),
+        vec![Element::new("a", Rc::new(Value::Integer(1)))],
+        None,
+    ));
+    let mut lso = Lso::new(
+        vec![
+            Element::new("object", object),
+            Element::new("again", Rc::new(Value::Amf3ObjectReference(ObjectId(1)))),
+            Element::new(
+                "array",
+                Rc::new(Value::ECMAArray(
+                    vec![Rc::new(Value::Bool(true))],
+                    vec![Element::new("key", Rc::new(Value::Null))],
+                    1,
+                )),
+            ),
+        ],
+        "events",
+        AMFVersion::AMF3,
+    );
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+
+    let (body, _) = Reader::default()
+        .parse_header(&bytes)
+        .expect("Failed to parse header");
+    let events = EventReader::body(body)
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Failed to read events");
+
+    assert_eq!(
+        events,
+        vec![
+            Event::Property("object".into()),
+            Event::BeginObject(ClassDefinition::anonymous()),
+            Event::Property("a".into()),
+            Event::Value(Value::Integer(1)),
+            Event::EndObject,
+            Event::Property("again".into()),
+            Event::Reference(0),
+            Event::Property("array".into()),
+            Event::BeginArray(1),
+            Event::Property("key".into()),
+            Event::Value(Value::Null),
+            Event::Value(Value::Bool(true)),
+            Event::EndArray,
+        ]
+    );
+}
+
+#[test]
+pub fn test_map_strings_and_numbers() {
+    let shared = Rc::new(Value::String("shared".into()));
+    let mut value = Value::Object(
+        ObjectId::INVALID,
+        vec![
+            Element::new("name", Rc::new(Value::String("a".into()))),
+            Element::new(
+                "list",
+                Rc::new(Value::StrictArray(vec![
+                    Rc::clone(&shared),
+                    Rc::new(Value::Number(1.5)),
+                ])),
+            ),
+            Element::new(
+                "dictionary",
+                Rc::new(Value::Dictionary(
+                    vec![(
+                        Rc::new(Value::String("key".into())),
+                        Rc::new(Value::VectorDouble(vec![1.0, 2.0], false)),
+                    )],
+                    false,
+                )),
+            ),
+        ],
+        None,
+    );
+
+    value.map_strings(|s| s.make_ascii_uppercase());
+    value.map_numbers(|n| *n *= 2.0);
+
+    assert_eq!(
+        value,
+        Value::Object(
+            ObjectId::INVALID,
+            vec![
+                Element::new("name", Rc::new(Value::String("A".into()))),
+                Element::new(
+                    "list",
+                    Rc::new(Value::StrictArray(vec![
+                        Rc::new(Value::String("SHARED".into())),
+                        Rc::new(Value::Number(3.0)),
+                    ])),
+                ),
+                Element::new(
+                    "dictionary",
+                    Rc::new(Value::Dictionary(
+                        vec![(
+                            Rc::new(Value::String("KEY".into())),
+                            Rc::new(Value::VectorDouble(vec![2.0, 4.0], false)),
+                        )],
+                        false,
+                    )),
+                ),
+            ],
+            None,
+        )
+    );
+    // Shared values are cloned rather than modified in place
+    assert_eq!(shared.as_ref(), &Value::String("shared".into()));
+}
+
+#[test]
+pub fn test_dedup_objects() {
+    let object = |id| {
+        Rc::new(Value::Object(
+            ObjectId(id),
+            vec![Element::new("a", Rc::new(Value::Integer(1)))],
+            None,
+        ))
+    };
+    let array = || {
+        Rc::new(Value::StrictArray(vec![
+            Rc::new(Value::Number(1.0)),
+            Rc::new(Value::Number(2.0)),
+        ]))
+    };
+    let outer = |id, inner_id| {
+        Rc::new(Value::Object(
+            ObjectId(id),
+            vec![Element::new(
+                "inner",
+                Rc::new(Value::Object(
+                    ObjectId(inner_id),
+                    vec![Element::new("b", Rc::new(Value::Integer(2)))],
+                    None,
+                )),
+            )],
+            None,
+        ))
+    };
+    let mut lso = Lso::new(
+        vec![
+            Element::new("array", array()),
+            Element::new("first", object(1)),
+            Element::new("second", object(2)),
+            Element::new("again", array()),
+            Element::new(
+                "reference",
+                Rc::new(Value::Amf3ObjectReference(ObjectId(2))),
+            ),
+            Element::new("outer", outer(10, 11)),
+            Element::new("outer_again", outer(20, 21)),
+            Element::new(
+                "inner_reference",
+                Rc::new(Value::Amf3ObjectReference(ObjectId(21))),
+            ),
+        ],
+        "dedup",
+        AMFVersion::AMF3,
+    );
+
+    let plain = flash_lso::write::write_to_bytes(&mut lso.clone()).expect("Failed to write lso");
+
+    let mut writer = Writer::default();
+    writer.amf3_encoder.options = EncoderOptions {
+        dedup_objects: true,
+        ..EncoderOptions::default()
+    };
+    let mut deduped = Vec::new();
+    writer
+        .write_full(&mut deduped, &mut lso)
+        .expect("Failed to write lso");
+    assert!(deduped.len() < plain.len());
+
+    let read = Reader::default()
+        .parse(&deduped)
+        .expect("Failed to parse lso");
+    assert_eq!(read.body[0].value, array());
+    assert_eq!(
+        read.body[1].value.deref(),
+        &Value::Object(
+            ObjectId(1),
+            vec![Element::new("a", Rc::new(Value::Integer(1)))],
+            Some(ClassDefinition::anonymous()),
+        )
+    );
+    assert_eq!(
+        read.body[2].value.deref(),
+        &Value::Amf3ObjectReference(ObjectId(1))
+    );
+    assert!(Rc::ptr_eq(&read.body[0].value, &read.body[3].value));
+    assert_eq!(
+        read.body[4].value.deref(),
+        &Value::Amf3ObjectReference(ObjectId(1))
+    );
+
+    // The objects inside a deduplicated value refer to the matching ones in the value it was written as
+    let Value::Object(outer_id, members, _) = read.body[5].value.deref() else {
+        panic!("Expected an object");
+    };
+    assert_eq!(
+        read.body[6].value.deref(),
+        &Value::Amf3ObjectReference(*outer_id)
+    );
+    let Value::Object(inner_id, _, _) = members[0].value.deref() else {
+        panic!("Expected an object");
+    };
+    assert_eq!(
+        read.body[7].value.deref(),
+        &Value::Amf3ObjectReference(*inner_id)
+    );
+}
+
+#[cfg(feature = "flex")]
+#[test]
+pub fn test_flex_raw_round_trip() {
+    use flash_lso::amf3::read::DecoderOptions;
+    use flash_lso::extra::flex;
+
+    let data = include_bytes!("sol/oppDetailPrefs.sol");
+    let mut reader = Reader::default();
+    flex::read::register_decoders(&mut reader.amf3_decoder);
+    reader.amf3_decoder.options = DecoderOptions {
+        decode_flex: false,
+        ..DecoderOptions::default()
+    };
+    let mut sol = reader.parse(data).expect("Failed to parse lso");
+
+    // No encoders are registered, the raw bytes are written back as they were read
+    let bytes = flash_lso::write::write_to_bytes(&mut sol).expect("Failed to write lso");
+    assert_eq!(PrettyArray(&bytes), PrettyArray(&data.to_vec()));
+}
+
+#[test]
+pub fn test_invalid_references_are_errors() {
+    // An array that contains itself
+    let self_referencing_array = [0x09, 0x03, 0x01, 0x09, 0x00];
+    assert!(flash_lso::amf3::read::AMF3Decoder::default()
+        .parse_single_element(&self_referencing_array)
+        .is_err());
+
+    // An object reference to an array
+    let object_reference_to_array = [0x09, 0x03, 0x01, 0x0a, 0x00];
+    assert!(flash_lso::amf3::read::AMF3Decoder::default()
+        .parse_single_element(&object_reference_to_array)
+        .is_err());
+
+    // Values that can't be encoded
+    use flash_lso::types::Attribute;
+    // An AMF0 strict array of an object and a reference to it
+    let (_, array) = flash_lso::amf0::read::AMF0Decoder::default()
+        .parse_single_element(&[
+            0x0A, 0x00, 0x00, 0x00, 0x02, 0x03, 0x00, 0x00, 0x09, 0x07, 0x00, 0x00,
+        ])
+        .unwrap();
+    let Value::StrictArray(items) = array.as_ref() else {
+        panic!("Expected an array");
+    };
+    let external = ClassDefinition {
+        name: "Foo".into(),
+        attributes: Attribute::External.into(),
+        static_properties: vec![],
+    };
+    for value in [
+        items[1].as_ref().clone(),
+        Value::Amf3ObjectReference(ObjectId(7)),
+        Value::Custom(
+            vec![Element::new("a", Value::Integer(1))],
+            vec![],
+            Some(external),
+        ),
+    ] {
+        assert!(flash_lso::amf3::encode(&value).is_err());
+    }
+}
+
+#[test]
+pub fn test_decode_error_paths() {
+    use flash_lso::errors::PathSegment;
+
+    let body = |class_def: Option<ClassDefinition>| {
+        let items = (0..4)
+            .map(|n| Rc::new(Value::String(if n == 2 { "zzzz" } else { "a" }.into())))
+            .collect();
+        vec![
+            Element::new("first", Rc::new(Value::Null)),
+            Element::new(
+                "inventory",
+                Rc::new(Value::Object(
+                    ObjectId(1),
+                    vec![Element::new("items", Rc::new(Value::StrictArray(items)))],
+                    class_def,
+                )),
+            ),
+        ]
+    };
+    let expected_path = vec![
+        PathSegment::Name("inventory".to_string()),
+        PathSegment::Name("items".to_string()),
+        PathSegment::Index(2),
+    ];
+
+    // Replace the marker of the "zzzz" string with one that can't be decoded, `offset` is the distance back from the
+    // string data to the marker
+    let corrupt = |version: AMFVersion, class_def, offset: usize, marker: u8| {
+        let mut lso = Lso::new(body(class_def), "test", version);
+        let mut bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+        let position = bytes
+            .windows(4)
+            .position(|w| w == b"zzzz")
+            .expect("String not written");
+        bytes[position - offset] = marker;
+        bytes
+    };
+
+    for (bytes, message) in [
+        (
+            corrupt(AMFVersion::AMF0, None, 3, 0x04),
+            "Unsupported tag at byte 67 in inventory.items[2]",
+        ),
+        (
+            corrupt(
+                AMFVersion::AMF3,
+                Some(ClassDefinition::anonymous()),
+                2,
+                0xFF,
+            ),
+            "Unsupported tag at byte 61 in inventory.items[2]",
+        ),
+    ] {
+        let mut reader = Reader::default();
+        let error = reader.parse(&bytes).expect_err("Corrupt lso was decoded");
+        assert_eq!(reader.error_path(), expected_path);
+        assert_eq!(reader.decode_error(&bytes, error).to_string(), message);
+    }
+
+    // An error outside of any value has no path
+    let mut reader = Reader::default();
+    let bytes = [0x00, 0xbf, 0x00];
+    let error = reader.parse(&bytes).expect_err("Truncated lso was decoded");
+    assert!(reader.error_path().is_empty());
+    assert_eq!(reader.decode_error(&bytes, error).path_string(), "");
+}
+
+#[test]
+pub fn test_decode_error_reasons() {
+    use flash_lso::errors::DecodeReason;
+
+    let write = |value: Value<'static>| {
+        let mut lso = Lso::new(vec![Element::new("a", value)], "test", AMFVersion::AMF3);
+        flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso")
+    };
+    let reason = |bytes: &[u8]| {
+        let mut reader = Reader::default();
+        let error = reader.parse(bytes).expect_err("Invalid lso was decoded");
+        let error = reader.decode_error(bytes, error);
+        (error.reason, error.offset)
+    };
+
+    let valid = write(Value::Integer(1));
+    let len = valid.len();
+
+    let mut bad_version = valid.clone();
+    bad_version[1] = 0;
+    assert_eq!(reason(&bad_version), (DecodeReason::BadMagic, Some(0)));
+
+    let mut bad_signature = valid.clone();
+    bad_signature[6] = b'X';
+    assert_eq!(reason(&bad_signature), (DecodeReason::BadMagic, Some(6)));
+
+    assert_eq!(
+        reason(&valid[..len - 2]),
+        (DecodeReason::UnexpectedEof, Some(len - 2))
+    );
+
+    // The integer marker is followed by the value and the padding
+    let mut unknown_marker = valid.clone();
+    unknown_marker[len - 3] = 0x20;
+    assert_eq!(
+        reason(&unknown_marker),
+        (DecodeReason::UnknownMarker(0x20), Some(len - 3))
+    );
+
+    // The vector marker is followed by its length, the fixed length flag, one value and the padding
+    let mut too_long = write(Value::VectorInt(vec![1], false));
+    let len = too_long.len();
+    too_long[len - 7] = 0x7F;
+    assert_eq!(reason(&too_long).0, DecodeReason::InvalidLength);
+}
+
+#[test]
+pub fn test_amf3_large_declared_counts() {
+    // Each value declares the largest possible count (2^28 - 1) followed by a few bytes of content
+    let values: [&[u8]; 6] = [
+        &[0x09, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x01],
+        &[0x0D, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x01],
+        &[0x0E, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x01],
+        &[
+            0x0F, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ],
+        &[0x10, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x01, 0x01, 0x01],
+        &[0x11, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x01, 0x01],
+    ];
+
+    for data in values {
+        assert!(
+            matches!(
+                flash_lso::amf3::read::AMF3Decoder::default().parse_single_element(data),
+                Err(nom::Err::Error(Error::Nom(_, ErrorKind::TooLarge)))
+            ),
+            "{:x?}",
+            data
+        );
+
+        let events: Result<Vec<_>, _> = EventReader::new(data).collect();
+        assert!(
+            matches!(
+                events,
+                Err(nom::Err::Error(Error::Nom(_, ErrorKind::TooLarge)))
+            ),
+            "{:x?}",
+            data
+        );
+    }
+}
+
+#[test]
+pub fn test_unknown_external_fallbacks() {
+    use flash_lso::amf3::read::{AMF3Decoder, DecoderOptions, UnknownExternal};
+    use flash_lso::types::Attribute;
+
+    // An externalizable `Foo` that wrote itself as the dynamic property `a = 1`
+    let data = [
+        0x0a, 0x07, 0x07, b'F', b'o', b'o', 0x03, b'a', 0x04, 0x01, 0x01,
+    ];
+    let decoder = |unknown_external| {
+        let mut decoder = AMF3Decoder::default();
+        decoder.options = DecoderOptions {
+            unknown_external,
+            ..DecoderOptions::default()
+        };
+        decoder
+    };
+    let def = ClassDefinition {
+        name: "Foo".into(),
+        attributes: Attribute::External.into(),
+        static_properties: vec![],
+    };
+
+    assert!(decoder(UnknownExternal::Error)
+        .parse_single_element(&data)
+        .is_err());
+
+    let (rest, value) = decoder(UnknownExternal::Dynamic)
+        .parse_single_element(&data)
+        .expect("Failed to parse object");
+    assert!(rest.is_empty());
+    assert_eq!(
+        value.deref(),
+        &Value::Custom(
+            vec![],
+            vec![Element::new("a", Rc::new(Value::Integer(1)))],
+            Some(def.clone()),
+        )
+    );
+
+    let mut lso = Lso::new(vec![Element::new("foo", value)], "test", AMFVersion::AMF3);
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+    let mut reader = Reader {
+        amf3_decoder: decoder(UnknownExternal::Dynamic),
+        ..Reader::default()
+    };
+    assert_eq!(reader.parse(&bytes).expect("Failed to parse lso"), lso);
+
+    let (rest, value) = decoder(UnknownExternal::Remaining)
+        .parse_single_element(&data)
+        .expect("Failed to parse object");
+    assert!(rest.is_empty());
+    let Value::RawExternal(raw) = value.deref() else {
+        panic!("Expected a raw external value");
+    };
+    assert_eq!(raw.class_def, def);
+    assert_eq!(raw.bytes, &data[6..]);
+    assert_eq!(value.raw_external_bytes(), Some(&data[6..]));
+    assert_eq!(
+        Value::Custom(vec![], vec![], None).raw_external_bytes(),
+        None
+    );
+
+    // The raw bytes are written back unchanged
+    assert_eq!(flash_lso::amf3::encode(&value).unwrap(), data);
+
+    // but only where the reference tables are the same as when they were read, here the name of the element is added
+    // to the string table first
+    let mut lso = Lso::new(
+        vec![Element::new("foo", Rc::clone(&value))],
+        "test",
+        AMFVersion::AMF3,
+    );
+    assert!(flash_lso::write::write_to_bytes(&mut lso).is_err());
+
+    // Raw values that were created rather than read can be written anywhere
+    let created = Value::RawExternal(Box::new(RawExternal::new(def, data[6..].to_vec())));
+    let mut lso = Lso::new(vec![Element::new("foo", created)], "test", AMFVersion::AMF3);
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+    assert!(bytes.ends_with(&[&data[..], &[0]].concat()));
+}
+
+#[cfg(feature = "flex")]
+#[test]
+pub fn test_flex_data_messages_round_trip() {
+    use flash_lso::extra::flex;
+    use flash_lso::types::Attribute;
+
+    let message = |name: &str, custom_elements: Vec<Element<'static>>| {
+        Rc::new(Value::Custom(
+            custom_elements,
+            vec![],
+            Some(ClassDefinition {
+                name: name.to_string(),
+                attributes: Attribute::External.into(),
+                static_properties: vec![],
+            }),
+        ))
+    };
+    let async_elements = vec![
+        Element::new("body", Rc::new(Value::String("hello".into()))),
+        Element::new("destination", Rc::new(Value::String("chat".into()))),
+        Element::new("message_id", Rc::new(Value::String("1234".into()))),
+        Element::new("correlation_id", Rc::new(Value::String("5678".into()))),
+    ];
+    let mut data_elements = async_elements.clone();
+    data_elements.push(Element::new(
+        "identity",
+        Rc::new(Value::String("row-7".into())),
+    ));
+    data_elements.push(Element::new("operation", Rc::new(Value::Integer(2))));
+
+    let mut lso = Lso::new(
+        vec![
+            Element::new(
+                "async",
+                message(
+                    "flex.messaging.messages.AsyncMessage",
+                    async_elements.clone(),
+                ),
+            ),
+            Element::new("small", message("DSA", async_elements)),
+            Element::new(
+                "data",
+                message("flex.data.messages.DataMessage", data_elements),
+            ),
+        ],
+        "test",
+        AMFVersion::AMF3,
+    );
+
+    let mut writer = Writer::default();
+    flex::write::register_encoders(&mut writer.amf3_encoder);
+    let mut bytes = vec![];
+    writer
+        .write_full(&mut bytes, &mut lso)
+        .expect("Failed to write lso");
+
+    let mut reader = Reader::default();
+    flex::read::register_decoders(&mut reader.amf3_decoder);
+    assert_eq!(reader.parse(&bytes).expect("Failed to parse lso"), lso);
+}
+
+#[cfg(feature = "flex")]
+#[test]
+pub fn test_flex_wrappers() {
+    use flash_lso::extra::flex::{self, FlexValue};
+
+    // An ObjectProxy wrapping an object with an ArrayCollection property
+    let items = Value::StrictArray(vec![
+        Rc::new(Value::Integer(1)),
+        Rc::new(Value::String("two".into())),
+    ]);
+    let proxy = flex::object_proxy(Value::Object(
+        ObjectId::INVALID,
+        vec![Element::new("items", flex::array_collection(items.clone()))],
+        None,
+    ));
+    let mut lso = Lso::new(
+        vec![
+            Element::new("proxy", proxy),
+            Element::new("plain", items.clone()),
+        ],
+        "test",
+        AMFVersion::AMF3,
+    );
+
+    let mut writer = Writer::default();
+    flex::write::register_encoders(&mut writer.amf3_encoder);
+    let bytes = flash_lso::write::write_to_bytes_with(&mut lso, writer).unwrap();
+    let mut reader = Reader::default();
+    flex::read::register_decoders(&mut reader.amf3_decoder);
+    let mut read = reader.parse(&bytes).unwrap();
+
+    let Some(FlexValue::ObjectProxy { class, object }) = FlexValue::from_value(&read.body[0])
+    else {
+        panic!("Expected an ObjectProxy");
+    };
+    assert_eq!(class, flex::OBJECT_PROXY);
+    let Some(FlexValue::ArrayCollection { class, source }) =
+        object.get_property("items").and_then(FlexValue::from_value)
+    else {
+        panic!("Expected an ArrayCollection");
+    };
+    assert_eq!(class, flex::ARRAY_COLLECTION);
+    assert_eq!(**source, items);
+    assert_eq!(FlexValue::from_value(&read.body[1]), None);
+
+    let mut writer = Writer::default();
+    flex::write::register_encoders(&mut writer.amf3_encoder);
+    assert_eq!(
+        flash_lso::write::write_to_bytes_with(&mut read, writer).unwrap(),
+        bytes
+    );
+}
+
+#[cfg(feature = "flex")]
+#[test]
+pub fn test_flex_uid_strings() {
+    use flash_lso::extra::flex;
+    use flash_lso::types::Attribute;
+
+    let uid: Vec<u8> = (0..16).map(|n| n * 17).collect();
+    assert_eq!(
+        flex::uid_to_string(&uid).as_deref(),
+        Some("00112233-4455-6677-8899-AABBCCDDEEFF")
+    );
+    assert_eq!(flex::uid_to_string(&uid[..15]), None);
+    assert_eq!(
+        flex::string_to_uid("00112233-4455-6677-8899-aabbccddeeff").map(|b| b.to_vec()),
+        Some(uid.clone())
+    );
+    assert_eq!(flex::string_to_uid("00112233-4455-6677-8899"), None);
+
+    // The decoder shows UID byte arrays as strings and the encoder writes them back as bytes
+    let message = |message_id: Value<'static>| {
+        Rc::new(Value::Custom(
+            vec![Element::new("message_id_bytes", Rc::new(message_id))],
+            vec![],
+            Some(ClassDefinition {
+                name: "DSA".into(),
+                attributes: Attribute::External.into(),
+                static_properties: vec![],
+            }),
+        ))
+    };
+    let mut lso = Lso::new(
+        vec![Element::new("msg", message(Value::ByteArray(uid.into())))],
+        "test",
+        AMFVersion::AMF3,
+    );
+
+    let write = |lso: &mut Lso| {
+        let mut writer = Writer::default();
+        flex::write::register_encoders(&mut writer.amf3_encoder);
+        let mut bytes = vec![];
+        writer
+            .write_full(&mut bytes, lso)
+            .expect("Failed to write lso");
+        bytes
+    };
+    let bytes = write(&mut lso);
+
+    let mut reader = Reader::default();
+    flex::read::register_decoders(&mut reader.amf3_decoder);
+    let mut read = reader.parse(&bytes).expect("Failed to parse lso");
+    assert_eq!(
+        read.body[0].value,
+        message(Value::String("00112233-4455-6677-8899-AABBCCDDEEFF".into()))
+    );
+
+    assert_eq!(PrettyArray(&write(&mut read)), PrettyArray(&bytes));
+}
+
+#[test]
+pub fn test_strict_array_shared_elements() {
+    use flash_lso::amf3::read::AMF3Decoder;
+    use flash_lso::amf3::write::AMF3Encoder;
+
+    // [a, a, a] where a = [1], then [o, o] where o = {}
+    let arrays = [
+        0x09, 0x07, 0x01, 0x09, 0x03, 0x01, 0x04, 0x01, 0x09, 0x02, 0x09, 0x02,
+    ];
+    let objects = [0x09, 0x05, 0x01, 0x0a, 0x0b, 0x01, 0x01, 0x0a, 0x02];
+
+    for data in [&arrays[..], &objects[..]] {
+        let (rest, value) = AMF3Decoder::default()
+            .parse_single_element(data)
+            .expect("Failed to parse array");
+        assert!(rest.is_empty());
+
+        let encoder = AMF3Encoder::default();
+        let mut bytes = vec![];
+        encoder
+            .write_value_element(&mut bytes, &value)
+            .expect("Failed to write array");
+        assert_eq!(PrettyArray(&bytes), PrettyArray(&data.to_vec()));
+    }
+
+    // Arrays are shared directly, objects are shared through `Amf3ObjectReference`
+    let (_, value) = AMF3Decoder::default()
+        .parse_single_element(&arrays)
+        .unwrap();
+    if let Value::StrictArray(elements) = value.deref() {
+        assert!(Rc::ptr_eq(&elements[0], &elements[1]));
+        assert!(Rc::ptr_eq(&elements[0], &elements[2]));
+    } else {
+        panic!("Expected strict array");
+    }
+
+    let (_, value) = AMF3Decoder::default()
+        .parse_single_element(&objects)
+        .unwrap();
+    if let Value::StrictArray(elements) = value.deref() {
+        if let Value::Object(id, _, _) = elements[0].deref() {
+            assert_eq!(elements[1].deref(), &Value::Amf3ObjectReference(*id));
+        } else {
+            panic!("Expected object");
+        }
+    } else {
+        panic!("Expected strict array");
+    }
+
+    // An object shared by `Rc` is written once and then referenced
+    let object = Rc::new(Value::Object(ObjectId(0), vec![], None));
+    let shared = Rc::new(Value::StrictArray(vec![object.clone(), object]));
+    let mut bytes = vec![];
+    AMF3Encoder::default()
+        .write_value_element(&mut bytes, &shared)
+        .expect("Failed to write array");
+    assert_eq!(PrettyArray(&bytes), PrettyArray(&objects.to_vec()));
+}
+
+#[test]
+pub fn test_record_references() {
+    use flash_lso::amf3::read::{AMF3Decoder, DecoderOptions};
+
+    // [o, a, o, a, a] where o = {} and a = []
+    let data = [
+        0x09, 0x0b, 0x01, 0x0a, 0x0b, 0x01, 0x01, 0x09, 0x01, 0x01, 0x0a, 0x02, 0x09, 0x04, 0x09,
+        0x04,
+    ];
+
+    let mut decoder = AMF3Decoder::default();
+    decoder.parse_single_element(&data).unwrap();
+    assert_eq!(decoder.reference_count(1), None);
+
+    let mut decoder = AMF3Decoder::default();
+    decoder.options = DecoderOptions {
+        record_references: true,
+        ..DecoderOptions::default()
+    };
+    let (_, value) = decoder.parse_single_element(&data).unwrap();
+    let object_id = match value.deref() {
+        Value::StrictArray(elements) => match elements[0].deref() {
+            Value::Object(id, _, _) => *id,
+            _ => panic!("Expected object"),
+        },
+        _ => panic!("Expected strict array"),
+    };
+
+    assert_eq!(decoder.object_index(object_id), Some(1));
+    assert_eq!(decoder.reference_count(0), Some(0));
+    assert_eq!(decoder.reference_count(1), Some(1));
+    assert_eq!(decoder.reference_count(2), Some(2));
+    assert_eq!(decoder.reference_count(3), None);
+}
+
+#[test]
+pub fn test_preserve_trailing() {
+    for data in [
+        &include_bytes!("sol/AS2-Demo.sol")[..],
+        &include_bytes!("sol/AS3-Dictionary-Demo.sol")[..],
+    ] {
+        // Append some data and update the length in the header to cover it
+        let trailing = b"META\x01\x02";
+        let mut data = data.to_vec();
+        data.extend_from_slice(trailing);
+        let length =
+            u32::from_be_bytes([data[2], data[3], data[4], data[5]]) + trailing.len() as u32;
+        data[2..6].copy_from_slice(&length.to_be_bytes());
+
+        assert!(Reader::default().parse(&data).is_err());
+
+        let mut reader = Reader {
+            preserve_trailing: true,
+            ..Reader::default()
+        };
+        let mut lso = reader.parse(&data).expect("Failed to parse lso");
+        assert_eq!(lso.trailing, trailing);
+
+        let bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+        assert_eq!(bytes, data);
+    }
+}
+
+#[test]
+pub fn test_validate() {
+    use flash_lso::errors::ValidationError;
+
+    let data = include_bytes!("sol/AS3-Demo.sol");
+    let sol = Reader::default().parse(data).unwrap();
+    assert_eq!(sol.validate(), Ok(()));
+
+    let long_name = "a".repeat(70000);
+    let sol = Lso::new(
+        vec![
+            Element::new(long_name.clone(), Rc::new(Value::Null)),
+            Element::new("int", Rc::new(Value::Integer(1))),
+            Element::new("wrapped", Rc::new(Value::AMF3(Rc::new(Value::Integer(1))))),
+        ],
+        "test",
+        AMFVersion::AMF0,
+    );
+    assert_eq!(
+        sol.validate(),
+        Err(vec![
+            ValidationError::NameTooLong {
+                path: format!("body.{}", long_name),
+                length: 70000,
+            },
+            ValidationError::UnsupportedValue {
+                path: "body.int".to_string(),
+                version: AMFVersion::AMF0,
+            },
+        ])
+    );
+
+    let sol = Lso::new(
+        vec![Element::new(
+            "names",
+            Rc::new(Value::VectorObject(
+                vec![
+                    Rc::new(Value::String("a".into())),
+                    Rc::new(Value::Integer(1)),
+                ],
+                "String".to_string(),
+                false,
+            )),
+        )],
+        "test",
+        FormatVersion::Unknown(2),
+    );
+    assert_eq!(
+        sol.validate(),
+        Err(vec![ValidationError::UnsupportedFormatVersion(
+            FormatVersion::Unknown(2)
+        )])
+    );
+
+    let mut sol = sol;
+    sol.header.format_version = FormatVersion::Amf3;
+    assert_eq!(
+        sol.validate(),
+        Err(vec![ValidationError::WrongVectorElement {
+            path: "body.names[1]".to_string(),
+            type_name: "String".into(),
+        }])
+    );
+}
+
+#[test]
+pub fn test_amf0_long_names() {
+    let mut sol = Lso::new(
+        vec![Element::new("a".repeat(70000), Rc::new(Value::Null))],
+        "test",
+        AMFVersion::AMF0,
+    );
+    let mut bytes = vec![];
+    let error = Writer::default()
+        .write_full(&mut bytes, &mut sol)
+        .expect_err("Long name should not be written");
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(error.to_string().contains(&"a".repeat(32)));
+
+    // Long string values use the long string marker instead
+    let long_string = "b".repeat(70000);
+    let mut sol = Lso::new(
+        vec![Element::new(
+            "long",
+            Rc::new(Value::String(long_string.clone().into())),
+        )],
+        "test",
+        AMFVersion::AMF0,
+    );
+    let bytes = flash_lso::write::write_to_bytes(&mut sol).expect("Failed to write lso");
+    let sol = Reader::default()
+        .parse(&bytes)
+        .expect("Failed to parse lso");
+    assert_eq!(*sol.body[0].value, Value::String(long_string.into()));
+}
+
+#[test]
+pub fn test_read_and_write_file() {
+    use flash_lso::errors::FileError;
+
+    let path = std::env::temp_dir().join(format!("flash-lso-test-{}.sol", std::process::id()));
+
+    let mut lso = flash_lso::read_file("tests/sol/AS2-Demo.sol").expect("Failed to read file");
+    flash_lso::write_file(&mut lso, &path).expect("Failed to write file");
+    assert_eq!(
+        std::fs::read(&path).unwrap(),
+        include_bytes!("sol/AS2-Demo.sol").to_vec()
+    );
+
+    std::fs::write(&path, [0x00, 0xbf, 0x00]).unwrap();
+    let error = flash_lso::read_file(&path).unwrap_err();
+    assert!(matches!(&error, FileError::Decode { path: p, .. } if p == &path));
+    assert!(error.to_string().contains(&path.display().to_string()));
+    std::fs::remove_file(&path).unwrap();
+
+    let error = flash_lso::read_file(&path).unwrap_err();
+    assert!(matches!(error, FileError::Io { path: p, .. } if p == path));
+}
+
+#[test]
+pub fn test_detect_amf_version() {
+    use flash_lso::read::sniff_amf_version;
+
+    for (data, version) in [
+        (&include_bytes!("sol/AS2-Demo.sol")[..], FormatVersion::Amf0),
+        (
+            &include_bytes!("sol/AS3-Boolean-Demo.sol")[..],
+            FormatVersion::Amf3,
+        ),
+    ] {
+        let expected = Reader::default().parse(data).unwrap();
+        let header_length = 2 + 4 + 10 + 2 + expected.header.name.len() + 3;
+        assert_eq!(
+            sniff_amf_version(&data[header_length + 1..]),
+            version.amf_version()
+        );
+
+        // Claim the other version in the header
+        let mut wrong = data.to_vec();
+        wrong[header_length] = if version == FormatVersion::Amf0 { 3 } else { 0 };
+
+        let mut reader = Reader::default();
+        assert_ne!(reader.parse(&wrong).ok().as_ref(), Some(&expected));
+        assert_eq!(reader.header_version_mismatch, None);
+
+        let mut reader = Reader {
+            detect_amf_version: true,
+            ..Reader::default()
+        };
+        assert_eq!(reader.parse(&wrong).unwrap(), expected);
+        assert_ne!(reader.header_version_mismatch, Some(version));
+        assert!(reader.header_version_mismatch.is_some());
+
+        // A correct header is left alone
+        let mut reader = Reader {
+            detect_amf_version: true,
+            ..Reader::default()
+        };
+        assert_eq!(reader.parse(data).unwrap(), expected);
+        assert_eq!(reader.header_version_mismatch, None);
+    }
+
+    assert_eq!(sniff_amf_version(&[]), None);
+}
+
+#[test]
+pub fn test_amf0_truthy_bool() {
+    use flash_lso::amf0::read::AMF0Decoder;
+    use flash_lso::amf0::write::AMF0Encoder;
+
+    let (rest, value) = AMF0Decoder::default()
+        .parse_single_element(&[0x01, 0x02])
+        .expect("Failed to parse bool");
+    assert!(rest.is_empty());
+    assert_eq!(value.deref(), &Value::Bool(true));
+
+    let mut bytes = vec![];
+    AMF0Encoder::default()
+        .write_value(&mut bytes, &value)
+        .expect("Failed to write bool");
+    assert_eq!(bytes, vec![0x01, 0x01]);
+}
+
+#[test]
+pub fn test_null_and_undefined_round_trip() {
+    use flash_lso::amf0::read::AMF0Decoder;
+    use flash_lso::amf0::write::AMF0Encoder;
+    use flash_lso::amf3::read::AMF3Decoder;
+    use flash_lso::amf3::write::AMF3Encoder;
+
+    for (value, amf0, amf3) in [(Value::Null, 0x05, 0x01), (Value::Undefined, 0x06, 0x00)] {
+        let value = Rc::new(value);
+
+        let mut bytes = vec![];
+        AMF0Encoder::default()
+            .write_value(&mut bytes, &value)
+            .unwrap();
+        assert_eq!(bytes, vec![amf0]);
+        let (_, read) = AMF0Decoder::default().parse_single_element(&bytes).unwrap();
+        assert_eq!(read, value);
+
+        let mut bytes = vec![];
+        AMF3Encoder::default()
+            .write_value_element(&mut bytes, &value)
+            .unwrap();
+        assert_eq!(bytes, vec![amf3]);
+        let (_, read) = AMF3Decoder::default().parse_single_element(&bytes).unwrap();
+        assert_eq!(read, value);
+    }
+
+    for version in [AMFVersion::AMF0, AMFVersion::AMF3] {
+        let mut lso = Lso::new(
+            vec![
+                Element::new("null", Rc::new(Value::Null)),
+                Element::new("undefined", Rc::new(Value::Undefined)),
+            ],
+            "test",
+            version,
+        );
+        let bytes = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+        assert_eq!(Reader::default().parse(&bytes).unwrap(), lso);
+    }
+}
+
+#[test]
+pub fn test_amf0_strict_array_declared_length() {
+    use flash_lso::amf0::read::AMF0Decoder;
+
+    let number = [0x00, 0x40, 0x09, 0x21, 0xfb, 0x54, 0x44, 0x2d, 0x18];
+    let array = |length: u32, elements: usize| {
+        let mut data = vec![0x0a];
+        data.extend_from_slice(&length.to_be_bytes());
+        for _ in 0..elements {
+            data.extend_from_slice(&number);
+        }
+        data
+    };
+
+    let data = array(2, 2);
+    let (rest, value) = AMF0Decoder::default()
+        .parse_single_element(&data)
+        .expect("Failed to parse array");
+    assert!(rest.is_empty());
+    assert_eq!(
+        value.deref(),
+        &Value::StrictArray(vec![
+            Rc::new(Value::Number(std::f64::consts::PI)),
+            Rc::new(Value::Number(std::f64::consts::PI))
+        ])
+    );
+
+    // Truncated, fewer elements than declared
+    let data = array(2, 1);
+    assert!(matches!(
+        AMF0Decoder::default().parse_single_element(&data),
+        Err(nom::Err::Error(Error::Nom(_, ErrorKind::Eof)))
+    ));
+
+    // More elements than there could be bytes for
+    let data = array(u32::MAX, 1);
+    assert!(matches!(
+        AMF0Decoder::default().parse_single_element(&data),
+        Err(nom::Err::Error(Error::Nom(_, ErrorKind::TooLarge)))
+    ));
+}
+
+#[test]
+pub fn test_amf3_regions_have_separate_reference_tables() {
+    // Two AMF0 body elements that switch to AMF3, each an array of a string and a reference to it
+    let region = |name: &str, s: &[u8; 3]| {
+        let mut data = vec![0x00, name.len() as u8];
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(&[0x11, 0x09, 0x05, 0x01, 0x06, 0x07]);
+        data.extend_from_slice(s);
+        data.extend_from_slice(&[0x06, 0x00, 0x00]);
+        data
+    };
+    let mut body = region("a", b"foo");
+    body.extend(region("b", b"bar"));
+
+    let mut data = vec![0x00, 0xbf];
+    let mut rest = vec![
+        0x54, 0x43, 0x53, 0x4f, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, b't', b'e', b's',
+        b't', 0x00, 0x00, 0x00, 0x00,
+    ];
+    rest.extend(body);
+    data.extend_from_slice(&(rest.len() as u32).to_be_bytes());
+    data.extend(rest);
+
+    let mut lso = Reader::default().parse(&data).expect("Failed to parse lso");
+    let strings = |s: &'static str| {
+        Rc::new(Value::AMF3(Rc::new(Value::StrictArray(vec![
+            Rc::new(Value::String(s.into())),
+            Rc::new(Value::String(s.into())),
+        ]))))
+    };
+    assert_eq!(
+        lso.body,
+        vec![
+            Element::new("a", strings("foo")),
+            Element::new("b", strings("bar"))
+        ]
+    );
+
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+    assert_eq!(PrettyArray(&bytes), PrettyArray(&data));
+}
+
+#[test]
+pub fn test_flatten() {
+    let string = |s: &'static str| Rc::new(Value::String(s.into()));
+    let lso = Lso::new(
+        vec![
+            Element::new(
+                "player",
+                Rc::new(Value::Object(
+                    ObjectId(1),
+                    vec![
+                        Element::new("name", string("bob")),
+                        Element::new(
+                            "items",
+                            Rc::new(Value::StrictArray(vec![
+                                Rc::new(Value::Integer(1)),
+                                Rc::new(Value::Object(
+                                    ObjectId(2),
+                                    vec![Element::new("id", string("sword"))],
+                                    None,
+                                )),
+                            ])),
+                        ),
+                        Element::new("empty", Rc::new(Value::StrictArray(vec![]))),
+                    ],
+                    None,
+                )),
+            ),
+            Element::new(
+                "wrapped",
+                Rc::new(Value::AMF3(Rc::new(Value::VectorObject(
+                    vec![string("a")],
+                    "String".to_string(),
+                    false,
+                )))),
+            ),
+            Element::new(
+                "dict",
+                Rc::new(Value::Dictionary(
+                    vec![(string("k"), Rc::new(Value::Bool(true)))],
+                    false,
+                )),
+            ),
+            Element::new("ints", Rc::new(Value::VectorInt(vec![1, 2], false))),
+        ],
+        "test",
+        AMFVersion::AMF3,
+    );
+
+    let flat: Vec<(String, Value)> = lso
+        .flatten()
+        .into_iter()
+        .map(|(path, value)| (path, value.clone()))
+        .collect();
+    assert_eq!(
+        flat,
+        vec![
+            ("player.name".to_string(), Value::String("bob".into())),
+            ("player.items.0".to_string(), Value::Integer(1)),
+            (
+                "player.items.1.id".to_string(),
+                Value::String("sword".into())
+            ),
+            ("wrapped.0".to_string(), Value::String("a".into())),
+            ("dict.0.key".to_string(), Value::String("k".into())),
+            ("dict.0.value".to_string(), Value::Bool(true)),
+            ("ints".to_string(), Value::VectorInt(vec![1, 2], false)),
+        ]
+    );
+}
+
+#[test]
+pub fn test_stats() {
+    let lso = Lso::new(
+        vec![
+            Element::new("n", Rc::new(Value::Number(1.0))),
+            Element::new(
+                "o",
+                Rc::new(Value::Object(
+                    ObjectId(1),
+                    vec![
+                        Element::new("n", Rc::new(Value::Number(2.0))),
+                        Element::new(
+                            "a",
+                            Rc::new(Value::StrictArray(vec![Rc::new(Value::String("s".into()))])),
+                        ),
+                    ],
+                    None,
+                )),
+            ),
+            Element::new("v", Rc::new(Value::VectorInt(vec![1, 2, 3], false))),
+        ],
+        "test",
+        AMFVersion::AMF3,
+    );
+
+    let stats = lso.stats();
+    assert_eq!(stats.nodes, 6);
+    assert_eq!(stats.max_depth, 3);
+    assert_eq!(stats.types.get("Number"), Some(&2));
+    assert_eq!(stats.types.get("Object"), Some(&1));
+    assert_eq!(stats.types.get("StrictArray"), Some(&1));
+    assert_eq!(stats.types.get("String"), Some(&1));
+    assert_eq!(stats.types.get("Vector<Int>"), Some(&1));
+    assert_eq!(stats.types.len(), 5);
+}
+
+#[test]
+pub fn test_max_depth_and_cycles() {
+    let data = include_bytes!("./amf/self-referential-object.amf");
+    let (_, obj) = flash_lso::amf3::read::AMF3Decoder::default()
+        .parse_single_element(data)
+        .expect("Failed to parse object");
+    assert!(obj.has_cycle());
+    assert_eq!(obj.max_depth(), 2);
+
+    let leaf = Rc::new(Value::Object(ObjectId(2), vec![], None));
+    let tree = Value::Object(
+        ObjectId(1),
+        vec![
+            Element::new("a", leaf),
+            Element::new(
+                "b",
+                Rc::new(Value::StrictArray(vec![Rc::new(
+                    Value::Amf3ObjectReference(ObjectId(2)),
+                )])),
+            ),
+        ],
+        None,
+    );
+    // A reference to a sibling is shared, but not a cycle
+    assert!(!tree.has_cycle());
+    assert_eq!(tree.max_depth(), 3);
+    assert_eq!(Value::Null.max_depth(), 1);
+}
+
+#[cfg(feature = "json")]
+#[test]
+pub fn test_json_references() {
+    use flash_lso::json::{from_json, to_json};
+
+    let shared = Rc::new(Value::StrictArray(vec![Rc::new(Value::Integer(1))]));
+    let lso = Lso::new(
+        vec![Element::new(
+            "root",
+            Rc::new(Value::Object(
+                ObjectId(0),
+                vec![
+                    Element::new("a", Rc::clone(&shared)),
+                    Element::new("b", shared),
+                    Element::new("self", Rc::new(Value::Amf3ObjectReference(ObjectId(0)))),
+                ],
+                None,
+            )),
+        )],
+        "Refs",
+        AMFVersion::AMF3,
+    );
+    assert!(lso.body[0].value.has_cycle());
+
+    let json = to_json(&lso).unwrap();
+    let elements = &json["body"][0]["value"]["Object"][1];
+    assert_eq!(elements[1]["value"], serde_json::json!({"$ref": "root.a"}));
+    assert_eq!(elements[2]["value"], serde_json::json!({"$ref": "root"}));
+
+    let text = serde_json::to_string(&json).unwrap();
+    let back = from_json(serde_json::from_str(&text).unwrap()).unwrap();
+    assert_eq!(back, lso);
+    let Value::Object(_, elements, _) = back.body[0].value.as_ref() else {
+        panic!("Expected an object");
+    };
+    assert!(Rc::ptr_eq(&elements[0].value, &elements[1].value));
+
+    assert!(from_json(serde_json::json!({
+        "header": serde_json::to_value(&lso.header).unwrap(),
+        "body": [{"name": "x", "value": {"$ref": "missing"}}],
+    }))
+    .is_err());
+}
+
+#[cfg(feature = "json")]
+#[test]
+pub fn test_json_byte_arrays() {
+    use flash_lso::json::{from_json, to_json_with_options, ByteArrayRepr, JsonOptions};
+
+    let lso = Lso::new(
+        vec![Element::new(
+            "bytes",
+            Rc::new(Value::ByteArray(vec![1, 2, 0xFF, 0x10].into())),
+        )],
+        "Bytes",
+        AMFVersion::AMF3,
+    );
+
+    for (byte_array, expected) in [
+        (ByteArrayRepr::IntArray, serde_json::json!([1, 2, 255, 16])),
+        (ByteArrayRepr::Hex, serde_json::json!({"hex": "0102ff10"})),
+        (
+            ByteArrayRepr::Base64,
+            serde_json::json!({"base64": "AQL/EA=="}),
+        ),
+    ] {
+        let json = to_json_with_options(&lso, JsonOptions { byte_array }).unwrap();
+        assert_eq!(json["body"][0]["value"]["ByteArray"], expected);
+        assert_eq!(from_json(json).unwrap(), lso);
+    }
+
+    let invalid = |bytes: serde_json::Value| {
+        from_json(serde_json::json!({
+            "header": serde_json::to_value(&lso.header).unwrap(),
+            "body": [{"name": "x", "value": {"ByteArray": bytes}}],
+        }))
+        .is_err()
+    };
+    assert!(invalid(serde_json::json!({"hex": "0g"})));
+    assert!(invalid(serde_json::json!({"hex": "012"})));
+    assert!(invalid(serde_json::json!({"base64": "A!"})));
+    assert!(invalid(serde_json::json!({"octal": "1"})));
+}
+
+#[cfg(feature = "json")]
+#[test]
+pub fn test_json_value_round_trip() {
+    use flash_lso::json::{value_from_json, value_to_json};
+
+    let class_def = Some(ClassDefinition::default_with_name("Point".to_string()));
+    let point = |x: i32| {
+        Rc::new(Value::Object(
+            ObjectId(1),
+            vec![Element::new("x", Value::Integer(x))],
+            class_def.clone(),
+        ))
+    };
+    let values = vec![
+        Value::Number(1.0),
+        Value::Number(-0.5),
+        Value::Number(f64::NAN),
+        Value::Number(f64::NEG_INFINITY),
+        Value::Bool(true),
+        Value::String("text".into()),
+        Value::Null,
+        Value::Undefined,
+        Value::Unsupported,
+        Value::Integer(-7),
+        Value::Object(ObjectId(0), vec![Element::new("p", point(3))], None),
+        Value::Custom(
+            vec![Element::new("c", Value::Integer(1))],
+            vec![Element::new("d", Value::Null)],
+            class_def.clone(),
+        ),
+        Value::ECMAArray(
+            vec![Rc::new(Value::Integer(0))],
+            vec![Element::new("key", Value::String("v".into()))],
+            4,
+        ),
+        Value::StrictArray(vec![Rc::new(Value::Number(2.0)), Rc::new(Value::Null)]),
+        Value::Date(1329777054281.0, Some(60)),
+        Value::Date(f64::NAN, None),
+        Value::XML("<a/>".into(), true),
+        Value::AMF3(point(4)),
+        Value::ByteArray(vec![0, 1, 0xFF].into()),
+        Value::VectorInt(vec![-1, 2], true),
+        Value::VectorUInt(vec![1, u32::MAX], false),
+        Value::VectorDouble(vec![0.5, f64::INFINITY, 3.0], true),
+        Value::VectorObject(vec![point(5)], "Point".to_string(), false),
+        Value::Dictionary(vec![(Rc::new(Value::Integer(1)), point(6))], true),
+        Value::Reference(serde_json::from_value(serde_json::json!(2)).unwrap()),
+        Value::Amf3ObjectReference(ObjectId(1)),
+    ];
+    let body: Vec<Element<'_>> = values
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| Element::new(index.to_string(), value))
+        .collect();
+    let lso = Lso::new(body, "Values", AMFVersion::AMF3);
+
+    for element in &lso.body {
+        let json = value_to_json(&element.value);
+        let text = serde_json::to_string(&json).unwrap();
+        let back = value_from_json(&serde_json::from_str(&text).unwrap()).unwrap();
+        // NaN isn't equal to itself, so values containing it are compared by the json written for both
+        if *element.value == *element.value {
+            assert_eq!(back, *element.value, "{}", element.name);
+        }
+        assert_eq!(value_to_json(&back), json, "{}", element.name);
+    }
+
+    assert_eq!(value_to_json(&Value::Integer(1)), serde_json::json!(1));
+    assert_eq!(
+        value_to_json(&Value::ByteArray(vec![1, 2].into())),
+        serde_json::json!({"__type": "bytearray", "data": "AQI="})
+    );
+    assert_eq!(
+        value_from_json(&serde_json::json!(1.0)).unwrap(),
+        Value::Number(1.0)
+    );
+    assert!(value_from_json(&serde_json::json!({"__type": "missing"})).is_err());
+    assert!(value_from_json(&serde_json::json!(1u64 << 40)).is_err());
+}
+
+#[test]
+pub fn test_to_dot() {
+    let data = include_bytes!("./amf/self-referential-object.amf");
+    let (_, obj) = flash_lso::amf3::read::AMF3Decoder::default()
+        .parse_single_element(data)
+        .expect("Failed to parse object");
+    let dot = flash_lso::viz::to_dot(&obj);
+    assert!(dot.starts_with("digraph {\n"));
+    assert!(dot.contains("n0 -> n0"));
+
+    let shared = Rc::new(Value::String("x\"y".into()));
+    let array = Value::StrictArray(vec![Rc::clone(&shared), shared]);
+    assert_eq!(
+        flash_lso::viz::to_dot(&array),
+        r#"digraph {
+  n0 [label="StrictArray"];
+  n1 [label="String: \"x\"y\""];
+  n0 -> n1 [label="0"];
+  n0 -> n1 [label="1"];
+}
+"#
+    );
+}
+
+#[test]
+pub fn test_into_owned() {
+    let data = include_bytes!("./sol/AS3-String-Demo.sol");
+    let lso = Reader::default().parse(data).expect("Failed to parse lso");
+    assert!(lso
+        .body
+        .iter()
+        .any(|e| matches!(e.value(), Value::String(std::borrow::Cow::Borrowed(_)))));
+
+    let owned = lso.clone().into_owned();
+    assert_eq!(owned, lso);
+    assert!(!owned
+        .body
+        .iter()
+        .any(|e| matches!(e.value(), Value::String(std::borrow::Cow::Borrowed(_)))));
+
+    let shared = Rc::new(Value::String("shared".into()));
+    let array = Value::StrictArray(vec![Rc::clone(&shared), shared]).into_owned();
+    let Value::StrictArray(values) = array else {
+        panic!("Expected an array");
+    };
+    assert!(Rc::ptr_eq(&values[0], &values[1]));
+}
+
+#[test]
+pub fn test_markers() {
+    use flash_lso::markers;
+
+    let mut amf0 = Vec::new();
+    flash_lso::amf0::write::write_value(&mut amf0, &Rc::new(Value::Bool(true))).unwrap();
+    assert_eq!(amf0[0], markers::amf0::BOOLEAN);
+
+    let mut amf3 = Vec::new();
+    flash_lso::amf3::write::AMF3Encoder::default()
+        .write_value_element(&mut amf3, &Rc::new(Value::ByteArray(vec![1].into())))
+        .unwrap();
+    assert_eq!(amf3[0], markers::amf3::BYTE_ARRAY);
+}
+
+#[test]
+pub fn test_decode_encode_value() {
+    let value = Value::StrictArray(vec![
+        Rc::new(Value::String("a".into())),
+        Rc::new(Value::Number(1.5)),
+    ]);
+
+    for version in [AMFVersion::AMF0, AMFVersion::AMF3] {
+        let mut bytes = flash_lso::encode_value(&value, version).unwrap();
+        bytes.push(0xFF);
+        let (rest, decoded) = flash_lso::decode_value(&bytes, version).unwrap();
+        assert_eq!(rest, &[0xFF]);
+        assert_eq!(decoded, value);
+    }
+
+    assert!(flash_lso::decode_value(&[0xFF], AMFVersion::AMF3).is_err());
+}
+
+#[test]
+pub fn test_amf3_integer_range() {
+    let round_trip = |i: i32| {
+        let bytes = flash_lso::encode_value(&Value::Integer(i), AMFVersion::AMF3).unwrap();
+        flash_lso::decode_value(&bytes, AMFVersion::AMF3)
+            .unwrap()
+            .1
+            .into_owned()
+    };
+
+    for i in [0, 0x0FFF_FFFF, -0x1000_0000] {
+        assert_eq!(round_trip(i), Value::Integer(i));
+    }
+    for i in [0x1000_0000, -0x1000_0001, i32::MAX, i32::MIN] {
+        assert_eq!(round_trip(i), Value::Number(f64::from(i)));
+    }
+}
+
+#[test]
+pub fn test_amf3_negative_integers() {
+    for i in [
+        -1,
+        -2,
+        -0x7F,
+        -0x80,
+        -0x1000_0000,
+        -0x0FFF_FFFF,
+        0x0FFF_FFFF,
+    ] {
+        let bytes = flash_lso::encode_value(&Value::Integer(i), AMFVersion::AMF3).unwrap();
+        let (rest, value) = flash_lso::decode_value(&bytes, AMFVersion::AMF3).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(value, Value::Integer(i));
+    }
+
+    // Negative values always take the full 4 bytes
+    assert_eq!(
+        flash_lso::encode_value(&Value::Integer(-1), AMFVersion::AMF3).unwrap(),
+        vec![0x04, 0xFF, 0xFF, 0xFF, 0xFF]
+    );
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+pub fn test_msgpack() {
+    use flash_lso::msgpack::from_msgpack;
+
+    let data = include_bytes!("./sol/AS3-Demo.sol");
+    let lso = Reader::default().parse(data).expect("Failed to parse lso");
+    for element in &lso.body {
+        // Compared as bytes as the values contain NaN
+        let bytes = element.value().to_msgpack().unwrap();
+        assert_eq!(from_msgpack(&bytes).unwrap().to_msgpack().unwrap(), bytes);
+    }
+
+    // Byte arrays are written as binary rather than as an array of numbers
+    let bytes = Value::ByteArray(vec![1, 2, 3].into()).to_msgpack().unwrap();
+    assert_eq!(
+        bytes,
+        [&[0x81, 0xa9][..], b"ByteArray", &[0xc4, 0x03, 1, 2, 3]].concat()
+    );
+    assert_eq!(
+        Value::Null.to_msgpack().unwrap(),
+        [&[0xa4][..], b"Null"].concat()
+    );
+
+    let date = Value::Date(1_600_000_000_000.5, Some(60));
+    assert_eq!(from_msgpack(&date.to_msgpack().unwrap()).unwrap(), date);
+    let nan = from_msgpack(&Value::Number(f64::NAN).to_msgpack().unwrap()).unwrap();
+    assert!(matches!(nan, Value::Number(n) if n.is_nan()));
+
+    assert!(from_msgpack(&bytes[..bytes.len() - 1]).is_err());
+    assert!(from_msgpack(&[bytes.as_slice(), &[0]].concat()).is_err());
+}
+
+#[test]
+pub fn test_from_data_url() {
+    use flash_lso::errors::DataUrlError;
+
+    fn base64(data: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        data.chunks(3)
+            .flat_map(|chunk| {
+                let n = chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0u32, |n, (i, b)| n | (u32::from(*b) << (16 - 8 * i)));
+                (0..4).map(move |i| {
+                    if i <= chunk.len() {
+                        ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char
+                    } else {
+                        '='
+                    }
+                })
+            })
+            .collect()
+    }
+
+    let data = include_bytes!("./sol/AS2-Demo.sol");
+    let expected = Reader::default().parse(data).unwrap();
+    let encoded = base64(data);
+
+    let url = format!("data:application/octet-stream;base64,{}", encoded);
+    assert_eq!(flash_lso::from_data_url(&url).unwrap(), expected);
+    assert_eq!(flash_lso::from_data_url(&encoded).unwrap(), expected);
+    assert_eq!(
+        flash_lso::from_data_url(encoded.trim_end_matches('=')).unwrap(),
+        expected
+    );
+
+    assert_eq!(
+        flash_lso::from_data_url("data:text/plain,hello"),
+        Err(DataUrlError::NotBase64)
+    );
+    assert_eq!(
+        flash_lso::from_data_url("data:;base64,AA*A"),
+        Err(DataUrlError::InvalidBase64(2))
+    );
+    assert!(matches!(
+        flash_lso::from_data_url("AAAA"),
+        Err(DataUrlError::Decode(_))
+    ));
+}
+
+#[cfg(feature = "compression")]
+#[test]
+pub fn test_compressed_files() {
+    use flash_lso::compression::{compress, decompress, Compression};
+
+    let plain = include_bytes!("sol/AS3-Dictionary-Demo.sol");
+
+    for (path, compression) in [
+        ("tests/sol/AS3-Dictionary-Demo.sol.zlib", Compression::Zlib),
+        ("tests/sol/AS3-Dictionary-Demo.sol.gz", Compression::Gzip),
+    ] {
+        let mut reader = Reader::default();
+        let mut lso = reader.parse_file(path).expect("Failed to read file");
+        assert_eq!(reader.compression, Some(compression));
+        assert_eq!(lso, Reader::default().parse(plain).unwrap());
+
+        let mut writer = Writer {
+            compression: reader.compression,
+            ..Writer::default()
+        };
+        let mut bytes = vec![];
+        writer.write_full(&mut bytes, &mut lso).unwrap();
+        assert_eq!(Compression::detect(&bytes), Some(compression));
+        assert_eq!(decompress(&bytes).unwrap().0, &plain[..]);
+    }
+
+    // Short data is compressed with the fixed Huffman codes
+    let fixed = [120, 218, 75, 76, 74, 78, 68, 66, 0, 45, 245, 5, 191];
+    assert_eq!(decompress(&fixed).unwrap().0, &b"abcabcabcabcabc"[..]);
+    assert!(decompress(&fixed[..fixed.len() - 1]).is_err());
+    assert!(decompress(&compress(&[], Compression::Gzip))
+        .unwrap()
+        .0
+        .is_empty());
+
+    let mut reader = Reader::default();
+    reader
+        .parse_file("tests/sol/AS3-Dictionary-Demo.sol")
+        .unwrap();
+    assert_eq!(reader.compression, None);
+}
+
+/// Round trips of single values through `encode_value` and `decode_value`
+mod round_trip {
+    use flash_lso::types::{AMFVersion, Attribute, ClassDefinition, Element, ObjectId, Value};
+    use flash_lso::{decode_value, encode_value};
+    use std::rc::Rc;
+
+    /// Check that `decode(encode(value)) == value` and that encoding the decoded value gives the same bytes
+    fn assert_round_trip(value: Value<'static>, version: AMFVersion) {
+        let bytes = encode_value(&value, version).expect("Failed to encode value");
+        let (rest, decoded) = decode_value(&bytes, version).expect("Failed to decode value");
+        assert!(rest.is_empty(), "{} bytes left over", rest.len());
+        assert_eq!(decoded, value);
+        assert_eq!(
+            encode_value(&decoded, version).expect("Failed to encode decoded value"),
+            bytes
+        );
+    }
+
+    /// Check that `encode(decode(bytes)) == bytes`
+    fn assert_bytes_round_trip(bytes: &[u8], version: AMFVersion) {
+        let (rest, decoded) = decode_value(bytes, version).expect("Failed to decode value");
+        assert!(rest.is_empty(), "{} bytes left over", rest.len());
+        assert_eq!(
+            encode_value(&decoded, version).expect("Failed to encode value"),
+            bytes
+        );
+    }
+
+    fn string(s: &'static str) -> Rc<Value<'static>> {
+        Rc::new(Value::String(s.into()))
+    }
+
+    fn class(name: &str, attributes: &[Attribute], static_properties: &[&str]) -> ClassDefinition {
+        ClassDefinition {
+            name: name.to_string(),
+            attributes: attributes.iter().copied().collect(),
+            static_properties: static_properties.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    /// Values that are written the same way by both versions
+    fn common_values() -> Vec<Value<'static>> {
+        vec![
+            Value::Number(0.0),
+            Value::Number(-1.5),
+            Value::Number(f64::MAX),
+            Value::Number(f64::INFINITY),
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::String("".into()),
+            Value::String("hello 😀 𠀀".into()),
+            Value::Null,
+            Value::Undefined,
+            Value::XML("<a><b/></a>".into(), true),
+            Value::StrictArray(vec![]),
+            Value::StrictArray(vec![
+                string("a"),
+                Rc::new(Value::Number(1.0)),
+                Rc::new(Value::Null),
+            ]),
+            Value::StrictArray(vec![Rc::new(Value::StrictArray(vec![string("nested")]))]),
+        ]
+    }
+
+    #[test]
+    fn amf0_values() {
+        let mut values = common_values();
+        values.extend([
+            Value::String("x".repeat(70_000).into()),
+            Value::Date(1_600_000_000_000.0, Some(60)),
+            Value::Date(0.0, Some(0)),
+            Value::Object(
+                ObjectId::INVALID,
+                vec![
+                    Element::new("a", string("b")),
+                    Element::new("c", Rc::new(Value::Bool(true))),
+                ],
+                None,
+            ),
+            Value::Object(
+                ObjectId::INVALID,
+                vec![Element::new("x", Rc::new(Value::Number(1.0)))],
+                Some(class("com.example.Point", &[], &[])),
+            ),
+            Value::ECMAArray(vec![], vec![Element::new("key", string("value"))], 1),
+            Value::AMF3(Rc::new(Value::Integer(5))),
+            Value::AMF3(Rc::new(Value::VectorInt(vec![1, -1], false))),
+        ]);
+
+        for value in values {
+            assert_round_trip(value, AMFVersion::AMF0);
+        }
+    }
+
+    #[test]
+    fn amf3_values() {
+        let mut values = common_values();
+        values.extend([
+            Value::Integer(0),
+            Value::Integer(-1),
+            Value::Integer(0x0FFF_FFFF),
+            Value::Integer(-0x1000_0000),
+            Value::Date(1_600_000_000_000.0, None),
+            Value::XML("<a/>".into(), false),
+            Value::ByteArray(vec![].into()),
+            Value::ByteArray(vec![0, 1, 2, 255].into()),
+            Value::VectorInt(vec![0, -1, i32::MAX, i32::MIN], false),
+            Value::VectorInt(vec![1], true),
+            Value::VectorUInt(vec![0, u32::MAX], false),
+            Value::VectorDouble(vec![0.5, -0.0, f64::MIN], true),
+            Value::ECMAArray(
+                vec![string("dense")],
+                vec![Element::new("key", string("value"))],
+                1,
+            ),
+        ]);
+
+        for value in values {
+            assert_round_trip(value, AMFVersion::AMF3);
+        }
+    }
+
+    #[test]
+    fn amf3_objects() {
+        let dynamic = Value::Object(
+            ObjectId(1),
+            vec![Element::new("a", string("b"))],
+            Some(class("", &[Attribute::Dynamic], &[])),
+        );
+        let sealed = Value::Object(
+            ObjectId(1),
+            vec![
+                Element::new("x", Rc::new(Value::Integer(1))),
+                Element::new("y", Rc::new(Value::Integer(2))),
+            ],
+            Some(class("com.example.Point", &[], &["x", "y"])),
+        );
+        let nested = Value::Object(
+            ObjectId(1),
+            vec![Element::new(
+                "child",
+                Rc::new(Value::Object(
+                    ObjectId(2),
+                    vec![Element::new("a", Rc::new(Value::Null))],
+                    Some(class("", &[Attribute::Dynamic], &[])),
+                )),
+            )],
+            Some(class("", &[Attribute::Dynamic], &[])),
+        );
+        let vector = Value::VectorObject(
+            vec![Rc::new(Value::Object(
+                ObjectId(1),
+                vec![],
+                Some(class("com.example.Item", &[], &[])),
+            ))],
+            "com.example.Item".to_string(),
+            false,
+        );
+        let dictionary = Value::Dictionary(
+            vec![
+                (string("key"), Rc::new(Value::Integer(1))),
+                (Rc::new(Value::Integer(2)), string("value")),
+            ],
+            true,
+        );
+        let cyclic = Value::Object(
+            ObjectId(1),
+            vec![Element::new(
+                "self",
+                Rc::new(Value::Amf3ObjectReference(ObjectId(1))),
+            )],
+            Some(class("", &[Attribute::Dynamic], &[])),
+        );
+
+        for value in [dynamic, sealed, nested, vector, dictionary, cyclic] {
+            assert_round_trip(value, AMFVersion::AMF3);
+        }
+    }
+
+    #[cfg(feature = "flex")]
+    #[test]
+    fn amf3_externalizable() {
+        use flash_lso::amf3::read::AMF3Decoder;
+        use flash_lso::amf3::write::AMF3Encoder;
+        use flash_lso::extra::flex;
+
+        let value = Rc::new(Value::Custom(
+            vec![Element::new(
+                "data",
+                Rc::new(Value::StrictArray(vec![string("a"), string("b")])),
+            )],
+            vec![],
+            Some(class(
+                "flex.messaging.io.ArrayCollection",
+                &[Attribute::External],
+                &[],
+            )),
+        ));
+
+        let mut encoder = AMF3Encoder::default();
+        flex::write::register_encoders(&mut encoder);
+        let mut bytes = Vec::new();
+        encoder.write_value_element(&mut bytes, &value).unwrap();
+
+        let mut decoder = AMF3Decoder::default();
+        flex::read::register_decoders(&mut decoder);
+        let (rest, decoded) = decoder.parse_single_element(&bytes).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(decoded, value);
+    }
+
+    #[test]
+    fn embedded_amf3_in_byte_array() {
+        let inner = Value::StrictArray(vec![string("a"), Rc::new(Value::Integer(7))]);
+        let bytes = encode_value(&inner, AMFVersion::AMF3).unwrap();
+        assert_round_trip(Value::ByteArray(bytes.clone().into()), AMFVersion::AMF3);
+
+        let outer_bytes = encode_value(&Value::ByteArray(bytes.into()), AMFVersion::AMF3).unwrap();
+        let (_, outer) = decode_value(&outer_bytes, AMFVersion::AMF3).unwrap();
+        let Value::ByteArray(embedded) = outer else {
+            panic!("Expected a byte array");
+        };
+        assert_eq!(decode_value(&embedded, AMFVersion::AMF3).unwrap().1, inner);
+    }
+
+    #[test]
+    fn fixtures() {
+        assert_bytes_round_trip(
+            include_bytes!("./amf/self-referential-object.amf"),
+            AMFVersion::AMF3,
+        );
+        assert_bytes_round_trip(
+            include_bytes!("./amf/LearnToFly3.profileData.saveString.amf"),
+            AMFVersion::AMF3,
+        );
+    }
+}
+
+#[test]
+pub fn test_error_conversions() {
+    fn read_name(bytes: &[u8]) -> Result<&str, Error<'_>> {
+        Ok(std::str::from_utf8(bytes)?)
+    }
+    fn fail_io() -> Result<(), Error<'static>> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "bad input",
+        ))?
+    }
+
+    assert_eq!(read_name(b"abc"), Ok("abc"));
+    assert!(matches!(read_name(&[0xFF]), Err(Error::InvalidUtf8(_))));
+    assert_eq!(
+        fail_io(),
+        Err(Error::IoError(
+            "bad input".to_string(),
+            std::io::ErrorKind::InvalidInput
+        ))
+    );
+}
+
+#[test]
+pub fn test_framed_reader() {
+    use flash_lso::errors::FrameError;
+    use flash_lso::FramedReader;
+
+    let values = vec![
+        Value::Number(1.5),
+        Value::String("hello".into()),
+        Value::StrictArray(vec![Rc::new(Value::Bool(true)), Rc::new(Value::Null)]),
+    ];
+    let mut data = Vec::new();
+    for value in &values {
+        let bytes = flash_lso::encode_value(value, AMFVersion::AMF0).expect("Failed to encode");
+        data.extend((bytes.len() as u32).to_be_bytes());
+        data.extend(bytes);
+    }
+
+    let read: Vec<Value<'static>> = FramedReader::new(&data[..], AMFVersion::AMF0)
+        .collect::<Result<_, _>>()
+        .expect("Failed to read frames");
+    assert_eq!(read, values);
+
+    // A stream that ends inside a frame gives an error after the complete frames
+    let mut reader = FramedReader::new(&data[..data.len() - 1], AMFVersion::AMF0);
+    assert!(matches!(reader.next(), Some(Ok(Value::Number(_)))));
+    assert!(matches!(reader.next(), Some(Ok(Value::String(_)))));
+    assert!(matches!(reader.next(), Some(Err(FrameError::Io(_)))));
+    assert!(reader.next().is_none());
+
+    // A frame must contain exactly one value
+    let data = [0, 0, 0, 2, 0x05, 0x05];
+    let mut reader = FramedReader::new(&data[..], AMFVersion::AMF0);
+    assert!(matches!(
+        reader.next(),
+        Some(Err(FrameError::TrailingBytes(1)))
+    ));
+
+    let data = [0, 0, 0, 1, 0x04];
+    let mut reader = FramedReader::new(&data[..], AMFVersion::AMF3);
+    assert!(matches!(reader.next(), Some(Err(FrameError::Decode(_)))));
+}
+
+#[test]
+pub fn test_stream_reader() {
+    use flash_lso::errors::StreamError;
+    use flash_lso::stream;
+
+    // Every file that can be read in one go writes the same bytes when streamed, compared as bytes as NaN isn't equal
+    // to itself
+    for entry in std::fs::read_dir("tests/sol").expect("Failed to list files") {
+        let path = entry.expect("Failed to list files").path();
+        if path.extension().and_then(|e| e.to_str()) != Some("sol") {
+            continue;
+        }
+        let data = std::fs::read(&path).expect("Failed to read file");
+        let Ok(mut lso) = Reader::default().parse(&data) else {
+            continue;
+        };
+
+        let reader = stream::Reader::new(&data[..]).expect("Failed to read header");
+        let mut streamed = Lso {
+            header: reader.header().clone(),
+            body: reader
+                .collect::<Result<_, _>>()
+                .unwrap_or_else(|e| panic!("Failed to stream {}: {}", path.display(), e)),
+            trailing: Vec::new(),
+            surrogate_strings: Default::default(),
+        };
+        assert_eq!(
+            flash_lso::write::write_to_bytes(&mut streamed).ok(),
+            flash_lso::write::write_to_bytes(&mut lso).ok(),
+            "{}",
+            path.display()
+        );
+    }
+
+    // Values referenced from a later element are shared with the earlier one
+    let shared = Rc::new(Value::StrictArray(vec![Rc::new(Value::Integer(1))]));
+    let mut lso = Lso::new(
+        vec![
+            Element::new("first", Rc::clone(&shared)),
+            Element::new("second", Value::StrictArray(vec![shared])),
+        ],
+        "Stream",
+        AMFVersion::AMF3,
+    );
+    let data = flash_lso::write::write_to_bytes(&mut lso).expect("Failed to write lso");
+    let body: Vec<Element<'static>> = stream::Reader::new(&data[..])
+        .expect("Failed to read header")
+        .collect::<Result<_, _>>()
+        .expect("Failed to stream lso");
+    let Value::StrictArray(items) = body[1].value() else {
+        panic!("Expected an array");
+    };
+    assert!(Rc::ptr_eq(&body[0].value, &items[0]));
+
+    // A truncated stream gives the complete elements, then an error at the end of the data
+    let mut reader = stream::Reader::new(&data[..data.len() - 2]).expect("Failed to read header");
+    assert!(matches!(reader.next(), Some(Ok(_))));
+    let Some(Err(StreamError::Decode(error))) = reader.next() else {
+        panic!("Expected a decode error");
+    };
+    assert_eq!(error.reason, flash_lso::errors::DecodeReason::UnexpectedEof);
+    assert_eq!(error.offset, Some(data.len() - 2));
+    assert!(reader.next().is_none());
+
+    assert!(matches!(
+        stream::Reader::new(&data[..10]),
+        Err(StreamError::Decode(_))
+    ));
+
+    // AMF0 references can be resolved once the value they refer to has been read
+    let data = include_bytes!("sol/AS2-Reference-Demo.sol");
+    let mut reader = stream::Reader::new(&data[..]).expect("Failed to read header");
+    let items = reader
+        .next()
+        .expect("Missing element")
+        .expect("Failed to stream lso");
+    let refs = reader
+        .next()
+        .expect("Missing element")
+        .expect("Failed to stream lso");
+    let Value::ECMAArray(_, refs, _) = refs.value() else {
+        panic!("Expected an array of references");
+    };
+    let Value::Reference(reference) = refs[0].value() else {
+        panic!("Expected a reference");
+    };
+    let resolved = reader
+        .resolve_reference(*reference)
+        .expect("Failed to resolve reference");
+    let Value::StrictArray(items) = items.value() else {
+        panic!("Expected an array of items");
+    };
+    assert!(items.iter().any(|item| Rc::ptr_eq(item, &resolved)));
+}
+
+#[test]
+pub fn test_amf0_references_in_arrays() {
+    let data = include_bytes!("sol/AS2-Reference-Demo.sol");
+    let mut reader = Reader::default();
+    let sol = reader.parse(data).expect("Failed to parse lso");
+
+    let Value::StrictArray(items) = sol.body[0].value() else {
+        panic!("Expected an array of items");
+    };
+    let Value::ECMAArray(_, refs, _) = sol.body[1].value() else {
+        panic!("Expected an array of references");
+    };
+
+    // The body is reference 0, so the items array is 1 and the objects in it are 2 and 3
+    let resolved: Vec<_> = refs
+        .iter()
+        .map(|e| match e.value() {
+            Value::Reference(r) => reader
+                .amf0_decoder
+                .resolve_reference(*r)
+                .expect("Reference not resolved"),
+            other => panic!("Expected a reference, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(resolved[0], items[0]);
+    assert_eq!(resolved[1], items[1]);
+    assert_eq!(resolved[2], sol.body[0].value);
+
+    // Outside of a body references start at 0, and can refer to a value that contains them
+    let mut decoder = flash_lso::amf0::read::AMF0Decoder::default();
+    let (_, array) = decoder
+        .parse_single_element(&[0x0A, 0x00, 0x00, 0x00, 0x01, 0x07, 0x00, 0x00])
+        .expect("Failed to parse array");
+    assert!(
+        matches!(array.deref(), Value::StrictArray(v) if matches!(v[0].deref(), Value::Reference(_)))
+    );
+
+    // A reference to a value that hasn't been read yet is an error
+    assert!(matches!(
+        flash_lso::amf0::read::AMF0Decoder::default().parse_single_element(&[0x07, 0x00, 0x00]),
+        Err(nom::Err::Error(Error::InvalidReference(0)))
+    ));
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+pub fn test_parse_many() {
+    let files = vec![
+        include_bytes!("sol/AS2-Demo.sol").to_vec(),
+        vec![0x00, 0xbf],
+        include_bytes!("sol/AS3-Dictionary-Demo.sol").to_vec(),
+    ];
+    let lengths = flash_lso::par::parse_many(&files, |lso| lso.map(|lso| lso.body.len()).ok());
+    assert_eq!(lengths, vec![Some(16), None, Some(1)]);
+}
+
+#[test]
+pub fn test_parse_annotated() {
+    let data = include_bytes!("sol/AS2-Demo.sol");
+    let (lso, spans) = Reader::default()
+        .parse_annotated(data)
+        .expect("Failed to parse lso file");
+    assert_eq!(lso, Reader::default().parse(data).unwrap());
+
+    for (path, value) in lso.flatten() {
+        let range = spans.get(&path).expect("No span for value");
+        assert!(range.end <= data.len());
+        if let Value::Number(n) = value {
+            assert_eq!(data[range.start], 0x00);
+            assert_eq!(&data[range.start + 1..range.end], &n.to_be_bytes());
+        }
+    }
+
+    let data = include_bytes!("sol/AS3-Dictionary-Demo.sol");
+    let (lso, spans) = Reader::default().parse_annotated(data).unwrap();
+    for (path, _) in lso.flatten() {
+        assert!(spans.get(&path).is_some(), "No span for {}", path);
+    }
+    let dict = spans.get(&lso.body[0].name).unwrap();
+    assert_eq!(data[dict.start], 0x11);
+}
+
+#[test]
+pub fn test_value_ordering() {
+    use std::cmp::Ordering;
+
+    assert!(Value::Integer(1) < Value::Number(1.5));
+    assert!(Value::Number(-2.0) < Value::Integer(-1));
+    assert!(Value::Integer(3) > Value::Integer(2));
+    assert_eq!(
+        Value::Integer(1).partial_cmp(&Value::Number(1.0)),
+        Some(Ordering::Less)
+    );
+    assert_eq!(
+        Value::Number(f64::NAN).partial_cmp(&Value::Number(f64::NAN)),
+        None
+    );
+    assert_eq!(Value::Number(1.0).partial_cmp(&Value::Bool(true)), None);
+    assert_eq!(Value::Null.partial_cmp(&Value::Undefined), None);
+    assert_eq!(Value::Null.partial_cmp(&Value::Null), Some(Ordering::Equal));
+
+    let mut values = vec![
+        Value::Number(2.5),
+        Value::Integer(-4),
+        Value::Number(1.0),
+        Value::Integer(1),
+    ];
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(
+        values,
+        vec![
+            Value::Integer(-4),
+            Value::Integer(1),
+            Value::Number(1.0),
+            Value::Number(2.5)
+        ]
+    );
+    assert_eq!(values.iter().filter(|v| **v > Value::Integer(1)).count(), 2);
+}
+
+#[test]
+pub fn test_lso_pointer() {
+    let item = Rc::new(Value::Object(
+        ObjectId::INVALID,
+        vec![Element::new("name", Rc::new(Value::String("sword".into())))],
+        None,
+    ));
+    let player = Value::Object(
+        ObjectId::INVALID,
+        vec![
+            Element::new(
+                "inventory",
+                Rc::new(Value::StrictArray(vec![item.clone(), item])),
+            ),
+            Element::new("a/b~c", Rc::new(Value::Integer(7))),
+            Element::new(
+                "seen",
+                Rc::new(Value::AMF3(Rc::new(Value::Dictionary(
+                    vec![(
+                        Rc::new(Value::String("level".into())),
+                        Rc::new(Value::Number(3.0)),
+                    )],
+                    false,
+                )))),
+            ),
+        ],
+        None,
+    );
+    let mut lso = Lso::new(
+        vec![Element::new("player", Rc::new(player))],
+        "test",
+        AMFVersion::AMF0,
+    );
+
+    assert_eq!(
+        lso.pointer("/player/inventory/1/name"),
+        Some(&Value::String("sword".into()))
+    );
+    assert_eq!(lso.pointer("/player/a~1b~0c"), Some(&Value::Integer(7)));
+    assert_eq!(
+        lso.pointer("/player/seen/0/value"),
+        Some(&Value::Number(3.0))
+    );
+    assert_eq!(
+        lso.pointer("/player/seen/0/key"),
+        Some(&Value::String("level".into()))
+    );
+    for invalid in [
+        "",
+        "player",
+        "/player/inventory/01",
+        "/player/inventory/2",
+        "/player/a~2b",
+        "/missing",
+    ] {
+        assert_eq!(lso.pointer(invalid), None, "{}", invalid);
+    }
+
+    *lso.pointer_mut("/player/inventory/0/name").unwrap() = Value::String("shield".into());
+    assert_eq!(
+        lso.pointer("/player/inventory/0/name"),
+        Some(&Value::String("shield".into()))
+    );
+    assert_eq!(
+        lso.pointer("/player/inventory/1/name"),
+        Some(&Value::String("sword".into()))
+    );
+}
+
+#[test]
+pub fn test_lso_find_paths() {
+    let data = include_bytes!("sol/AS3-Dictionary-Demo.sol");
+    let lso = Reader::default().parse(data).unwrap();
+
+    let containers = lso.find_paths(|v| matches!(v, Value::Dictionary(..)));
+    assert_eq!(containers, vec![lso.body[0].name.to_string()]);
+
+    let leaves = lso.flatten();
+    let containers = lso.find_paths(|v| {
+        matches!(
+            v,
+            Value::Object(..)
+                | Value::StrictArray(_)
+                | Value::ECMAArray(..)
+                | Value::Dictionary(..)
+        )
+    });
+    let all = lso.find_paths(|_| true);
+    assert_eq!(all.len(), leaves.len() + containers.len());
+
+    for path in lso.find_paths(|v| matches!(v, Value::String(_))) {
+        let pointer = format!("/{}", path.replace('.', "/"));
+        assert!(matches!(lso.pointer(&pointer), Some(Value::String(_))));
+    }
+}
+
+#[test]
+pub fn test_parse_annotated_versions() {
+    let mut lso = Lso::new(
+        vec![
+            Element::new("plain", Rc::new(Value::Number(1.0))),
+            Element::new("switched", Rc::new(Value::AMF3(Rc::new(Value::Integer(2))))),
+        ],
+        "test",
+        AMFVersion::AMF0,
+    );
+    let data = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+    let (_, spans) = Reader::default().parse_annotated(&data).unwrap();
+    assert_eq!(spans.version("plain"), Some(AMFVersion::AMF0));
+    assert_eq!(spans.version("switched"), Some(AMFVersion::AMF3));
+    assert_eq!(spans.version("missing"), None);
+
+    let data = include_bytes!("sol/AS3-Dictionary-Demo.sol");
+    let (lso, spans) = Reader::default().parse_annotated(data).unwrap();
+    assert_eq!(spans.version(&lso.body[0].name), Some(AMFVersion::AMF3));
+}
+
+#[test]
+pub fn test_write_converted_version() {
+    use flash_lso::write::{write_to_bytes_with, Writer};
+
+    // AMF0 references become shared values in AMF3
+    let data = include_bytes!("sol/AS2-Reference-Demo.sol");
+    let mut lso = Reader::default().parse(data).unwrap();
+    let before = lso.clone();
+    let mut writer = Writer::default();
+    writer.amf3_encoder.options.amf_version = Some(AMFVersion::AMF3);
+    let bytes = write_to_bytes_with(&mut lso, writer).unwrap();
+    assert_eq!(lso.header.length as usize, bytes.len() - 6);
+    assert_eq!(lso.header.format_version, before.header.format_version);
+    assert_eq!(lso.body, before.body);
+    let read = Reader::default().parse(&bytes).unwrap();
+    assert_eq!(
+        read.header.format_version.amf_version(),
+        Some(AMFVersion::AMF3)
+    );
+    assert!(read
+        .find_paths(|v| matches!(v, Value::Reference(_)))
+        .is_empty());
+    assert_eq!(read.body.len(), lso.body.len());
+
+    // AMF3 only values are kept embedded in AMF0, integers become numbers
+    let mut lso = Lso::new(
+        vec![
+            Element::new("int", Rc::new(Value::Integer(5))),
+            Element::new(
+                "dict",
+                Rc::new(Value::Dictionary(
+                    vec![(Rc::new(Value::Integer(1)), Rc::new(Value::Bool(true)))],
+                    false,
+                )),
+            ),
+        ],
+        "test",
+        AMFVersion::AMF3,
+    );
+    let mut writer = Writer::default();
+    writer.amf3_encoder.options.amf_version = Some(AMFVersion::AMF0);
+    let bytes = write_to_bytes_with(&mut lso, writer).unwrap();
+    let read = Reader::default().parse(&bytes).unwrap();
+    assert_eq!(read.body[0].value(), &Value::Number(5.0));
+    assert!(
+        matches!(read.body[1].value(), Value::AMF3(v) if matches!(v.as_ref(), Value::Dictionary(..)))
+    );
+    assert_eq!(
+        read.convert_to(AMFVersion::AMF3).body[1].value(),
+        lso.convert_to(AMFVersion::AMF3).body[1].value()
+    );
+}
+
+#[test]
+pub fn test_dictionary_order_round_trip() {
+    let object = |id: i64, name: &'static str| {
+        Rc::new(Value::Object(
+            ObjectId(id),
+            vec![Element::new("name", Rc::new(Value::String(name.into())))],
+            Some(ClassDefinition::anonymous()),
+        ))
+    };
+    let key_order = [
+        Rc::new(Value::String("zebra".into())),
+        object(0, "second"),
+        Rc::new(Value::String("apple".into())),
+        object(1, "first"),
+        Rc::new(Value::Integer(3)),
+        Rc::new(Value::String("mango".into())),
+    ];
+    let entries = key_order
+        .iter()
+        .enumerate()
+        .map(|(i, k)| (Rc::clone(k), Rc::new(Value::Integer(i as i32))))
+        .collect::<Vec<_>>();
+    let lso = Lso::new(
+        vec![Element::new(
+            "dict",
+            Rc::new(Value::Dictionary(entries.clone(), false)),
+        )],
+        "order",
+        AMFVersion::AMF3,
+    );
+
+    for dedup_objects in [false, true] {
+        let mut writer = Writer::default();
+        writer.amf3_encoder.options = EncoderOptions {
+            dedup_objects,
+            ..EncoderOptions::default()
+        };
+        let mut bytes = Vec::new();
+        writer.write_full(&mut bytes, &mut lso.clone()).unwrap();
+
+        let read = Reader::default().parse(&bytes).unwrap();
+        let Value::Dictionary(read_entries, _) = read.body[0].value() else {
+            panic!("Expected a dictionary");
+        };
+        // Objects are renumbered when read, so they are compared by their contents
+        let describe = |k: &Rc<Value<'_>>| -> Value<'static> {
+            match k.as_ref() {
+                Value::Object(_, elements, _) => elements[0].value().clone().into_owned(),
+                key => key.clone().into_owned(),
+            }
+        };
+        let keys = read_entries
+            .iter()
+            .map(|(k, _)| describe(k))
+            .collect::<Vec<Value<'static>>>();
+        let values = read_entries
+            .iter()
+            .map(|(_, v)| v.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            key_order
+                .iter()
+                .map(describe)
+                .collect::<Vec<Value<'static>>>()
+        );
+        assert_eq!(
+            values,
+            entries.iter().map(|(_, v)| v.clone()).collect::<Vec<_>>()
+        );
+
+        // Writing what was read gives the same bytes, so the order is stable across repeated saves
+        let mut rewritten = Vec::new();
+        let mut writer = Writer::default();
+        writer.amf3_encoder.options = EncoderOptions {
+            dedup_objects,
+            ..EncoderOptions::default()
+        };
+        writer
+            .write_full(&mut rewritten, &mut read.clone())
+            .unwrap();
+        assert_eq!(rewritten, bytes);
+    }
+}
+
+#[test]
+pub fn test_dictionary_numeric_keys() {
+    let keys = [
+        Value::Integer(0),
+        Value::Integer(-5),
+        Value::Integer(300_000),
+        Value::Number(2.5),
+    ];
+    let entries = keys
+        .iter()
+        .enumerate()
+        .map(|(i, k)| {
+            (
+                Rc::new(k.clone()),
+                Rc::new(Value::String(format!("asset{}", i).into())),
+            )
+        })
+        .collect();
+    let mut lso = Lso::new(
+        vec![Element::new(
+            "cache",
+            Rc::new(Value::Dictionary(entries, false)),
+        )],
+        "numeric",
+        AMFVersion::AMF3,
+    );
+
+    let bytes = flash_lso::write::write_to_bytes(&mut lso.clone()).unwrap();
+    let read = Reader::default().parse(&bytes).unwrap();
+    assert_eq!(read.body, lso.body);
+    let dictionary = read.body[0].value().as_dictionary().unwrap();
+    assert_eq!(
+        dictionary.get(&Value::Integer(300_000)),
+        Some(&Value::String("asset2".into()))
+    );
+    assert_eq!(dictionary.get(&Value::String("300000".into())), None);
+
+    #[cfg(feature = "json")]
+    {
+        let json = flash_lso::json::to_json(&lso).unwrap();
+        assert_eq!(flash_lso::json::from_json(json).unwrap().body, lso.body);
+    }
+
+    // Keys outside the range of an AMF3 integer are still numeric, as a `Number`
+    lso.body[0] = Element::new(
+        "cache",
+        Rc::new(Value::Dictionary(
+            vec![(
+                Rc::new(Value::Integer(i32::MAX)),
+                Rc::new(Value::Bool(true)),
+            )],
+            false,
+        )),
+    );
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+    let read = Reader::default().parse(&bytes).unwrap();
+    assert_eq!(
+        read.body[0].value().as_dictionary().unwrap().iter().next(),
+        Some((&Value::Number(f64::from(i32::MAX)), &Value::Bool(true)))
+    );
+}
+
+#[test]
+pub fn test_parse_prefix_and_all() {
+    let first = include_bytes!("sol/AS3-Dictionary-Demo.sol");
+    let second = include_bytes!("sol/AS2-Demo.sol");
+    let both = [&first[..], &second[..]].concat();
+
+    let outcome = Reader::default().parse_prefix(&both).unwrap();
+    assert_eq!(outcome.consumed, first.len());
+    assert_eq!(outcome.remaining, &second[..]);
+    assert_eq!(outcome.lso, Reader::default().parse(first).unwrap());
+
+    let all = Reader::default().parse_all(&both).unwrap();
+    assert_eq!(all.len(), 2);
+    assert_eq!(all[1], Reader::default().parse(second).unwrap());
+    assert!(Reader::default().parse_all(&[]).unwrap().is_empty());
+    assert!(Reader::default()
+        .parse_all(&both[..both.len() - 1])
+        .is_err());
+}
+
+#[test]
+pub fn test_element_conveniences() {
+    let shared = Rc::new(Value::String("shared".into()));
+    let object = Element::new(
+        "player",
+        Value::Object(
+            ObjectId::INVALID,
+            vec![
+                Element::new("name", Value::String("bob".into())),
+                Element::new("tag", Rc::clone(&shared)),
+            ],
+            None,
+        ),
+    );
+
+    assert_eq!(
+        object.get_property("name"),
+        Some(&Value::String("bob".into()))
+    );
+    assert_eq!(object.type_name(), "Object");
+    let Value::Object(_, elements, _) = &*object else {
+        panic!("Expected an object");
+    };
+    assert!(Rc::ptr_eq(&elements[1].value, &shared));
+}
+
+#[test]
+pub fn test_write_to() {
+    /// A sink that takes at most three bytes at a time
+    struct Trickle(Vec<u8>);
+
+    impl std::io::Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(3);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    for data in [
+        &include_bytes!("sol/AS3-Demo.sol")[..],
+        &include_bytes!("sol/AS2-Demo.sol")[..],
+        &include_bytes!("sol/slot1.sol")[..],
+    ] {
+        let mut lso = Reader::default().parse(data).unwrap();
+        let mut out = Trickle(Vec::new());
+        flash_lso::write::write_to(&mut lso, &mut out).unwrap();
+        assert_eq!(out.0, flash_lso::write::write_to_bytes(&mut lso).unwrap());
+        assert_eq!(lso.header.length as usize, out.0.len() - 6);
+    }
+
+    // Each body starts with empty reference tables, so a reused writer writes the same bytes again
+    let mut lso = Reader::default()
+        .parse(include_bytes!("sol/AS3-Demo.sol"))
+        .unwrap();
+    let mut writer = Writer::default();
+    let mut first = vec![];
+    writer.write_full(&mut first, &mut lso).unwrap();
+    let mut second = vec![];
+    writer.write_full(&mut second, &mut lso).unwrap();
+    assert_eq!(first, second);
+}
+
+#[test]
+pub fn test_value_accessors() {
+    assert_eq!(Value::String("abc".into()).as_str(), Some("abc"));
+    assert_eq!(Value::Integer(7).as_f64(), Some(7.0));
+    assert_eq!(Value::Number(2.5).as_f64(), Some(2.5));
+    assert_eq!(Value::Number(-3.0).as_i32(), Some(-3));
+    assert_eq!(Value::Number(2.5).as_i32(), None);
+    assert_eq!(Value::Number(f64::from(i32::MAX) + 1.0).as_i32(), None);
+    assert_eq!(Value::Bool(true).as_bool(), Some(true));
+    assert_eq!(
+        Value::ByteArray(vec![1, 2][..].into()).as_bytes(),
+        Some(&[1, 2][..])
+    );
+    assert!(Value::Null.is_null());
+    assert!(Value::Undefined.is_null());
+    assert!(!Value::Integer(0).is_null());
+    assert_eq!(Value::Integer(1).as_str(), None);
+
+    let lso = Reader::default()
+        .parse(include_bytes!("sol/AS3-Demo.sol"))
+        .unwrap();
+    let object = lso.body.iter().find(|e| e.name == "myObject").unwrap();
+    let (elements, def) = object.as_object().unwrap();
+    assert_eq!(def.map(|d| d.name.as_str()), Some(""));
+    let p2 = elements.iter().find(|e| e.name == "p2").unwrap();
+    assert_eq!(p2.as_str(), Some("hallo"));
+}
+
+#[test]
+pub fn test_value_get_path() {
+    let item = Rc::new(Value::Object(
+        ObjectId::INVALID,
+        vec![Element::new("count", Value::Integer(3))],
+        None,
+    ));
+    let mut player = Value::Object(
+        ObjectId::INVALID,
+        vec![
+            Element::new(
+                "inventory",
+                Value::StrictArray(vec![Rc::new(Value::Null), Rc::clone(&item)]),
+            ),
+            Element::new(
+                "slots",
+                Value::ECMAArray(
+                    vec![Rc::new(Value::Bool(true))],
+                    vec![Element::new("extra", Value::Integer(4))],
+                    1,
+                ),
+            ),
+            Element::new(
+                "lookup",
+                Value::Dictionary(
+                    vec![
+                        (Rc::new(Value::String("a".into())), Rc::clone(&item)),
+                        (Rc::new(Value::Integer(7)), Rc::new(Value::Number(1.5))),
+                    ],
+                    false,
+                ),
+            ),
+            Element::new("wrapped", Value::AMF3(Rc::clone(&item))),
+        ],
+        None,
+    );
+
+    assert_eq!(player.get_path(""), Some(&player));
+    assert_eq!(
+        player.get_path("inventory[1].count"),
+        Some(&Value::Integer(3))
+    );
+    assert_eq!(player.get_path("slots[0]"), Some(&Value::Bool(true)));
+    assert_eq!(player.get_path("slots.extra"), Some(&Value::Integer(4)));
+    assert_eq!(player.get_path("lookup.a.count"), Some(&Value::Integer(3)));
+    assert_eq!(player.get_path("lookup[7]"), Some(&Value::Number(1.5)));
+    assert_eq!(player.get_path("wrapped.count"), Some(&Value::Integer(3)));
+    assert_eq!(
+        Value::StrictArray(vec![item.clone()]).get_path("[0].count"),
+        Some(&Value::Integer(3))
+    );
+
+    for missing in [
+        "inventory[2]",
+        "inventory.count",
+        "inventory[1].count.x",
+        "inventory[01]",
+        "inventory[1",
+        "inventory..count",
+        "slots[1]",
+        "lookup.b",
+    ] {
+        assert_eq!(player.get_path(missing), None, "{}", missing);
+    }
+
+    // Shared values are copied before they are changed
+    *player.get_path_mut("inventory[1].count").unwrap() = Value::Integer(10);
+    assert_eq!(
+        player.get_path("inventory[1].count"),
+        Some(&Value::Integer(10))
+    );
+    assert_eq!(player.get_path("lookup.a.count"), Some(&Value::Integer(3)));
+    assert_eq!(item.get_path("count"), Some(&Value::Integer(3)));
+    assert!(player.get_path_mut("inventory[5]").is_none());
+}
+
+#[test]
+pub fn test_structural_eq() {
+    let nan = || {
+        Value::Object(
+            ObjectId::INVALID,
+            vec![
+                Element::new("n", Value::Number(f64::NAN)),
+                Element::new("v", Value::VectorDouble(vec![f64::NAN, 1.0], false)),
+                Element::new("d", Value::AMF3(Rc::new(Value::Date(f64::NAN, None)))),
+            ],
+            None,
+        )
+    };
+    assert_ne!(nan(), nan());
+    assert!(nan().structural_eq(&nan()));
+    assert!(!nan().structural_eq(&Value::Number(f64::NAN)));
+    assert!(!Value::Number(f64::NAN).structural_eq(&Value::Number(1.0)));
+    assert!(Value::Number(0.0).structural_eq(&Value::Number(-0.0)));
+    assert!(
+        Value::Object(ObjectId(1), vec![], None).structural_eq(&Value::Object(
+            ObjectId(2),
+            vec![],
+            None
+        ))
+    );
+
+    // Values held by Rc are compared by value, not by address
+    assert_eq!(
+        Value::AMF3(Rc::new(Value::Integer(1))),
+        Value::AMF3(Rc::new(Value::Integer(1)))
+    );
+
+    // The vector of numbers in this file contains a NaN
+    let data = include_bytes!("sol/AS3-Demo.sol");
+    let lso = Reader::default().parse(data).unwrap();
+    assert_ne!(lso, Reader::default().parse(data).unwrap());
+    assert!(lso.structural_eq(&Reader::default().parse(data).unwrap()));
+    let mut renamed = lso.clone();
+    renamed.body[0].name = "other".into();
+    assert!(!lso.structural_eq(&renamed));
+}
+
+#[test]
+pub fn test_mixed_array_round_trip() {
+    // array[0] = 1; array["foo"] = "bar", and an array with two of each
+    let data = include_bytes!("sol/AS3-MixedArray-Demo.sol");
+    let mut lso = Reader::default().parse(data).unwrap();
+    assert_eq!(
+        *lso.body[0].value,
+        Value::ECMAArray(
+            vec![Rc::new(Value::Integer(1))],
+            vec![Element::new("foo", Value::String("bar".into()))],
+            1,
+        )
+    );
+    let Value::ECMAArray(dense, assoc, length) = lso.body[1].value() else {
+        panic!("Expected an array");
+    };
+    assert_eq!(dense.len(), 2);
+    assert_eq!(*length, 2);
+    let names: Vec<&str> = assoc.iter().map(|e| e.name()).collect();
+    assert_eq!(names, ["foo", "qux"]);
+    assert_eq!(
+        flash_lso::write::write_to_bytes(&mut lso).unwrap(),
+        &data[..]
+    );
+
+    // The AMF0 count is the length of the array, not the number of named elements
+    let mut amf0 = lso.convert_to(AMFVersion::AMF0);
+    let bytes = flash_lso::write::write_to_bytes(&mut amf0).unwrap();
+    let read = Reader::default().parse(&bytes).unwrap();
+    let Value::ECMAArray(_, elements, length) = read.body[1].value() else {
+        panic!("Expected an array");
+    };
+    assert_eq!(*length, 2);
+    let names: Vec<&str> = elements.iter().map(|e| e.name()).collect();
+    assert_eq!(names, ["0", "1", "foo", "qux"]);
+
+    // An empty name would end the associative part early, dropping the elements after it
+    let mut lso = Lso::new(
+        vec![Element::new(
+            "array",
+            Value::ECMAArray(
+                Vec::new(),
+                vec![
+                    Element::new("", Value::Integer(1)),
+                    Element::new("after", Value::Integer(2)),
+                ],
+                0,
+            ),
+        )],
+        "Mixed",
+        AMFVersion::AMF3,
+    );
+    assert!(flash_lso::write::write_to_bytes(&mut lso).is_err());
+}
+
+#[test]
+fn test_lso_builder() {
+    use flash_lso::types::LsoBuilder;
+
+    let mut builder = LsoBuilder::new("save");
+    builder
+        .add("name", "bob")
+        .add("score", 12.5)
+        .add("level", 3)
+        .add("alive", true)
+        .add("blob", vec![1u8, 2, 3])
+        .add("scores", vec![Value::from(1.5), Value::from(2.0)]);
+
+    let mut lso = builder.build();
+    assert_eq!(
+        lso.header.format_version.amf_version(),
+        Some(AMFVersion::AMF3)
+    );
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+    assert_eq!(lso.header.length as usize, bytes.len() - 6);
+    let read = Reader::default().parse(&bytes).unwrap();
+    assert_eq!(read, lso);
+    assert_eq!(read.body[0].value().as_str(), Some("bob"));
+    assert_eq!(read.body[2].value(), &Value::Integer(3));
+    assert_eq!(
+        read.body[5].value(),
+        &Value::StrictArray(vec![
+            Rc::new(Value::Number(1.5)),
+            Rc::new(Value::Number(2.0))
+        ])
+    );
+
+    // Values that AMF0 can't hold are converted
+    let mut lso = builder.version(AMFVersion::AMF0).build();
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+    assert_eq!(lso.header.length as usize, bytes.len() - 6);
+    let read = Reader::default().parse(&bytes).unwrap();
+    assert_eq!(read, lso);
+    assert_eq!(read.body[2].value(), &Value::Number(3.0));
+    let Value::AMF3(blob) = read.body[4].value() else {
+        panic!("Expected a wrapped AMF3 value");
+    };
+    assert_eq!(blob.as_bytes(), Some(&[1u8, 2, 3][..]));
+
+    let pet = Value::from(vec![
+        ("name".to_string(), Value::from("rex")),
+        ("age".to_string(), Value::from(4)),
+    ]);
+    assert_eq!(
+        pet,
+        Value::Object(
+            ObjectId::INVALID,
+            vec![
+                Element::new("name", Value::String("rex".into())),
+                Element::new("age", Value::Integer(4)),
+            ],
+            None,
+        )
+    );
+}
+
+#[test]
+fn test_value_pretty() {
+    let value = Value::Object(
+        ObjectId::INVALID,
+        vec![
+            Element::new("name", Value::String("bob".into())),
+            Element::new(
+                "items",
+                Value::StrictArray(vec![
+                    Rc::new(Value::Integer(1)),
+                    Rc::new(Value::ByteArray(vec![0; 1234].into())),
+                ]),
+            ),
+            Element::new("empty", Value::StrictArray(Vec::new())),
+            Element::new("self", Value::Amf3ObjectReference(ObjectId::INVALID)),
+        ],
+        Some(ClassDefinition::default_with_name("Player".to_string())),
+    );
+    assert_eq!(
+        value.pretty(2),
+        "Player {\n  name: \"bob\",\n  items: [\n    1,\n    ByteArray(1234 bytes),\n  ],\n  empty: [],\n  self: <ref>,\n}"
+    );
+    assert_eq!(value.to_string(), value.pretty(2));
+
+    // Every file can be printed
+    let data = std::fs::read("tests/sol/AS3-Demo.sol").unwrap();
+    let lso = Reader::default().parse(&data).unwrap();
+    for element in &lso.body {
+        assert!(!element.value().pretty(4).is_empty());
+    }
+}
+
+#[test]
+pub fn test_nesting_depth_and_cycles() {
+    use flash_lso::errors::DecodeReason;
+    use flash_lso::read::DEFAULT_MAX_DEPTH;
+
+    // A single element named "a", in the encoding of the given version
+    let lso = |version: u8, value: &[u8]| {
+        let mut body = match version {
+            0 => vec![0x00, 0x01, b'a'],
+            _ => vec![0x03, b'a'],
+        };
+        body.extend_from_slice(value);
+        body.push(0x00);
+
+        let mut data = vec![0x00, 0xbf];
+        data.extend_from_slice(&(body.len() as u32 + 18).to_be_bytes());
+        data.extend_from_slice(b"TCSO\x00\x04\x00\x00\x00\x00\x00\x04test\x00\x00\x00");
+        data.push(version);
+        data.extend(body);
+        data
+    };
+    // Strict arrays of one value nested `depth` times around a null
+    let nested = |version: u8, depth: usize| {
+        let (array, null): (&[u8], u8) = match version {
+            0 => (&[0x0A, 0x00, 0x00, 0x00, 0x01], 0x05),
+            _ => (&[0x09, 0x03, 0x01], 0x01),
+        };
+        let mut value = array.repeat(depth);
+        value.push(null);
+        lso(version, &value)
+    };
+    let decode = |max_depth: usize, data: &[u8]| {
+        let mut reader = Reader::default();
+        reader.set_max_depth(max_depth);
+        reader
+            .parse(data)
+            .map(|_| ())
+            .map_err(|e| reader.decode_error(data, e).reason)
+    };
+
+    for version in [0, 3] {
+        assert_eq!(
+            decode(DEFAULT_MAX_DEPTH, &nested(version, DEFAULT_MAX_DEPTH - 1)),
+            Ok(())
+        );
+        assert_eq!(
+            decode(DEFAULT_MAX_DEPTH, &nested(version, DEFAULT_MAX_DEPTH)),
+            Err(DecodeReason::DepthExceeded)
+        );
+        // Far deeper than the stack could take without the limit
+        assert_eq!(
+            decode(DEFAULT_MAX_DEPTH, &nested(version, 1_000_000)),
+            Err(DecodeReason::DepthExceeded)
+        );
+
+        assert_eq!(decode(4, &nested(version, 3)), Ok(()));
+        assert_eq!(
+            decode(4, &nested(version, 4)),
+            Err(DecodeReason::DepthExceeded)
+        );
+    }
+
+    // AMF3 embedded in AMF0 counts the values around it, including the switch to AMF3
+    let mut value = vec![0x0A, 0x00, 0x00, 0x00, 0x01];
+    value.extend_from_slice(&[0x11, 0x01]);
+    assert_eq!(decode(3, &lso(0, &value)), Ok(()));
+    value.splice(0..0, [0x0A, 0x00, 0x00, 0x00, 0x01]);
+    assert_eq!(decode(3, &lso(0, &value)), Err(DecodeReason::DepthExceeded));
+
+    // An array that contains itself, and an object that contains itself through an array marker
+    for value in [
+        &[0x09, 0x03, 0x01, 0x09, 0x00][..],
+        &[0x0A, 0x0B, 0x01, 0x03, b'b', 0x09, 0x00, 0x01],
+    ] {
+        assert_eq!(
+            decode(DEFAULT_MAX_DEPTH, &lso(3, value)),
+            Err(DecodeReason::CyclicReference)
+        );
+    }
+
+    // An object that contains itself through an object marker is kept as a reference
+    let data = lso(3, &[0x0A, 0x0B, 0x01, 0x03, b'b', 0x0A, 0x00, 0x01]);
+    let read = Reader::default().parse(&data).unwrap();
+    let (elements, _) = read.body[0].value().as_object().unwrap();
+    assert!(matches!(elements[0].value(), Value::Amf3ObjectReference(_)));
+}
+
+#[test]
+pub fn test_allocation_limit() {
+    use flash_lso::errors::DecodeReason;
+
+    let nulls = Value::StrictArray((0..100_000).map(|_| Rc::new(Value::Null)).collect());
+    for version in [AMFVersion::AMF0, AMFVersion::AMF3] {
+        let mut lso = Lso::new(vec![Element::new("a", nulls.clone())], "test", version);
+        let data = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+
+        assert!(Reader::default().parse(&data).is_ok());
+
+        // The declared length is checked before any of the values are read
+        let mut reader = Reader::default();
+        reader.set_max_alloc(1024 * 1024);
+        let error = reader.parse(&data).expect_err("Decoded past the limit");
+        let error = reader.decode_error(&data, error);
+        assert_eq!(error.reason, DecodeReason::AllocationLimit);
+
+        // The budget is for each Lso, not the lifetime of the reader
+        reader.set_max_alloc(100 * 1024 * 1024);
+        assert!(reader.parse(&data).is_ok());
+        assert!(reader.parse(&data).is_ok());
+    }
+}
+
+#[test]
+#[cfg(feature = "compression")]
+pub fn test_decompress_with_limit() {
+    use flash_lso::compression::{compress, decompress_with_limit, Compression};
+    use flash_lso::errors::CompressionError;
+
+    let data = vec![0; 100_000];
+    for compression in [Compression::Zlib, Compression::Gzip] {
+        let compressed = compress(&data, compression);
+        assert!(compressed.len() < 1000);
+        assert_eq!(
+            decompress_with_limit(&compressed, data.len()).unwrap().0,
+            &data[..]
+        );
+        assert_eq!(
+            decompress_with_limit(&compressed, data.len() - 1),
+            Err(CompressionError::TooLarge)
+        );
+    }
+
+    // The start of a zlib stream of zeros, a kilobyte of which inflates to a megabyte
+    let mut bomb = vec![
+        0x78, 0x9c, 0xed, 0xc1, 0x01, 0x01, 0x00, 0x00, 0x00, 0x82, 0x20, 0xff, 0xaf, 0x6e, 0x48,
+        0x40, 0x01,
+    ];
+    bomb.resize(1017, 0);
+    assert_eq!(
+        decompress_with_limit(&bomb, 1000),
+        Err(CompressionError::TooLarge)
+    );
+}
+
+#[test]
+#[cfg(feature = "chrono")]
+pub fn test_date_chrono_round_trip() {
+    use chrono::{FixedOffset, TimeZone};
+
+    // Fractions of a millisecond survive as far as the double can hold them
+    for millis in [
+        0.0,
+        1_577_836_800_000.0,
+        1_600_000_000_000.5,
+        -150_000_000_000.25,
+        253_402_300_799_999.0,
+    ] {
+        let dt = Value::Date(millis, None).to_datetime().unwrap();
+        assert_eq!(Value::date_from_chrono(dt), Value::Date(millis, Some(0)));
+    }
+    for value in [
+        Value::Date(f64::NAN, None),
+        Value::Date(f64::INFINITY, None),
+        // The latest date in ActionScript is later than chrono can represent
+        Value::Date(8.64e15, None),
+        Value::Number(0.0),
+    ] {
+        assert_eq!(value.to_datetime(), None);
+    }
+
+    // Dates read from a file keep their time
+    let data = std::fs::read("tests/sol/AS2-Date-Demo.sol").unwrap();
+    let lso = Reader::default().parse(&data).unwrap();
+    let date = lso.body[0].value();
+    let Value::Date(millis, _) = date else {
+        panic!("Expected a date");
+    };
+    let Value::Date(round_trip, _) = Value::date_from_chrono(date.to_datetime().unwrap()) else {
+        panic!("Expected a date");
+    };
+    assert_eq!(millis.to_bits(), round_trip.to_bits());
+
+    // The timezone is kept as a signed offset, which is ignored unless asked for
+    let dt = FixedOffset::west_opt(5 * 3600)
+        .unwrap()
+        .with_ymd_and_hms(2001, 9, 9, 1, 46, 40)
+        .unwrap();
+    let date = Value::date_from_chrono(dt);
+    assert_eq!(
+        date,
+        Value::Date(
+            1_000_000_000_000.0 + 5.0 * 3_600_000.0,
+            Some((-300i16) as u16)
+        )
+    );
+    assert_eq!(date.to_datetime_with_offset(), Some(dt));
+    assert_eq!(
+        date.to_datetime().unwrap().to_rfc3339(),
+        "2001-09-09T06:46:40+00:00"
+    );
+    assert_eq!(
+        Value::Date(0.0, None)
+            .to_datetime_with_offset()
+            .unwrap()
+            .offset()
+            .local_minus_utc(),
+        0
+    );
+}
+
+#[test]
+pub fn test_lso_map_access() {
+    let data = std::fs::read("tests/sol/AS2-Demo.sol").unwrap();
+    let mut lso = Reader::default().parse(&data).unwrap();
+
+    let names: Vec<String> = (&lso).into_iter().map(|e| e.name().to_string()).collect();
+    assert_eq!(names, lso.iter().map(|e| e.name()).collect::<Vec<_>>());
+    for element in &lso {
+        assert_eq!(lso.get(element.name()), Some(element.value()));
+    }
+    assert_eq!(lso.get("missing"), None);
+
+    // Replacing keeps the position of the element, adding appends it
+    let first = names[0].clone();
+    let old = lso.insert(first.clone(), Value::Bool(true));
+    assert!(old.is_some());
+    assert_eq!(lso.body[0].value(), &Value::Bool(true));
+    assert_eq!(lso.insert("added", Value::Null), None);
+    assert_eq!(lso.body.len(), names.len() + 1);
+    assert_eq!(lso.body.last().unwrap().name(), "added");
+    assert_eq!(lso.get(&first), Some(&Value::Bool(true)));
+}
+
+#[test]
+pub fn test_headerless_decode() {
+    // A dense array of "a" and a reference to the first string
+    let data = [0x09, 0x05, 0x01, 0x06, 0x03, b'a', 0x06, 0x00, 0xFF];
+    let (rest, value) = flash_lso::amf3::decode(&data).unwrap();
+    assert_eq!(rest, &[0xFF]);
+    assert_eq!(
+        value,
+        Value::StrictArray(vec![
+            Rc::new(Value::String("a".into())),
+            Rc::new(Value::String("a".into())),
+        ])
+    );
+
+    // An anonymous object with a property holding a reference to the object
+    let data = [
+        0x03, 0x00, 0x01, b'x', 0x07, 0x00, 0x00, 0x00, 0x00, 0x09, 0xFF,
+    ];
+    let (rest, value) = flash_lso::amf0::decode(&data).unwrap();
+    assert_eq!(rest, &[0xFF]);
+    let (elements, _) = value.as_object().unwrap();
+    assert!(matches!(elements[0].value(), Value::Reference(_)));
+
+    assert!(flash_lso::amf0::decode(&[0x0A, 0x00]).is_err());
+}
+
+#[test]
+pub fn test_headerless_encode() {
+    let shared = Rc::new(Value::Object(
+        ObjectId::INVALID,
+        vec![Element::new("name", Value::String("bob".into()))],
+        Some(ClassDefinition::default_with_name("Player".to_string())),
+    ));
+    let values = [
+        Value::Integer(-5),
+        Value::Number(1.5),
+        Value::String("text".into()),
+        Value::ByteArray(vec![1, 2, 3].into()),
+        Value::VectorInt(vec![1, -1], false),
+        Value::StrictArray(vec![Rc::clone(&shared), Rc::clone(&shared)]),
+        Value::Dictionary(vec![(Rc::new(Value::Integer(1)), shared)], false),
+    ];
+
+    for value in &values {
+        let bytes = flash_lso::amf3::encode(value).unwrap();
+        // Each call starts with empty tables
+        assert_eq!(flash_lso::amf3::encode(value).unwrap(), bytes);
+        let (rest, decoded) = flash_lso::amf3::decode(&bytes).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(flash_lso::amf3::encode(&decoded).unwrap(), bytes);
+        // Decoding gives objects ids, so they are compared by their encoding
+        if values[..5].contains(value) {
+            assert_eq!(&decoded, value);
+        }
+    }
+
+    for value in &values[1..3] {
+        let bytes = flash_lso::amf0::encode(value).unwrap();
+        assert_eq!(
+            flash_lso::amf0::decode(&bytes).unwrap(),
+            (&[][..], value.clone())
+        );
+    }
+}
+
+#[test]
+pub fn test_lso_to_amf3_and_amf0() {
+    let shared = Rc::new(Value::Object(
+        ObjectId(0),
+        vec![Element::new("hp", Value::Integer(10))],
+        None,
+    ));
+    let lso = Lso::new(
+        vec![
+            Element::new("level", Value::Integer(3)),
+            Element::new("blob", Value::ByteArray(vec![1, 2, 3].into())),
+            Element::new(
+                "party",
+                Value::StrictArray(vec![Rc::clone(&shared), Rc::clone(&shared)]),
+            ),
+        ],
+        "save",
+        AMFVersion::AMF3,
+    );
+
+    let mut amf0 = lso.to_amf0().unwrap();
+    assert_eq!(
+        amf0.header.format_version.amf_version(),
+        Some(AMFVersion::AMF0)
+    );
+    assert_eq!(amf0.body[0].value(), &Value::Number(3.0));
+    assert!(matches!(amf0.body[1].value(), Value::AMF3(_)));
+    let bytes = flash_lso::write::write_to_bytes(&mut amf0).unwrap();
+    let read = Reader::default().parse(&bytes).unwrap();
+    assert_eq!(read.body[0].value(), &Value::Number(3.0));
+
+    let mut amf3 = read.to_amf3();
+    assert_eq!(
+        amf3.header.format_version.amf_version(),
+        Some(AMFVersion::AMF3)
+    );
+    assert_eq!(amf3.body[1].value(), lso.body[1].value());
+    let bytes = flash_lso::write::write_to_bytes(&mut amf3).unwrap();
+    assert!(Reader::default().parse(&bytes).is_ok());
+
+    // An object containing a reference to itself has no AMF0 form
+    let looped = Lso::new(
+        vec![Element::new(
+            "node",
+            Value::Object(
+                ObjectId(0),
+                vec![Element::new(
+                    "children",
+                    Value::StrictArray(vec![Rc::new(Value::Amf3ObjectReference(ObjectId(0)))]),
+                )],
+                None,
+            ),
+        )],
+        "save",
+        AMFVersion::AMF3,
+    );
+    let err = looped.to_amf0().unwrap_err();
+    assert_eq!(err.version, AMFVersion::AMF0);
+    assert_eq!(err.paths, ["body.node.children[0]"]);
+    assert_eq!(
+        err.to_string(),
+        "1 values can't be converted to AMF0: body.node.children[0]"
+    );
+}
+
+#[test]
+pub fn test_dictionary_weak_keys_and_object_keys() {
+    for weak_keys in [true, false] {
+        let key = Rc::new(Value::Object(
+            ObjectId::INVALID,
+            vec![Element::new("id", Value::Integer(1))],
+            None,
+        ));
+        let dictionary = Value::Dictionary(
+            vec![
+                (Rc::clone(&key), Rc::new(Value::String("first".into()))),
+                (Rc::new(Value::Integer(2)), Rc::clone(&key)),
+            ],
+            weak_keys,
+        );
+        let mut lso = Lso::new(
+            vec![
+                Element::new("key", Rc::clone(&key)),
+                Element::new("dict", dictionary),
+            ],
+            "dict",
+            AMFVersion::AMF3,
+        );
+        let bytes = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+        let read = Reader::default().parse(&bytes).unwrap();
+
+        let Value::Dictionary(entries, read_weak_keys) = read.body[1].value() else {
+            panic!("Expected a dictionary");
+        };
+        assert_eq!(*read_weak_keys, weak_keys);
+
+        // The object is written once, the key and the value in the dictionary are references to it
+        let Value::Object(id, _, _) = read.body[0].value() else {
+            panic!("Expected an object");
+        };
+        assert_eq!(entries[0].0.as_ref(), &Value::Amf3ObjectReference(*id));
+        assert_eq!(entries[1].1.as_ref(), &Value::Amf3ObjectReference(*id));
+        assert_eq!(entries[0].1.as_ref(), &Value::String("first".into()));
+
+        let mut read = read;
+        assert_eq!(flash_lso::write::write_to_bytes(&mut read).unwrap(), bytes);
+    }
+
+    // Any non-zero flag is weak keys
+    let (_, value) = flash_lso::amf3::decode(&[0x11, 0x01, 0x02]).unwrap();
+    assert_eq!(value, Value::Dictionary(Vec::new(), true));
+    let events: Vec<_> = EventReader::new(&[0x11, 0x01, 0x02])
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        events,
+        vec![Event::BeginDictionary(true), Event::EndDictionary]
+    );
+}
+
+#[test]
+pub fn test_vector_fixed_length_round_trip() {
+    for fixed in [true, false] {
+        let values = [
+            Value::VectorInt(vec![1, -2, 3], fixed),
+            Value::VectorUInt(vec![1, 2, u32::MAX], fixed),
+            Value::VectorDouble(vec![1.5, -0.25], fixed),
+            Value::VectorObject(
+                vec![Rc::new(Value::String("a".into()))],
+                "String".to_string(),
+                fixed,
+            ),
+            Value::VectorObject(Vec::new(), "com.example.Item".to_string(), fixed),
+        ];
+        for value in values {
+            let bytes = flash_lso::amf3::encode(&value).unwrap();
+            let (rest, decoded) = flash_lso::amf3::decode(&bytes).unwrap();
+            assert!(rest.is_empty());
+            assert_eq!(decoded, value);
+            assert_eq!(flash_lso::amf3::encode(&decoded).unwrap(), bytes);
+
+            let mut lso = Lso::new(
+                vec![Element::new("vector", value.clone())],
+                "vectors",
+                AMFVersion::AMF3,
+            );
+            let bytes = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+            let read = Reader::default().parse(&bytes).unwrap();
+            assert_eq!(read.body[0].value(), &value);
+        }
+    }
+
+    // Any non-zero flag is fixed
+    let (_, value) = flash_lso::amf3::decode(&[0x0D, 0x03, 0x02, 0x00, 0x00, 0x00, 0x01]).unwrap();
+    assert_eq!(value, Value::VectorInt(vec![1], true));
+    for (data, value) in [
+        (
+            &[0x0D, 0x03, 0x02, 0x00, 0x00, 0x00, 0x01][..],
+            Value::VectorInt(vec![1], true),
+        ),
+        (
+            &[0x0E, 0x03, 0x02, 0x00, 0x00, 0x00, 0x01][..],
+            Value::VectorUInt(vec![1], true),
+        ),
+        (
+            &[
+                0x0F, 0x03, 0x02, 0x3F, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ][..],
+            Value::VectorDouble(vec![1.0], true),
+        ),
+    ] {
+        let events: Vec<_> = EventReader::new(data).collect::<Result<_, _>>().unwrap();
+        assert_eq!(events, vec![Event::Value(value)]);
+    }
+    let events: Vec<_> = EventReader::new(&[0x10, 0x01, 0x02, 0x01])
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        events,
+        vec![Event::BeginVector(String::new(), true), Event::EndVector]
+    );
+}
+
+#[test]
+pub fn test_xml_markers_round_trip() {
+    // XMLDocument (0x07) and XML (0x0B) with the same content and a length prefix of 4 bytes
+    for (bytes, string) in [
+        (&[0x07, 0x09, b'<', b'a', b'/', b'>'], false),
+        (&[0x0B, 0x09, b'<', b'a', b'/', b'>'], true),
+    ] {
+        let (rest, value) = flash_lso::amf3::decode(bytes).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(value, Value::XML("<a/>".into(), string));
+        assert_eq!(flash_lso::amf3::encode(&value).unwrap(), bytes);
+    }
+
+    // A shared value is written as a reference with the marker of its kind
+    let document = Rc::new(Value::XML("<a/>".into(), false));
+    let mut lso = Lso::new(
+        vec![
+            Element::new("first", Rc::clone(&document)),
+            Element::new("second", Rc::clone(&document)),
+            Element::new("xml", Value::XML("<b/>".into(), true)),
+        ],
+        "xml",
+        AMFVersion::AMF3,
+    );
+    let bytes = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+    assert!(bytes.windows(2).any(|w| w == [0x07, 0x00]));
+    let mut read = Reader::default().parse(&bytes).unwrap();
+    assert_eq!(read.body[1].value(), &Value::XML("<a/>".into(), false));
+    assert_eq!(read.body[2].value(), &Value::XML("<b/>".into(), true));
+    assert_eq!(flash_lso::write::write_to_bytes(&mut read).unwrap(), bytes);
+}
+
+#[test]
+pub fn test_repeated_strings_written_as_references() {
+    let strings: Vec<_> = (0..300)
+        .map(|i| Rc::new(Value::String(format!("s{}", i % 150).into())))
+        .collect();
+    let value = Value::StrictArray(strings);
+    let bytes = flash_lso::amf3::encode(&value).unwrap();
+
+    // The second half of the array are references to the first, the last is to string 149, a two byte U29
+    assert!(bytes.ends_with(&[0x06, 0x82, 0x2A]));
+    let (rest, decoded) = flash_lso::amf3::decode(&bytes).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(decoded, value);
+}
+
+#[test]
+pub fn test_dynamic_members_follow_static_members() {
+    use flash_lso::types::Attribute;
+
+    let def = ClassDefinition {
+        name: "Player".to_string(),
+        attributes: Attribute::Dynamic.into(),
+        static_properties: vec!["name".to_string(), "level".to_string()],
+    };
+    // Members are out of trait order, with a dynamic member first
+    let value = Value::Object(
+        ObjectId::INVALID,
+        vec![
+            Element::new("bonus", Value::Bool(true)),
+            Element::new("level", Value::Integer(3)),
+            Element::new("name", Value::String("bob".into())),
+        ],
+        Some(def.clone()),
+    );
+    let bytes = flash_lso::amf3::encode(&value).unwrap();
+
+    let (rest, decoded) = flash_lso::amf3::decode(&bytes).unwrap();
+    assert!(rest.is_empty());
+    let names: Vec<_> = decoded.static_members().map(|e| e.name.as_ref()).collect();
+    assert_eq!(names, ["name", "level"]);
+    let names: Vec<_> = decoded.dynamic_members().map(|e| e.name.as_ref()).collect();
+    assert_eq!(names, ["bonus"]);
+    assert_eq!(flash_lso::amf3::encode(&decoded).unwrap(), bytes);
+
+    // The dynamic members end with the empty string
+    assert!(bytes.ends_with(&[0x03, 0x01]));
+
+    // Objects without a class definition only have dynamic members
+    let anonymous = Value::from(vec![("a".to_string(), Value::Null)]);
+    assert_eq!(anonymous.static_members().count(), 0);
+    assert_eq!(anonymous.dynamic_members().count(), 1);
+
+    // Every static member has to be present
+    let missing = Value::Object(
+        ObjectId::INVALID,
+        vec![Element::new("name", Value::String("bob".into()))],
+        Some(def),
+    );
+    assert!(flash_lso::amf3::encode(&missing).is_err());
+}
+
+#[test]
+pub fn test_diff() {
+    use flash_lso::diff::{diff, Change};
+
+    let player = |gold: i32| {
+        Value::Object(
+            ObjectId::INVALID,
+            vec![
+                Element::new("name", Value::String("bob".into())),
+                Element::new(
+                    "inventory",
+                    Value::StrictArray(vec![Rc::new(Value::from(vec![(
+                        "gold".to_string(),
+                        Value::Integer(gold),
+                    )]))]),
+                ),
+            ],
+            None,
+        )
+    };
+    let mut before = Lso::new_empty("save", AMFVersion::AMF3);
+    before.insert("player", player(10));
+    before.insert("level", Value::Integer(3));
+    let mut after = Lso::new_empty("save", AMFVersion::AMF3);
+    after.insert("player", player(25));
+    after.insert("level", Value::Integer(3));
+
+    let changes = diff(&before, &after);
+    assert_eq!(
+        changes,
+        [Change::Modified {
+            path: "player.inventory[0].gold".to_string(),
+            old: Rc::new(Value::Integer(10)),
+            new: Rc::new(Value::Integer(25)),
+        }]
+    );
+    assert_eq!(
+        after.get("player").unwrap().get_path("inventory[0].gold"),
+        Some(&Value::Integer(25))
+    );
+    assert!(diff(&before, &before).is_empty());
+
+    // A change of type is a modification, and elements only on one side are added or removed
+    after.insert("level", Value::String("3".into()));
+    after.body.retain(|e| e.name != "player");
+    after.insert("seed", Value::Number(1.5));
+    let changes = diff(&before, &after);
+    let paths: Vec<_> = changes.iter().map(Change::path).collect();
+    assert_eq!(paths, ["player", "level", "seed"]);
+    assert!(matches!(changes[0], Change::Removed { .. }));
+    assert!(matches!(changes[1], Change::Modified { .. }));
+    assert!(matches!(changes[2], Change::Added { .. }));
+}
+
+#[test]
+pub fn test_lso_get_path() {
+    let mut lso = Lso::new_empty("save", AMFVersion::AMF3);
+    lso.insert(
+        "player",
+        Value::from(vec![(
+            "inventory".to_string(),
+            Value::from(vec![Value::from(vec![(
+                "gold".to_string(),
+                Value::Integer(25),
+            )])]),
+        )]),
+    );
+    lso.insert("level", Value::Integer(3));
+
+    assert_eq!(lso.get_path("level"), Some(&Value::Integer(3)));
+    assert_eq!(
+        lso.get_path("player.inventory[0].gold"),
+        Some(&Value::Integer(25))
+    );
+    assert_eq!(
+        lso.get_path("player.inventory[0]"),
+        lso.get("player").unwrap().get_path("inventory[0]")
+    );
+    assert_eq!(lso.get_path("player.inventory[1]"), None);
+    assert_eq!(lso.get_path("player..inventory"), None);
+    assert_eq!(lso.get_path("missing"), None);
+    assert_eq!(lso.get_path(""), None);
+
+    let lso = Reader::default()
+        .parse(include_bytes!("sol/AS2-Demo.sol"))
+        .unwrap();
+    assert_eq!(
+        lso.get_path("myObject2.p3").and_then(Value::as_str),
+        Some("hallo")
+    );
+}
+
+#[test]
+pub fn test_value_hash() {
+    use flash_lso::types::{LsoBuilder, StructuralKey};
+    use flash_lso::write::write_to_bytes;
+    use std::collections::HashSet;
+
+    let object = || {
+        Value::from(vec![
+            ("name".to_string(), Value::from("bob")),
+            (
+                "scores".to_string(),
+                Value::from(vec![Value::from(1.5), Value::from(f64::NAN)]),
+            ),
+        ])
+    };
+    let mut set = HashSet::new();
+    set.insert(StructuralKey(Rc::new(object())));
+    set.insert(StructuralKey(Rc::new(object())));
+    assert_eq!(set.len(), 1);
+    assert!(set.contains(&StructuralKey(Rc::new(object()))));
+
+    // Equal values have equal hashes, whatever their Rc or the sign of a zero
+    set.insert(StructuralKey(Rc::new(Value::Number(0.0))));
+    assert!(set.contains(&StructuralKey(Rc::new(Value::Number(-0.0)))));
+    assert!(!set.contains(&StructuralKey(Rc::new(Value::Integer(0)))));
+    assert_eq!(set.len(), 2);
+
+    // The same object read from two files is one key, although it has a different id in each
+    let files = [
+        LsoBuilder::new("first").add("player", object()).build(),
+        LsoBuilder::new("second")
+            .add(
+                "level",
+                Value::from(vec![("id".to_string(), Value::from(3))]),
+            )
+            .add("player", object())
+            .build(),
+    ]
+    .map(|mut lso| write_to_bytes(&mut lso).unwrap());
+    let decoded: Vec<_> = files
+        .iter()
+        .map(|bytes| Reader::default().parse(bytes).unwrap())
+        .collect();
+    let players: Vec<_> = decoded
+        .iter()
+        .map(|lso| lso.get_path("player").unwrap())
+        .collect();
+    let ids: Vec<_> = players
+        .iter()
+        .map(|player| match player {
+            Value::Object(id, _, _) => *id,
+            _ => panic!("Expected an object"),
+        })
+        .collect();
+    assert_ne!(ids[0], ids[1]);
+
+    let keys: HashSet<_> = decoded
+        .iter()
+        .flat_map(|lso| &lso.body)
+        .map(|e| StructuralKey(e.value.clone()))
+        .collect();
+    assert_eq!(keys.len(), 2);
+    assert!(keys.contains(&StructuralKey(Rc::new(players[1].clone()))));
+}
+
+#[test]
+pub fn test_amf0_string_decoder() {
+    let mut lso = Lso::new(
+        vec![Element::new("name", Value::String("cafX".into()))],
+        "save",
+        AMFVersion::AMF0,
+    );
+    let mut bytes = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+    // Replace the X with é in Latin-1, which isn't valid UTF-8
+    let x = bytes.iter().rposition(|b| *b == b'X').unwrap();
+    bytes[x] = 0xE9;
+
+    assert!(Reader::default().parse(&bytes).is_err());
+
+    let mut reader = Reader::default();
+    reader.set_string_decoder(|bytes| bytes.iter().map(|b| char::from(*b)).collect());
+    let read = reader.parse(&bytes).unwrap();
+    assert_eq!(read.body[0].name, "name");
+    assert_eq!(read.body[0].value().as_str(), Some("café"));
+}
+
+#[test]
+pub fn test_visitors() {
+    use flash_lso::errors::PathSegment;
+    use flash_lso::types::{Visitor, VisitorMut};
+
+    struct Paths(Vec<String>);
+
+    impl<'a> Visitor<'a> for Paths {
+        fn visit_value(&mut self, path: &[PathSegment], value: &Value<'a>) {
+            let path: Vec<String> = path
+                .iter()
+                .map(|segment| match segment {
+                    PathSegment::Name(name) => name.clone(),
+                    PathSegment::Index(index) => index.to_string(),
+                })
+                .collect();
+            self.0
+                .push(format!("{} {}", path.join("/"), value.type_name()));
+        }
+    }
+
+    struct MaskPasswords;
+
+    impl<'a> VisitorMut<'a> for MaskPasswords {
+        fn visit_value(&mut self, path: &[PathSegment], value: &mut Value<'a>) {
+            if let (Some(PathSegment::Name(name)), Value::String(s)) = (path.last(), value) {
+                if name == "password" {
+                    *s = "***".into();
+                }
+            }
+        }
+    }
+
+    let account = Rc::new(Value::Object(
+        ObjectId::INVALID,
+        vec![
+            Element::new("user", Value::String("bob".into())),
+            Element::new("password", Value::String("hunter2".into())),
+        ],
+        None,
+    ));
+    let mut lso = Lso::new(
+        vec![
+            Element::new("accounts", Value::StrictArray(vec![Rc::clone(&account)])),
+            Element::new(
+                "lookup",
+                Value::Dictionary(
+                    vec![(Rc::new(Value::Integer(1)), Rc::clone(&account))],
+                    false,
+                ),
+            ),
+        ],
+        "accounts",
+        AMFVersion::AMF3,
+    );
+
+    let mut paths = Paths(Vec::new());
+    lso.accept(&mut paths);
+    assert_eq!(
+        paths.0,
+        [
+            "accounts StrictArray",
+            "accounts/0 Object",
+            "accounts/0/user String",
+            "accounts/0/password String",
+            "lookup Dictionary",
+            "lookup/0/key Integer",
+            "lookup/0/value Object",
+            "lookup/0/value/user String",
+            "lookup/0/value/password String",
+        ]
+    );
+
+    lso.accept_mut(&mut MaskPasswords);
+    assert_eq!(
+        lso.get("accounts").unwrap().get_path("[0].password"),
+        Some(&Value::String("***".into()))
+    );
+    assert_eq!(
+        lso.get("lookup").unwrap().get_path("[1].password"),
+        Some(&Value::String("***".into()))
+    );
+    assert_eq!(
+        lso.get("accounts").unwrap().get_path("[0].user"),
+        Some(&Value::String("bob".into()))
+    );
+    // The original value isn't changed
+    assert_eq!(
+        account.get_path("password"),
+        Some(&Value::String("hunter2".into()))
+    );
+}
+
+#[test]
+#[cfg(feature = "compression")]
+pub fn test_compressed_byte_array() {
+    use flash_lso::compression::{
+        compress_bytes, uncompress, uncompress_with_limit, CompressionAlgorithm,
+    };
+    use flash_lso::errors::CompressionError;
+
+    // An AMF3 object `{score: 42, name: "bob"}` compressed by zlib
+    let zlib = [
+        0x78, 0xda, 0xe3, 0xe2, 0x66, 0xe4, 0x2e, 0x4e, 0xce, 0x2f, 0x4a, 0x65, 0xd1, 0xe2, 0xcc,
+        0x4b, 0xcc, 0x4d, 0x65, 0x63, 0x4f, 0xca, 0x4f, 0x62, 0x04, 0x00, 0x38, 0xdb, 0x05, 0x57,
+    ];
+    let value = Value::ByteArray(zlib.to_vec().into());
+    let bytes = value.as_bytes().unwrap();
+    assert_eq!(
+        CompressionAlgorithm::detect(bytes),
+        Some(CompressionAlgorithm::Zlib)
+    );
+
+    let inner = value.inflate(CompressionAlgorithm::Zlib).unwrap();
+    assert_eq!(CompressionAlgorithm::detect(&inner), None);
+    let (rest, object) = flash_lso::amf3::decode(&inner).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(object.get_path("score"), Some(&Value::Integer(42)));
+    assert_eq!(object.get_path("name").and_then(Value::as_str), Some("bob"));
+
+    // Without the zlib header and checksum it is raw deflate data
+    let raw = &zlib[2..zlib.len() - 4];
+    assert_eq!(
+        uncompress(raw, CompressionAlgorithm::Deflate).unwrap(),
+        inner
+    );
+
+    for algorithm in [CompressionAlgorithm::Zlib, CompressionAlgorithm::Deflate] {
+        let compressed = compress_bytes(&inner, algorithm);
+        assert_eq!(uncompress(&compressed, algorithm).unwrap(), inner);
+
+        let value = Value::deflate(&inner, algorithm);
+        assert_eq!(value.inflate(algorithm).unwrap(), inner);
+
+        let repeated = inner.repeat(100);
+        assert!(compress_bytes(&repeated, algorithm).len() < repeated.len() / 10);
+    }
+
+    assert_eq!(
+        Value::Integer(1).inflate(CompressionAlgorithm::Zlib),
+        Err(CompressionError::NotByteArray)
+    );
+
+    assert_eq!(
+        uncompress(&inner, CompressionAlgorithm::Zlib),
+        Err(CompressionError::InvalidHeader)
+    );
+    assert_eq!(
+        uncompress_with_limit(bytes, CompressionAlgorithm::Zlib, inner.len() - 1),
+        Err(CompressionError::TooLarge)
+    );
+}
+
+#[test]
+pub fn test_parse_lenient() {
+    use flash_lso::errors::{DecodeReason, PathSegment};
+
+    for data in [
+        &include_bytes!("sol/AS2-Demo.sol")[..],
+        &include_bytes!("sol/AS3-Demo.sol")[..],
+    ] {
+        let full = Reader::default().parse(data).unwrap();
+        let (lso, error) = Reader::default().parse_lenient(data).unwrap();
+        assert!(lso.structural_eq(&full));
+        assert_eq!(error, None);
+
+        let truncated = &data[..data.len() - 8];
+        let (lso, error) = Reader::default().parse_lenient(truncated).unwrap();
+        assert_eq!(lso.header, full.header);
+        assert!(lso.body.len() < full.body.len());
+        assert!(lso
+            .body
+            .iter()
+            .zip(&full.body)
+            .all(|(a, b)| a.structural_eq(b)));
+
+        let error = error.unwrap();
+        assert_eq!(error.reason, DecodeReason::UnexpectedEof);
+        assert!(error.offset.unwrap() <= truncated.len());
+        let name = full.body[lso.body.len()].name().to_string();
+        assert_eq!(error.path.first(), Some(&PathSegment::Name(name)));
+    }
+
+    // The header must be readable
+    assert!(Reader::default().parse_lenient(&[0x00, 0xbf]).is_err());
+}
+
+#[test]
+pub fn test_spans_of_references() {
+    let shared = Rc::new(Value::Object(
+        ObjectId::INVALID,
+        vec![Element::new("hp", Value::Number(10.0))],
+        None,
+    ));
+    let mut lso = Lso::new(
+        vec![
+            Element::new("first", Rc::clone(&shared)),
+            Element::new(
+                "list",
+                Value::StrictArray(vec![Rc::new(Value::Null), Rc::clone(&shared)]),
+            ),
+        ],
+        "refs",
+        AMFVersion::AMF3,
+    );
+    let data = flash_lso::write::write_to_bytes(&mut lso).unwrap();
+    let (_, spans) = Reader::default().parse_annotated(&data).unwrap();
+
+    let first = spans.get("first").unwrap();
+    assert_eq!(data[first.start], 0x0A);
+    assert_eq!(spans.definition("first"), Some(first.clone()));
+
+    // The reference is two bytes, its definition is the object it refers to
+    let reference = spans.get("list.1").unwrap();
+    assert_eq!(&data[reference], &[0x0A, 0x00]);
+    assert_eq!(spans.definition("list.1"), Some(first));
+    assert_eq!(spans.definition("list.0"), spans.get("list.0"));
+    assert_eq!(spans.definition("missing"), None);
+
+    // AMF0 references to earlier arrays and objects
+    let data = include_bytes!("sol/AS2-Reference-Demo.sol");
+    let (lso, spans) = Reader::default().parse_annotated(data).unwrap();
+    let mut references = 0;
+    for (path, value) in lso.flatten() {
+        if let Value::Reference(_) = value {
+            let reference = spans.get(&path).unwrap();
+            assert_eq!(data[reference.start], 0x07);
+            let definition = spans.definition(&path).unwrap();
+            assert!(definition.end <= reference.start);
+            assert!([0x03, 0x08, 0x0A].contains(&data[definition.start]));
+            references += 1;
+        }
+    }
+    assert!(references > 0);
+}
@@ -3,12 +3,13 @@
 
 #![deny(missing_docs, clippy::missing_docs_in_private_items)]
 
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command};
 use flash_lso::amf3::read::AMF3Decoder;
+use flash_lso::errors::FileError;
 use flash_lso::extra::*;
 use flash_lso::read::Reader;
 use flash_lso::types::Lso;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
@@ -16,7 +17,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let matched = Command::new("Lso -> json converter")
         .version("1.0")
         .author("CUB3D <callumthom11@gmail.com>")
-        .subcommand(Command::new("file").arg(Arg::new("INPUT").help("").required(true)))
+        .subcommand(
+            Command::new("file")
+                .arg(Arg::new("INPUT").help("").required(true))
+                .arg(
+                    Arg::new("refs")
+                        .long("refs")
+                        .help("Write shared and cyclic values as {\"$ref\": path} markers")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
         .subcommand(Command::new("object-amf3").arg(Arg::new("INPUT").help("").required(true)))
         .subcommand(Command::new("regen").arg(Arg::new("INPUT").help("").required(true)))
         .subcommand_required(true)
@@ -28,15 +38,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     match cmd {
         "file" => {
-            let data = std::fs::read(PathBuf::from(file_name))?;
-            match parse_file(&data) {
+            match parse_file(file_name) {
                 Ok(lso) => {
-                    let json = serde_json::to_string(&lso).expect("Unable to encode lso as json");
+                    let json = if args.get_flag("refs") {
+                        flash_lso::json::to_json(&lso).and_then(|v| serde_json::to_string(&v))
+                    } else {
+                        serde_json::to_string(&lso)
+                    }
+                    .expect("Unable to encode lso as json");
                     println!("{}", json);
                 }
                 Err(e) => {
                     eprintln!("Couldn't read lso file, maybe open a issue on github at https://github.com/CUB3D/rust-flash-lso");
-                    eprintln!("Error = {:?}", e);
+                    eprintln!("Error = {}", e);
                 }
             };
         }
@@ -44,15 +58,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             for f in std::fs::read_dir(file_name)? {
                 let f = f?;
                 if f.file_type()?.is_file() && f.file_name().to_string_lossy().ends_with(".sol") {
-                    let data = std::fs::read(f.path())?;
-
                     let out = f
                         .path()
                         .parent()
                         .unwrap()
                         .join(f.file_name().to_string_lossy().replace(".sol", ".json"));
 
-                    match parse_file(&data) {
+                    match parse_file(f.path()) {
                         Ok(lso) => {
                             let json =
                                 serde_json::to_string(&lso).expect("Unable to encode lso as json");
@@ -61,7 +73,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                         Err(e) => {
                             eprintln!("Couldn't read lso file, maybe open a issue on github at https://github.com/CUB3D/rust-flash-lso");
-                            eprintln!("Error = {:?}", e);
+                            eprintln!("Error = {}", e);
                         }
                     };
                 }
@@ -83,10 +95,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-/// Parse a given slice into an Lso
-fn parse_file(data: &[u8]) -> Result<Lso, Box<dyn std::error::Error + '_>> {
+/// Parse the file at the given path into an Lso
+fn parse_file(path: impl AsRef<Path>) -> Result<Lso<'static>, FileError> {
     let mut d = Reader::default();
     flex::read::register_decoders(&mut d.amf3_decoder);
-    let lso = d.parse(data)?;
-    Ok(lso)
+    d.parse_file(path)
 }
@@ -3,6 +3,7 @@ use yew::prelude::*;
 
 use flash_lso::extra::flex;
 use flash_lso::read::Reader;
+use flash_lso::spans::Spans;
 use flash_lso::types::{Attribute, Element, Lso, Value};
 
 use crate::blob_bindgen::Blob;
@@ -14,21 +15,26 @@ use crate::component_string_input::StringInput;
 use crate::component_tab::Tab;
 use crate::component_tabs::Tabs;
 use crate::component_treenode::TreeNode;
-use crate::uintarray_bindgen::Uint8Array;
 use crate::url_bindgen::URL;
 use crate::web_expect::WebSafeExpect;
 use crate::EditableValue;
 use crate::TreeNodePath;
 use flash_lso::write::write_to_bytes;
 use std::ops::Deref;
+use std::rc::Rc;
 use wasm_bindgen::JsCast;
 use web_sys::File;
 use web_sys::{EventTarget, HtmlInputElement};
 use yew::events::Event;
 
+/// The most memory the values of an uploaded file can use, so a crafted file can't exhaust the page's memory
+const MAX_ALLOC: usize = 256 * 1024 * 1024;
+
 pub struct LoadedFile {
     pub file_name: String,
-    pub file: Option<Lso>,
+    pub file: Option<Lso<'static>>,
+    /// Where each value was in the file as it was loaded
+    pub spans: Rc<Spans>,
 }
 
 impl LoadedFile {
@@ -36,6 +42,7 @@ impl LoadedFile {
         LoadedFile {
             file: None,
             file_name: file.name(),
+            spans: Rc::default(),
         }
     }
 }
@@ -61,14 +68,16 @@ pub struct FileData {
 pub enum Msg {
     Files(Vec<File>),
     Loaded(usize, FileData),
+    ImportJson(Vec<File>),
+    JsonLoaded(usize, FileData),
     Selection(EditableValue),
-    Edited(Value),
+    Edited(Value<'static>),
     TabSelected(usize),
     CloseTab(usize),
     CloseModal(usize),
     RootSelected,
     SearchQuery(String),
-    ElementChange(Element),
+    ElementChange(Element<'static>),
 }
 
 impl Component for Model {
@@ -88,38 +97,41 @@ impl Component for Model {
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         log::info!("MODEL msg={:?}", msg);
         match msg {
-            Msg::Files(files) => {
-                for file in files.into_iter() {
-                    let index = self.files.len();
-                    self.files.push(LoadedFile::empty_from_file(&file));
-
-                    let file_name = file.name();
-                    let cb = ctx.link().callback(move |data: Vec<u8>| {
-                        Msg::Loaded(
-                            index,
-                            FileData {
-                                name: file_name.clone(),
-                                content: data,
-                            },
-                        )
-                    });
-
-                    let task = gloo_file::callbacks::read_as_bytes(
-                        &gloo_file::Blob::from(file),
-                        move |res| {
-                            if let Ok(res) = res {
-                                cb.emit(res);
-                            }
-                        },
-                    );
-                    self.tasks.push(task);
-                }
-            }
+            Msg::Files(files) => self.read_files(ctx, files, Msg::Loaded),
+            Msg::ImportJson(files) => self.read_files(ctx, files, Msg::JsonLoaded),
             Msg::Loaded(index, file) => {
                 let mut parser = Reader::default();
+                parser.set_max_alloc(MAX_ALLOC);
                 flex::read::register_decoders(&mut parser.amf3_decoder);
 
-                match parser.parse(&file.content) {
+                match parser.parse_annotated(&file.content) {
+                    Ok((sol, spans)) => {
+                        let loaded = self
+                            .files
+                            .get_mut(index)
+                            .web_expect(&format!("No loading file at index {}", index));
+                        loaded.file = Some(sol.into_owned());
+                        loaded.spans = Rc::new(spans);
+
+                        if self.current_tab.is_none() {
+                            self.current_tab = Some(0);
+                        }
+                    }
+                    Err(e) => {
+                        log::warn!("Got error {:?}", e);
+                        let error = parser.decode_error(&file.content, e);
+                        self.error_messages
+                            .push(format!("Failed to load '{}': {}", file.name, error));
+                        self.files.remove(index);
+                    }
+                }
+            }
+            Msg::JsonLoaded(index, file) => {
+                let lso = serde_json::from_slice(&file.content)
+                    .map_err(|e| e.to_string())
+                    .and_then(|json| flash_lso::json::from_json(json).map_err(|e| e.to_string()));
+
+                match lso {
                     Ok(sol) => {
                         self.files
                             .get_mut(index)
@@ -131,9 +143,8 @@ impl Component for Model {
                         }
                     }
                     Err(e) => {
-                        log::warn!("Got error {:?}", e);
                         self.error_messages
-                            .push(format!("Failed to load '{}'", file.name));
+                            .push(format!("Failed to import '{}': {}", file.name, e));
                         self.files.remove(index);
                     }
                 }
@@ -207,7 +218,7 @@ impl Component for Model {
                     { for self.files.iter().enumerate().map(|(i,f)| html_nested! {
                     <Tab label={f.file_name.clone()} loading={f.file.is_none()}>
                         { if let Some(file) = &f.file {
-                            self.view_file(ctx, i, file)
+                            self.view_file(ctx, i, file, &f.spans)
                         } else {
                             html! {}
                         }}
@@ -316,23 +327,23 @@ impl Model {
                 html! {
                 <>
                     <HexView
-                        bytes={n.clone()}
-                        onchange={ctx.link().callback(move |data| Msg::Edited(Value::ByteArray(data)))}
+                        bytes={n.to_vec()}
+                        onchange={ctx.link().callback(move |data: Vec<u8>| Msg::Edited(Value::ByteArray(data.into())))}
                         onadd={ctx.link().callback(move |_| {
-                            let mut e = n.clone();
+                            let mut e = n.to_vec();
                             e.push(0);
-                            Msg::Edited(Value::ByteArray(e))
+                            Msg::Edited(Value::ByteArray(e.into()))
                         })}
                         onremove={ctx.link().callback(move |index| {
-                            let mut e = n_clone.clone();
+                            let mut e = n_clone.to_vec();
                             e.remove(index);
-                            Msg::Edited(Value::ByteArray(e))
+                            Msg::Edited(Value::ByteArray(e.into()))
                         })}/>
                   </>
                 }
             }
             Value::String(s) => html! {
-                <StringInput onchange={ctx.link().callback(move |s| Msg::Edited(Value::String(s)))} value={s}/>
+                <StringInput onchange={ctx.link().callback(move |s: String| Msg::Edited(Value::String(s.into())))} value={s.to_string()}/>
             },
             Value::Bool(b) => html! {
                 <div class="custom-control custom-switch">
@@ -386,7 +397,7 @@ impl Model {
                 </>
             },
             Value::XML(content, string) => html! {
-                <StringInput onchange={ctx.link().callback(move |s| Msg::Edited(Value::XML(s, string)))} value={content}/>
+                <StringInput onchange={ctx.link().callback(move |s: String| Msg::Edited(Value::XML(s.into(), string)))} value={content.to_string()}/>
             },
             Value::VectorInt(elements, fixed_length) => {
                 let elements_clone = elements.clone();
@@ -594,6 +605,38 @@ impl Model {
         }
     }
 
+    /// Open a tab for each file and read it, sending `loaded` with its contents
+    fn read_files(
+        &mut self,
+        ctx: &Context<Self>,
+        files: Vec<File>,
+        loaded: fn(usize, FileData) -> Msg,
+    ) {
+        for file in files.into_iter() {
+            let index = self.files.len();
+            self.files.push(LoadedFile::empty_from_file(&file));
+
+            let file_name = file.name();
+            let cb = ctx.link().callback(move |data: Vec<u8>| {
+                loaded(
+                    index,
+                    FileData {
+                        name: file_name.clone(),
+                        content: data,
+                    },
+                )
+            });
+
+            let task =
+                gloo_file::callbacks::read_as_bytes(&gloo_file::Blob::from(file), move |res| {
+                    if let Ok(res) = res {
+                        cb.emit(res);
+                    }
+                });
+            self.tasks.push(task);
+        }
+    }
+
     fn navbar(&self, ctx: &Context<Self>) -> Html {
         html! {
             <nav class="navbar navbar-expand-lg">
@@ -602,8 +645,27 @@ impl Model {
                         <div class="btn-group mr-2" role="group">
                             <label for="files" class="btn btn-primary">{"Open"}</label>
                             { self.save_button(ctx) }
+                            <label for="json-files" class="btn btn-primary">{"Import JSON"}</label>
+                            { self.export_json_button(ctx) }
                         </div>
                     </li>
+                    <input id="json-files" style="visibility:hidden;" type="file" accept=".json" onchange={ctx.link().batch_callback(|e: Event| {
+                        let target: Option<EventTarget> = e.target();
+                        let input = target.and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+
+                        input.map(|input| {
+                            let mut result = Vec::new();
+
+                            if let Some(files_list) = input.files() {
+                                for ii in 0..files_list.length() {
+                                    let file = files_list.item(ii).web_expect("filelist::item");
+                                    result.push(file);
+                                }
+                            }
+
+                            Msg::ImportJson(result)
+                        })
+                    })}/>
                     <input id="files" style="visibility:hidden;" type="file" onchange={ctx.link().batch_callback(|e: Event| {
                         let target: Option<EventTarget> = e.target();
                         let input = target.and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
@@ -636,10 +698,7 @@ impl Model {
 
             let options: js_sys::Object = js_sys::Object::new();
 
-            let arr: Uint8Array = Uint8Array::new(bytes.len() as u32);
-            for (i, b) in bytes.iter().enumerate() {
-                arr.set(i as u32, (*b).into());
-            }
+            let arr = js_sys::Uint8Array::from(&bytes[..]);
 
             let arr2: js_sys::Array = js_sys::Array::new_with_length(1);
             arr2.set(0, arr.into());
@@ -655,7 +714,40 @@ impl Model {
         }
     }
 
-    fn view_file(&self, ctx: &Context<Self>, _index: usize, data: &Lso) -> Html {
+    fn export_json_button(&self, _ctx: &Context<Self>) -> Html {
+        let Some(lso) = self.current_tab.and_then(|i| self.files[i].file.as_ref()) else {
+            return html! {};
+        };
+
+        match flash_lso::json::to_json(lso) {
+            Ok(json) => {
+                let options: js_sys::Object = js_sys::Object::new();
+
+                let arr = js_sys::Uint8Array::from(json.to_string().as_bytes());
+
+                let arr2: js_sys::Array = js_sys::Array::new_with_length(1);
+                arr2.set(0, arr.into());
+
+                let blob = Blob::new(arr2, options.into());
+                let url = URL::createObjectURL(&blob);
+
+                html! {
+                    <a href={url} download={"save.json"} class="btn btn-primary" style="height: 38px">{"Export JSON"}</a>
+                }
+            }
+            Err(e) => html! {
+                <button class="btn btn-primary" style="height: 38px" disabled=true title={e.to_string()}>{"Export JSON"}</button>
+            },
+        }
+    }
+
+    fn view_file(
+        &self,
+        ctx: &Context<Self>,
+        _index: usize,
+        data: &Lso<'static>,
+        spans: &Rc<Spans>,
+    ) -> Html {
         let root_class = "text-white bg-primary rounded-pill pl-2 pr-2 user-select-none";
 
         html! {
@@ -673,7 +765,7 @@ impl Model {
                                 onclick={ctx.link().callback(move |_| Msg::RootSelected)}>{ "/" }</span>
                             <ul>
                                 { for data.body.iter().map(|e| html! {
-                                    <TreeNode element_callback={ctx.link().callback(Msg::ElementChange)} filter={self.search.clone()} selection={self.current_selection.clone()} parent_path={TreeNodePath::root()} name={e.name.clone()} value={e.value.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
+                                    <TreeNode element_callback={ctx.link().callback(Msg::ElementChange)} filter={self.search.clone()} selection={self.current_selection.clone()} parent_path={TreeNodePath::root()} spans={spans.clone()} span_path={e.name.to_string()} name={e.name.to_string()} value={e.value.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
                                 })}
                             </ul>
                         </div>
@@ -683,27 +775,6 @@ impl Model {
                             if let Some(selection) = &self.current_selection {
                                 let details_content = self.value_details(selection.clone(), ctx);
                                 let value_type = match &selection.value {
-                                    Value::Reference(_) => "Reference".to_string(),
-                                    Value::Number(_) => "Number".to_string(),
-                                    Value::Bool(_) => "Boolean".to_string(),
-                                    Value::String(_) => "String".to_string(),
-                                    Value::Object(_, _, _) => "Object".to_string(),
-                                    Value::Null => "Null".to_string(),
-                                    Value::Undefined => "Undefined".to_string(),
-                                    Value::ECMAArray(_, _, _) => "ECMAArray".to_string(),
-                                    Value::StrictArray(_) => "StrictArray".to_string(),
-                                    Value::Date(_, _) => "Date".to_string(),
-                                    Value::Unsupported => "Unsupported".to_string(),
-                                    Value::XML(_, _) => "XML".to_string(),
-                                    Value::AMF3(_) => "AMF3<TODO>".to_string(),
-                                    Value::Integer(_) => "Integer".to_string(),
-                                    Value::ByteArray(_) => "ByteArray".to_string(),
-                                    Value::VectorInt(_, _) => "Vector<Int>".to_string(),
-                                    Value::VectorUInt(_, _) => "Vector<UInt>".to_string(),
-                                    Value::VectorDouble(_, _) => "Vector<Double>".to_string(),
-                                    Value::VectorObject(_, _, _) => "Vector<Object>".to_string(),
-                                    Value::Amf3ObjectReference(_) => "Reference".to_string(),
-                                    Value::Dictionary(_, _) => "Dictionary".to_string(),
                                     Value::Custom(_, _, cd) => {
                                         if let Some(cd) = cd {
                                             format!("Custom<{}>", cd.name)
@@ -711,6 +782,7 @@ impl Model {
                                             "Custom<Unknown>".to_string()
                                         }
                                     },
+                                    value => value.type_name().to_string(),
                                 };
 
                                 html! {
@@ -1,4 +1,5 @@
 use crate::{EditableValue, TreeNodePath};
+use flash_lso::spans::Spans;
 use flash_lso::types::{Element, Value};
 use std::ops::Deref;
 use std::rc::Rc;
@@ -9,27 +10,33 @@ use yew::{Component, Html, Properties};
 pub enum Msg {
     Selection(EditableValue),
     Toggle,
-    Edited(Value),
-    ElementChange(Element),
-    CustomElementChange(Element),
-    CustomElementChangeStandard(Element),
+    Edited(Value<'static>),
+    ElementChange(Element<'static>),
+    CustomElementChange(Element<'static>),
+    CustomElementChangeStandard(Element<'static>),
 }
 
 pub struct TreeNode {
     expanded: bool,
-    value: Value,
+    value: Value<'static>,
 }
 
 #[derive(PartialEq, Properties, Clone)]
 pub struct Props {
     pub parent_path: TreeNodePath,
     pub name: String,
-    pub value: Value,
+    pub value: Value<'static>,
     pub parent_callback: Callback<EditableValue>,
     pub selection: Option<EditableValue>,
     pub filter: String,
     #[prop_or(None)]
-    pub element_callback: Option<Callback<Element>>,
+    pub element_callback: Option<Callback<Element<'static>>>,
+    /// Where each value was in the file as it was loaded
+    #[prop_or_default]
+    pub spans: Rc<Spans>,
+    /// The path of this node in `spans`
+    #[prop_or_default]
+    pub span_path: String,
 }
 
 impl Component for TreeNode {
@@ -63,16 +70,10 @@ impl Component for TreeNode {
                 true
             }
             Msg::ElementChange(el) => {
-                match &mut self.value {
-                    Value::Object(_, old_el, _) => {
-                        let index = old_el.iter().position(|e| e.name == el.name);
-                        if let Some(index) = index {
-                            old_el[index] = el;
-                        }
-                    }
-                    _ => {
-                        log::warn!("Unknown element change");
-                    }
+                if let Value::Object(..) = self.value {
+                    self.value.set_property(el.name, el.value);
+                } else {
+                    log::warn!("Unknown element change");
                 }
 
                 true
@@ -150,6 +151,7 @@ impl Component for TreeNode {
                         callback: callback.clone(),
                         path: path.clone(),
                     }))}>{ name }</span>
+                { self.view_span(ctx) }
                 { if self.expanded {
                     self.view_sol_value(ctx, Rc::new(self.value.clone()))
                 } else {
@@ -196,9 +198,25 @@ impl TreeNode {
         ctx.props().parent_path.join(ctx.props().name.clone())
     }
 
+    pub fn child_span_path(&self, ctx: &Context<Self>, child: &str) -> String {
+        format!("{}.{}", ctx.props().span_path, child)
+    }
+
+    /// Show where this value was in the file, if it's known
+    pub fn view_span(&self, ctx: &Context<Self>) -> Html {
+        match ctx.props().spans.get(&ctx.props().span_path) {
+            Some(range) => html! {
+                <small class="text-muted ml-2">
+                    { format!("0x{:x}..0x{:x} ({} bytes)", range.start, range.end, range.len()) }
+                </small>
+            },
+            None => html! {},
+        }
+    }
+
     pub fn selected(&self, ctx: &Context<Self>) -> bool {
         let selected_path = ctx.props().selection.clone().map(|s| s.path);
-        selected_path.map_or(false, |tnp| tnp.contains(self.path(ctx)))
+        selected_path.is_some_and(|tnp| tnp.contains(self.path(ctx)))
     }
 
     pub fn has_children(data: &Value) -> bool {
@@ -214,21 +232,26 @@ impl TreeNode {
         )
     }
 
-    pub fn view_array_element(&self, ctx: &Context<Self>, index: usize, data: &Rc<Value>) -> Html {
+    pub fn view_array_element(
+        &self,
+        ctx: &Context<Self>,
+        index: usize,
+        data: &Rc<Value<'static>>,
+    ) -> Html {
         html! {
             <div>
-                <TreeNode filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} name={format!("{}", index)} value={data.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
+                <TreeNode filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} spans={ctx.props().spans.clone()} span_path={self.child_span_path(ctx, &index.to_string())} name={format!("{}", index)} value={data.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
             </div>
         }
     }
 
-    pub fn view_sol_value(&self, ctx: &Context<Self>, data: Rc<Value>) -> Html {
+    pub fn view_sol_value(&self, ctx: &Context<Self>, data: Rc<Value<'static>>) -> Html {
         match data.deref() {
             Value::AMF3(e) => self.view_sol_value(ctx, e.clone()),
             Value::Object(_, elements, _class_def) => html! {
                 <ul>
                     { for elements.iter().map(|e| html! {
-                        <TreeNode element_callback={ctx.link().callback(Msg::ElementChange)} filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} name={e.name.clone()} value={e.value.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
+                        <TreeNode element_callback={ctx.link().callback(Msg::ElementChange)} filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} spans={ctx.props().spans.clone()} span_path={self.child_span_path(ctx, &e.name)} name={e.name.to_string()} value={e.value.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
                     })}
                 </ul>
             },
@@ -241,7 +264,7 @@ impl TreeNode {
                     <ul>
                        { for dense.iter().enumerate().map(|(i, v)| self.view_array_element(ctx, i, v))}
                         { for assoc.iter().map(|e| html! {
-                            <TreeNode filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} name={e.name.clone()} value={e.value.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
+                            <TreeNode filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} spans={ctx.props().spans.clone()} span_path={self.child_span_path(ctx, &e.name)} name={e.name.to_string()} value={e.value.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
                         })}
                     </ul>
             },
@@ -252,13 +275,13 @@ impl TreeNode {
             },
             Value::Dictionary(children, _) => html! {
                 <ul>
-                    { for children.iter().map(|(k, v)| html! {
+                    { for children.iter().enumerate().map(|(i, (k, v))| html! {
                             <>
                             <li>
-                                <TreeNode filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} name="key" value={k.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
+                                <TreeNode filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} spans={ctx.props().spans.clone()} span_path={self.child_span_path(ctx, &format!("{}.key", i))} name="key" value={k.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
                             </li>
                             <li>
-                                <TreeNode filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} name="value" value={v.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
+                                <TreeNode filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} spans={ctx.props().spans.clone()} span_path={self.child_span_path(ctx, &format!("{}.value", i))} name="value" value={v.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
                             </li>
                             </>
                         })}
@@ -270,7 +293,7 @@ impl TreeNode {
                         {"Custom elements"}
                         <ul>
                             { for el.iter().map(|e| html! {
-                                <TreeNode element_callback={ctx.link().callback(Msg::CustomElementChange)} filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} name={e.name.clone()} value={e.value.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
+                                <TreeNode element_callback={ctx.link().callback(Msg::CustomElementChange)} filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} spans={ctx.props().spans.clone()} span_path={self.child_span_path(ctx, &e.name)} name={e.name.to_string()} value={e.value.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
                             })}
                         </ul>
                     </li>
@@ -278,7 +301,7 @@ impl TreeNode {
                         {"Standard elements"}
                         <ul>
                            { for el2.iter().map(|e| html! {
-                                <TreeNode element_callback={ctx.link().callback(Msg::CustomElementChangeStandard)} filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} name={e.name.clone()} value={e.value.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
+                                <TreeNode element_callback={ctx.link().callback(Msg::CustomElementChangeStandard)} filter={ctx.props().filter.clone()} selection={ctx.props().selection.clone()} parent_path={self.path(ctx)} spans={ctx.props().spans.clone()} span_path={self.child_span_path(ctx, &e.name)} name={e.name.to_string()} value={e.value.deref().clone()} parent_callback={ctx.link().callback(Msg::Selection)}></TreeNode>
                             })}
                         </ul>
                     </li>
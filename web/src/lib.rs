@@ -15,7 +15,6 @@ pub(crate) mod component_tab;
 pub(crate) mod component_tabs;
 pub(crate) mod component_treenode;
 pub(crate) mod jquery_bindgen;
-pub(crate) mod uintarray_bindgen;
 pub(crate) mod url_bindgen;
 pub(crate) mod web_expect;
 
@@ -23,8 +22,8 @@ use crate::component_model::Model;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct EditableValue {
-    pub value: Value,
-    pub callback: Callback<Value>,
+    pub value: Value<'static>,
+    pub callback: Callback<Value<'static>>,
     pub path: TreeNodePath,
 }
 